@@ -0,0 +1,180 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+//! A toy protocol simulated without any LibraBFT type: the leader of round `r` is the node
+//! `r % n` and appends the `r`-th entry of a shared log. Other nodes catch up by requesting the
+//! entries they are missing. Run with `cargo run --example round_robin`.
+
+extern crate bft_simulator_runtime;
+
+use bft_simulator_runtime::{
+    base_types::{Author, Duration, NodeTime, Result, Round},
+    simulator::{GlobalTime, RandomDelay, Simulator},
+    ActiveRound, BlockProposer, CommittedChain, ConsensusNode, DataSyncNode, NodeUpdateActions,
+    NotificationKind, RoundActions, Validate,
+};
+
+/// Delay between two updates of a node.
+const UPDATE_PERIOD: Duration = 50;
+
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+struct Entry {
+    round: Round,
+    author: Author,
+}
+
+impl Validate for Entry {
+    fn validate(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Number of entries known to the sender.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+struct LogLength(usize);
+
+impl NotificationKind for LogLength {}
+
+/// Entries committed by a node, as seen by the simulator.
+#[derive(Debug, Default)]
+struct Ledger {
+    entries: Vec<Entry>,
+}
+
+impl CommittedChain for Ledger {
+    type Entry = Entry;
+
+    fn committed_chain(&self) -> &[Entry] {
+        &self.entries
+    }
+}
+
+#[derive(Debug)]
+struct RoundRobinNode {
+    author: Author,
+    num_nodes: usize,
+    /// Local copy of the log, used to serve requests.
+    entries: Vec<Entry>,
+}
+
+impl RoundRobinNode {
+    fn new(author: Author, num_nodes: usize) -> Self {
+        RoundRobinNode {
+            author,
+            num_nodes,
+            entries: Vec::new(),
+        }
+    }
+
+    fn leader(&self, round: Round) -> Author {
+        Author(round.0 % self.num_nodes)
+    }
+
+    /// Entries are final as soon as they are appended, so the log is the committed chain.
+    fn append(&mut self, entry: Entry, ledger: &mut Ledger) {
+        self.entries.push(entry);
+        ledger.entries.push(entry);
+    }
+}
+
+impl ConsensusNode<Ledger> for RoundRobinNode {
+    fn update_node(&mut self, clock: NodeTime, _ledger: &mut Ledger) -> NodeUpdateActions {
+        let mut actions = NodeUpdateActions::new();
+        actions.next_scheduled_update = clock + UPDATE_PERIOD;
+        actions.should_broadcast = self.round_actions().should_propose;
+        actions
+    }
+}
+
+impl BlockProposer<Ledger> for RoundRobinNode {
+    type Block = Entry;
+    type Record = Entry;
+
+    fn create_block(&mut self, round: Round, _ledger: &mut Ledger) -> Option<Entry> {
+        if self.leader(round) != self.author {
+            return None;
+        }
+        Some(Entry {
+            round,
+            author: self.author,
+        })
+    }
+
+    fn send_block(&mut self, entry: Entry, ledger: &mut Ledger) {
+        self.append(entry, ledger);
+    }
+}
+
+impl DataSyncNode<Ledger> for RoundRobinNode {
+    type Notification = LogLength;
+    type Request = LogLength;
+    type Response = Vec<Entry>;
+
+    fn create_notification(&self) -> LogLength {
+        LogLength(self.entries.len())
+    }
+
+    fn create_request(&self) -> LogLength {
+        LogLength(self.entries.len())
+    }
+
+    fn handle_request(&self, request: LogLength) -> Vec<Entry> {
+        self.entries.iter().skip(request.0).cloned().collect()
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: LogLength,
+        _ledger: &mut Ledger,
+    ) -> Option<LogLength> {
+        if notification.0 > self.entries.len() {
+            Some(self.create_request())
+        } else {
+            None
+        }
+    }
+
+    fn handle_response(&mut self, response: Vec<Entry>, ledger: &mut Ledger, _clock: NodeTime) {
+        for entry in response {
+            // Responses may overlap when several requests were in flight.
+            if entry.round.0 == self.entries.len() {
+                self.append(entry, ledger);
+            }
+        }
+    }
+}
+
+impl ActiveRound for RoundRobinNode {
+    fn active_round(&self) -> Round {
+        Round(self.entries.len())
+    }
+
+    fn current_timeout(&self) -> Option<Duration> {
+        Some(UPDATE_PERIOD)
+    }
+
+    /// The leader of the next entry proposes it at each update.
+    fn round_actions(&self) -> RoundActions {
+        RoundActions {
+            should_propose: self.leader(self.active_round()) == self.author,
+            ..RoundActions::default()
+        }
+    }
+}
+
+fn main() {
+    let num_nodes = 4;
+    let mut sim: Simulator<RoundRobinNode, Ledger, LogLength, LogLength, Vec<Entry>> =
+        Simulator::new(
+            num_nodes,
+            RandomDelay::new(10.0, 4.0),
+            |_, _| Ledger::default(),
+            |author, _, _| RoundRobinNode::new(author, num_nodes),
+        );
+    let summary = sim.loop_until(GlobalTime(10_000), None).summary_string();
+    println!("{}", summary);
+    for (author, node) in sim.nodes_iter() {
+        println!("{:?} reached {}", author, node.active_round());
+    }
+    sim.assert_eventual_consistency(10 * UPDATE_PERIOD);
+}
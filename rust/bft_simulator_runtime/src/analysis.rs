@@ -0,0 +1,189 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    base_types::{Author, Duration, Round},
+    simulator::{percentile, GlobalTime, LoggedMessage},
+    EpochConfiguration,
+};
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+#[path = "unit_tests/analysis_tests.rs"]
+mod analysis_tests;
+
+/// One-way delays realized during a simulation.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct MessageDelays {
+    /// Fastest delivery for each sender, receiver and round.
+    delays: BTreeMap<(Author, Author, Round), Duration>,
+    /// Fastest delivery for each sender and receiver, at any round.
+    link_delays: BTreeMap<(Author, Author), Duration>,
+}
+
+impl MessageDelays {
+    pub fn new() -> Self {
+        MessageDelays::default()
+    }
+
+    /// Collect the delays of the messages of an event log.
+    pub fn from_log(log: &[LoggedMessage]) -> Self {
+        log.iter().fold(MessageDelays::new(), |delays, message| {
+            delays.with_delay(
+                message.sender,
+                message.receiver,
+                message.round,
+                message.deadline.0 - message.send_time.0,
+            )
+        })
+    }
+
+    /// Record a delivery, keeping the fastest one of each sender, receiver and round.
+    pub fn with_delay(
+        mut self,
+        sender: Author,
+        receiver: Author,
+        round: Round,
+        delay: Duration,
+    ) -> Self {
+        let entry = self
+            .delays
+            .entry((sender, receiver, round))
+            .or_insert(delay);
+        *entry = std::cmp::min(*entry, delay);
+        let entry = self.link_delays.entry((sender, receiver)).or_insert(delay);
+        *entry = std::cmp::min(*entry, delay);
+        self
+    }
+
+    /// Delay of a message sent at the given round. Falls back to the fastest delivery between
+    /// the two nodes when no message of this round was recorded. Local messages are free.
+    pub fn delay(&self, sender: Author, receiver: Author, round: Round) -> Option<Duration> {
+        if sender == receiver {
+            return Some(0);
+        }
+        self.delays
+            .get(&(sender, receiver, round))
+            .or_else(|| self.link_delays.get(&(sender, receiver)))
+            .cloned()
+    }
+}
+
+/// Realized timing of a committed block.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct CommittedBlock {
+    pub round: Round,
+    pub proposal_time: GlobalTime,
+    pub commit_time: GlobalTime,
+}
+
+/// Realized and ideal commit latencies of a block.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct OracleEntry {
+    pub round: Round,
+    pub realized_latency: Duration,
+    pub ideal_latency: Duration,
+}
+
+impl OracleEntry {
+    /// How many times slower than the ideal the commit was.
+    pub fn ratio(&self) -> f64 {
+        if self.ideal_latency > 0 {
+            self.realized_latency as f64 / self.ideal_latency as f64
+        } else {
+            1.0
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct OracleReport {
+    pub entries: Vec<OracleEntry>,
+    /// Rounds of the blocks whose ideal commit could not be computed for lack of delays.
+    pub skipped_rounds: Vec<Round>,
+}
+
+impl OracleReport {
+    /// Realized-vs-ideal latency ratios, in increasing order.
+    pub fn ratios(&self) -> Vec<f64> {
+        let mut ratios: Vec<_> = self.entries.iter().map(OracleEntry::ratio).collect();
+        ratios.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        ratios
+    }
+
+    /// Smallest ratio such that at least `percent`% of the ratios are lower or equal.
+    pub fn ratio_percentile(&self, percent: usize) -> Option<f64> {
+        percentile(&self.ratios(), percent)
+    }
+}
+
+/// Earliest time at which a block proposed at `proposal_time` could be committed, or `None` if
+/// some delay is unknown. Following the 3-chain commit rule, this is the time at which the QC of
+/// the block of round `round + 2` is formed, assuming that votes are sent to the leader of the
+/// next round, and that each leader proposes as soon as it forms the QC of the previous round.
+/// Processing times and the pacemaker are ignored.
+pub fn ideal_commit_time<L>(
+    delays: &MessageDelays,
+    configuration: &EpochConfiguration,
+    leader: L,
+    round: Round,
+    proposal_time: GlobalTime,
+) -> Option<GlobalTime>
+where
+    L: Fn(Round) -> Author,
+{
+    let mut time = proposal_time;
+    for offset in 0..3 {
+        let round = round + offset;
+        let proposer = leader(round);
+        let next_leader = leader(round.next());
+        let mut arrivals = Vec::new();
+        for voter in configuration.voters() {
+            if let (Some(proposal_delay), Some(vote_delay)) = (
+                delays.delay(proposer, *voter, round),
+                delays.delay(*voter, next_leader, round),
+            ) {
+                arrivals.push((time + proposal_delay + vote_delay, *voter));
+            }
+        }
+        arrivals.sort();
+        let mut weight = 0;
+        let quorum_time = arrivals.into_iter().find(|(_, voter)| {
+            weight += configuration.weight(voter);
+            weight >= configuration.quorum_threshold()
+        })?;
+        time = quorum_time.0;
+    }
+    Some(time)
+}
+
+/// Compare the realized commit latency of each block with the ideal one, given the realized
+/// delays of the messages and the leader of each round.
+pub fn oracle_report<L>(
+    delays: &MessageDelays,
+    configuration: &EpochConfiguration,
+    leader: L,
+    blocks: &[CommittedBlock],
+) -> OracleReport
+where
+    L: Fn(Round) -> Author,
+{
+    let mut report = OracleReport::default();
+    for block in blocks {
+        match ideal_commit_time(
+            delays,
+            configuration,
+            &leader,
+            block.round,
+            block.proposal_time,
+        ) {
+            Some(ideal_time) => report.entries.push(OracleEntry {
+                round: block.round,
+                realized_latency: block.commit_time.0 - block.proposal_time.0,
+                ideal_latency: ideal_time.0 - block.proposal_time.0,
+            }),
+            None => report.skipped_rounds.push(block.round),
+        }
+    }
+    report
+}
@@ -4,6 +4,7 @@
 use failure::Error;
 use std::{
     collections::hash_map::DefaultHasher,
+    convert::TryFrom,
     fmt,
     hash::{Hash, Hasher},
 };
@@ -31,17 +32,53 @@ impl fmt::Debug for NodeTime {
     }
 }
 
+impl Round {
+    /// The round after this one. Panics on overflow.
+    pub fn next(self) -> Round {
+        self + 1
+    }
+
+    /// `n` rounds after this one, or `None` on overflow.
+    pub fn checked_add(self, n: u64) -> Option<Round> {
+        usize::try_from(n)
+            .ok()
+            .and_then(|n| self.0.checked_add(n))
+            .map(Round)
+    }
+
+    /// Number of rounds between this round and `other`, in either order.
+    pub fn distance(self, other: Round) -> usize {
+        if self >= other {
+            self.0 - other.0
+        } else {
+            other.0 - self.0
+        }
+    }
+
+    /// Whether this round comes right after `other`.
+    pub fn follows(self, other: Round) -> bool {
+        other.checked_add(1) == Some(self)
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "r{}", self.0)
+    }
+}
+
 impl std::ops::Add<usize> for Round {
     type Output = Round;
 
     fn add(self, rhs: usize) -> Self::Output {
-        Round(self.0 + rhs)
+        self.checked_add(rhs as u64)
+            .unwrap_or_else(|| panic!("Round overflow: {} + {}", self, rhs))
     }
 }
 
 impl NodeTime {
     pub fn never() -> Self {
-        NodeTime(std::i64::MAX)
+        NodeTime(i64::MAX)
     }
 }
 
@@ -70,6 +107,53 @@ impl Signature {
     }
 }
 
+/// A 64-bit FNV-1a hasher. Unlike `DefaultHasher`, its output is fully specified, so that
+/// hashes can be persisted and compared across builds.
+#[derive(Clone, Debug)]
+pub struct StableHasher(u64);
+
+impl StableHasher {
+    pub fn new() -> Self {
+        StableHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Default for StableHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    // Integers are written in little-endian order regardless of the platform.
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+}
+
 impl Round {
     pub fn max_update(&mut self, round: Round) {
         *self = std::cmp::max(*self, round);
@@ -2,7 +2,11 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
-use std::collections::BTreeMap;
+use crate::base_types::StableHasher;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    hash::Hasher,
+};
 
 #[cfg(test)]
 #[path = "unit_tests/configuration_tests.rs"]
@@ -14,9 +18,63 @@ impl EpochConfiguration {
         EpochConfiguration {
             voting_rights,
             total_votes,
+            max_block_bytes: None,
+            aggregated_signatures: false,
+            inactive_authors: BTreeSet::new(),
         }
     }
 
+    /// Leaders must not propose blocks with a larger payload, and validators reject them.
+    pub fn with_max_block_bytes(mut self, max_block_bytes: usize) -> Self {
+        self.max_block_bytes = Some(max_block_bytes);
+        self
+    }
+
+    pub fn max_block_bytes(&self) -> Option<usize> {
+        self.max_block_bytes
+    }
+
+    /// QCs carry a single aggregated signature and a bitmap of the signers among the voters,
+    /// as in BLS schemes, and validators verify them as such.
+    pub fn with_aggregated_signatures(mut self) -> Self {
+        self.aggregated_signatures = true;
+        self
+    }
+
+    pub fn aggregates_signatures(&self) -> bool {
+        self.aggregated_signatures
+    }
+
+    /// Validators whose votes were missing from the recent commits of the previous epochs.
+    /// Leader elections may skip them. Their voting rights are unchanged.
+    pub fn with_inactive_authors(mut self, inactive_authors: BTreeSet<Author>) -> Self {
+        self.inactive_authors = inactive_authors;
+        self
+    }
+
+    pub fn inactive_authors(&self) -> &BTreeSet<Author> {
+        &self.inactive_authors
+    }
+
+    /// Stable digest of the validator set and the voting rights, which nodes of the same
+    /// epoch must agree on.
+    pub fn digest(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        for (author, votes) in &self.voting_rights {
+            hasher.write_u64(author.0 as u64);
+            hasher.write_u64(*votes as u64);
+        }
+        hasher.finish()
+    }
+
+    /// Authors with voting rights, in increasing order.
+    pub fn voters(&self) -> impl Iterator<Item = &Author> {
+        self.voting_rights
+            .iter()
+            .filter(|(_, votes)| **votes > 0)
+            .map(|(author, _)| author)
+    }
+
     pub fn weight(&self, author: &Author) -> usize {
         *self.voting_rights.get(author).unwrap_or(&0)
     }
@@ -2,11 +2,16 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    base_types::Author,
+    schema::{self, ArtifactSchema},
     simulator::{Event, GlobalTime, Simulator},
     ActiveRound,
 };
-use std::{fs, path::Path};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
 
 pub struct DataWriter {
     data_files_path: String,
@@ -15,6 +20,38 @@ pub struct DataWriter {
     max_round_per_node: Vec<usize>,
     nodes_round_switch: Vec<Vec<(usize, GlobalTime)>>,
     message_counter: usize, // Counts the number of messages
+    // Type of each round, decided by the first commit or timeout observed during the round
+    classified_rounds: BTreeMap<usize, RoundType>,
+}
+
+/// Outcome of a round, as first observed by a node while it was in this round.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum RoundType {
+    /// A node committed without timing out (optimistic path).
+    Fast,
+    /// No commit without a timeout: either the round needed a retry, or the chain of
+    /// certificates was still being rebuilt after a timeout.
+    Slow,
+    /// A node timed out without committing.
+    Timeout,
+}
+
+impl RoundType {
+    fn classify(is_commit: bool, is_timeout: bool) -> Self {
+        match (is_commit, is_timeout) {
+            (true, false) => RoundType::Fast,
+            (false, true) => RoundType::Timeout,
+            _ => RoundType::Slow,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RoundType::Fast => "Fast",
+            RoundType::Slow => "Slow",
+            RoundType::Timeout => "Timeout",
+        }
+    }
 }
 
 impl DataWriter {
@@ -25,6 +62,7 @@ impl DataWriter {
             nodes_round_switch: vec![Vec::new(); nodes_num],
             data_files_path: path,
             message_counter: 0,
+            classified_rounds: BTreeMap::new(),
         };
         if !Path::new(&data_writer.data_files_path).exists() {
             fs::create_dir(&data_writer.data_files_path).expect("could not create result dir");
@@ -39,12 +77,11 @@ impl DataWriter {
     ) where
         State: ActiveRound,
     {
-        for node_num in 0..self.nodes_len {
-            let node = simulator.simulated_node(Author(node_num));
+        for (author, node) in simulator.nodes_iter() {
             let node_round = node.active_round().0;
-            if node_round > *self.max_round_per_node.get(node_num).unwrap() {
-                self.max_round_per_node[node_num] = node_round;
-                self.nodes_round_switch[node_num].push((node_round, *clock))
+            if node_round > *self.max_round_per_node.get(author.0).unwrap() {
+                self.max_round_per_node[author.0] = node_round;
+                self.nodes_round_switch[author.0].push((node_round, *clock))
             }
         }
     }
@@ -59,39 +96,68 @@ impl DataWriter {
         }
     }
 
-    pub fn write_to_file(&self) {
-        let mut wtr =
-            csv::Writer::from_path(format!("{}/{}", self.data_files_path, "round_switches.txt"))
-                .unwrap();
+    /// Record whether handling the event made its destination commit or time out. The first
+    /// such outcome in a round, among all the nodes, classifies the round once and for all.
+    pub fn add_round_type_counter<Notification, Request, Response>(
+        &mut self,
+        event: &Event<Notification, Request, Response>,
+        is_commit: bool,
+        is_timeout: bool,
+    ) {
+        if !is_commit && !is_timeout {
+            return;
+        }
+        let round = self.max_round_per_node[event.destination().0];
+        self.classified_rounds
+            .entry(round)
+            .or_insert_with(|| RoundType::classify(is_commit, is_timeout));
+    }
 
+    /// Outcome of each round up to the highest round reached, excluded.
+    pub fn round_types(&self) -> Vec<RoundType> {
+        let max_round = *self.max_round_per_node.iter().max().unwrap();
+        (0..max_round)
+            .map(|round| {
+                // Rounds without commit nor timeout were still rebuilding the chain.
+                self.classified_rounds
+                    .get(&round)
+                    .cloned()
+                    .unwrap_or(RoundType::Slow)
+            })
+            .collect()
+    }
+
+    /// Create a CSV file starting with the version and the header of the schema.
+    fn create_file(&self, schema: &ArtifactSchema) -> csv::Writer<File> {
+        let mut file = File::create(format!("{}/{}", self.data_files_path, schema.file_name))
+            .expect("could not create data file");
+        writeln!(file, "{}", schema.version_line()).expect("Writing did not succeed");
+        let mut wtr = csv::Writer::from_writer(file);
+        wtr.write_record(schema.header(self.nodes_len))
+            .expect("Writing did not succeed");
+        wtr
+    }
+
+    pub fn write_to_file(&self) {
         // CSV of the round switch
-        let headers: Vec<_> = (0..self.nodes_len).collect();
-        let headers: Vec<String> = headers
-            .iter()
-            .map(|x| format!("node {}", x.to_string()))
-            .collect();
-        wtr.serialize(&headers).expect("writing did not succeed");
+        let mut wtr = self.create_file(&schema::round_switches());
 
-        let max_round = *self.max_round_per_node.iter().max().unwrap() as i32;
-        for round_num in 0..max_round {
-            let mut time_row: Vec<Option<i64>> = Vec::new();
+        for (round_num, round_type) in self.round_types().into_iter().enumerate() {
+            let mut row: Vec<String> = Vec::new();
             for node_num in 0..self.nodes_len {
                 let time = self.nodes_round_switch[node_num]
                     .iter()
-                    .find(|&x| x.0 == round_num as usize);
+                    .find(|&x| x.0 == round_num);
                 match time {
-                    Some(time) => time_row.push(Some((time.1).0)),
-                    None => time_row.push(None),
+                    Some(time) => row.push((time.1).0.to_string()),
+                    None => row.push(String::new()),
                 };
             }
-            wtr.serialize(time_row).expect("Writing did not succeed");
+            row.push(round_type.name().to_string());
+            wtr.write_record(row).expect("Writing did not succeed");
         }
 
-        let mut wtr = csv::Writer::from_path(format!(
-            "{}/{}",
-            self.data_files_path, "number_of_messages.txt"
-        ))
-        .unwrap();
+        let mut wtr = self.create_file(&schema::number_of_messages());
         wtr.serialize(Some(self.message_counter))
             .expect("Writing did not succeed");
     }
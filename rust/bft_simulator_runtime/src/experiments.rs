@@ -0,0 +1,317 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    base_types::{Author, Duration, Round},
+    simulator::{GlobalTime, LoggedMessage, Simulator},
+    ActiveRound, BlockProposer, CommittedChain, DataSyncNode, NotificationKind,
+};
+use std::{collections::BTreeSet, time::Instant};
+
+/// Outcome of `gst_liveness` for one seed.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct GstOutcome {
+    pub seed: u64,
+    /// Number of rounds between GST and the first commit after GST, if any.
+    pub rounds_to_commit: Option<usize>,
+    /// Whether a block was committed within the expected number of rounds.
+    pub is_live: bool,
+}
+
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct GstReport {
+    /// Maximal number of rounds expected between GST and the next commit.
+    pub max_rounds: usize,
+    pub outcomes: Vec<GstOutcome>,
+}
+
+impl GstReport {
+    /// Seeds for which no block was committed within the expected number of rounds.
+    pub fn failures(&self) -> Vec<u64> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| !outcome.is_live)
+            .map(|outcome| outcome.seed)
+            .collect()
+    }
+}
+
+/// Check empirically that a block is committed within `max_rounds` rounds after GST. For each
+/// seed, the simulator created by `make_simulator` holds back all messages before `gst`, then
+/// runs until a new block is committed by some node, until the rounds of all nodes exceed
+/// the bound, or until `timeout` has elapsed after `gst`.
+pub fn gst_liveness<Node, Context, Notification, Request, Response, F>(
+    make_simulator: F,
+    gst: GlobalTime,
+    max_rounds: usize,
+    seeds: &[u64],
+    timeout: Duration,
+) -> GstReport
+where
+    F: Fn(u64) -> Simulator<Node, Context, Notification, Request, Response>,
+    Context: CommittedChain + std::fmt::Debug,
+    Node: BlockProposer<Context>
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>
+        + ActiveRound
+        + std::fmt::Debug,
+    Notification: std::cmp::Ord + std::fmt::Debug + std::clone::Clone + NotificationKind,
+    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
+    Response: std::cmp::Ord + std::fmt::Debug,
+{
+    let mut report = GstReport {
+        max_rounds,
+        outcomes: Vec::new(),
+    };
+    for seed in seeds {
+        let mut sim = make_simulator(*seed).with_gst(gst);
+        sim.loop_until(gst, None);
+        let initial_round = highest_round(&sim);
+        let initial_commits = num_commits(&sim);
+        let mut rounds_to_commit = None;
+        // Check for a new commit after every event.
+        while highest_round(&sim) <= initial_round + max_rounds && sim.step(gst + timeout) {
+            if num_commits(&sim) > initial_commits {
+                rounds_to_commit = Some(highest_round(&sim) - initial_round);
+                break;
+            }
+        }
+        report.outcomes.push(GstOutcome {
+            seed: *seed,
+            rounds_to_commit,
+            is_live: rounds_to_commit.map_or(false, |rounds| rounds <= max_rounds),
+        });
+    }
+    report
+}
+
+fn highest_round<Node, Context, Notification, Request, Response>(
+    sim: &Simulator<Node, Context, Notification, Request, Response>,
+) -> usize
+where
+    Node: ActiveRound,
+{
+    sim.nodes_iter()
+        .map(|(_, node)| node.active_round().0)
+        .max()
+        .unwrap_or(0)
+}
+
+/// Length of the longest committed chain.
+fn num_commits<Node, Context, Notification, Request, Response>(
+    sim: &Simulator<Node, Context, Notification, Request, Response>,
+) -> usize {
+    sim.nodes_iter()
+        .map(|(_, node)| node.commit_times().len())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Parameters of `time_to_first_commit`.
+#[derive(Copy, Clone, Debug)]
+pub struct FirstCommitConfig {
+    /// Number of nodes that must have committed a block.
+    pub quorum: usize,
+    /// Time at which to give up.
+    pub max_clock: GlobalTime,
+}
+
+impl FirstCommitConfig {
+    /// Wait for a quorum of `num_nodes` nodes with equal voting rights, without time limit.
+    pub fn new(num_nodes: usize) -> Self {
+        FirstCommitConfig {
+            quorum: 2 * num_nodes / 3 + 1,
+            max_clock: GlobalTime(i64::MAX),
+        }
+    }
+
+    pub fn with_max_clock(mut self, max_clock: GlobalTime) -> Self {
+        self.max_clock = max_clock;
+        self
+    }
+}
+
+/// Outcome of `time_to_first_commit`, measured from the start of the call.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct FirstCommitReport {
+    /// Simulated time until a quorum of nodes had committed a block, if they did before
+    /// `max_clock`.
+    pub elapsed: Option<Duration>,
+    /// Number of rounds started by the most advanced node.
+    pub rounds: usize,
+    /// Number of messages sent by all nodes.
+    pub messages: usize,
+}
+
+impl std::fmt::Display for FirstCommitReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.elapsed {
+            Some(elapsed) => write!(f, "First commit after {}ms", elapsed)?,
+            None => write!(f, "No commit")?,
+        }
+        write!(f, ", {} rounds, {} messages", self.rounds, self.messages)
+    }
+}
+
+/// Run the simulation until a quorum of nodes has committed a block, e.g. to tune startup and
+/// pacemaker parameters without simulating a full run. Unlike `loop_until`, no data file is
+/// written and the run stops at the first commit, however late `max_clock` is.
+pub fn time_to_first_commit<Node, Context, Notification, Request, Response>(
+    sim: &mut Simulator<Node, Context, Notification, Request, Response>,
+    config: FirstCommitConfig,
+) -> FirstCommitReport
+where
+    Context: CommittedChain + std::fmt::Debug,
+    Node: BlockProposer<Context>
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>
+        + ActiveRound
+        + std::fmt::Debug,
+    Notification: std::cmp::Ord + std::fmt::Debug + std::clone::Clone + NotificationKind,
+    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
+    Response: std::cmp::Ord + std::fmt::Debug,
+{
+    let start = sim.current_clock();
+    let initial_round = highest_round(sim);
+    let initial_messages = num_messages(sim);
+    let num_committed = |sim: &Simulator<Node, Context, Notification, Request, Response>| {
+        sim.nodes_iter()
+            .filter(|(_, node)| node.first_commit_time().is_some())
+            .count()
+    };
+    let mut elapsed = None;
+    while sim.step(config.max_clock) {
+        if num_committed(sim) >= config.quorum {
+            elapsed = Some(sim.current_clock().0 - start.0);
+            break;
+        }
+    }
+    FirstCommitReport {
+        elapsed,
+        rounds: highest_round(sim) - initial_round,
+        messages: num_messages(sim) - initial_messages,
+    }
+}
+
+fn num_messages<Node, Context, Notification, Request, Response>(
+    sim: &Simulator<Node, Context, Notification, Request, Response>,
+) -> usize {
+    sim.nodes_iter()
+        .map(|(_, node)| node.message_stats().sent)
+        .sum()
+}
+
+/// A recorded run, to be replayed with `Simulator::replay_with_omission`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct EventTrace {
+    /// Messages delivered during the run, as recorded by the event log.
+    pub messages: Vec<LoggedMessage>,
+    /// Messages dropped during the run, identified by sender, receiver and active round of
+    /// the sender.
+    pub omitted: Vec<(Author, Author, Round)>,
+    /// Time at which the run stops.
+    pub end: GlobalTime,
+}
+
+impl EventTrace {
+    pub fn new(messages: Vec<LoggedMessage>, end: GlobalTime) -> Self {
+        EventTrace {
+            messages,
+            omitted: Vec::new(),
+            end,
+        }
+    }
+
+    /// Delivered messages, identified as omitted ones, in the order they were first sent.
+    fn message_ids(&self) -> Vec<(Author, Author, Round)> {
+        let mut seen = BTreeSet::new();
+        self.messages
+            .iter()
+            .map(|message| (message.sender, message.receiver, message.round))
+            .filter(|id| seen.insert(*id))
+            .collect()
+    }
+}
+
+/// Limits of `minimize`. When the budget is exhausted, the smallest failing trace found so
+/// far is returned.
+#[derive(Copy, Clone, Debug)]
+pub struct MinimizationBudget {
+    /// Maximal number of replays.
+    pub max_replays: usize,
+    /// Maximal wall-clock duration of the search, if any.
+    pub max_duration: Option<std::time::Duration>,
+}
+
+/// Shrink a failing trace by delta debugging: drop chunks of delivered messages, halving the
+/// chunks when no removal preserves the failure, until no single message can be dropped or
+/// the budget is exhausted. Each candidate is replayed on a simulator created by
+/// `make_simulator`, which must be configured as the original run, and `checker` tells whether
+/// the invariant is still violated at the end of the replay. Timer events are never removed.
+pub fn minimize<Node, Context, Notification, Request, Response, F, C>(
+    make_simulator: F,
+    trace: &EventTrace,
+    checker: C,
+    budget: MinimizationBudget,
+) -> EventTrace
+where
+    F: Fn() -> Simulator<Node, Context, Notification, Request, Response>,
+    C: Fn(&Simulator<Node, Context, Notification, Request, Response>) -> bool,
+    Context: CommittedChain + std::fmt::Debug,
+    Node: BlockProposer<Context>
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>
+        + ActiveRound
+        + std::fmt::Debug,
+    Notification: std::cmp::Ord + std::fmt::Debug + std::clone::Clone + NotificationKind,
+    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
+    Response: std::cmp::Ord + std::fmt::Debug,
+{
+    let start = Instant::now();
+    let mut num_replays = 0;
+    let mut current = trace.clone();
+    let mut granularity = 2;
+    loop {
+        let ids = current.message_ids();
+        if ids.is_empty() {
+            break;
+        }
+        let chunk_size = (ids.len() + granularity - 1) / granularity;
+        let mut reduced = false;
+        for chunk in ids.chunks(chunk_size) {
+            if num_replays >= budget.max_replays
+                || budget
+                    .max_duration
+                    .map_or(false, |duration| start.elapsed() >= duration)
+            {
+                return current;
+            }
+            num_replays += 1;
+            let mut omitted = current.omitted.clone();
+            omitted.extend_from_slice(chunk);
+            let mut sim = make_simulator().replay_with_omission(&trace.messages, &omitted);
+            sim.enable_event_log();
+            sim.loop_until(trace.end, None);
+            if checker(&sim) {
+                debug!(
+                    "Replay {}: dropping {} more messages preserves the failure",
+                    num_replays,
+                    chunk.len()
+                );
+                current = EventTrace {
+                    messages: sim.event_log().unwrap().to_vec(),
+                    omitted,
+                    end: trace.end,
+                };
+                reduced = true;
+                break;
+            }
+        }
+        if reduced {
+            granularity = std::cmp::max(granularity - 1, 2);
+        } else if chunk_size == 1 {
+            // Local minimum: no single message can be dropped.
+            break;
+        } else {
+            granularity *= 2;
+        }
+    }
+    current
+}
@@ -2,28 +2,51 @@
 // SPDX-License-Identifier: Apache-2.0
 
 #![allow(bare_trait_objects)]
+// Lints whose suggestions need a newer toolchain than the one of `rust-toolchain`.
+#![allow(
+    unknown_lints,
+    clippy::manual_div_ceil,
+    clippy::manual_is_multiple_of,
+    clippy::manual_slice_fill,
+    clippy::unnecessary_map_or
+)]
 
 #[macro_use]
 extern crate failure;
+#[cfg(feature = "simulator")]
 extern crate rand;
+#[cfg(feature = "simulator")]
 extern crate rand_distr;
+#[cfg(feature = "simulator")]
 #[macro_use]
 extern crate log;
+#[cfg(feature = "simulator")]
 extern crate env_logger;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 // Comments in the following form are used for code-block generation in the consensus report:
 //    "// -- BEGIN FILE name --"
 //    "// -- END FILE --"
 // Do not modify definitions without changing the report as well :)
 
+// Only the traits below, `base_types`, `configuration` and `schema` are needed to implement a
+// node. The other modules make up the simulator.
+#[cfg(feature = "simulator")]
+pub mod analysis;
 pub mod base_types;
 pub mod configuration;
+#[cfg(feature = "simulator")]
 pub mod data_writer;
+#[cfg(feature = "simulator")]
+pub mod experiments;
+pub mod schema;
+#[cfg(feature = "simulator")]
 pub mod simulator;
+#[cfg(feature = "simulator")]
+pub mod trace;
 
-use crate::base_types::{Author, NodeTime, Round};
+use crate::base_types::{Author, Duration, NodeTime, Result, Round};
 
 // -- BEGIN FILE node_update_actions --
 #[derive(Debug)]
@@ -39,6 +62,12 @@ pub struct NodeUpdateActions {
 }
 // -- END FILE --
 
+impl Default for NodeUpdateActions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NodeUpdateActions {
     pub fn new() -> Self {
         NodeUpdateActions {
@@ -56,6 +85,20 @@ pub trait ConsensusNode<Context> {
 }
 // -- END FILE --
 
+/// Nodes whose proposals are created on request of the simulator, which validates them before
+/// they are sent, see `RoundActions::should_propose`.
+pub trait BlockProposer<Context>: ConsensusNode<Context> {
+    type Block;
+    type Record: From<Self::Block> + Validate;
+
+    /// Propose a block for the given round, without sending it. Return `None` if we are not the
+    /// leader of the round or if there is no command to propose.
+    fn create_block(&mut self, round: Round, context: &mut Context) -> Option<Self::Block>;
+    /// Send our proposal once validated, as part of the update that requested it, e.g. keep it
+    /// for the notifications of the update and vote for it.
+    fn send_block(&mut self, record: Self::Record, context: &mut Context);
+}
+
 // -- BEGIN FILE data_sync_node --
 pub trait DataSyncNode<Context> {
     type Notification;
@@ -64,23 +107,181 @@ pub trait DataSyncNode<Context> {
 
     /// Sender role: what to send to initiate a data-synchronization exchange with a receiver.
     fn create_notification(&self) -> Self::Notification;
+    /// Sender role: what to send to a given receiver. Override to tailor notifications to each
+    /// receiver.
+    fn create_notification_for(&self, _receiver: Author) -> Self::Notification {
+        self.create_notification()
+    }
     /// Query role: what to send to initiate a query exchange and obtain data from a sender.
     fn create_request(&self) -> Self::Request;
     /// Sender role: handle a request from a receiver.
     fn handle_request(&self, request: Self::Request) -> Self::Response;
+    /// Sender role: handle a request from a given receiver. Override to tailor responses to
+    /// each receiver.
+    fn handle_request_from(&self, _receiver: Author, request: Self::Request) -> Self::Response {
+        self.handle_request(request)
+    }
+    /// Sender role: whether requests from the given receiver are served after the others.
+    fn deprioritizes_requests_from(&self, _receiver: Author) -> bool {
+        false
+    }
+    /// Sender role: whether requests from the given receiver are answered without data.
+    fn refuses_requests_from(&self, _receiver: Author) -> bool {
+        false
+    }
     /// Receiver role: accept or refuse a notification.
     fn handle_notification(
         &mut self,
         notification: Self::Notification,
         context: &mut Context,
     ) -> Option<Self::Request>;
+    /// Receiver role: accept or refuse notifications received at the same time. Return one
+    /// optional request per notification.
+    fn handle_notifications(
+        &mut self,
+        notifications: Vec<Self::Notification>,
+        context: &mut Context,
+    ) -> Vec<Option<Self::Request>> {
+        notifications
+            .into_iter()
+            .map(|notification| self.handle_notification(notification, context))
+            .collect()
+    }
     /// Receiver role: receive data.
     fn handle_response(&mut self, response: Self::Response, context: &mut Context, clock: NodeTime);
 }
 // -- END FILE --
 
+/// Classification of notifications, used by the simulator to apply network conditions to
+/// specific kinds of messages.
+pub trait NotificationKind {
+    /// Whether the notification is meant to propagate a new quorum certificate.
+    fn announces_quorum_certificate(&self) -> bool {
+        false
+    }
+}
+
+/// Estimated size of a message once serialized, used by the simulator to model the bandwidth
+/// of the network, see `simulator::NetworkCore`.
+pub trait MessageSize {
+    fn message_size(&self) -> usize;
+
+    /// Number of records carried by the message, used by the simulator to model the processing
+    /// cost of the receiver, see `simulator::Simulator::with_node_cpu_budget`.
+    fn num_records(&self) -> usize {
+        0
+    }
+}
+
+impl<T: MessageSize> MessageSize for Vec<T> {
+    fn message_size(&self) -> usize {
+        self.iter().map(MessageSize::message_size).sum()
+    }
+
+    fn num_records(&self) -> usize {
+        self.iter().map(MessageSize::num_records).sum()
+    }
+}
+
+/// Timeout of the consensus protocol chosen for the whole simulation, see
+/// `simulator::Simulator::with_consensus_timeout`.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub struct ConsensusTimeoutHint(pub Duration);
+
+/// Contexts and nodes that use a consensus timeout chosen by the simulator. Hints are ignored
+/// by default.
+pub trait ConsensusTimeout {
+    /// Receive the hint. A new context receives it before its node is created, and a new node
+    /// right after.
+    fn set_consensus_timeout_hint(&mut self, _hint: ConsensusTimeoutHint) {}
+
+    /// Latest hint received, if any.
+    fn consensus_timeout_hint(&self) -> Option<ConsensusTimeoutHint> {
+        None
+    }
+}
+
+/// Part of the state of a node that survives a reboot, e.g. a write-ahead log.
+pub trait DurableState<Context> {
+    type Log;
+
+    /// Highest round whose block is committed, as far as the node knows.
+    fn highest_committed_round(&self) -> Round;
+    /// What the node persisted about its committed chain, up to the given round, and about its
+    /// votes.
+    fn durable_log(&self, last_committed_round: Round) -> Self::Log;
+    /// Restore a node created afresh from a persisted log.
+    fn recover(&mut self, log: Self::Log, context: &mut Context, clock: NodeTime);
+}
+
+/// Context-free checks on a record, e.g. signatures.
+pub trait Validate {
+    fn validate(&self) -> Result<()>;
+}
+
 pub trait ActiveRound {
     fn active_round(&self) -> Round;
+
+    /// Number of rounds whose block was proposed but is not committed yet, as far as the node
+    /// knows.
+    fn in_flight_rounds(&self) -> usize {
+        0
+    }
+
+    /// Rounds in which the node detected an equivocation, e.g. two blocks signed by the same
+    /// leader, as far as the node knows.
+    fn equivocation_rounds(&self) -> Vec<Round> {
+        Vec::new()
+    }
+
+    /// Number of rounds in which the node timed out so far.
+    fn num_timeouts(&self) -> usize {
+        0
+    }
+
+    /// Highest round whose block is committed, as far as the node knows, or `None` if the node
+    /// does not track commits by round.
+    fn committed_round(&self) -> Option<Round> {
+        None
+    }
+
+    /// Maximal duration of the active round before timing out, or `None` if the node does not
+    /// time out rounds.
+    fn current_timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// What the latest call to `update_node` did to the rounds of the node.
+    fn round_actions(&self) -> RoundActions {
+        RoundActions::default()
+    }
+}
+
+/// Round changes made by an update of a node, see `ActiveRound::round_actions`.
+#[derive(Eq, PartialEq, Copy, Clone, Default, Debug)]
+pub struct RoundActions {
+    /// Round that we just timed out, if any.
+    pub on_timeout: Option<Round>,
+    /// Whether we need to propose a block for the active round, see `BlockProposer`.
+    pub should_propose: bool,
+}
+
+pub trait CommittedChain {
+    type Entry: Eq + std::fmt::Debug;
+
+    /// Entries committed so far, in order of execution.
+    fn committed_chain(&self) -> &[Self::Entry];
+
+    /// Author of an entry and local time of the author when the entry was proposed, if known.
+    fn proposal(&self, _entry: &Self::Entry) -> Option<(Author, NodeTime)> {
+        None
+    }
+
+    /// Total time spent executing entries so far, committed or not. Messages of an update are
+    /// sent once the execution of the update completes.
+    fn execution_time(&self) -> Duration {
+        0
+    }
 }
 
 #[derive(Eq, PartialEq, Clone, Debug)]
@@ -88,4 +289,10 @@ pub trait ActiveRound {
 pub struct EpochConfiguration {
     voting_rights: BTreeMap<Author, usize>,
     total_votes: usize,
+    /// Maximal size of the payload of a block, if any.
+    max_block_bytes: Option<usize>,
+    /// Whether QCs carry one aggregated signature instead of one signature per vote.
+    aggregated_signatures: bool,
+    /// Validators that participated too little before the epoch, as measured by the execution.
+    inactive_authors: BTreeSet<Author>,
 }
@@ -0,0 +1,296 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use std::fmt;
+
+#[cfg(all(test, feature = "simulator"))]
+#[path = "unit_tests/schema_tests.rs"]
+mod schema_tests;
+
+/// Version of the format of an output file. Within a major version, changes are additive
+/// only: columns may be appended, but never removed, renamed or retyped.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+pub struct SchemaVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl fmt::Display for SchemaVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ColumnType {
+    Integer,
+    /// An integer, or an empty field (`null` in JSON).
+    OptionalInteger,
+    Float,
+    /// A float, or an empty field (`null` in JSON).
+    OptionalFloat,
+    Boolean,
+    /// A list of integers, e.g. of authors.
+    IntegerList,
+    /// A list of `[time, value]` pairs, e.g. a measure over successive windows of time.
+    TimeSeries,
+    /// A string among a fixed set of values.
+    Text,
+    /// A string among a fixed set of values, or an empty field (`null` in JSON).
+    OptionalText,
+}
+
+impl ColumnType {
+    fn name(self) -> &'static str {
+        match self {
+            ColumnType::Integer => "integer",
+            ColumnType::OptionalInteger => "optional_integer",
+            ColumnType::Float => "float",
+            ColumnType::OptionalFloat => "optional_float",
+            ColumnType::Boolean => "boolean",
+            ColumnType::IntegerList => "integer_list",
+            ColumnType::TimeSeries => "time_series",
+            ColumnType::Text => "text",
+            ColumnType::OptionalText => "optional_text",
+        }
+    }
+}
+
+/// Encoding of an output file.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum ArtifactFormat {
+    /// A comment line holding the version, followed by a header row and the rows.
+    Csv,
+    /// A single JSON object whose first field is `schema_version`, followed by the columns.
+    Json,
+    /// A first JSON object holding `schema_version`, followed by one JSON object per line.
+    JsonLines,
+}
+
+impl ArtifactFormat {
+    fn name(self) -> &'static str {
+        match self {
+            ArtifactFormat::Csv => "csv",
+            ArtifactFormat::Json => "json",
+            ArtifactFormat::JsonLines => "jsonl",
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct Column {
+    /// Name of the column, or of the field in JSON files. For columns repeated for each node,
+    /// `{node}` stands for the index of the node.
+    pub name: &'static str,
+    pub column_type: ColumnType,
+    /// Whether the column is repeated for each node.
+    pub per_node: bool,
+}
+
+/// Descriptor of a file written by the simulator, see `ArtifactFormat` for the embedding of
+/// the version.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ArtifactSchema {
+    pub name: &'static str,
+    pub file_name: &'static str,
+    pub format: ArtifactFormat,
+    pub version: SchemaVersion,
+    pub columns: Vec<Column>,
+}
+
+impl ArtifactSchema {
+    /// Start of the file: the first line of CSV and JSON lines files, or the beginning of the
+    /// single line of JSON files.
+    pub fn version_line(&self) -> String {
+        match self.format {
+            ArtifactFormat::Csv => format!("# schema_version: {}", self.version),
+            ArtifactFormat::Json => format!("{{\"schema_version\": \"{}\"", self.version),
+            ArtifactFormat::JsonLines => format!("{{\"schema_version\": \"{}\"}}", self.version),
+        }
+    }
+
+    /// Header row of the file for the given number of nodes.
+    pub fn header(&self, num_nodes: usize) -> Vec<String> {
+        let mut header = Vec::new();
+        for column in &self.columns {
+            if column.per_node {
+                for index in 0..num_nodes {
+                    header.push(column.name.replace("{node}", &index.to_string()));
+                }
+            } else {
+                header.push(column.name.to_string());
+            }
+        }
+        header
+    }
+
+    pub fn to_json(&self) -> String {
+        let columns: Vec<_> = self
+            .columns
+            .iter()
+            .map(|column| {
+                format!(
+                    "{{\"name\": \"{}\", \"type\": \"{}\", \"per_node\": {}}}",
+                    column.name,
+                    column.column_type.name(),
+                    column.per_node
+                )
+            })
+            .collect();
+        format!(
+            "{{\"name\": \"{}\", \"file_name\": \"{}\", \"format\": \"{}\", \"schema_version\": \"{}\", \"columns\": [{}]}}",
+            self.name,
+            self.file_name,
+            self.format.name(),
+            self.version,
+            columns.join(", ")
+        )
+    }
+}
+
+/// Time at which each node entered each round, one row per round, followed by the outcome of
+/// the round (`Fast`, `Slow` or `Timeout`).
+pub fn round_switches() -> ArtifactSchema {
+    ArtifactSchema {
+        name: "round_switches",
+        file_name: "round_switches.txt",
+        format: ArtifactFormat::Csv,
+        version: SchemaVersion { major: 1, minor: 1 },
+        columns: vec![
+            Column {
+                name: "node {node}",
+                column_type: ColumnType::OptionalInteger,
+                per_node: true,
+            },
+            Column {
+                name: "round_type",
+                column_type: ColumnType::Text,
+                per_node: false,
+            },
+        ],
+    }
+}
+
+/// Total number of messages delivered during the simulation.
+pub fn number_of_messages() -> ArtifactSchema {
+    ArtifactSchema {
+        name: "number_of_messages",
+        file_name: "number_of_messages.txt",
+        format: ArtifactFormat::Csv,
+        version: SchemaVersion { major: 1, minor: 0 },
+        columns: vec![Column {
+            name: "messages",
+            column_type: ColumnType::Integer,
+            per_node: false,
+        }],
+    }
+}
+
+fn column(name: &'static str, column_type: ColumnType) -> Column {
+    Column {
+        name,
+        column_type,
+        per_node: false,
+    }
+}
+
+/// Key results of the simulation, see `SimulationSummary::to_json`.
+pub fn report() -> ArtifactSchema {
+    ArtifactSchema {
+        name: "report",
+        file_name: "report.json",
+        format: ArtifactFormat::Json,
+        version: SchemaVersion { major: 1, minor: 1 },
+        columns: vec![
+            column("duration", ColumnType::Integer),
+            column("rounds_completed", ColumnType::Integer),
+            column("in_flight_rounds", ColumnType::Integer),
+            column("committed_entries", ColumnType::Integer),
+            column("mean_commit_latency", ColumnType::OptionalFloat),
+            column("p50_commit_latency", ColumnType::OptionalInteger),
+            column("p99_commit_latency", ColumnType::OptionalInteger),
+            column("timeout_rate", ColumnType::Float),
+            column("messages_sent", ColumnType::Integer),
+            column("messages_dropped", ColumnType::Integer),
+            column("safety_violations", ColumnType::Integer),
+            column("crashed_nodes", ColumnType::IntegerList),
+            column("partitioned", ColumnType::Boolean),
+            column("omitted_messages", ColumnType::Integer),
+            column("byzantine_fault_rate", ColumnType::OptionalFloat),
+            column("round_inflations", ColumnType::OptionalInteger),
+            column("peak_core_utilization", ColumnType::OptionalFloat),
+            column("core_utilization", ColumnType::TimeSeries),
+        ],
+    }
+}
+
+/// Records kept by a bounded trace, see `BoundedTrace::write`. The `record` field is one of
+/// `Event`, `RoundChange`, `Timeout` and `Fault`, and `kind` is the kind of the event or of
+/// the fault.
+pub fn trace() -> ArtifactSchema {
+    ArtifactSchema {
+        name: "trace",
+        file_name: "trace.jsonl",
+        format: ArtifactFormat::JsonLines,
+        version: SchemaVersion { major: 1, minor: 0 },
+        columns: vec![
+            column("time", ColumnType::Integer),
+            column("record", ColumnType::Text),
+            column("author", ColumnType::OptionalInteger),
+            column("round", ColumnType::OptionalInteger),
+            column("kind", ColumnType::OptionalText),
+        ],
+    }
+}
+
+/// Outcome of each round, as seen by one node.
+pub fn round_table() -> ArtifactSchema {
+    ArtifactSchema {
+        name: "round_table",
+        file_name: "round_table.txt",
+        format: ArtifactFormat::Csv,
+        version: SchemaVersion { major: 1, minor: 0 },
+        columns: vec![
+            column("round", ColumnType::Integer),
+            column("leader", ColumnType::Integer),
+            column("outcome", ColumnType::Text),
+            column("commit_latency", ColumnType::OptionalInteger),
+            column("votes", ColumnType::Integer),
+        ],
+    }
+}
+
+/// Votes, missed rounds and timeouts of each node.
+pub fn participation() -> ArtifactSchema {
+    ArtifactSchema {
+        name: "participation",
+        file_name: "participation.txt",
+        format: ArtifactFormat::Csv,
+        version: SchemaVersion { major: 1, minor: 0 },
+        columns: vec![
+            column("author", ColumnType::Integer),
+            column("voted_rounds", ColumnType::Integer),
+            column("missed_rounds", ColumnType::Integer),
+            column("timed_out_rounds", ColumnType::Integer),
+            column("qcs_included", ColumnType::Integer),
+        ],
+    }
+}
+
+/// Descriptors of all the files written by the simulator.
+pub fn current() -> Vec<ArtifactSchema> {
+    vec![
+        round_switches(),
+        number_of_messages(),
+        report(),
+        trace(),
+        round_table(),
+        participation(),
+    ]
+}
+
+/// JSON array of descriptors, e.g. for code generation in analysis pipelines.
+pub fn to_json(schemas: &[ArtifactSchema]) -> String {
+    let schemas: Vec<_> = schemas.iter().map(ArtifactSchema::to_json).collect();
+    format!("[{}]", schemas.join(", "))
+}
@@ -1,13 +1,27 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
+use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
 use rand_distr::{Distribution, LogNormal};
-use std::collections::{BinaryHeap, HashSet};
+use std::{
+    cell::RefCell,
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+    fs,
+    hash::{Hash, Hasher},
+    io,
+    ops::Range,
+    path::Path,
+    rc::Rc,
+};
 
 use crate::{
-    base_types::{Author, Duration, NodeTime, Round},
+    base_types::{Author, Duration, NodeTime, Round, StableHasher},
     data_writer::*,
-    ActiveRound, ConsensusNode, DataSyncNode, NodeUpdateActions,
+    schema,
+    trace::{BoundedTrace, TraceRecord},
+    ActiveRound, BlockProposer, CommittedChain, ConsensusTimeout, ConsensusTimeoutHint,
+    DataSyncNode, DurableState, MessageSize, NodeUpdateActions, NotificationKind, Validate,
 };
 
 #[cfg(test)]
@@ -15,7 +29,7 @@ use crate::{
 mod simulator_tests;
 
 // Simulated global clock
-#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
 pub struct GlobalTime(pub i64);
 
 impl std::ops::Add<Duration> for GlobalTime {
@@ -28,24 +42,104 @@ impl std::ops::Add<Duration> for GlobalTime {
 
 #[derive(Copy, Clone)]
 pub struct RandomDelay {
-    distribution: LogNormal<f64>,
+    mean: f64,
+    variance: f64,
+    /// Log-normal distribution of the delays, or `None` if the variance is zero.
+    distribution: Option<LogNormal<f64>>,
+    /// Bounds applied to sampled delays, if any.
+    min_delay: Option<Duration>,
+    max_delay: Option<Duration>,
 }
 
 impl RandomDelay {
     pub fn new(mean: f64, variance: f64) -> RandomDelay {
-        // https://en.wikipedia.org/wiki/Log-normal_distribution
-        let mu = f64::ln(mean / f64::sqrt(1.0 + variance / (mean * mean)));
-        let sigma = f64::sqrt(f64::ln(1.0 + variance / (mean * mean)));
+        assert!(
+            mean >= 0.0 && variance >= 0.0,
+            "The mean and the variance of delays must be non-negative"
+        );
+        assert!(
+            mean > 0.0 || variance == 0.0,
+            "Delays with a zero mean cannot vary"
+        );
+        let distribution = if variance > 0.0 {
+            // https://en.wikipedia.org/wiki/Log-normal_distribution
+            let mu = f64::ln(mean / f64::sqrt(1.0 + variance / (mean * mean)));
+            let sigma = f64::sqrt(f64::ln(1.0 + variance / (mean * mean)));
+            Some(LogNormal::new(mu, sigma).unwrap())
+        } else {
+            None
+        };
         RandomDelay {
-            distribution: LogNormal::new(mu, sigma).unwrap(),
+            mean,
+            variance,
+            distribution,
+            min_delay: None,
+            max_delay: None,
+        }
+    }
+
+    /// A degenerate distribution always returning `delay`.
+    pub fn constant(delay: f64) -> RandomDelay {
+        RandomDelay::new(delay, 0.0)
+    }
+
+    /// Raise sampled delays below `min_delay` to `min_delay`, e.g. to rule out near-zero delays.
+    pub fn with_min_delay(mut self, min_delay: Duration) -> RandomDelay {
+        self.min_delay = Some(min_delay);
+        self
+    }
+
+    /// Cap sampled delays at `max_delay`, e.g. to rule out the extreme outliers of the
+    /// log-normal distribution.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> RandomDelay {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        let value = match &self.distribution {
+            Some(distribution) => distribution.sample(rng),
+            None => self.mean,
+        };
+        let mut delay = value.round() as Duration;
+        if let Some(min_delay) = self.min_delay {
+            delay = std::cmp::max(delay, min_delay);
+        }
+        if let Some(max_delay) = self.max_delay {
+            delay = std::cmp::min(delay, max_delay);
         }
+        delay
+    }
+}
+
+impl Hash for RandomDelay {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.mean.to_bits().hash(state);
+        self.variance.to_bits().hash(state);
+        self.min_delay.hash(state);
+        self.max_delay.hash(state);
     }
 }
 
 impl GlobalTime {
-    fn add_delay(self, delay: RandomDelay) -> GlobalTime {
-        let v = delay.distribution.sample(&mut rand::thread_rng());
-        GlobalTime(self.0 + (v as i64))
+    fn add_delay<R: Rng>(self, delay: RandomDelay, rng: &mut R) -> GlobalTime {
+        self + delay.sample(rng)
+    }
+
+    /// Add a random delay and a uniform noise bounded by `jitter`, without going back in time.
+    fn add_jittered_delay<R: Rng>(
+        self,
+        delay: RandomDelay,
+        jitter: f64,
+        rng: &mut R,
+    ) -> GlobalTime {
+        let deadline = self.add_delay(delay, rng);
+        if jitter > 0.0 {
+            let jitter = rng.gen_range(-jitter, jitter);
+            std::cmp::max(self, deadline + jitter.round() as Duration)
+        } else {
+            deadline
+        }
     }
 
     fn to_node_time(self, startup_time: GlobalTime) -> NodeTime {
@@ -57,6 +151,61 @@ impl GlobalTime {
     }
 }
 
+/// Seeded random generator that counts the 32-bit words drawn so far, so that a generator
+/// with the same seed can be fast-forwarded to the same state.
+#[derive(Clone, Debug)]
+pub struct CountingRng {
+    inner: StdRng,
+    position: u64,
+}
+
+impl CountingRng {
+    pub fn seed_from_u64(seed: u64) -> Self {
+        CountingRng {
+            inner: StdRng::seed_from_u64(seed),
+            position: 0,
+        }
+    }
+
+    /// Number of 32-bit words drawn since the generator was seeded.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Discard the next `num_words` words, as if they had been drawn.
+    pub fn advance(&mut self, num_words: u64) {
+        for _ in 0..num_words {
+            self.next_u32();
+        }
+    }
+}
+
+impl RngCore for CountingRng {
+    fn next_u32(&mut self) -> u32 {
+        self.position += 1;
+        self.inner.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        // The underlying block generator draws the two halves as consecutive words.
+        self.position += 2;
+        self.inner.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        // Draw whole words one at a time, so that the position stays exact.
+        for chunk in dest.chunks_mut(4) {
+            let bytes = self.next_u32().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
 #[derive(Eq, PartialEq, Ord, PartialOrd, Debug)]
 pub enum Event<Notification, Request, Response> {
     DataSyncNotifyEvent {
@@ -79,289 +228,4478 @@ pub enum Event<Notification, Request, Response> {
     },
 }
 
-#[derive(Eq, PartialEq, Ord, PartialOrd)]
-struct ScheduledEvent<Notification, Request, Response>(
-    std::cmp::Reverse<GlobalTime>,
-    Event<Notification, Request, Response>,
-);
+impl<Notification, Request, Response> Event<Notification, Request, Response> {
+    /// The node in charge of handling the event, e.g. the responder of a request.
+    pub fn destination(&self) -> Author {
+        match self {
+            Event::DataSyncNotifyEvent { receiver, .. } => *receiver,
+            Event::DataSyncRequestEvent { sender, .. } => *sender,
+            Event::DataSyncResponseEvent { receiver, .. } => *receiver,
+            Event::UpdateTimerEvent { author } => *author,
+        }
+    }
 
-type PendingEvents<Notification, Request, Response> =
-    BinaryHeap<ScheduledEvent<Notification, Request, Response>>;
+    /// The node that sent the message, if the event is a message.
+    pub fn source(&self) -> Option<Author> {
+        match self {
+            Event::DataSyncNotifyEvent { sender, .. } => Some(*sender),
+            Event::DataSyncRequestEvent { receiver, .. } => Some(*receiver),
+            Event::DataSyncResponseEvent { sender, .. } => Some(*sender),
+            Event::UpdateTimerEvent { .. } => None,
+        }
+    }
 
-#[derive(Debug)]
-pub struct SimulatedNode<Node, Context> {
-    startup_time: GlobalTime,
-    ignore_scheduled_updates_until: GlobalTime,
-    node: Node,
-    context: Context,
-}
+    /// The same event without its payload, e.g. to describe it once it is processed.
+    pub fn header(&self) -> Event<(), (), ()> {
+        match self {
+            Event::DataSyncNotifyEvent {
+                receiver, sender, ..
+            } => Event::DataSyncNotifyEvent {
+                receiver: *receiver,
+                sender: *sender,
+                notification: (),
+            },
+            Event::DataSyncRequestEvent {
+                receiver, sender, ..
+            } => Event::DataSyncRequestEvent {
+                receiver: *receiver,
+                sender: *sender,
+                request: (),
+            },
+            Event::DataSyncResponseEvent {
+                receiver, sender, ..
+            } => Event::DataSyncResponseEvent {
+                receiver: *receiver,
+                sender: *sender,
+                response: (),
+            },
+            Event::UpdateTimerEvent { author } => Event::UpdateTimerEvent { author: *author },
+        }
+    }
 
-impl<Node, Context> SimulatedNode<Node, Context>
-where
-    Node: ConsensusNode<Context>,
-{
-    fn update(&mut self, global_clock: GlobalTime) -> NodeUpdateActions {
-        let local_clock = global_clock.to_node_time(self.startup_time);
-        self.node.update_node(local_clock, &mut self.context)
+    /// Name of the variant, for diagnostics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::DataSyncNotifyEvent { .. } => "DataSyncNotifyEvent",
+            Event::DataSyncRequestEvent { .. } => "DataSyncRequestEvent",
+            Event::DataSyncResponseEvent { .. } => "DataSyncResponseEvent",
+            Event::UpdateTimerEvent { .. } => "UpdateTimerEvent",
+        }
     }
 }
 
-impl<Node, Context> SimulatedNode<Node, Context>
+impl<Notification, Request, Response> Event<Notification, Request, Response>
 where
-    Node: ActiveRound,
+    Notification: MessageSize,
+    Request: MessageSize,
+    Response: MessageSize,
 {
-    pub fn active_round(&self) -> Round {
-        self.node.active_round()
+    /// Size of the message once serialized, or zero for timers.
+    pub fn message_size(&self) -> usize {
+        match self {
+            Event::DataSyncNotifyEvent { notification, .. } => notification.message_size(),
+            Event::DataSyncRequestEvent { request, .. } => request.message_size(),
+            Event::DataSyncResponseEvent { response, .. } => response.message_size(),
+            Event::UpdateTimerEvent { .. } => 0,
+        }
     }
 }
 
-pub struct Simulator<Node, Context, Notification, Request, Response> {
-    clock: GlobalTime,
-    network_delay: RandomDelay,
-    pending_events: PendingEvents<Notification, Request, Response>,
-    nodes: Vec<SimulatedNode<Node, Context>>,
+/// Time needed by a node to produce a response after receiving a request, e.g. to read
+/// records from storage. This comes on top of the network delay.
+#[derive(Copy, Clone, Hash)]
+pub enum ResponseLatency {
+    Constant(Duration),
+    Random(RandomDelay),
 }
 
-impl<Node, Context, Notification, Request, Response>
-    Simulator<Node, Context, Notification, Request, Response>
-where
-    Notification: std::cmp::Ord + std::fmt::Debug,
-    Request: std::cmp::Ord + std::fmt::Debug,
-    Response: std::cmp::Ord + std::fmt::Debug,
-{
-    pub fn new<F, G>(
-        num_nodes: usize,
-        network_delay: RandomDelay,
-        context_factory: F,
-        node_factory: G,
-    ) -> Simulator<Node, Context, Notification, Request, Response>
-    where
-        F: Fn(Author, usize) -> Context,
-        G: Fn(Author, &Context, NodeTime) -> Node,
-    {
-        let clock = GlobalTime(0);
-        let mut pending_events = BinaryHeap::new();
-        let nodes = (0..num_nodes)
-            .map(|index| {
-                let author = Author(index);
-                let context = context_factory(author, num_nodes);
-                let startup_time = clock.add_delay(network_delay) + 1;
-                let node_time = NodeTime(0);
-                let deadline = GlobalTime::from_node_time(node_time, startup_time);
-                let event = Event::UpdateTimerEvent { author };
-                trace!(
-                    "Scheduling initial event {:?} for time {:?}",
-                    event,
-                    deadline
-                );
-                pending_events.push(ScheduledEvent(std::cmp::Reverse(deadline), event));
-                SimulatedNode {
-                    startup_time,
-                    ignore_scheduled_updates_until: startup_time + (-1),
-                    node: node_factory(author, &context, node_time),
-                    context,
-                }
-            })
-            .collect();
-        Simulator {
-            clock,
-            network_delay,
-            pending_events,
-            nodes,
+impl ResponseLatency {
+    fn sample<R: Rng>(&self, rng: &mut R) -> Duration {
+        match self {
+            ResponseLatency::Constant(latency) => *latency,
+            ResponseLatency::Random(delay) => delay.sample(rng),
         }
     }
+}
 
-    fn schedule_event(
-        &mut self,
-        deadline: GlobalTime,
-        event: Event<Notification, Request, Response>,
-    ) {
-        trace!("Scheduling event {:?} for {:?}", event, deadline);
-        self.pending_events
-            .push(ScheduledEvent(std::cmp::Reverse(deadline), event));
+/// Accumulated latencies, keeping the time spent on the network separate from the time spent
+/// by responders.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct LatencyStats {
+    pub network_messages: usize,
+    pub total_network_delay: Duration,
+    pub responses: usize,
+    pub total_response_latency: Duration,
+}
+
+impl LatencyStats {
+    pub fn mean_network_delay(&self) -> f64 {
+        self.total_network_delay as f64 / std::cmp::max(self.network_messages, 1) as f64
     }
 
-    fn schedule_network_event(&mut self, event: Event<Notification, Request, Response>) {
-        let deadline = self.clock.add_delay(self.network_delay);
-        self.schedule_event(deadline, event);
+    pub fn mean_response_latency(&self) -> f64 {
+        self.total_response_latency as f64 / std::cmp::max(self.responses, 1) as f64
     }
 }
 
-impl<Node, Context, Notification, Request, Response>
-    Simulator<Node, Context, Notification, Request, Response>
-{
-    pub fn simulated_node(&self, author: Author) -> &SimulatedNode<Node, Context> {
-        self.nodes.get(author.0).unwrap()
+/// Latencies injected on the responders, and latencies observed so far.
+#[derive(Default)]
+struct Latencies {
+    responses: HashMap<Author, ResponseLatency>,
+    stats: LatencyStats,
+}
+
+impl Latencies {
+    /// Time needed by a node to produce a response, if its responses are delayed.
+    fn sample_response_latency<R: Rng>(&self, author: Author, rng: &mut R) -> Duration {
+        match self.responses.get(&author) {
+            Some(latency) => latency.sample(rng),
+            None => 0,
+        }
     }
 
-    fn simulated_node_mut(&mut self, author: Author) -> &mut SimulatedNode<Node, Context> {
-        self.nodes.get_mut(author.0).unwrap()
+    fn record_network_delay(&mut self, delay: Duration) {
+        self.stats.network_messages += 1;
+        self.stats.total_network_delay += delay;
+    }
+
+    fn record_response_latency(&mut self, latency: Duration) {
+        self.stats.responses += 1;
+        self.stats.total_response_latency += latency;
     }
 }
 
-impl<Node, Context, Notification, Request, Response>
-    Simulator<Node, Context, Notification, Request, Response>
-where
-    Context: std::fmt::Debug,
-    Node: ConsensusNode<Context>
-        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>
-        + ActiveRound
-        + std::fmt::Debug,
-    Notification: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
-    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
-    Response: std::cmp::Ord + std::fmt::Debug,
-{
-    fn process_node_actions(
-        &mut self,
-        clock: GlobalTime,
-        author: Author,
-        actions: NodeUpdateActions,
-    ) {
-        debug!(
-            "@{:?} Processing node actions for {:?}: {:?}",
-            clock, author, actions
-        );
-        // Timers
-        let new_deadline = {
-            let mut node = self.nodes.get_mut(author.0).unwrap();
-            let new_deadline = std::cmp::max(
-                GlobalTime::from_node_time(actions.next_scheduled_update, node.startup_time),
-                // Make sure we schedule the update strictly in the future so it does not get
-                // ignored by `ignore_scheduled_updates_until` below.
-                clock + 1,
-            );
-            // We don't remove the previously scheduled updates but this will cancel them.
-            node.ignore_scheduled_updates_until = new_deadline + (-1);
-            new_deadline
-            // scoping the mutable 'node' for the borrow checker
-        };
-        let event = Event::UpdateTimerEvent { author };
-        self.schedule_event(new_deadline, event);
-        // Notifications
-        let mut receivers = HashSet::new();
-        for node in actions.should_send {
-            receivers.insert(node);
+/// When nodes start, relative to the beginning of the simulation.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub enum StartupSchedule {
+    /// Each node starts after a random network delay.
+    Random,
+    /// Nodes start one after the other, in the order of their authors, separated by the given
+    /// duration.
+    Ordered(Duration),
+    /// Each node is ready after a random network delay, then all nodes start at the same time,
+    /// once the last one is ready.
+    Barrier,
+}
+
+/// Counters on the data-synchronization sessions opened by nodes. A session starts when a
+/// request is sent and ends when the corresponding response is received or after a timeout.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct SyncStats {
+    pub opened_sessions: usize,
+    pub completed_sessions: usize,
+    pub timed_out_sessions: usize,
+    /// Number of sessions whose request or response was lost, if sessions do not time out.
+    pub abandoned_sessions: usize,
+    /// Number of requests that had to wait for a session to be available.
+    pub queued_requests: usize,
+    pub max_active_sessions: usize,
+    /// Number of responses held back until the responses to older requests were delivered, see
+    /// `Simulator::with_ordered_sync_responses`.
+    pub buffered_responses: usize,
+    /// Total size of the responses that were held back, if messages are measured, see
+    /// `Simulator::with_message_sizes`.
+    pub buffered_bytes: usize,
+    /// Number of requests whose response was not awaited any longer before delivering the
+    /// responses to newer requests.
+    pub cancelled_sessions: usize,
+}
+
+/// A network message sent during a simulation, as recorded by the event log.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct LoggedMessage {
+    pub send_time: GlobalTime,
+    pub deadline: GlobalTime,
+    pub sender: Author,
+    pub receiver: Author,
+    /// Active round of the sender when the message was sent.
+    pub round: Round,
+    /// See `Event::kind`.
+    pub kind: &'static str,
+}
+
+/// Role of a node in the round of a message, according to a leader oracle.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Debug)]
+pub enum NodeRole {
+    Leader,
+    Validator,
+}
+
+impl std::fmt::Display for NodeRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NodeRole::Leader => write!(f, "Leader"),
+            NodeRole::Validator => write!(f, "Validator"),
         }
-        if actions.should_broadcast {
-            for index in 0..self.nodes.len() {
-                if index != author.0 {
-                    receivers.insert(Author(index));
-                }
-            }
+    }
+}
+
+/// Messages counted by `Simulator::message_complexity`, as (sender role, receiver role, kind).
+pub type MessageClass = (NodeRole, NodeRole, &'static str);
+
+/// An event processed during a simulation, as recorded by the processing trace.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct TraceEntry {
+    pub time: GlobalTime,
+    /// See `Event::kind`.
+    pub kind: &'static str,
+    /// Destination of the event.
+    pub author: Author,
+}
+
+impl std::fmt::Display for TraceEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {}", self.time.0, self.kind, self.author.0)
+    }
+}
+
+/// First difference between a golden trace and the trace of a simulation.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct TraceDivergence {
+    /// Index of the first differing event.
+    pub index: usize,
+    /// Event of the golden trace, if it is not over.
+    pub expected: Option<String>,
+    /// Event of the simulation, if its trace is not over.
+    pub found: Option<String>,
+}
+
+impl std::fmt::Display for TraceDivergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let or_end = |line: &Option<String>| line.clone().unwrap_or_else(|| "<end>".to_string());
+        write!(
+            f,
+            "Traces diverge at event {}: expected {}, found {}",
+            self.index,
+            or_end(&self.expected),
+            or_end(&self.found)
+        )
+    }
+}
+
+/// Sync sessions of a node.
+struct SyncSessions<Request, Response> {
+    /// Responders of the sessions in progress, with the time each session started.
+    active: Vec<(Author, GlobalTime)>,
+    /// Requests waiting for a session to be available, with their responders.
+    queued: VecDeque<(Author, Request)>,
+    /// Requests sent while responses are ordered, oldest first, with their responders, the
+    /// time they were sent, and their response if it arrived before the older responses.
+    ordered: VecDeque<(Author, GlobalTime, Option<Response>)>,
+}
+
+impl<Request, Response> SyncSessions<Request, Response> {
+    fn new() -> Self {
+        SyncSessions {
+            active: Vec::new(),
+            queued: VecDeque::new(),
+            ordered: VecDeque::new(),
         }
-        let notification = self.simulated_node(author).node.create_notification();
-        for receiver in receivers {
-            self.schedule_network_event(Event::DataSyncNotifyEvent {
-                sender: author,
-                receiver,
-                notification: notification.clone(),
-            });
+    }
+}
+
+/// Sync sessions of all the nodes, see `Simulator::with_max_sync_sessions`.
+struct SyncTracker<Request, Response> {
+    /// Maximal number of sync sessions in progress per node, and timeout of a session.
+    max_sessions: Option<(usize, Duration)>,
+    /// How long a node waits for the response to a request before delivering the responses to
+    /// newer requests, if responses are delivered in the order of the requests.
+    ordered_responses: Option<Duration>,
+    sessions: Vec<SyncSessions<Request, Response>>,
+    stats: SyncStats,
+}
+
+impl<Request, Response> SyncTracker<Request, Response> {
+    fn new(num_nodes: usize) -> Self {
+        SyncTracker {
+            max_sessions: None,
+            ordered_responses: None,
+            sessions: (0..num_nodes).map(|_| SyncSessions::new()).collect(),
+            stats: SyncStats::default(),
         }
-        // Queries
-        let mut senders = HashSet::new();
-        if actions.should_query_all {
-            for index in 0..self.nodes.len() {
-                if index != author.0 {
-                    senders.insert(Author(index));
-                }
+    }
+
+    /// Remove the oldest session of `requester` with `responder`, if any.
+    fn remove_session(&mut self, requester: Author, responder: Author) -> bool {
+        let sessions = &mut self.sessions[requester.0];
+        match sessions
+            .active
+            .iter()
+            .position(|(author, _)| *author == responder)
+        {
+            Some(position) => {
+                sessions.active.remove(position);
+                true
             }
-        }
-        let request = self.simulated_node(author).node.create_request();
-        for sender in senders {
-            self.schedule_network_event(Event::DataSyncRequestEvent {
-                receiver: author,
-                sender,
-                request: request.clone(),
-            });
+            None => false,
         }
     }
 
-    pub fn loop_until(&mut self, max_clock: GlobalTime, csv_path: Option<String>) -> Vec<&Context> {
-        let mut data_writer = { csv_path.map(|path| DataWriter::new(self.nodes.len(), path)) };
+    fn expire_sessions(&mut self, requester: Author, clock: GlobalTime) {
+        if let Some((_, timeout)) = self.max_sessions {
+            let sessions = &mut self.sessions[requester.0];
+            let num_sessions = sessions.active.len();
+            sessions
+                .active
+                .retain(|(_, start_time)| clock.0 - start_time.0 < timeout);
+            self.stats.timed_out_sessions += num_sessions - sessions.active.len();
+        }
+    }
+}
 
-        while let Some(ScheduledEvent(std::cmp::Reverse(clock), event)) = self.pending_events.pop()
-        {
-            if clock > max_clock {
-                break;
-            }
+/// Freezes of the nodes and the events buffered for the frozen nodes, see
+/// `Simulator::schedule_freeze`.
+struct Freezes<Notification, Request, Response> {
+    /// Windows `[from, until)` during which a node is frozen.
+    windows: Vec<(Author, GlobalTime, GlobalTime)>,
+    /// Events received by frozen nodes, in order of reception.
+    buffered_events: HashMap<Author, Vec<Event<Notification, Request, Response>>>,
+    /// Time needed by a node to process each buffered event after a freeze.
+    thaw_processing_delay: Duration,
+}
 
-            if let Some(data_writer_val) = data_writer.as_mut() {
-                data_writer_val.update_round_number(&self, &clock);
-                data_writer_val.add_message_counter(&event);
-            }
-
-            // Events scheduled in the past are fine but they do not move the clock.
-            let clock = std::cmp::max(clock, self.clock);
-            self.clock = clock;
-            debug!("@{:?} Processing event {:?}", clock, event);
-            match event {
-                Event::UpdateTimerEvent { author } => {
-                    let actions = {
-                        let node = self.simulated_node_mut(author);
-                        if clock <= node.ignore_scheduled_updates_until {
-                            // This scheduled update was invalidated in the meantime.
-                            debug!("@{:?} Timer was cancelled: {:?}", clock, event);
-                            continue;
-                        }
-                        node.update(clock)
-                    };
-                    trace!("Node state: {:?}", self.simulated_node(author));
-                    self.process_node_actions(clock, author, actions);
-                }
-                Event::DataSyncNotifyEvent {
-                    receiver,
-                    sender,
-                    notification,
-                } => {
-                    let node = self.simulated_node_mut(receiver);
-                    let result = node
-                        .node
-                        .handle_notification(notification, &mut node.context);
-                    let actions = node.update(clock);
-                    if let Some(request) = result {
-                        self.schedule_network_event(Event::DataSyncRequestEvent {
-                            sender,
-                            receiver,
-                            request,
-                        });
-                    }
-                    trace!(
-                        "Node state: {:?}, node index: {:?}",
-                        self.simulated_node(receiver),
-                        receiver
-                    );
-                    self.process_node_actions(clock, receiver, actions);
-                }
-                Event::DataSyncRequestEvent {
-                    receiver,
-                    sender,
-                    request,
-                } => {
-                    let response = self.simulated_node_mut(sender).node.handle_request(request);
-                    self.schedule_network_event(Event::DataSyncResponseEvent {
-                        sender,
-                        receiver,
-                        response,
-                    });
-                }
-                Event::DataSyncResponseEvent {
-                    receiver, response, ..
-                } => {
-                    let node = self.simulated_node_mut(receiver);
-                    let local_clock = clock.to_node_time(node.startup_time);
-                    node.node
-                        .handle_response(response, &mut node.context, local_clock);
-                    let actions = node.update(clock);
-                    trace!("Node state: {:?}", node);
-                    self.process_node_actions(clock, receiver, actions);
-                }
-            }
+impl<Notification, Request, Response> Freezes<Notification, Request, Response> {
+    fn new() -> Self {
+        Freezes {
+            windows: Vec::new(),
+            buffered_events: HashMap::new(),
+            thaw_processing_delay: 0,
         }
+    }
 
-        if let Some(data_writer_val) = data_writer {
-            data_writer_val.write_to_file();
+    fn is_frozen(&self, author: Author, clock: GlobalTime) -> bool {
+        self.windows
+            .iter()
+            .any(|(node, from, until)| *node == author && *from <= clock && clock < *until)
+    }
+}
+
+/// How to handle a request addressed to a crashed node.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub enum CrashedSenderPolicy {
+    /// The request is moved to the dead-letter queue and no response is sent.
+    DeadLetter,
+    /// The request is still answered, e.g. to model a storage layer that survived the crash.
+    Respond,
+}
+
+/// How to handle a message sent to a node whose incoming queue is full.
+#[derive(Eq, PartialEq, Copy, Clone, Hash, Debug)]
+pub enum BackpressurePolicy {
+    /// The message is dropped, as with UDP when the buffer of the receiver overflows.
+    Drop,
+    /// The message is delivered after the given additional delay, as with TCP flow control.
+    Delay(Duration),
+}
+
+/// Bounds of the incoming queues of the nodes, see `Simulator::with_backpressure`.
+#[derive(Debug)]
+struct Inboxes {
+    /// Maximal number of messages in flight toward each node, if bounded.
+    capacities: HashMap<Author, usize>,
+    /// What happens to messages sent beyond the capacity of an inbox.
+    policy: BackpressurePolicy,
+}
+
+impl Inboxes {
+    fn new() -> Self {
+        Inboxes {
+            capacities: HashMap::new(),
+            policy: BackpressurePolicy::Drop,
         }
+    }
 
-        self.nodes.iter().map(|node| &node.context).collect()
+    /// Number of messages in flight above which messages to the node are subject to the
+    /// backpressure policy, if any.
+    fn capacity(&self, author: Author) -> Option<usize> {
+        self.capacities.get(&author).cloned()
     }
 }
+
+/// Assignment of the nodes to shards, each running an independent consensus instance. Authors
+/// without a shard, e.g. observers, communicate with every shard.
+#[derive(Eq, PartialEq, Clone, Default, Hash, Debug)]
+pub struct ShardAssignment {
+    shards: BTreeMap<Author, usize>,
+}
+
+impl ShardAssignment {
+    /// The shard of each author is the index of its group.
+    pub fn new(groups: Vec<Vec<Author>>) -> Self {
+        let mut shards = BTreeMap::new();
+        for (index, group) in groups.into_iter().enumerate() {
+            for author in group {
+                shards.insert(author, index);
+            }
+        }
+        ShardAssignment { shards }
+    }
+
+    /// Split the authors `0..num_authors` into `num_shards` shards of consecutive authors.
+    pub fn contiguous(num_authors: usize, num_shards: usize) -> Self {
+        assert!(num_shards > 0, "There must be at least one shard");
+        let shard_size = (num_authors + num_shards - 1) / num_shards;
+        let groups = (0..num_shards)
+            .map(|shard| {
+                (shard * shard_size..std::cmp::min((shard + 1) * shard_size, num_authors))
+                    .map(Author)
+                    .collect()
+            })
+            .collect();
+        ShardAssignment::new(groups)
+    }
+
+    pub fn shard(&self, author: Author) -> Option<usize> {
+        self.shards.get(&author).cloned()
+    }
+
+    /// Authors of the given shard, in increasing order.
+    pub fn members(&self, shard: usize) -> Vec<Author> {
+        self.shards
+            .iter()
+            .filter(|(_, index)| **index == shard)
+            .map(|(author, _)| *author)
+            .collect()
+    }
+
+    /// Whether two authors take part in the same consensus instance.
+    pub fn same_shard(&self, author1: Author, author2: Author) -> bool {
+        match (self.shard(author1), self.shard(author2)) {
+            (Some(shard1), Some(shard2)) => shard1 == shard2,
+            _ => true,
+        }
+    }
+}
+
+/// Faults of the network that depend on the time at which messages are sent.
+#[derive(Default, Debug)]
+struct NetworkFaults {
+    /// Global stabilization time. Messages sent before it are held by the network until then.
+    gst: Option<GlobalTime>,
+    /// Group of each node while the network is partitioned.
+    partition: Option<BTreeMap<Author, usize>>,
+    /// Start and duration of the gradual recovery of the partition, if any.
+    partition_recovery: Option<(GlobalTime, Duration)>,
+    /// Windows `[from, until)` during which a victim only receives messages from an attacker,
+    /// given as (victim, attacker, from, until).
+    eclipses: Vec<(Author, Author, GlobalTime, GlobalTime)>,
+}
+
+impl NetworkFaults {
+    /// Time until which a message sent at the given time is held, if it is sent before GST.
+    fn held_until(&self, send_time: GlobalTime) -> Option<GlobalTime> {
+        self.gst.filter(|gst| send_time < *gst)
+    }
+
+    /// Probability that a message sent at the given time travels between the two nodes.
+    fn link_up_probability(&self, author1: Author, author2: Author, time: GlobalTime) -> f64 {
+        match &self.partition {
+            None => 1.0,
+            Some(groups) if groups.get(&author1) == groups.get(&author2) => 1.0,
+            Some(_) => match self.partition_recovery {
+                Some((start, ramp)) if time >= start + ramp => 1.0,
+                Some((start, ramp)) if time > start => (time.0 - start.0) as f64 / ramp as f64,
+                _ => 0.0,
+            },
+        }
+    }
+
+    /// Whether some messages sent at the given time are dropped by the partition.
+    fn is_partitioned(&self, time: GlobalTime) -> bool {
+        match (&self.partition, self.partition_recovery) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(_), Some((start, ramp))) => time < start + ramp,
+        }
+    }
+
+    fn is_eclipsed(&self, sender: Author, receiver: Author, clock: GlobalTime) -> bool {
+        self.eclipses.iter().any(|(victim, attacker, from, until)| {
+            *victim == receiver && *attacker != sender && *from <= clock && clock < *until
+        })
+    }
+}
+
+/// Shared bottleneck crossed by every message, e.g. the core of a datacenter network. Messages
+/// reach the core after the sampled network delay, are transmitted one after the other in their
+/// order of arrival at `capacity` bytes per unit of time, and are delivered once transmitted.
+/// The bytes queued ahead of a message thus delay it, and messages leave the core in FIFO
+/// order.
+#[derive(Clone, Debug)]
+pub struct NetworkCore {
+    capacity: f64,
+    /// Length of the windows over which utilization is measured.
+    utilization_window: Duration,
+    /// Time at which the transmission of the messages queued so far completes.
+    busy_until: f64,
+    /// Time spent transmitting in each window, indexed by the start of the window divided by
+    /// its length.
+    busy_time: BTreeMap<i64, f64>,
+    /// Total time that messages waited behind others.
+    total_queueing_delay: f64,
+    num_messages: usize,
+}
+
+impl NetworkCore {
+    pub fn new(capacity: f64) -> Self {
+        assert!(
+            capacity > 0.0,
+            "The capacity of the network core must be positive"
+        );
+        NetworkCore {
+            capacity,
+            utilization_window: 100,
+            busy_until: 0.0,
+            busy_time: BTreeMap::new(),
+            total_queueing_delay: 0.0,
+            num_messages: 0,
+        }
+    }
+
+    pub fn with_utilization_window(mut self, utilization_window: Duration) -> Self {
+        assert!(utilization_window > 0);
+        self.utilization_window = utilization_window;
+        self
+    }
+
+    /// Queue a message of `size` bytes arriving at `time` and return the delay until it has
+    /// been transmitted.
+    fn transmit(&mut self, time: GlobalTime, size: usize) -> Duration {
+        let arrival = time.0 as f64;
+        let start = self.busy_until.max(arrival);
+        let end = start + size as f64 / self.capacity;
+        self.busy_until = end;
+        self.total_queueing_delay += start - arrival;
+        self.num_messages += 1;
+        let window = self.utilization_window as f64;
+        let mut time = start;
+        while time < end {
+            let index = (time / window).floor();
+            let window_end = f64::min(end, (index + 1.0) * window);
+            *self.busy_time.entry(index as i64).or_insert(0.0) += window_end - time;
+            time = window_end;
+        }
+        (end - arrival).ceil() as Duration
+    }
+
+    /// Fraction of each window spent transmitting, from the first window with traffic to the
+    /// last one, together with the start of the window.
+    pub fn utilization(&self) -> Vec<(GlobalTime, f64)> {
+        let (first, last) = match (self.busy_time.keys().next(), self.busy_time.keys().last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return Vec::new(),
+        };
+        let window = self.utilization_window;
+        (first..=last)
+            .map(|index| {
+                let busy_time = self.busy_time.get(&index).cloned().unwrap_or(0.0);
+                (GlobalTime(index * window), busy_time / window as f64)
+            })
+            .collect()
+    }
+
+    /// Highest utilization of a window, if any message was transmitted.
+    pub fn peak_utilization(&self) -> Option<f64> {
+        self.utilization()
+            .into_iter()
+            .map(|(_, utilization)| utilization)
+            .fold(None, |peak, utilization| {
+                Some(peak.map_or(utilization, |peak: f64| peak.max(utilization)))
+            })
+    }
+
+    /// Mean time that a message waited behind others before its own transmission.
+    pub fn mean_queueing_delay(&self) -> f64 {
+        self.total_queueing_delay / std::cmp::max(self.num_messages, 1) as f64
+    }
+}
+
+/// Computational power of the nodes, see `Simulator::with_node_cpu_budget`.
+#[derive(Copy, Clone, Debug)]
+struct CpuBudget {
+    /// Number of records that a node handles per millisecond.
+    ops_per_ms: f64,
+}
+
+impl CpuBudget {
+    /// Time needed by a node to handle the given number of records.
+    fn processing_delay(self, num_records: usize) -> Duration {
+        (num_records as f64 / self.ops_per_ms).round() as Duration
+    }
+}
+
+/// Accessors of `MessageSize`, recorded by the options that need them so that the messages of
+/// other simulations need not implement it.
+struct MessageSizes<Notification, Request, Response> {
+    notification_size: fn(&Notification) -> usize,
+    request_size: fn(&Request) -> usize,
+    response_size: fn(&Response) -> usize,
+    notification_records: fn(&Notification) -> usize,
+    response_records: fn(&Response) -> usize,
+}
+
+impl<Notification, Request, Response> MessageSizes<Notification, Request, Response>
+where
+    Notification: MessageSize,
+    Request: MessageSize,
+    Response: MessageSize,
+{
+    fn new() -> Self {
+        MessageSizes {
+            notification_size: Notification::message_size,
+            request_size: Request::message_size,
+            response_size: Response::message_size,
+            notification_records: Notification::num_records,
+            response_records: Response::num_records,
+        }
+    }
+}
+
+impl<Notification, Request, Response> MessageSizes<Notification, Request, Response> {
+    fn event_size(&self, event: &Event<Notification, Request, Response>) -> usize {
+        match event {
+            Event::DataSyncNotifyEvent { notification, .. } => {
+                (self.notification_size)(notification)
+            }
+            Event::DataSyncRequestEvent { request, .. } => (self.request_size)(request),
+            Event::DataSyncResponseEvent { response, .. } => (self.response_size)(response),
+            Event::UpdateTimerEvent { .. } => 0,
+        }
+    }
+}
+
+/// Consensus timeout given to the contexts and the nodes, see
+/// `Simulator::with_consensus_timeout`.
+struct ConsensusTimeoutConfig<Node, Context> {
+    hint: ConsensusTimeoutHint,
+    set_context_hint: fn(&mut Context, ConsensusTimeoutHint),
+    set_node_hint: fn(&mut Node, ConsensusTimeoutHint),
+}
+
+/// Round-robin processing of simultaneous events across their destinations, see
+/// `Simulator::with_fair_event_processing`.
+struct FairScheduler<Notification, Request, Response> {
+    enabled: bool,
+    /// The events due at `clock` that were taken from `pending_events`, per destination.
+    queues: BTreeMap<Author, VecDeque<Event<Notification, Request, Response>>>,
+    clock: GlobalTime,
+    /// Destination of the last event served at `clock`.
+    last_served: Option<Author>,
+}
+
+impl<Notification, Request, Response> FairScheduler<Notification, Request, Response> {
+    fn new(clock: GlobalTime) -> Self {
+        FairScheduler {
+            enabled: false,
+            queues: BTreeMap::new(),
+            clock,
+            last_served: None,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queues.values().all(VecDeque::is_empty)
+    }
+
+    /// Start serving the events due at the given time.
+    fn start_round(&mut self, clock: GlobalTime) {
+        self.clock = clock;
+        self.last_served = None;
+    }
+
+    fn push(&mut self, event: Event<Notification, Request, Response>) {
+        self.queues
+            .entry(event.destination())
+            .or_default()
+            .push_back(event);
+    }
+
+    /// Take the next event of the first destination after the last one served.
+    fn pop(&mut self) -> Option<(GlobalTime, Event<Notification, Request, Response>)> {
+        let last_served = self.last_served;
+        let destinations: Vec<_> = self
+            .queues
+            .iter()
+            .filter(|(_, queue)| !queue.is_empty())
+            .map(|(author, _)| *author)
+            .collect();
+        let destination = destinations
+            .iter()
+            .find(|author| Some(**author) > last_served)
+            .or_else(|| destinations.first())
+            .cloned()?;
+        self.last_served = Some(destination);
+        let event = self.queues.get_mut(&destination)?.pop_front()?;
+        Some((self.clock, event))
+    }
+
+    /// Events taken from `pending_events` and not processed yet, in round-robin order of their
+    /// destinations.
+    fn schedule(
+        &self,
+    ) -> impl Iterator<Item = (&GlobalTime, &Event<Notification, Request, Response>)> {
+        let (served, next): (Vec<_>, Vec<_>) = self
+            .queues
+            .iter()
+            .partition(|(author, _)| Some(**author) <= self.last_served);
+        let queues: Vec<_> = next
+            .into_iter()
+            .chain(served)
+            .map(|(_, queue)| queue)
+            .collect();
+        let depth = queues.iter().map(|queue| queue.len()).max().unwrap_or(0);
+        let clock = &self.clock;
+        (0..depth)
+            .flat_map(move |index| {
+                queues
+                    .iter()
+                    .filter_map(|queue| queue.get(index))
+                    .collect::<Vec<_>>()
+            })
+            .map(move |event| (clock, event))
+    }
+}
+
+/// Messages of a previous run to deliver at the same times or to drop, see
+/// `Simulator::replay_with_omission`.
+#[derive(Default, Debug)]
+struct Replay {
+    /// Delivery times taken from the previous run, indexed by send time, sender, receiver and
+    /// round.
+    deadlines: HashMap<(GlobalTime, Author, Author, Round), VecDeque<GlobalTime>>,
+    /// Messages to drop, identified by sender, receiver and round.
+    omitted_messages: BTreeSet<(Author, Author, Round)>,
+}
+
+impl Replay {
+    fn new(log: &[LoggedMessage], omit: &[(Author, Author, Round)]) -> Self {
+        let mut deadlines = HashMap::new();
+        for message in log {
+            deadlines
+                .entry((
+                    message.send_time,
+                    message.sender,
+                    message.receiver,
+                    message.round,
+                ))
+                .or_insert_with(VecDeque::new)
+                .push_back(message.deadline);
+        }
+        Replay {
+            deadlines,
+            omitted_messages: omit.iter().cloned().collect(),
+        }
+    }
+
+    /// Delivery time of the next message sent at the given time by `sender` to `receiver` in
+    /// the given round, as in the previous run.
+    fn next_deadline(
+        &mut self,
+        send_time: GlobalTime,
+        sender: Author,
+        receiver: Author,
+        round: Round,
+    ) -> Option<GlobalTime> {
+        self.deadlines
+            .get_mut(&(send_time, sender, receiver, round))
+            .and_then(VecDeque::pop_front)
+    }
+
+    fn is_omitted(&self, sender: Author, receiver: Author, round: Round) -> bool {
+        self.omitted_messages.contains(&(sender, receiver, round))
+    }
+}
+
+/// Peers known by a node that discovers the network after joining, see
+/// `start_peer_discovery`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+struct PeerDiscovery {
+    start_time: GlobalTime,
+    bootstrap_peers: BTreeSet<Author>,
+    /// Period of the discovery tick, at which the node learns one more peer, if any.
+    discovery_period: Option<Duration>,
+    known_peers: BTreeSet<Author>,
+    /// Number of discovery ticks applied so far.
+    num_ticks: usize,
+    /// Time at which the node first knew all the other nodes, if it did.
+    full_connectivity_time: Option<GlobalTime>,
+}
+
+impl PeerDiscovery {
+    fn learn(&mut self, peer: Author, time: GlobalTime, num_peers: usize) {
+        self.known_peers.insert(peer);
+        if self.full_connectivity_time.is_none() && self.known_peers.len() >= num_peers {
+            self.full_connectivity_time = Some(time);
+        }
+    }
+}
+
+/// Peers known by the nodes that discover the network. Other nodes know every peer.
+#[derive(Debug)]
+struct PeerDirectory {
+    num_nodes: usize,
+    discoveries: BTreeMap<Author, PeerDiscovery>,
+}
+
+impl PeerDirectory {
+    fn new(num_nodes: usize) -> Self {
+        PeerDirectory {
+            num_nodes,
+            discoveries: BTreeMap::new(),
+        }
+    }
+
+    /// See `Simulator::start_peer_discovery`.
+    fn start(
+        &mut self,
+        author: Author,
+        bootstrap_peers: Vec<Author>,
+        discovery_period: Option<Duration>,
+        clock: GlobalTime,
+    ) {
+        let bootstrap_peers: BTreeSet<_> = bootstrap_peers
+            .into_iter()
+            .filter(|peer| *peer != author)
+            .collect();
+        let mut discovery = PeerDiscovery {
+            start_time: clock,
+            bootstrap_peers: bootstrap_peers.clone(),
+            discovery_period,
+            known_peers: BTreeSet::new(),
+            num_ticks: 0,
+            full_connectivity_time: None,
+        };
+        for peer in bootstrap_peers {
+            discovery.learn(peer, clock, self.num_nodes - 1);
+        }
+        self.discoveries.insert(author, discovery);
+    }
+
+    fn known_peers(&self, author: Author) -> BTreeSet<Author> {
+        match self.discoveries.get(&author) {
+            Some(discovery) => discovery.known_peers.clone(),
+            None => (0..self.num_nodes)
+                .map(Author)
+                .filter(|peer| *peer != author)
+                .collect(),
+        }
+    }
+
+    fn time_to_full_connectivity(&self, author: Author) -> Option<Duration> {
+        match self.discoveries.get(&author) {
+            Some(discovery) => discovery
+                .full_connectivity_time
+                .map(|time| time.0 - discovery.start_time.0),
+            None => Some(0),
+        }
+    }
+
+    /// Apply the discovery ticks of a node that are due.
+    fn discover_peers(&mut self, author: Author, clock: GlobalTime) {
+        let num_peers = self.num_nodes - 1;
+        let discovery = match self.discoveries.get_mut(&author) {
+            Some(discovery) => discovery,
+            None => return,
+        };
+        let period = match discovery.discovery_period {
+            Some(period) => period,
+            None => return,
+        };
+        let num_ticks = (clock.0 - discovery.start_time.0) / period;
+        while (discovery.num_ticks as Duration) < num_ticks {
+            discovery.num_ticks += 1;
+            let tick_time = discovery.start_time + (discovery.num_ticks as Duration) * period;
+            // Each tick reveals the first peer that is still unknown.
+            let peer = (0..=num_peers)
+                .map(Author)
+                .find(|peer| *peer != author && !discovery.known_peers.contains(peer));
+            if let Some(peer) = peer {
+                discovery.learn(peer, tick_time, num_peers);
+            }
+        }
+    }
+
+    /// Let a node learn the sender of a message that it received.
+    fn learn_peer(&mut self, author: Author, peer: Author, clock: GlobalTime) {
+        self.discover_peers(author, clock);
+        let num_peers = self.num_nodes - 1;
+        if let Some(discovery) = self.discoveries.get_mut(&author) {
+            if !discovery.known_peers.contains(&peer) {
+                discovery.learn(peer, clock, num_peers);
+            }
+        }
+    }
+
+    fn knows_peer(&self, author: Author, peer: Author) -> bool {
+        self.discoveries
+            .get(&author)
+            .map_or(true, |discovery| discovery.known_peers.contains(&peer))
+    }
+}
+
+/// Passive component following a simulation from outside the nodes, e.g. to audit their
+/// decisions or to collect metrics, see `Simulator::add_observer`.
+pub trait Observer<Node, Context> {
+    /// Called after each update of a node, with the actions returned by the update.
+    fn on_update(
+        &mut self,
+        clock: GlobalTime,
+        author: Author,
+        node: &Node,
+        context: &Context,
+        actions: &NodeUpdateActions,
+    );
+
+    /// Records to add to the bounded trace after each update, if any, e.g. the warnings and
+    /// rejections noticed by the observer. See `Simulator::enable_bounded_trace`.
+    fn take_trace_records(&mut self) -> Vec<TraceRecord> {
+        Vec::new()
+    }
+}
+
+/// Active network adversary against data synchronization, see
+/// `Simulator::run_adversarial_sync`.
+pub trait AdversarySyncStrategy<Notification> {
+    /// Called for each notification sent from `sender` to `receiver`. Return the notifications
+    /// to deliver instead, from the same sender, with their receivers: none to drop the
+    /// notification, or several to amplify it, possibly modified.
+    fn intercept_notification(
+        &mut self,
+        sender: Author,
+        receiver: Author,
+        notification: &mut Notification,
+    ) -> Vec<(Author, Notification)>;
+}
+
+/// Adversary holding back the notifications of each link until `batch_size` of them were
+/// sent, then delivering the batch newest first, each notification `num_copies` times.
+#[derive(Debug)]
+pub struct DelayAndReplayAdversary<Notification> {
+    batch_size: usize,
+    num_copies: usize,
+    /// Notifications held back, per (sender, receiver).
+    held: BTreeMap<(Author, Author), Vec<Notification>>,
+}
+
+impl<Notification> DelayAndReplayAdversary<Notification> {
+    pub fn new(batch_size: usize, num_copies: usize) -> Self {
+        assert!(batch_size > 0, "Batches must not be empty");
+        assert!(
+            num_copies > 0,
+            "Notifications must be delivered at least once"
+        );
+        DelayAndReplayAdversary {
+            batch_size,
+            num_copies,
+            held: BTreeMap::new(),
+        }
+    }
+}
+
+impl<Notification> AdversarySyncStrategy<Notification> for DelayAndReplayAdversary<Notification>
+where
+    Notification: Clone,
+{
+    fn intercept_notification(
+        &mut self,
+        sender: Author,
+        receiver: Author,
+        notification: &mut Notification,
+    ) -> Vec<(Author, Notification)> {
+        let held = self.held.entry((sender, receiver)).or_default();
+        held.push(notification.clone());
+        if held.len() < self.batch_size {
+            return Vec::new();
+        }
+        let mut deliveries = Vec::new();
+        for notification in held.drain(..).rev() {
+            for _ in 0..self.num_copies {
+                deliveries.push((receiver, notification.clone()));
+            }
+        }
+        deliveries
+    }
+}
+
+/// Order in which events scheduled at the same time are processed: `Ordering::Less` means
+/// that the first event is processed first.
+pub type EventOrder<Notification, Request, Response> = dyn Fn(
+    &Event<Notification, Request, Response>,
+    &Event<Notification, Request, Response>,
+) -> Ordering;
+
+struct ScheduledEvent<Notification, Request, Response>(
+    std::cmp::Reverse<GlobalTime>,
+    Event<Notification, Request, Response>,
+    /// Custom tie-breaker, if any. By default, simultaneous events are processed in decreasing
+    /// order.
+    Option<Rc<EventOrder<Notification, Request, Response>>>,
+    /// Whether the event was scheduled by a fault injection rather than by the nodes.
+    bool,
+    /// Whether the message is yet to cross the network core, see `cross_network_core`.
+    bool,
+    /// Time at which the message was sent, if the event is a network message.
+    Option<GlobalTime>,
+);
+
+impl<Notification, Request, Response> Ord for ScheduledEvent<Notification, Request, Response>
+where
+    Notification: Ord,
+    Request: Ord,
+    Response: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.cmp(&other.0).then_with(|| match &self.2 {
+            // The heap pops the greatest element first.
+            Some(event_order) => event_order(&other.1, &self.1),
+            None => self.1.cmp(&other.1),
+        })
+    }
+}
+
+impl<Notification, Request, Response> PartialOrd for ScheduledEvent<Notification, Request, Response>
+where
+    Notification: Ord,
+    Request: Ord,
+    Response: Ord,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<Notification, Request, Response> PartialEq for ScheduledEvent<Notification, Request, Response>
+where
+    Notification: Ord,
+    Request: Ord,
+    Response: Ord,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<Notification, Request, Response> Eq for ScheduledEvent<Notification, Request, Response>
+where
+    Notification: Ord,
+    Request: Ord,
+    Response: Ord,
+{
+}
+
+type PendingEvents<Notification, Request, Response> =
+    BinaryHeap<ScheduledEvent<Notification, Request, Response>>;
+
+#[derive(Debug)]
+pub struct SimulatedNode<Node, Context> {
+    startup_time: GlobalTime,
+    ignore_scheduled_updates_until: GlobalTime,
+    crashed: bool,
+    /// Time at which each entry of the committed chain was first observed.
+    commit_times: Vec<GlobalTime>,
+    /// Successive values of the round timeout of the node.
+    timeouts: Vec<Duration>,
+    /// Messages sent by the node, including those dropped by the network.
+    messages_sent: usize,
+    /// Messages delivered to the node while it was up.
+    messages_received: usize,
+    /// Messages sent to the node and not processed yet.
+    in_flight_messages: usize,
+    /// Messages dropped because the inbox of the node was full.
+    dropped_messages: usize,
+    /// Requests answered without data because the node refuses to serve their sender.
+    refused_requests: usize,
+    /// Blocks proposed by the node that failed validation and were dropped.
+    invalid_proposals: usize,
+    /// Requests sent by the node and not answered yet.
+    pending_requests: usize,
+    /// Number of calls to `update_node`.
+    num_updates: usize,
+    /// Number of records delivered to the node by its last update.
+    num_records_in_last_update: usize,
+    /// Total time spent executing commands, as reported by the context.
+    execution_time: Duration,
+    /// Time spent executing commands during the last update.
+    execution_time_in_last_update: Duration,
+    node: Node,
+    context: Context,
+}
+
+impl<Node, Context> SimulatedNode<Node, Context>
+where
+    Node: BlockProposer<Context> + ActiveRound,
+    Context: CommittedChain,
+{
+    /// Update the node after delivering it the given number of records.
+    fn update(&mut self, global_clock: GlobalTime, num_records: usize) -> NodeUpdateActions {
+        let local_clock = global_clock.to_node_time(self.startup_time);
+        let actions = self.node.update_node(local_clock, &mut self.context);
+        // Proposals are part of the update: they are sent with its notifications.
+        if self.node.round_actions().should_propose {
+            self.propose_block(global_clock);
+        }
+        self.num_updates += 1;
+        self.num_records_in_last_update = num_records;
+        let execution_time = self.context.execution_time();
+        self.execution_time_in_last_update = execution_time - self.execution_time;
+        self.execution_time = execution_time;
+        if let Some(timeout) = self.node.current_timeout() {
+            if self.timeouts.last() != Some(&timeout) {
+                self.timeouts.push(timeout);
+            }
+        }
+        actions
+    }
+
+    /// Create the block requested by the latest update and send it unless it is invalid.
+    fn propose_block(&mut self, clock: GlobalTime) {
+        let round = self.node.active_round();
+        let block = match self.node.create_block(round, &mut self.context) {
+            Some(block) => block,
+            None => return,
+        };
+        let record = Node::Record::from(block);
+        if let Err(error) = record.validate() {
+            warn!(
+                "@{:?} Dropping an invalid block proposed at {:?}: {}",
+                clock, round, error
+            );
+            self.invalid_proposals += 1;
+            return;
+        }
+        debug!("@{:?} Proposed block at {:?}", clock, round);
+        self.node.send_block(record, &mut self.context);
+    }
+}
+
+impl<Node, Context> SimulatedNode<Node, Context>
+where
+    Node: ActiveRound,
+{
+    pub fn active_round(&self) -> Round {
+        self.node.active_round()
+    }
+}
+
+impl<Node, Context> SimulatedNode<Node, Context> {
+    fn new(node: Node, context: Context, startup_time: GlobalTime) -> Self {
+        SimulatedNode {
+            startup_time,
+            ignore_scheduled_updates_until: startup_time + (-1),
+            crashed: false,
+            commit_times: Vec::new(),
+            timeouts: Vec::new(),
+            messages_sent: 0,
+            messages_received: 0,
+            in_flight_messages: 0,
+            dropped_messages: 0,
+            refused_requests: 0,
+            invalid_proposals: 0,
+            pending_requests: 0,
+            num_updates: 0,
+            num_records_in_last_update: 0,
+            execution_time: 0,
+            execution_time_in_last_update: 0,
+            node,
+            context,
+        }
+    }
+
+    /// The node itself, e.g. to inspect protocol internals in whitebox tests.
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// The context of the node, e.g. to inspect its committed state in whitebox tests.
+    pub fn context(&self) -> &Context {
+        &self.context
+    }
+
+    pub fn startup_time(&self) -> GlobalTime {
+        self.startup_time
+    }
+
+    pub fn is_crashed(&self) -> bool {
+        self.crashed
+    }
+
+    pub fn commit_times(&self) -> &[GlobalTime] {
+        &self.commit_times
+    }
+
+    /// Time of the first commit of the node, if any.
+    pub fn first_commit_time(&self) -> Option<GlobalTime> {
+        self.commit_times.first().cloned()
+    }
+
+    pub fn timeouts(&self) -> &[Duration] {
+        &self.timeouts
+    }
+
+    pub fn dropped_messages(&self) -> usize {
+        self.dropped_messages
+    }
+
+    pub fn refused_requests(&self) -> usize {
+        self.refused_requests
+    }
+
+    pub fn invalid_proposals(&self) -> usize {
+        self.invalid_proposals
+    }
+
+    /// Number of requests sent by the node that may still receive a response, including queued
+    /// requests. Requests and responses dropped by the network or by a crashed node are not
+    /// counted.
+    pub fn pending_requests(&self) -> usize {
+        self.pending_requests
+    }
+
+    pub fn num_updates(&self) -> usize {
+        self.num_updates
+    }
+
+    pub fn execution_time(&self) -> Duration {
+        self.execution_time
+    }
+
+    pub fn message_stats(&self) -> MessageStats {
+        MessageStats {
+            sent: self.messages_sent,
+            received: self.messages_received,
+            dropped: self.dropped_messages,
+            in_flight: self.in_flight_messages,
+        }
+    }
+}
+
+/// Message counters of a node.
+#[derive(Eq, PartialEq, Copy, Clone, Default, Debug)]
+pub struct MessageStats {
+    /// Messages sent by the node, including those dropped by the network.
+    pub sent: usize,
+    /// Messages delivered to the node while it was up.
+    pub received: usize,
+    /// Messages to the node dropped because its inbox was full.
+    pub dropped: usize,
+    /// Messages to the node not processed yet.
+    pub in_flight: usize,
+}
+
+/// Read-only view of a simulated node, see `Simulator::node_view`.
+pub struct NodeView<'a, Node, Context> {
+    node: &'a SimulatedNode<Node, Context>,
+}
+
+impl<'a, Node, Context> NodeView<'a, Node, Context> {
+    pub fn context(&self) -> &'a Context {
+        &self.node.context
+    }
+
+    pub fn startup_time(&self) -> GlobalTime {
+        self.node.startup_time
+    }
+
+    /// Time elapsed since the node started, or 0 if it has not started yet at `clock`.
+    pub fn uptime(&self, clock: GlobalTime) -> Duration {
+        std::cmp::max(clock.0 - self.node.startup_time.0, 0)
+    }
+
+    pub fn message_stats(&self) -> MessageStats {
+        self.node.message_stats()
+    }
+}
+
+impl<'a, Node, Context> NodeView<'a, Node, Context>
+where
+    Node: ActiveRound,
+{
+    pub fn active_round(&self) -> Round {
+        self.node.active_round()
+    }
+}
+
+impl<Node, Context> SimulatedNode<Node, Context>
+where
+    Context: CommittedChain,
+{
+    fn record_commits(&mut self, clock: GlobalTime) {
+        let num_commits = self.context.committed_chain().len();
+        while self.commit_times.len() < num_commits {
+            self.commit_times.push(clock);
+        }
+    }
+}
+
+/// Outcome of a call to `Simulator::loop_until`.
+#[derive(Debug)]
+pub struct SimulationResult<'a, Context> {
+    /// Final contexts of all nodes, indexed by author.
+    pub contexts: Vec<&'a Context>,
+    /// Stable hash of the configuration that produced this result.
+    pub fingerprint: u64,
+    /// Network and responder latencies observed during the run.
+    pub latencies: LatencyStats,
+    pub sync: SyncStats,
+    pub summary: SimulationSummary,
+    /// Events left in flight when the run stopped.
+    pub pending: PendingCensus,
+    /// See `Simulator::expected_latency_under_good_network`.
+    pub expected_latency: Duration,
+}
+
+/// Census of the events still pending when a run stops, e.g. to tell whether the run stopped
+/// in the middle of a burst of messages. Pending events include the events waiting in the fair
+/// queues and the events buffered by frozen nodes. Timers cancelled by a later update of their
+/// node are not counted.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct PendingCensus {
+    /// Number of pending events of each kind, e.g. `DataSyncNotifyEvent`, per destination.
+    pub counts: BTreeMap<(&'static str, Author), usize>,
+    /// Deadline of the earliest pending event, if any. Events buffered by frozen nodes have no
+    /// deadline.
+    pub earliest_deadline: Option<GlobalTime>,
+    /// Scheduled events coming from fault injections, e.g. notifications released by an
+    /// adversary or the wake-up of a frozen node.
+    pub injected_events: usize,
+    /// Scheduled events coming from the nodes themselves.
+    pub organic_events: usize,
+    /// Events taken from the schedule in fair mode but not processed yet.
+    pub queued_events: usize,
+    /// Events buffered by frozen nodes until they thaw.
+    pub frozen_events: usize,
+}
+
+impl PendingCensus {
+    pub fn total(&self) -> usize {
+        self.injected_events + self.organic_events + self.queued_events + self.frozen_events
+    }
+
+    fn record<Notification, Request, Response>(
+        &mut self,
+        event: &Event<Notification, Request, Response>,
+        deadline: Option<GlobalTime>,
+    ) {
+        *self
+            .counts
+            .entry((event.kind(), event.destination()))
+            .or_insert(0) += 1;
+        if let Some(deadline) = deadline {
+            self.earliest_deadline = Some(match self.earliest_deadline {
+                Some(earliest_deadline) => std::cmp::min(earliest_deadline, deadline),
+                None => deadline,
+            });
+        }
+    }
+
+    /// Number of pending events of the given kind, for all receivers.
+    pub fn count_of_kind(&self, kind: &str) -> usize {
+        self.counts
+            .iter()
+            .filter(|((event_kind, _), _)| *event_kind == kind)
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+impl<'a, Context> SimulationResult<'a, Context> {
+    /// Human-readable digest of the key results of the run.
+    pub fn summary_string(&self) -> String {
+        self.summary.to_string()
+    }
+
+    /// Messages sent per entry of the longest committed chain, including the messages dropped
+    /// before delivery. Infinite if nothing was committed.
+    pub fn messages_per_commit(&self) -> f64 {
+        if self.summary.committed_entries == 0 {
+            return f64::INFINITY;
+        }
+        let messages = self.summary.messages_sent + self.summary.messages_dropped;
+        messages as f64 / self.summary.committed_entries as f64
+    }
+
+    /// Ratio of `expected_latency` to the median commit latency, in (0, 1], where 1 means that
+    /// the protocol achieves the theoretical minimum. A value above 1 means that the minimum is
+    /// wrong for this protocol. Zero if nothing was committed.
+    pub fn latency_efficiency(&self) -> f64 {
+        match self.summary.p50_commit_latency {
+            Some(p50) => self.expected_latency as f64 / std::cmp::max(p50, 1) as f64,
+            None => 0.0,
+        }
+    }
+}
+
+/// Parameter varied by `Simulator::run_sensitivity_analysis`.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum SensitivityParam {
+    NetworkDelayMean,
+    NetworkDelayVariance,
+    MessageLossRate,
+    /// Values are rounded to the nearest integer.
+    NumNodes,
+}
+
+/// Parameters of the simulations of a sensitivity analysis.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct SimulationParameters {
+    pub num_nodes: usize,
+    pub network_delay_mean: f64,
+    pub network_delay_variance: f64,
+    pub message_loss_rate: f64,
+}
+
+impl SimulationParameters {
+    fn with_value(mut self, param: SensitivityParam, value: f64) -> Self {
+        match param {
+            SensitivityParam::NetworkDelayMean => self.network_delay_mean = value,
+            SensitivityParam::NetworkDelayVariance => self.network_delay_variance = value,
+            SensitivityParam::MessageLossRate => self.message_loss_rate = value,
+            SensitivityParam::NumNodes => self.num_nodes = value.round() as usize,
+        }
+        self
+    }
+}
+
+/// Outcome of `Simulator::run_sensitivity_analysis`, one entry per simulation.
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct SensitivityResult {
+    pub param_values: Vec<f64>,
+    /// Committed entries per unit of time.
+    pub commit_rates: Vec<f64>,
+    /// 95th percentile of commit latencies, or NaN if nothing was committed.
+    pub latencies_p95: Vec<f64>,
+}
+
+/// Key results of a simulation.
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct SimulationSummary {
+    pub duration: Duration,
+    /// Highest round completed by any node.
+    pub rounds_completed: usize,
+    /// Highest number of rounds proposed but not committed at the end, among running nodes.
+    pub in_flight_rounds: usize,
+    /// Length of the longest committed chain.
+    pub committed_entries: usize,
+    /// Statistics on the time between the proposal of an entry and its commit by each node.
+    pub mean_commit_latency: Option<f64>,
+    pub p50_commit_latency: Option<Duration>,
+    pub p99_commit_latency: Option<Duration>,
+    /// Fraction of the completed rounds during which at least one node timed out.
+    pub timeout_rate: f64,
+    pub messages_sent: usize,
+    /// Messages dropped before delivery, e.g. lost by the network. They are not counted in
+    /// `messages_sent`.
+    pub messages_dropped: usize,
+    /// Number of pairs of nodes whose committed chains diverge.
+    pub safety_violations: usize,
+    pub crashed_nodes: Vec<Author>,
+    pub partitioned: bool,
+    /// Number of (sender, receiver, round) triples of messages dropped during a replay.
+    pub omitted_messages: usize,
+    pub fault_coverage: FaultCoverage,
+    /// See `Simulator::byzantine_fault_rate`, if safety checking is enabled.
+    pub byzantine_fault_rate: Option<f64>,
+    /// Number of round inflations detected, if the detector is enabled.
+    pub round_inflations: Option<usize>,
+    /// Highest utilization of the network core over a window, if congestion is simulated and
+    /// some message was sent.
+    pub peak_core_utilization: Option<f64>,
+    /// Utilization of the network core in each window, see `NetworkCore::utilization`.
+    pub core_utilization: Vec<(GlobalTime, f64)>,
+}
+
+/// JSON value of an optional field.
+fn json_option<T: std::fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "null".to_string(), |value| value.to_string())
+}
+
+impl SimulationSummary {
+    /// Single-line JSON object following `schema::report`. Fault coverage is not included.
+    pub fn to_json(&self) -> String {
+        let crashed_nodes: Vec<_> = self
+            .crashed_nodes
+            .iter()
+            .map(|author| author.0.to_string())
+            .collect();
+        let core_utilization: Vec<_> = self
+            .core_utilization
+            .iter()
+            .map(|(time, utilization)| format!("[{}, {}]", time.0, utilization))
+            .collect();
+        let fields = vec![
+            ("duration", self.duration.to_string()),
+            ("rounds_completed", self.rounds_completed.to_string()),
+            ("in_flight_rounds", self.in_flight_rounds.to_string()),
+            ("committed_entries", self.committed_entries.to_string()),
+            ("mean_commit_latency", json_option(self.mean_commit_latency)),
+            ("p50_commit_latency", json_option(self.p50_commit_latency)),
+            ("p99_commit_latency", json_option(self.p99_commit_latency)),
+            ("timeout_rate", self.timeout_rate.to_string()),
+            ("messages_sent", self.messages_sent.to_string()),
+            ("messages_dropped", self.messages_dropped.to_string()),
+            ("safety_violations", self.safety_violations.to_string()),
+            ("crashed_nodes", format!("[{}]", crashed_nodes.join(", "))),
+            ("partitioned", self.partitioned.to_string()),
+            ("omitted_messages", self.omitted_messages.to_string()),
+            (
+                "byzantine_fault_rate",
+                json_option(self.byzantine_fault_rate),
+            ),
+            ("round_inflations", json_option(self.round_inflations)),
+            (
+                "peak_core_utilization",
+                json_option(self.peak_core_utilization),
+            ),
+            (
+                "core_utilization",
+                format!("[{}]", core_utilization.join(", ")),
+            ),
+        ];
+        let mut json = schema::report().version_line();
+        for (name, value) in fields {
+            json.push_str(&format!(", \"{}\": {}", name, value));
+        }
+        json.push('}');
+        json
+    }
+
+    /// Write `to_json` to the file of `schema::report` in the directory `path`.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        fs::write(
+            path.join(schema::report().file_name),
+            format!("{}\n", self.to_json()),
+        )
+    }
+}
+
+impl std::fmt::Display for SimulationSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "Simulated duration: {}", self.duration)?;
+        writeln!(f, "Rounds completed: {}", self.rounds_completed)?;
+        writeln!(f, "Rounds in flight: {}", self.in_flight_rounds)?;
+        writeln!(f, "Blocks committed: {}", self.committed_entries)?;
+        match (self.mean_commit_latency, self.p99_commit_latency) {
+            (Some(mean), Some(p99)) => {
+                writeln!(f, "Commit latency: mean {:.1}, p99 {}", mean, p99)?
+            }
+            _ => writeln!(f, "Commit latency: n/a")?,
+        }
+        writeln!(f, "Timeout rate: {:.2}", self.timeout_rate)?;
+        writeln!(f, "Messages sent: {}", self.messages_sent)?;
+        writeln!(f, "Safety violations: {}", self.safety_violations)?;
+        if let Some(rate) = self.byzantine_fault_rate {
+            writeln!(f, "Byzantine fault rate: {:.2}", rate)?;
+        }
+        if let Some(count) = self.round_inflations {
+            writeln!(f, "Round inflations: {}", count)?;
+        }
+        if let Some(utilization) = self.peak_core_utilization {
+            writeln!(f, "Peak core utilization: {:.2}", utilization)?;
+        }
+        let mut faults = Vec::new();
+        if !self.crashed_nodes.is_empty() {
+            faults.push(format!("crashed nodes {:?}", self.crashed_nodes));
+        }
+        if self.partitioned {
+            faults.push("network partition".to_string());
+        }
+        if self.omitted_messages > 0 {
+            faults.push(format!("{} message omissions", self.omitted_messages));
+        }
+        if faults.is_empty() {
+            faults.push("none".to_string());
+        }
+        writeln!(f, "Faults injected: {}", faults.join(", "))?;
+        write!(f, "Fault coverage: {}", self.fault_coverage)
+    }
+}
+
+/// A node whose active round got ahead of its highest committed round by more than the
+/// threshold of `with_round_inflation_threshold`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct RoundInflation {
+    pub time: GlobalTime,
+    pub author: Author,
+    pub active_round: Round,
+    pub committed_round: Round,
+}
+
+impl RoundInflation {
+    pub fn gap(&self) -> usize {
+        self.active_round.0.saturating_sub(self.committed_round.0)
+    }
+}
+
+/// Gaps between the active round and the highest committed round of the nodes, see
+/// `Simulator::with_round_inflation_threshold`.
+#[derive(Debug)]
+struct RoundInflationDetector {
+    /// Gap of each node, as of the last event handled by the node.
+    gaps: Vec<usize>,
+    /// Largest gap accepted before reporting a round inflation, if the detector is enabled.
+    threshold: Option<usize>,
+    /// Gaps above the threshold, in order of detection.
+    inflations: Vec<RoundInflation>,
+}
+
+impl RoundInflationDetector {
+    fn new(num_nodes: usize) -> Self {
+        RoundInflationDetector {
+            gaps: vec![0; num_nodes],
+            threshold: None,
+            inflations: Vec::new(),
+        }
+    }
+
+    fn update(
+        &mut self,
+        clock: GlobalTime,
+        author: Author,
+        active_round: Round,
+        committed_round: Round,
+    ) {
+        let gap = active_round.0.saturating_sub(committed_round.0);
+        let previous_gap = std::mem::replace(&mut self.gaps[author.0], gap);
+        if let Some(threshold) = self.threshold {
+            if gap > threshold && previous_gap <= threshold {
+                debug!(
+                    "@{:?} Round inflation at {:?}: active round {}, committed round {}",
+                    clock, author, active_round, committed_round
+                );
+                self.inflations.push(RoundInflation {
+                    time: clock,
+                    author,
+                    active_round,
+                    committed_round,
+                });
+            }
+        }
+    }
+}
+
+/// Outcome of `run_network_partition_tolerance_test`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct PartitionToleranceReport {
+    pub majority: Vec<Author>,
+    pub minority: Vec<Author>,
+    /// Number of entries committed by each node while the network was partitioned.
+    pub new_commits: Vec<usize>,
+    /// Pairs of nodes whose committed chains diverge, with the index of the divergence.
+    pub safety_violations: Vec<(Author, Author, usize)>,
+    /// Live nodes of the majority that committed nothing while the network was partitioned.
+    pub stalled_nodes: Vec<Author>,
+}
+
+impl PartitionToleranceReport {
+    pub fn is_safe(&self) -> bool {
+        self.safety_violations.is_empty()
+    }
+
+    pub fn is_live(&self) -> bool {
+        self.stalled_nodes.is_empty()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.is_safe() && self.is_live()
+    }
+}
+
+/// Fault model of the simulator, as counted by `FaultCoverage`.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+pub enum FaultKind {
+    /// A message was lost by the network, see `with_message_loss_rate`.
+    LostMessage,
+    /// A message was dropped by an omission schedule, see `replay_with_omission`.
+    OmittedMessage,
+    /// A message was dropped between partitioned nodes.
+    PartitionDrop,
+    /// A message to an eclipsed node was dropped.
+    EclipseDrop,
+    /// A message was dropped because the inbox of the receiver was full.
+    InboxOverflow,
+    /// A message was delayed because the queue of the receiver was full.
+    BackpressureDelay,
+    /// A message was held back until GST.
+    HeldUntilGst,
+    /// An event was buffered by a frozen node.
+    FrozenEvent,
+    /// A scheduled update of a node was dropped, see `with_timer_loss_rate`.
+    MissedTimer,
+    /// A node was crashed.
+    Crash,
+    /// A node rebooted without its state, see `inject_reboot_with_full_state_loss`.
+    StateLoss,
+    /// A node rebooted with its durable state only, see `inject_reboot_with_partial_state`.
+    PartialStateLoss,
+    /// A node restarted with its committed state only, see `restart_with_committed_state`.
+    UncommittedStateLoss,
+    /// The network was partitioned.
+    Partition,
+}
+
+impl FaultKind {
+    fn name(self) -> &'static str {
+        match self {
+            FaultKind::LostMessage => "lost messages",
+            FaultKind::OmittedMessage => "omitted messages",
+            FaultKind::PartitionDrop => "messages dropped by partitions",
+            FaultKind::EclipseDrop => "messages dropped by eclipses",
+            FaultKind::InboxOverflow => "inbox overflows",
+            FaultKind::BackpressureDelay => "messages delayed by backpressure",
+            FaultKind::HeldUntilGst => "messages held until GST",
+            FaultKind::FrozenEvent => "events buffered by frozen nodes",
+            FaultKind::MissedTimer => "missed timers",
+            FaultKind::Crash => "crashes",
+            FaultKind::StateLoss => "reboots with state loss",
+            FaultKind::PartialStateLoss => "reboots with partial state loss",
+            FaultKind::UncommittedStateLoss => "restarts with uncommitted state loss",
+            FaultKind::Partition => "partitions",
+        }
+    }
+}
+
+/// Number of times that each fault model was actually activated during a run, e.g. to detect
+/// that a low loss rate did not drop any message.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct FaultCoverage {
+    activations: BTreeMap<FaultKind, usize>,
+}
+
+impl FaultCoverage {
+    fn record(&mut self, kind: FaultKind) {
+        *self.activations.entry(kind).or_insert(0) += 1;
+    }
+
+    pub fn count(&self, kind: FaultKind) -> usize {
+        self.activations.get(&kind).cloned().unwrap_or(0)
+    }
+}
+
+impl std::fmt::Display for FaultCoverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.activations.is_empty() {
+            return write!(f, "none");
+        }
+        let activations: Vec<_> = self
+            .activations
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind.name()))
+            .collect();
+        write!(f, "{}", activations.join(", "))
+    }
+}
+
+pub struct Simulator<Node, Context, Notification, Request, Response> {
+    clock: GlobalTime,
+    seed: u64,
+    rng: CountingRng,
+    /// Random generator of the probes, kept apart so that measuring does not alter the run.
+    probe_rng: StdRng,
+    startup_schedule: StartupSchedule,
+    network_delay: RandomDelay,
+    /// Bound of the uniform noise added to the delay of every message.
+    message_latency_jitter: f64,
+    /// Probability that a message is lost by the network.
+    message_loss_rate: f64,
+    /// Additional delay of the notifications that announce a quorum certificate.
+    qc_extra_delay: Duration,
+    /// Shared bottleneck of the network, if congestion is simulated.
+    network_core: Option<NetworkCore>,
+    /// Sizes of the messages, if some option needs them.
+    message_sizes: Option<MessageSizes<Notification, Request, Response>>,
+    /// Whether the messages addressed to some peers are sent to every peer instead.
+    relay_to_all: bool,
+    /// Timeout of the consensus protocol given to the contexts and the nodes, if any.
+    consensus_timeout: Option<ConsensusTimeoutConfig<Node, Context>>,
+    network_faults: NetworkFaults,
+    latencies: Latencies,
+    /// Additional delay of the responses to deprioritized requests.
+    low_priority_response_delay: Duration,
+    /// Computational power of the nodes, if processing time is simulated.
+    node_cpu_budget: Option<CpuBudget>,
+    /// Probability that each scheduled update of a node is dropped, if any.
+    timer_loss_rates: BTreeMap<Author, f64>,
+    inboxes: Inboxes,
+    /// Shard of each node, if nodes are sharded.
+    shard_assignment: Option<ShardAssignment>,
+    /// Additional delay of the messages between nodes of different shards.
+    cross_shard_delay: Duration,
+    /// Nodes that only send to the peers they discovered.
+    peers: PeerDirectory,
+    sync: SyncTracker<Request, Response>,
+    /// Number of nodes that timed out at each round.
+    timeouts_per_round: HashMap<Round, usize>,
+    pending_events: PendingEvents<Notification, Request, Response>,
+    /// Number of messages in `pending_events`, to detect quiescence without scanning the heap.
+    scheduled_messages: usize,
+    /// Number of messages delivered before they were sent, see `verify_no_future_messages`.
+    future_messages: usize,
+    nodes: Vec<SimulatedNode<Node, Context>>,
+    crashed_sender_policy: CrashedSenderPolicy,
+    /// Events that could not be delivered because their destination had crashed.
+    dead_letters: Vec<Event<Notification, Request, Response>>,
+    /// Number of messages dropped before delivery, see `drop_message`.
+    num_dropped_messages: usize,
+    /// Messages sent so far, if the event log is enabled.
+    event_log: Option<Vec<LoggedMessage>>,
+    /// Whether summaries report the `byzantine_fault_rate`.
+    safety_checking: bool,
+    round_inflations: RoundInflationDetector,
+    replay: Replay,
+    freezes: Freezes<Notification, Request, Response>,
+    /// Fault domains to crash at the given times, see `schedule_domain_crash`.
+    domain_crashes: Vec<(GlobalTime, BTreeSet<Author>)>,
+    /// Whether notifications delivered to the same node at the same time are handled as a
+    /// batch, followed by a single update.
+    coalesce_notifications: bool,
+    /// Custom order of simultaneous events, if any.
+    event_order: Option<Rc<EventOrder<Notification, Request, Response>>>,
+    /// Adversary intercepting notifications during `run_adversarial_sync`.
+    adversary: Option<Box<dyn AdversarySyncStrategy<Notification>>>,
+    /// Observers notified of every update of a node.
+    observers: Vec<Rc<RefCell<dyn Observer<Node, Context>>>>,
+    fair_scheduler: FairScheduler<Notification, Request, Response>,
+    /// Time and destination of the processed events, if the trace is enabled.
+    processing_trace: Option<Vec<TraceEntry>>,
+    /// Downsampled trace with full detail around anomalies, if enabled.
+    bounded_trace: Option<BoundedTrace>,
+    fault_coverage: FaultCoverage,
+    /// Minimal number of activations of each fault model expected by the scenario.
+    min_fault_activations: BTreeMap<FaultKind, usize>,
+}
+
+impl<Node, Context, Notification, Request, Response>
+    Simulator<Node, Context, Notification, Request, Response>
+where
+    Node: ActiveRound,
+    Notification: std::cmp::Ord + std::fmt::Debug + NotificationKind,
+    Request: std::cmp::Ord + std::fmt::Debug,
+    Response: std::cmp::Ord + std::fmt::Debug,
+{
+    pub fn new<F, G>(
+        num_nodes: usize,
+        network_delay: RandomDelay,
+        context_factory: F,
+        node_factory: G,
+    ) -> Simulator<Node, Context, Notification, Request, Response>
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        let clock = GlobalTime(0);
+        let seed = rand::thread_rng().gen();
+        let nodes = (0..num_nodes)
+            .map(|index| {
+                let author = Author(index);
+                let context = context_factory(author, num_nodes);
+                let node = node_factory(author, &context, NodeTime(0));
+                // The startup time is set by `schedule_startup` below.
+                SimulatedNode::new(node, context, clock)
+            })
+            .collect();
+        let mut simulator = Simulator {
+            clock,
+            seed,
+            rng: CountingRng::seed_from_u64(seed),
+            probe_rng: StdRng::seed_from_u64(seed),
+            startup_schedule: StartupSchedule::Random,
+            network_delay,
+            message_latency_jitter: 0.0,
+            message_loss_rate: 0.0,
+            qc_extra_delay: 0,
+            network_core: None,
+            message_sizes: None,
+            relay_to_all: false,
+            consensus_timeout: None,
+            network_faults: NetworkFaults::default(),
+            latencies: Latencies::default(),
+            low_priority_response_delay: 0,
+            node_cpu_budget: None,
+            timer_loss_rates: BTreeMap::new(),
+            inboxes: Inboxes::new(),
+            shard_assignment: None,
+            cross_shard_delay: 0,
+            peers: PeerDirectory::new(num_nodes),
+            sync: SyncTracker::new(num_nodes),
+            timeouts_per_round: HashMap::new(),
+            pending_events: BinaryHeap::new(),
+            scheduled_messages: 0,
+            future_messages: 0,
+            nodes,
+            crashed_sender_policy: CrashedSenderPolicy::DeadLetter,
+            dead_letters: Vec::new(),
+            num_dropped_messages: 0,
+            event_log: None,
+            safety_checking: false,
+            round_inflations: RoundInflationDetector::new(num_nodes),
+            replay: Replay::default(),
+            freezes: Freezes::new(),
+            domain_crashes: Vec::new(),
+            coalesce_notifications: false,
+            event_order: None,
+            adversary: None,
+            observers: Vec::new(),
+            fair_scheduler: FairScheduler::new(clock),
+            processing_trace: None,
+            bounded_trace: None,
+            fault_coverage: FaultCoverage::default(),
+            min_fault_activations: BTreeMap::new(),
+        };
+        simulator.schedule_startup();
+        simulator
+    }
+
+    /// Make the simulation reproducible. Startup times are sampled again using the new seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self.rng = CountingRng::seed_from_u64(seed);
+        self.probe_rng = StdRng::seed_from_u64(seed);
+        self.schedule_startup();
+        self
+    }
+
+    /// Seed the random generator again and fast-forward it to `position`, e.g. a value of
+    /// `rng_position` noted during an earlier run with the same seed, so that the next random
+    /// draws of the earlier run are reproduced without replaying its events. Must come after
+    /// `with_seed`. The startup times already sampled are kept.
+    pub fn with_rng_position(mut self, position: u64) -> Self {
+        self.rng = CountingRng::seed_from_u64(self.seed);
+        self.rng.advance(position);
+        self
+    }
+
+    /// Start node `i` at time `i * delay_between_nodes` instead of after a random delay,
+    /// e.g. to model a rolling deployment.
+    pub fn with_ordered_startup(mut self, delay_between_nodes: Duration) -> Self {
+        self.startup_schedule = StartupSchedule::Ordered(delay_between_nodes);
+        self.schedule_startup();
+        self
+    }
+
+    /// Start all nodes at the same time, once the slowest node is ready, e.g. to model a
+    /// coordinated launch.
+    pub fn with_startup_barrier(mut self) -> Self {
+        self.startup_schedule = StartupSchedule::Barrier;
+        self.schedule_startup();
+        self
+    }
+
+    /// Add an independent uniform noise in `[-jitter_ms, jitter_ms]` to every message delay.
+    pub fn with_message_latency_jitter(mut self, jitter_ms: f64) -> Self {
+        self.message_latency_jitter = jitter_ms;
+        self
+    }
+
+    /// Send the messages that nodes address to some peers, e.g. votes to a leader, to every
+    /// peer instead, as protocols that broadcast their votes do. The traffic of each round then
+    /// grows quadratically with the number of nodes.
+    pub fn with_relay_to_all(mut self) -> Self {
+        self.relay_to_all = true;
+        self
+    }
+
+    /// Drop every message independently with the given probability.
+    pub fn with_message_loss_rate(mut self, rate: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&rate),
+            "The message loss rate must be a probability."
+        );
+        self.message_loss_rate = rate;
+        self
+    }
+
+    /// Drop every scheduled update of the given node independently with the given probability,
+    /// without rescheduling it, e.g. to model scheduler starvation. The node is still updated
+    /// when it receives messages.
+    pub fn with_timer_loss_rate(mut self, author: Author, rate: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&rate),
+            "The timer loss rate must be a probability."
+        );
+        self.timer_loss_rates.insert(author, rate);
+        self
+    }
+
+    /// Model partial synchrony: before `gst`, the network is adversarial and holds all messages
+    /// back; after `gst`, delays follow the configured distribution.
+    pub fn with_gst(mut self, gst: GlobalTime) -> Self {
+        self.network_faults.gst = Some(gst);
+        self
+    }
+
+    /// Delay the responses of the given node, e.g. to model slow storage reads.
+    pub fn with_response_latency(mut self, author: Author, latency: ResponseLatency) -> Self {
+        self.latencies.responses.insert(author, latency);
+        self
+    }
+
+    /// Delay the responses to the requests that a node deprioritizes, e.g. requests from
+    /// authors outside the validator set, as if they were served once the other requests are.
+    pub fn with_low_priority_response_delay(mut self, delay: Duration) -> Self {
+        self.low_priority_response_delay = delay;
+        self
+    }
+
+    /// Hand notifications delivered to the same node at the same time to the node as a batch,
+    /// followed by a single update.
+    pub fn with_notification_coalescing(mut self) -> Self {
+        self.coalesce_notifications = true;
+        self
+    }
+
+    /// Process events scheduled at the same time in the order given by `cmp`, e.g. votes
+    /// before other notifications: `Ordering::Less` means that the first event is processed
+    /// first. By default, simultaneous events are processed in decreasing order of `Event`.
+    pub fn with_custom_ord_for_events<F>(mut self, cmp: F) -> Self
+    where
+        F: Fn(
+                &Event<Notification, Request, Response>,
+                &Event<Notification, Request, Response>,
+            ) -> Ordering
+            + 'static,
+    {
+        let event_order: Rc<EventOrder<Notification, Request, Response>> = Rc::new(cmp);
+        self.event_order = Some(event_order);
+        // Re-schedule the initial events with the new order.
+        let pending_events = std::mem::take(&mut self.pending_events);
+        self.scheduled_messages = 0;
+        for ScheduledEvent(std::cmp::Reverse(deadline), event, _, injected, in_core, send_time) in
+            pending_events.into_vec()
+        {
+            self.push_event(deadline, event, injected, in_core, send_time);
+        }
+        self
+    }
+
+    /// Interleave the processing of simultaneous events across their destinations, in
+    /// round-robin order of authors, instead of processing them in the order of the heap. The
+    /// events of each destination keep their order, and so does the backlog of a thawed node
+    /// unless a thaw processing delay is set.
+    pub fn with_fair_event_processing(mut self) -> Self {
+        self.fair_scheduler.enabled = true;
+        self
+    }
+
+    /// Bound the inbox of the given node, e.g. to model a saturated node: messages sent to the
+    /// node while `capacity` messages are in flight toward it are handled according to the
+    /// backpressure policy, which drops them on delivery by default.
+    pub fn with_inbox_capacity(mut self, author: Author, capacity: usize) -> Self {
+        self.inboxes.capacities.insert(author, capacity);
+        self
+    }
+
+    /// Bound the inbox of every node, see `with_inbox_capacity`. Inboxes that are already
+    /// bounded keep the lowest capacity.
+    pub fn with_backpressure(mut self, max_queue_per_node: usize) -> Self {
+        for index in 0..self.nodes.len() {
+            let capacity = self
+                .inboxes
+                .capacities
+                .entry(Author(index))
+                .or_insert(max_queue_per_node);
+            *capacity = std::cmp::min(*capacity, max_queue_per_node);
+        }
+        self
+    }
+
+    pub fn with_backpressure_policy(mut self, policy: BackpressurePolicy) -> Self {
+        self.inboxes.policy = policy;
+        self
+    }
+
+    /// Limit the number of sync requests of each node that may await a response at the same
+    /// time. Further requests are queued until a response arrives or a session times out.
+    pub fn with_max_sync_sessions(mut self, max_sessions: usize, timeout: Duration) -> Self {
+        assert!(
+            max_sessions > 0,
+            "At least one sync session must be allowed."
+        );
+        self.sync.max_sessions = Some((max_sessions, timeout));
+        self
+    }
+
+    /// Deliver the sync responses of each node in the order of its requests, e.g. so that a
+    /// response that overtook the response to an older request does not leave a gap in the
+    /// records of the requester. A response is held back until the older responses are
+    /// delivered, or until `timeout` has passed since their requests, e.g. if they were lost.
+    pub fn with_ordered_sync_responses(mut self, timeout: Duration) -> Self {
+        self.sync.ordered_responses = Some(timeout);
+        self
+    }
+
+    /// Report a round inflation whenever the active round of a node gets ahead of its highest
+    /// committed round by more than `threshold` rounds, e.g. because rounds keep timing out.
+    /// Nodes that do not track commits by round are ignored.
+    pub fn with_round_inflation_threshold(mut self, threshold: usize) -> Self {
+        self.round_inflations.threshold = Some(threshold);
+        self
+    }
+
+    pub fn with_crashed_sender_policy(mut self, policy: CrashedSenderPolicy) -> Self {
+        self.crashed_sender_policy = policy;
+        self
+    }
+
+    /// Slow down the propagation of quorum certificates, independently of other messages.
+    pub fn set_qc_extra_delay(&mut self, delay: Duration) {
+        self.qc_extra_delay = delay;
+    }
+
+    /// Run an independent consensus instance in each shard: broadcasts and queries to all only
+    /// reach the nodes of the same shard, and other messages between shards take
+    /// `cross_shard_delay` more. Nodes are expected to be configured with the validators of
+    /// their shard.
+    pub fn with_shard_assignment(
+        mut self,
+        shard_assignment: ShardAssignment,
+        cross_shard_delay: Duration,
+    ) -> Self {
+        self.shard_assignment = Some(shard_assignment);
+        self.cross_shard_delay = cross_shard_delay;
+        self
+    }
+
+    pub fn shard_assignment(&self) -> Option<&ShardAssignment> {
+        self.shard_assignment.as_ref()
+    }
+
+    /// Peers that a node can send messages to.
+    pub fn known_peers(&self, author: Author) -> BTreeSet<Author> {
+        self.peers.known_peers(author)
+    }
+
+    /// Time needed by a node to discover all the other nodes since `start_peer_discovery`, if
+    /// it did.
+    pub fn time_to_full_connectivity(&self, author: Author) -> Option<Duration> {
+        self.peers.time_to_full_connectivity(author)
+    }
+
+    /// Whether two nodes take part in the same consensus instance.
+    fn same_shard(&self, author1: Author, author2: Author) -> bool {
+        self.shard_assignment
+            .as_ref()
+            .map_or(true, |shards| shards.same_shard(author1, author2))
+    }
+
+    /// After a freeze, process buffered events one after the other, separated by the given
+    /// delay, instead of all at once.
+    pub fn with_thaw_processing_delay(mut self, delay: Duration) -> Self {
+        self.freezes.thaw_processing_delay = delay;
+        self
+    }
+
+    /// Freeze a node between `from` (included) and `until` (excluded), e.g. to model a GC
+    /// pause. Unlike a crash, events addressed to the node are buffered, then delivered in
+    /// their original order at `until`.
+    pub fn schedule_freeze(&mut self, author: Author, from: GlobalTime, until: GlobalTime) {
+        self.freezes.windows.push((author, from, until));
+        // Make sure that the node wakes up.
+        self.schedule_injected_event(until, Event::UpdateTimerEvent { author });
+    }
+
+    /// Crash all the nodes of a fault domain at once, e.g. a rack that loses power. The nodes
+    /// are crashed before handling any event due at or after `at`.
+    pub fn schedule_domain_crash(&mut self, domain: HashSet<Author>, at: GlobalTime) {
+        self.domain_crashes.push((at, domain.into_iter().collect()));
+    }
+
+    /// Restart a crashed node with its state intact. Events dropped in the meantime are lost.
+    pub fn recover_node(&mut self, author: Author) {
+        self.simulated_node_mut(author).crashed = false;
+        let clock = self.clock;
+        self.schedule_injected_event(clock, Event::UpdateTimerEvent { author });
+    }
+
+    /// From now on, let a node know only `bootstrap_peers`, e.g. a late joiner that resolved a
+    /// seed list. The node learns the sender of every message that it receives and, if
+    /// `discovery_period` is given, one more peer at each period. It only sends messages to
+    /// the peers that it knows.
+    pub fn start_peer_discovery(
+        &mut self,
+        author: Author,
+        bootstrap_peers: Vec<Author>,
+        discovery_period: Option<Duration>,
+    ) {
+        self.peers
+            .start(author, bootstrap_peers, discovery_period, self.clock);
+    }
+
+    /// Reboot a node with no memory of its previous state, e.g. after a disk failure. Its
+    /// context and node are created afresh by the given factories, as in `new`, and the node
+    /// starts again now, crashed or not. Pending events addressed to the node are moved to the
+    /// dead-letter queue, so that the node must catch up through data synchronization. Only
+    /// the message counters of the simulated node are kept.
+    pub fn inject_reboot_with_full_state_loss<F, G>(
+        &mut self,
+        author: Author,
+        context_factory: F,
+        node_factory: G,
+    ) where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        self.record_fault(FaultKind::StateLoss);
+        self.reboot(author, context_factory, node_factory);
+    }
+
+    /// Reboot a node that only kept the durable part of its state, e.g. a write-ahead log that
+    /// survived the loss of its in-memory vote pool. The node and its context are created afresh
+    /// as in `inject_reboot_with_full_state_loss`, then the node recovers what it persisted
+    /// about its committed chain up to `last_committed_round`, see `DurableState`.
+    pub fn inject_reboot_with_partial_state<F, G>(
+        &mut self,
+        author: Author,
+        last_committed_round: Round,
+        context_factory: F,
+        node_factory: G,
+    ) where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+        Node: DurableState<Context>,
+    {
+        self.record_fault(FaultKind::PartialStateLoss);
+        self.reboot_from_durable_log(author, last_committed_round, context_factory, node_factory);
+    }
+
+    /// Restart a node, crashed or not, with its committed state only, see `DurableState`.
+    /// Unlike with `recover_node`, uncommitted records are lost and must be fetched again from
+    /// other nodes. The node and its context are recreated by the given factories.
+    pub fn restart_with_committed_state<F, G>(
+        &mut self,
+        author: Author,
+        context_factory: F,
+        node_factory: G,
+    ) where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+        Node: DurableState<Context>,
+    {
+        self.record_fault(FaultKind::UncommittedStateLoss);
+        let round = self.simulated_node(author).node.highest_committed_round();
+        self.reboot_from_durable_log(author, round, context_factory, node_factory);
+    }
+
+    fn reboot_from_durable_log<F, G>(
+        &mut self,
+        author: Author,
+        last_committed_round: Round,
+        context_factory: F,
+        node_factory: G,
+    ) where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+        Node: DurableState<Context>,
+    {
+        let log = self
+            .simulated_node(author)
+            .node
+            .durable_log(last_committed_round);
+        self.reboot(author, context_factory, node_factory);
+        let clock = self.clock;
+        let rebooted = self.simulated_node_mut(author);
+        let local_clock = clock.to_node_time(rebooted.startup_time);
+        rebooted
+            .node
+            .recover(log, &mut rebooted.context, local_clock);
+    }
+
+    fn reboot<F, G>(&mut self, author: Author, context_factory: F, node_factory: G)
+    where
+        F: Fn(Author, usize) -> Context,
+        G: Fn(Author, &Context, NodeTime) -> Node,
+    {
+        let (lost_events, pending_events): (Vec<_>, Vec<_>) =
+            std::mem::take(&mut self.pending_events)
+                .into_iter()
+                .partition(|ScheduledEvent(_, event, ..)| event.destination() == author);
+        self.pending_events = pending_events.into_iter().collect();
+        self.scheduled_messages -= lost_events
+            .iter()
+            .filter(|ScheduledEvent(_, event, ..)| event.source().is_some())
+            .count();
+        // Events waiting in a fair queue were already taken out of `scheduled_messages`.
+        let lost_events = self
+            .fair_scheduler
+            .queues
+            .remove(&author)
+            .unwrap_or_default()
+            .into_iter()
+            .chain(
+                lost_events
+                    .into_iter()
+                    .map(|ScheduledEvent(_, event, ..)| event),
+            )
+            .chain(
+                self.freezes
+                    .buffered_events
+                    .remove(&author)
+                    .unwrap_or_default(),
+            );
+        for event in lost_events {
+            self.abandon_request(&event);
+            self.dead_letters.push(event);
+        }
+        self.sync.sessions[author.0] = SyncSessions::new();
+        if let Some(trace) = &mut self.bounded_trace {
+            trace.forget_rounds(author);
+        }
+        let clock = self.clock;
+        let mut context = context_factory(author, self.nodes.len());
+        if let Some(config) = &self.consensus_timeout {
+            (config.set_context_hint)(&mut context, config.hint);
+        }
+        let mut node = node_factory(author, &context, NodeTime(0));
+        if let Some(config) = &self.consensus_timeout {
+            (config.set_node_hint)(&mut node, config.hint);
+        }
+        let previous = std::mem::replace(
+            &mut self.nodes[author.0],
+            SimulatedNode::new(node, context, clock),
+        );
+        let rebooted = self.simulated_node_mut(author);
+        rebooted.messages_sent = previous.messages_sent;
+        rebooted.messages_received = previous.messages_received;
+        rebooted.dropped_messages = previous.dropped_messages;
+        rebooted.refused_requests = previous.refused_requests;
+        rebooted.invalid_proposals = previous.invalid_proposals;
+        self.schedule_injected_event(clock, Event::UpdateTimerEvent { author });
+    }
+
+    /// Human-readable listing of all pending events, in the order of processing. Timers that
+    /// were cancelled by a later update of their node are marked as such.
+    pub fn dump_schedule(&self) -> String {
+        let mut events: Vec<_> = self.pending_events.iter().collect();
+        // Deadlines are reversed in the heap, hence the order of processing is decreasing.
+        events.sort_unstable();
+        let scheduled_events = events
+            .into_iter()
+            .rev()
+            .map(|ScheduledEvent(std::cmp::Reverse(clock), event, ..)| (clock, event));
+        let mut lines = Vec::new();
+        for (clock, event) in self.fair_scheduler.schedule().chain(scheduled_events) {
+            let line = match event.source() {
+                Some(source) => format!(
+                    "@{} {} {:?} -> {:?}",
+                    clock.0,
+                    event.kind(),
+                    source,
+                    event.destination()
+                ),
+                None => {
+                    let author = event.destination();
+                    let cancelled =
+                        *clock <= self.simulated_node(author).ignore_scheduled_updates_until;
+                    format!(
+                        "@{} {} {:?}{}",
+                        clock.0,
+                        event.kind(),
+                        author,
+                        if cancelled { " (cancelled)" } else { "" }
+                    )
+                }
+            };
+            lines.push(line);
+        }
+        lines.join("\n")
+    }
+
+    /// Count the pending events by kind, receiver and origin. Timers cancelled by a later update
+    /// of their node are skipped.
+    pub fn pending_census(&self) -> PendingCensus {
+        let mut census = PendingCensus::default();
+        for ScheduledEvent(std::cmp::Reverse(clock), event, _, injected, ..) in &self.pending_events
+        {
+            if self.is_cancelled_timer(*clock, event) {
+                continue;
+            }
+            census.record(event, Some(*clock));
+            if *injected {
+                census.injected_events += 1;
+            } else {
+                census.organic_events += 1;
+            }
+        }
+        for (clock, event) in self.fair_scheduler.schedule() {
+            if self.is_cancelled_timer(*clock, event) {
+                continue;
+            }
+            census.record(event, Some(*clock));
+            census.queued_events += 1;
+        }
+        for event in self.freezes.buffered_events.values().flatten() {
+            census.record(event, None);
+            census.frozen_events += 1;
+        }
+        census
+    }
+
+    /// Whether the event is a timer that was invalidated by a later update of its node.
+    fn is_cancelled_timer(
+        &self,
+        clock: GlobalTime,
+        event: &Event<Notification, Request, Response>,
+    ) -> bool {
+        match event {
+            Event::UpdateTimerEvent { author } => {
+                clock <= self.simulated_node(*author).ignore_scheduled_updates_until
+            }
+            _ => false,
+        }
+    }
+
+    /// Replay a run recorded with `enable_event_log` but drop the given messages, identified
+    /// by sender, receiver and active round of the sender. Messages of the original run keep
+    /// their delivery times, so that only the consequences of the omission differ. `self` must
+    /// be configured as the original simulation, including the seed.
+    pub fn replay_with_omission(
+        mut self,
+        log: &[LoggedMessage],
+        omit: &[(Author, Author, Round)],
+    ) -> Self {
+        self.replay = Replay::new(log, omit);
+        self
+    }
+
+    fn schedule_startup(&mut self) {
+        let clock = self.clock;
+        let network_delay = self.network_delay;
+        let startup_schedule = self.startup_schedule;
+        let rng = &mut self.rng;
+        let mut startup_times: Vec<_> = (0..self.nodes.len())
+            .map(|index| match startup_schedule {
+                StartupSchedule::Random | StartupSchedule::Barrier => {
+                    clock.add_delay(network_delay, rng) + 1
+                }
+                StartupSchedule::Ordered(delay) => clock + (index as Duration) * delay,
+            })
+            .collect();
+        if startup_schedule == StartupSchedule::Barrier {
+            let barrier = startup_times.iter().cloned().max().unwrap_or(clock);
+            for startup_time in &mut startup_times {
+                *startup_time = barrier;
+            }
+        }
+        self.reset_startup_times(startup_times);
+    }
+
+    /// Replace the startup times of all nodes and schedule their initial updates accordingly.
+    /// This must be called before the simulation starts.
+    fn reset_startup_times(&mut self, startup_times: Vec<GlobalTime>) {
+        self.pending_events.clear();
+        self.scheduled_messages = 0;
+        for (index, startup_time) in startup_times.into_iter().enumerate() {
+            let author = Author(index);
+            let node = self.simulated_node_mut(author);
+            node.startup_time = startup_time;
+            node.ignore_scheduled_updates_until = startup_time + (-1);
+            let deadline = GlobalTime::from_node_time(NodeTime(0), startup_time);
+            let event = Event::UpdateTimerEvent { author };
+            trace!(
+                "Scheduling initial event {:?} for time {:?}",
+                event,
+                deadline
+            );
+            self.schedule_event(deadline, event);
+        }
+    }
+
+    fn schedule_event(
+        &mut self,
+        deadline: GlobalTime,
+        event: Event<Notification, Request, Response>,
+    ) {
+        self.push_event(deadline, event, false, false, None);
+    }
+
+    /// Schedule an event on behalf of a fault injection, e.g. the wake-up of a frozen node.
+    fn schedule_injected_event(
+        &mut self,
+        deadline: GlobalTime,
+        event: Event<Notification, Request, Response>,
+    ) {
+        self.push_event(deadline, event, true, false, None);
+    }
+
+    /// Schedule an event. Messages that must cross the network core are scheduled at their
+    /// arrival in the core.
+    fn push_event(
+        &mut self,
+        deadline: GlobalTime,
+        event: Event<Notification, Request, Response>,
+        injected: bool,
+        in_core: bool,
+        send_time: Option<GlobalTime>,
+    ) {
+        trace!("Scheduling event {:?} for {:?}", event, deadline);
+        if event.source().is_some() {
+            self.scheduled_messages += 1;
+        }
+        self.pending_events.push(ScheduledEvent(
+            std::cmp::Reverse(deadline),
+            event,
+            self.event_order.clone(),
+            injected,
+            in_core,
+            send_time,
+        ));
+    }
+
+    fn schedule_network_event(&mut self, event: Event<Notification, Request, Response>) {
+        self.schedule_network_event_at(self.clock, event);
+    }
+
+    /// Account for a message dropped by the network.
+    fn drop_message(&mut self, event: &Event<Notification, Request, Response>, kind: FaultKind) {
+        self.record_fault(kind);
+        self.num_dropped_messages += 1;
+        self.abandon_request(event);
+    }
+
+    /// The request carried by the event, or answered by it, will never be answered.
+    fn abandon_request(&mut self, event: &Event<Notification, Request, Response>) {
+        match event {
+            Event::DataSyncRequestEvent {
+                receiver, sender, ..
+            }
+            | Event::DataSyncResponseEvent {
+                receiver, sender, ..
+            } => {
+                let node = self.simulated_node_mut(*receiver);
+                node.pending_requests = node.pending_requests.saturating_sub(1);
+                self.abandon_sync_session(*receiver, *sender);
+            }
+            _ => (),
+        }
+    }
+
+    /// Issue a new request from `requester` to `responder`.
+    fn issue_sync_request(&mut self, requester: Author, responder: Author, request: Request) {
+        self.simulated_node_mut(requester).pending_requests += 1;
+        self.send_sync_request(requester, responder, request);
+    }
+
+    /// Send a request from `requester` to `responder`, or queue it if too many sync sessions
+    /// of the requester are in progress.
+    fn send_sync_request(&mut self, requester: Author, responder: Author, request: Request) {
+        self.sync.expire_sessions(requester, self.clock);
+        let sessions = &mut self.sync.sessions[requester.0];
+        if let Some((max_sessions, _)) = self.sync.max_sessions {
+            if sessions.active.len() >= max_sessions {
+                sessions.queued.push_back((responder, request));
+                self.sync.stats.queued_requests += 1;
+                return;
+            }
+        }
+        sessions.active.push((responder, self.clock));
+        self.sync.stats.opened_sessions += 1;
+        self.sync.stats.max_active_sessions =
+            std::cmp::max(self.sync.stats.max_active_sessions, sessions.active.len());
+        if self.sync.ordered_responses.is_some() {
+            sessions.ordered.push_back((responder, self.clock, None));
+        }
+        self.schedule_network_event(Event::DataSyncRequestEvent {
+            receiver: requester,
+            sender: responder,
+            request,
+        });
+    }
+
+    fn complete_sync_session(&mut self, requester: Author, responder: Author) {
+        if self.sync.remove_session(requester, responder) {
+            self.sync.stats.completed_sessions += 1;
+        }
+        self.send_queued_sync_requests(requester);
+    }
+
+    /// Close the session of a request that will never be answered, so that sessions do not
+    /// pile up when their number is not bounded. With a maximal number of sessions, sessions
+    /// are only released by their timeout, since the requester cannot tell that a message was
+    /// lost.
+    fn abandon_sync_session(&mut self, requester: Author, responder: Author) {
+        if self.sync.max_sessions.is_some() {
+            return;
+        }
+        if self.sync.remove_session(requester, responder) {
+            self.sync.stats.abandoned_sessions += 1;
+        }
+    }
+
+    fn send_queued_sync_requests(&mut self, requester: Author) {
+        self.sync.expire_sessions(requester, self.clock);
+        if let Some((max_sessions, _)) = self.sync.max_sessions {
+            while self.sync.sessions[requester.0].active.len() < max_sessions {
+                match self.sync.sessions[requester.0].queued.pop_front() {
+                    Some((responder, request)) => {
+                        self.send_sync_request(requester, responder, request)
+                    }
+                    None => break,
+                }
+            }
+        }
+    }
+
+    /// Route a message through the network faults and delays. Return the delivery time of the
+    /// message, or its arrival in the network core if any, followed by the delay imposed by the
+    /// inbox of the receiver, or the fault that drops the message. Probes draw from their own
+    /// random generator and leave the fault coverage untouched, so that measuring the network
+    /// does not alter the simulation.
+    fn route(
+        &mut self,
+        sender: Author,
+        receiver: Author,
+        send_time: GlobalTime,
+        announces_quorum_certificate: bool,
+        probe: bool,
+    ) -> std::result::Result<(GlobalTime, Duration), FaultKind> {
+        let round = self.simulated_node(sender).node.active_round();
+        let link_up_probability = self
+            .network_faults
+            .link_up_probability(sender, receiver, send_time);
+        if link_up_probability < 1.0
+            && (link_up_probability == 0.0 || !self.draw(probe, link_up_probability))
+        {
+            return Err(FaultKind::PartitionDrop);
+        }
+        if self.network_faults.is_eclipsed(sender, receiver, send_time) {
+            return Err(FaultKind::EclipseDrop);
+        }
+        let departure = self.hold_until_gst(send_time, probe);
+        let (delay, jitter) = (self.network_delay, self.message_latency_jitter);
+        let mut deadline = if probe {
+            departure.add_jittered_delay(delay, jitter, &mut self.probe_rng)
+        } else {
+            departure.add_jittered_delay(delay, jitter, &mut self.rng)
+        };
+        if announces_quorum_certificate {
+            deadline = deadline + self.qc_extra_delay;
+        }
+        if !self.same_shard(sender, receiver) {
+            deadline = deadline + self.cross_shard_delay;
+        }
+        if self.replay.is_omitted(sender, receiver, round) {
+            return Err(FaultKind::OmittedMessage);
+        }
+        // Only draw when needed so that lossless runs are not affected.
+        if self.message_loss_rate > 0.0 && self.draw(probe, self.message_loss_rate) {
+            return Err(FaultKind::LostMessage);
+        }
+        let mut inbox_delay = 0;
+        if let (Some(capacity), BackpressurePolicy::Delay(delay)) =
+            (self.inboxes.capacity(receiver), self.inboxes.policy)
+        {
+            if self.simulated_node(receiver).in_flight_messages >= capacity {
+                if !probe {
+                    self.record_fault(FaultKind::BackpressureDelay);
+                }
+                inbox_delay = delay;
+            }
+        }
+        Ok((deadline, inbox_delay))
+    }
+
+    /// Before GST, the adversary delays messages as much as it can.
+    fn hold_until_gst(&mut self, send_time: GlobalTime, probe: bool) -> GlobalTime {
+        match self.network_faults.held_until(send_time) {
+            Some(gst) => {
+                if !probe {
+                    self.record_fault(FaultKind::HeldUntilGst);
+                }
+                gst
+            }
+            None => send_time,
+        }
+    }
+
+    /// Draw a boolean with the given probability, from the generator of probes if requested.
+    fn draw(&mut self, probe: bool, probability: f64) -> bool {
+        if probe {
+            self.probe_rng.gen_bool(probability)
+        } else {
+            self.rng.gen_bool(probability)
+        }
+    }
+
+    /// Delivery time of a probe sent at the given time, or `None` if the network drops it.
+    fn probe_deadline(
+        &mut self,
+        sender: Author,
+        receiver: Author,
+        send_time: GlobalTime,
+    ) -> Option<GlobalTime> {
+        let (deadline, inbox_delay) = self
+            .route(sender, receiver, send_time, false, /* probe */ true)
+            .ok()?;
+        Some(deadline + inbox_delay)
+    }
+
+    /// Send a probe from `a` to `b` at the current time and return the delay until the answer
+    /// of `b` reaches `a`, or `None` if the network drops either message. Probes go through the
+    /// same faults and delays as messages, without reaching the nodes. The response latency of
+    /// `b` applies in between.
+    pub fn measure_rtt(&mut self, a: Author, b: Author) -> Option<Duration> {
+        let arrival = self.probe_deadline(a, b, self.clock)?;
+        let latency = self
+            .latencies
+            .sample_response_latency(b, &mut self.probe_rng);
+        let return_time = self.probe_deadline(b, a, arrival + latency)?;
+        Some(return_time.0 - self.clock.0)
+    }
+
+    /// Schedule the delivery of a message sent at the given time.
+    fn schedule_network_event_at(
+        &mut self,
+        send_time: GlobalTime,
+        event: Event<Notification, Request, Response>,
+    ) {
+        self.schedule_message(send_time, event, false);
+    }
+
+    /// Send a message through the network. `injected` tells whether the message is sent by a
+    /// fault injection, e.g. an adversary, rather than by its sender.
+    fn schedule_message(
+        &mut self,
+        send_time: GlobalTime,
+        event: Event<Notification, Request, Response>,
+        injected: bool,
+    ) {
+        let announces_quorum_certificate = match &event {
+            Event::DataSyncNotifyEvent { notification, .. } => {
+                notification.announces_quorum_certificate()
+            }
+            _ => false,
+        };
+        let (sender, receiver) = match event.source() {
+            Some(sender) => (sender, event.destination()),
+            None => {
+                // Events without a sender are only subject to network delays.
+                let departure = self.hold_until_gst(send_time, /* probe */ false);
+                let deadline = departure.add_jittered_delay(
+                    self.network_delay,
+                    self.message_latency_jitter,
+                    &mut self.rng,
+                );
+                self.latencies
+                    .record_network_delay(deadline.0 - send_time.0);
+                self.push_event(deadline, event, injected, false, Some(send_time));
+                return;
+            }
+        };
+        self.simulated_node_mut(sender).messages_sent += 1;
+        let round = self.simulated_node(sender).node.active_round();
+        let (mut deadline, inbox_delay) = match self.route(
+            sender,
+            receiver,
+            send_time,
+            announces_quorum_certificate,
+            /* probe */ false,
+        ) {
+            Ok(route) => route,
+            Err(kind) => {
+                debug!(
+                    "@{:?} Dropping message ({:?}): {:?}",
+                    send_time, kind, event
+                );
+                self.drop_message(&event, kind);
+                return;
+            }
+        };
+        let replayed_deadline = self
+            .replay
+            .next_deadline(send_time, sender, receiver, round);
+        if let Some(replayed_deadline) = replayed_deadline {
+            deadline = replayed_deadline;
+        }
+        if let Some(log) = &mut self.event_log {
+            log.push(LoggedMessage {
+                send_time,
+                deadline,
+                sender,
+                receiver,
+                round,
+                kind: event.kind(),
+            });
+        }
+        // Messages to a full inbox are delayed here, or dropped on delivery, see
+        // `accept_event`.
+        if inbox_delay > 0 {
+            debug!(
+                "@{:?} Delaying message to full queue: {:?}",
+                send_time, event
+            );
+            deadline = deadline + inbox_delay;
+        }
+        self.simulated_node_mut(receiver).in_flight_messages += 1;
+        self.latencies
+            .record_network_delay(deadline.0 - send_time.0);
+        let in_core = self.network_core.is_some();
+        self.push_event(deadline, event, injected, in_core, Some(send_time));
+    }
+}
+
+impl<Node, Context, Notification, Request, Response>
+    Simulator<Node, Context, Notification, Request, Response>
+where
+    Notification: MessageSize,
+    Request: MessageSize,
+    Response: MessageSize,
+{
+    /// Measure the messages with `MessageSize`, e.g. to count the bytes of the sync responses
+    /// held back by `with_ordered_sync_responses`.
+    pub fn with_message_sizes(mut self) -> Self {
+        self.message_sizes = Some(MessageSizes::new());
+        self
+    }
+
+    /// Make every message cross a shared bottleneck, so that the total volume of traffic
+    /// raises the latency of all messages.
+    pub fn with_network_core(self, network_core: NetworkCore) -> Self {
+        let mut sim = self.with_message_sizes();
+        sim.network_core = Some(network_core);
+        sim
+    }
+
+    /// Delay the next scheduled update of a node by the time needed to handle the records that
+    /// it received since its previous update, at `ops_per_ms` records per millisecond.
+    pub fn with_node_cpu_budget(self, ops_per_ms: f64) -> Self {
+        assert!(ops_per_ms > 0.0, "CPU budget must be positive");
+        let mut sim = self.with_message_sizes();
+        sim.node_cpu_budget = Some(CpuBudget { ops_per_ms });
+        sim
+    }
+}
+
+impl<Node, Context, Notification, Request, Response>
+    Simulator<Node, Context, Notification, Request, Response>
+where
+    Node: ConsensusTimeout,
+    Context: ConsensusTimeout,
+{
+    /// Give the same consensus timeout to every context and node, including the ones restarted
+    /// later, so that factories need not be written for it. Contexts receive the hint before
+    /// their node is created, and nodes right after. Those that ignore the hint keep the
+    /// timeout chosen by the factories, see `ConsensusTimeout`.
+    pub fn with_consensus_timeout(mut self, timeout: Duration) -> Self {
+        let config = ConsensusTimeoutConfig {
+            hint: ConsensusTimeoutHint(timeout),
+            set_context_hint: Context::set_consensus_timeout_hint,
+            set_node_hint: Node::set_consensus_timeout_hint,
+        };
+        for node in &mut self.nodes {
+            node.context.set_consensus_timeout_hint(config.hint);
+            node.node.set_consensus_timeout_hint(config.hint);
+        }
+        self.consensus_timeout = Some(config);
+        self
+    }
+}
+
+impl<Node, Context, Notification, Request, Response>
+    Simulator<Node, Context, Notification, Request, Response>
+{
+    /// Account for the activation of a fault model.
+    fn record_fault(&mut self, kind: FaultKind) {
+        self.fault_coverage.record(kind);
+        if let Some(trace) = &mut self.bounded_trace {
+            trace.record(TraceRecord::Fault {
+                time: self.clock,
+                kind,
+            });
+        }
+    }
+
+    pub fn simulated_node(&self, author: Author) -> &SimulatedNode<Node, Context> {
+        self.nodes.get(author.0).unwrap()
+    }
+
+    /// Read-only view of a node.
+    pub fn node_view(&self, author: Author) -> NodeView<'_, Node, Context> {
+        NodeView {
+            node: self.simulated_node(author),
+        }
+    }
+
+    fn simulated_node_mut(&mut self, author: Author) -> &mut SimulatedNode<Node, Context> {
+        self.nodes.get_mut(author.0).unwrap()
+    }
+
+    /// Iterate on all simulated nodes together with their authors, in increasing order of
+    /// authors.
+    pub fn nodes_iter(&self) -> impl Iterator<Item = (Author, &SimulatedNode<Node, Context>)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (Author(index), node))
+    }
+
+    /// Mutable variant of `nodes_iter`, meant for test helpers. Modifying nodes during a
+    /// simulation may break the invariants of the protocol under test.
+    pub fn nodes_iter_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (Author, &mut SimulatedNode<Node, Context>)> {
+        self.nodes
+            .iter_mut()
+            .enumerate()
+            .map(|(index, node)| (Author(index), node))
+    }
+
+    pub fn num_nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn current_clock(&self) -> GlobalTime {
+        self.clock
+    }
+
+    pub fn fault_coverage(&self) -> &FaultCoverage {
+        &self.fault_coverage
+    }
+
+    /// Declare that the scenario expects the given fault model to be activated at least
+    /// `min_activations` times, see `assert_fault_coverage`.
+    pub fn expect_fault_activations(&mut self, kind: FaultKind, min_activations: usize) {
+        self.min_fault_activations.insert(kind, min_activations);
+    }
+
+    /// Check that every fault model was activated as often as expected so far. Panics with
+    /// the unmet expectations otherwise.
+    pub fn assert_fault_coverage(&self) {
+        let unmet: Vec<_> = self
+            .min_fault_activations
+            .iter()
+            .filter(|(kind, min_activations)| self.fault_coverage.count(**kind) < **min_activations)
+            .map(|(kind, min_activations)| {
+                format!(
+                    "expected at least {} {}, got {}",
+                    min_activations,
+                    kind.name(),
+                    self.fault_coverage.count(*kind)
+                )
+            })
+            .collect();
+        if !unmet.is_empty() {
+            panic!("Fault coverage not met: {}", unmet.join("; "));
+        }
+    }
+
+    /// Stop a node: from now on, events addressed to it are moved to the dead-letter queue.
+    pub fn crash_node(&mut self, author: Author) {
+        if !self.simulated_node(author).crashed {
+            self.record_fault(FaultKind::Crash);
+        }
+        self.simulated_node_mut(author).crashed = true;
+    }
+
+    fn apply_domain_crashes(&mut self, clock: GlobalTime) {
+        let (due, pending) = std::mem::take(&mut self.domain_crashes)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(at, _)| *at <= clock);
+        self.domain_crashes = pending;
+        for (at, domain) in due {
+            debug!("@{:?} Crashing fault domain {:?}", at, domain);
+            for author in domain {
+                self.crash_node(author);
+            }
+        }
+    }
+
+    /// Split the network: from now on, messages between nodes of different groups are dropped.
+    /// Nodes that do not appear in any group form an additional group.
+    pub fn set_partition(&mut self, groups: Vec<Vec<Author>>) {
+        let mut partition = BTreeMap::new();
+        for (index, group) in groups.into_iter().enumerate() {
+            for author in group {
+                partition.insert(author, index);
+            }
+        }
+        self.network_faults.partition = Some(partition);
+        self.network_faults.partition_recovery = None;
+        self.record_fault(FaultKind::Partition);
+    }
+
+    pub fn heal_partition(&mut self) {
+        self.network_faults.partition = None;
+        self.network_faults.partition_recovery = None;
+    }
+
+    /// Heal the partition over `recovery_ramp`, starting now: the probability that a message
+    /// crosses the partition rises linearly from 0 to 1 during the ramp.
+    pub fn heal_partition_gradually(&mut self, recovery_ramp: Duration) {
+        if self.network_faults.partition.is_some() {
+            self.network_faults.partition_recovery = Some((self.clock, recovery_ramp));
+        }
+    }
+
+    /// Eclipse a victim between `from` (included) and `until` (excluded): messages sent to the
+    /// victim during this window are dropped, unless they come from the attacker. Messages
+    /// sent by the victim are not affected.
+    pub fn schedule_eclipse(
+        &mut self,
+        victim: Author,
+        attacker: Author,
+        from: GlobalTime,
+        until: GlobalTime,
+    ) {
+        self.network_faults
+            .eclipses
+            .push((victim, attacker, from, until));
+    }
+
+    /// Number of nodes that timed out at each round so far. Rounds that completed without
+    /// any timeout are omitted.
+    pub fn count_timeouts_per_round(&self) -> HashMap<Round, usize> {
+        self.timeouts_per_round.clone()
+    }
+
+    /// Check that the round timeout of every node never grew by more than the given factor
+    /// from one value to the next. Panics otherwise.
+    pub fn verify_pacemaker_safety(&self, max_timeout_increase_factor: f64) {
+        for (author, node) in self.nodes_iter() {
+            for pair in node.timeouts.windows(2) {
+                let (previous, next) = (pair[0], pair[1]);
+                // Nodes may start without a timeout.
+                if previous == 0 {
+                    continue;
+                }
+                assert!(
+                    next as f64 <= previous as f64 * max_timeout_increase_factor,
+                    "Timeout of {:?} increased from {} to {}, more than a factor {}",
+                    author,
+                    previous,
+                    next,
+                    max_timeout_increase_factor
+                );
+            }
+        }
+    }
+
+    /// Shared bottleneck of the network, if any, e.g. to plot its utilization over time.
+    pub fn network_core(&self) -> Option<&NetworkCore> {
+        self.network_core.as_ref()
+    }
+
+    pub fn dead_letters(&self) -> &[Event<Notification, Request, Response>] {
+        &self.dead_letters
+    }
+
+    /// Notify the given observer of every node update from now on. The caller keeps a handle
+    /// to read what the observer collected.
+    pub fn add_observer<O>(&mut self, observer: Rc<RefCell<O>>)
+    where
+        O: Observer<Node, Context> + 'static,
+    {
+        self.observers.push(observer);
+    }
+
+    /// Record all messages sent from now on, e.g. to replay the run with
+    /// `replay_with_omission`.
+    pub fn enable_event_log(&mut self) {
+        if self.event_log.is_none() {
+            self.event_log = Some(Vec::new());
+        }
+    }
+
+    /// Number of rounds between the active round and the highest committed round of a node, as
+    /// of the last event handled by the node.
+    pub fn round_gap(&self, author: Author) -> usize {
+        self.round_inflations.gaps[author.0]
+    }
+
+    /// Round inflations detected so far, see `with_round_inflation_threshold`. A node is
+    /// reported again only after its gap went back under the threshold.
+    pub fn round_inflations(&self) -> &[RoundInflation] {
+        &self.round_inflations.inflations
+    }
+
+    /// Report the `byzantine_fault_rate` in the summaries of the simulation.
+    pub fn enable_safety_checking(&mut self) {
+        self.safety_checking = true;
+    }
+
+    pub fn event_log(&self) -> Option<&[LoggedMessage]> {
+        self.event_log.as_deref()
+    }
+
+    /// Check that no message was delivered before it was sent, e.g. because a computed deadline
+    /// underflowed. Delivery times include all the delays, e.g. in the network core.
+    pub fn verify_no_future_messages(&self) -> bool {
+        self.future_messages == 0
+    }
+
+    /// Number of logged messages of each kind, between leaders and validators. A node is the
+    /// leader of a message if `leader` designates it for the round of the message.
+    pub fn message_complexity<L>(&self, leader: L) -> BTreeMap<MessageClass, usize>
+    where
+        L: Fn(Round) -> Author,
+    {
+        let mut counts = BTreeMap::new();
+        for message in self.event_log().expect("The event log should be enabled") {
+            let leader = leader(message.round);
+            let role = |author| {
+                if author == leader {
+                    NodeRole::Leader
+                } else {
+                    NodeRole::Validator
+                }
+            };
+            let class = (role(message.sender), role(message.receiver), message.kind);
+            *counts.entry(class).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// ASCII table of `message_complexity`, one row per class of messages, e.g.
+    /// `Leader->Validator    Notify       1200`.
+    pub fn message_complexity_table<L>(&self, leader: L) -> String
+    where
+        L: Fn(Round) -> Author,
+    {
+        let mut table = String::new();
+        for ((sender, receiver, kind), count) in self.message_complexity(leader) {
+            let roles = format!("{}->{}", sender, receiver);
+            let kind = kind
+                .trim_start_matches("DataSync")
+                .trim_end_matches("Event");
+            table += &format!("{:<20} {:<8} {:>8}\n", roles, kind, count);
+        }
+        table
+    }
+
+    pub fn print_message_complexity_table<L>(&self, leader: L)
+    where
+        L: Fn(Round) -> Author,
+    {
+        print!("{}", self.message_complexity_table(leader));
+    }
+
+    /// Record the time, the kind and the destination of all events processed from now on.
+    pub fn enable_processing_trace(&mut self) {
+        if self.processing_trace.is_none() {
+            self.processing_trace = Some(Vec::new());
+        }
+    }
+
+    pub fn processing_trace(&self) -> Option<&[TraceEntry]> {
+        self.processing_trace.as_deref()
+    }
+
+    /// Record a trace of bounded size from now on, see `BoundedTrace`.
+    pub fn enable_bounded_trace(&mut self, trace: BoundedTrace) {
+        self.bounded_trace = Some(trace);
+    }
+
+    pub fn bounded_trace(&self) -> Option<&BoundedTrace> {
+        self.bounded_trace.as_ref()
+    }
+
+    fn processing_trace_lines(&self) -> Vec<String> {
+        self.processing_trace()
+            .expect("The processing trace should be enabled")
+            .iter()
+            .map(TraceEntry::to_string)
+            .collect()
+    }
+
+    /// Save the processing trace as a golden file, one event per line.
+    pub fn write_golden_trace(&self, golden_path: &Path) -> io::Result<()> {
+        let mut content = self.processing_trace_lines().join("\n");
+        content.push('\n');
+        fs::write(golden_path, content)
+    }
+
+    /// Compare the processing trace with a golden file and return the first divergence, if any.
+    pub fn golden_trace_divergence(
+        &self,
+        golden_path: &Path,
+    ) -> io::Result<Option<TraceDivergence>> {
+        let golden = fs::read_to_string(golden_path)?;
+        let expected: Vec<_> = golden.lines().collect();
+        let found = self.processing_trace_lines();
+        let index = (0..std::cmp::max(expected.len(), found.len()))
+            .find(|index| expected.get(*index).cloned() != found.get(*index).map(String::as_str));
+        Ok(index.map(|index| TraceDivergence {
+            index,
+            expected: expected.get(index).map(|line| line.to_string()),
+            found: found.get(index).cloned(),
+        }))
+    }
+
+    /// Check that the processing trace matches a golden file written by `write_golden_trace`,
+    /// e.g. to catch unintended behavior changes.
+    pub fn assert_trace_matches(&self, golden_path: &Path) {
+        match self.golden_trace_divergence(golden_path) {
+            Ok(None) => (),
+            Ok(Some(divergence)) => panic!("{}", divergence),
+            Err(err) => panic!("Could not read golden trace {:?}: {}", golden_path, err),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Number of 32-bit words drawn from the random generator of the simulation so far, e.g.
+    /// the network delays and message drops. Probes do not count.
+    pub fn rng_position(&self) -> u64 {
+        self.rng.position()
+    }
+
+    /// Stable hash of the configuration of the simulation, used to tag results.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        self.nodes.len().hash(&mut hasher);
+        self.seed.hash(&mut hasher);
+        self.network_delay.hash(&mut hasher);
+        self.startup_schedule.hash(&mut hasher);
+        self.message_latency_jitter.to_bits().hash(&mut hasher);
+        self.message_loss_rate.to_bits().hash(&mut hasher);
+        self.qc_extra_delay.hash(&mut hasher);
+        if let Some(core) = &self.network_core {
+            core.capacity.to_bits().hash(&mut hasher);
+        }
+        self.relay_to_all.hash(&mut hasher);
+        self.consensus_timeout
+            .as_ref()
+            .map(|config| config.hint)
+            .hash(&mut hasher);
+        self.network_faults.gst.map(|gst| gst.0).hash(&mut hasher);
+        self.network_faults.partition.hash(&mut hasher);
+        self.network_faults.partition_recovery.hash(&mut hasher);
+        self.shard_assignment.hash(&mut hasher);
+        self.cross_shard_delay.hash(&mut hasher);
+        self.network_faults.eclipses.hash(&mut hasher);
+        for (author, discovery) in &self.peers.discoveries {
+            author.hash(&mut hasher);
+            discovery.bootstrap_peers.hash(&mut hasher);
+            discovery.discovery_period.hash(&mut hasher);
+        }
+        self.sync.max_sessions.hash(&mut hasher);
+        self.sync.ordered_responses.hash(&mut hasher);
+        self.replay.omitted_messages.hash(&mut hasher);
+        self.freezes.windows.hash(&mut hasher);
+        self.domain_crashes.hash(&mut hasher);
+        self.freezes.thaw_processing_delay.hash(&mut hasher);
+        self.coalesce_notifications.hash(&mut hasher);
+        self.fair_scheduler.enabled.hash(&mut hasher);
+        // Custom orders and adversaries cannot be hashed.
+        self.event_order.is_some().hash(&mut hasher);
+        self.adversary.is_some().hash(&mut hasher);
+        self.crashed_sender_policy.hash(&mut hasher);
+        let mut response_latencies: Vec<_> = self.latencies.responses.iter().collect();
+        response_latencies.sort_by_key(|(author, _)| **author);
+        response_latencies.hash(&mut hasher);
+        self.low_priority_response_delay.hash(&mut hasher);
+        self.node_cpu_budget
+            .map(|budget| budget.ops_per_ms.to_bits())
+            .hash(&mut hasher);
+        for (author, rate) in &self.timer_loss_rates {
+            author.hash(&mut hasher);
+            rate.to_bits().hash(&mut hasher);
+        }
+        let mut inbox_capacities: Vec<_> = self.inboxes.capacities.iter().collect();
+        inbox_capacities.sort_by_key(|(author, _)| **author);
+        inbox_capacities.hash(&mut hasher);
+        self.inboxes.policy.hash(&mut hasher);
+        for node in &self.nodes {
+            node.crashed.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+impl<Node, Context, Notification, Request, Response>
+    Simulator<Node, Context, Notification, Request, Response>
+where
+    Context: CommittedChain + std::fmt::Debug,
+    Node: BlockProposer<Context>
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>
+        + ActiveRound
+        + std::fmt::Debug,
+    Notification: std::cmp::Ord + std::fmt::Debug + std::clone::Clone + NotificationKind,
+    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
+    Response: std::cmp::Ord + std::fmt::Debug,
+{
+    fn process_node_actions(
+        &mut self,
+        clock: GlobalTime,
+        author: Author,
+        actions: NodeUpdateActions,
+    ) {
+        debug!(
+            "@{:?} Processing node actions for {:?}: {:?}",
+            clock, author, actions
+        );
+        let node = &self.nodes[author.0];
+        for observer in &self.observers {
+            let mut observer = observer.borrow_mut();
+            observer.on_update(clock, author, &node.node, &node.context, &actions);
+            let records = observer.take_trace_records();
+            if let Some(trace) = &mut self.bounded_trace {
+                for record in records {
+                    trace.record(record);
+                }
+            }
+        }
+        // Messages of this update are sent once its commands are executed.
+        let execution_time = self.nodes[author.0].execution_time_in_last_update;
+        let send_time = clock + execution_time;
+        // Timers
+        let node_cpu_budget = self.node_cpu_budget;
+        let new_deadline = {
+            let node = self.nodes.get_mut(author.0).unwrap();
+            let processing_delay = node_cpu_budget.map_or(0, |budget| {
+                budget.processing_delay(node.num_records_in_last_update)
+            });
+            let new_deadline = std::cmp::max(
+                GlobalTime::from_node_time(actions.next_scheduled_update, node.startup_time),
+                // Make sure we schedule the update strictly in the future so it does not get
+                // ignored by `ignore_scheduled_updates_until` below.
+                clock + 1,
+            ) + processing_delay
+                + execution_time;
+            // We don't remove the previously scheduled updates but this will cancel them.
+            node.ignore_scheduled_updates_until = new_deadline + (-1);
+            new_deadline
+            // scoping the mutable 'node' for the borrow checker
+        };
+        let event = Event::UpdateTimerEvent { author };
+        self.schedule_event(new_deadline, event);
+        let timed_out_round = self.simulated_node(author).node.round_actions().on_timeout;
+        if let Some(round) = timed_out_round {
+            *self.timeouts_per_round.entry(round).or_insert(0) += 1;
+        }
+        if self.bounded_trace.is_some() {
+            self.trace_round(clock, author, timed_out_round);
+        }
+        // Notifications. Ordered sets keep the random draws of seeded simulations reproducible.
+        // Nodes discovering the network only reach the peers they know.
+        self.peers.discover_peers(author, clock);
+        let should_broadcast =
+            actions.should_broadcast || (self.relay_to_all && !actions.should_send.is_empty());
+        let mut receivers = BTreeSet::new();
+        for node in actions.should_send {
+            if self.peers.knows_peer(author, node) {
+                receivers.insert(node);
+            }
+        }
+        if should_broadcast {
+            for index in 0..self.nodes.len() {
+                if index != author.0
+                    && self.same_shard(author, Author(index))
+                    && self.peers.knows_peer(author, Author(index))
+                {
+                    receivers.insert(Author(index));
+                }
+            }
+        }
+        for receiver in receivers {
+            let mut notification = self
+                .simulated_node(author)
+                .node
+                .create_notification_for(receiver);
+            let (deliveries, injected) = match &mut self.adversary {
+                Some(adversary) => (
+                    adversary.intercept_notification(author, receiver, &mut notification),
+                    true,
+                ),
+                None => (vec![(receiver, notification)], false),
+            };
+            for (receiver, notification) in deliveries {
+                self.schedule_message(
+                    send_time,
+                    Event::DataSyncNotifyEvent {
+                        sender: author,
+                        receiver,
+                        notification,
+                    },
+                    injected,
+                );
+            }
+        }
+        // Queries
+        let mut senders = BTreeSet::new();
+        if actions.should_query_all {
+            for index in 0..self.nodes.len() {
+                if index != author.0
+                    && self.same_shard(author, Author(index))
+                    && self.peers.knows_peer(author, Author(index))
+                {
+                    senders.insert(Author(index));
+                }
+            }
+        }
+        let request = self.simulated_node(author).node.create_request();
+        for sender in senders {
+            self.issue_sync_request(author, sender, request.clone());
+        }
+    }
+
+    /// Record in the bounded trace that a node timed out a round and whether it entered a
+    /// higher round.
+    fn trace_round(&mut self, clock: GlobalTime, author: Author, timed_out_round: Option<Round>) {
+        let round = self.simulated_node(author).node.active_round();
+        let trace = self.bounded_trace.as_mut().unwrap();
+        if let Some(timed_out_round) = timed_out_round {
+            trace.record(TraceRecord::Timeout {
+                time: clock,
+                author,
+                round: timed_out_round,
+            });
+        }
+        trace.record_round(clock, author, round);
+    }
+
+    /// Remove a delivered event from the inbox of its destination. Return the event unless it
+    /// must be dropped because the destination has crashed.
+    fn accept_event(
+        &mut self,
+        clock: GlobalTime,
+        event: Event<Notification, Request, Response>,
+    ) -> Option<Event<Notification, Request, Response>> {
+        let destination = event.destination();
+        // Messages in flight toward the destination, this one included.
+        let mut queue = 0;
+        if event.source().is_some() {
+            // Messages may also be injected with `schedule_event`.
+            let node = self.simulated_node_mut(destination);
+            queue = node.in_flight_messages;
+            node.in_flight_messages = node.in_flight_messages.saturating_sub(1);
+        }
+        if self.simulated_node(destination).crashed {
+            let is_answered_request = match event {
+                Event::DataSyncRequestEvent { .. } => {
+                    self.crashed_sender_policy == CrashedSenderPolicy::Respond
+                }
+                _ => false,
+            };
+            if !is_answered_request {
+                debug!("@{:?} Dropping event for crashed node: {:?}", clock, event);
+                self.abandon_request(&event);
+                self.dead_letters.push(event);
+                return None;
+            }
+        }
+        if self.inboxes.policy == BackpressurePolicy::Drop
+            && self
+                .inboxes
+                .capacity(destination)
+                .map_or(false, |capacity| queue > capacity)
+        {
+            debug!("@{:?} Dropping message to full inbox: {:?}", clock, event);
+            self.simulated_node_mut(destination).dropped_messages += 1;
+            self.drop_message(&event, FaultKind::InboxOverflow);
+            return None;
+        }
+        if let Some(source) = event.source() {
+            self.simulated_node_mut(destination).messages_received += 1;
+            self.peers.learn_peer(destination, source, clock);
+        }
+        Some(event)
+    }
+
+    fn process_event(&mut self, clock: GlobalTime, event: Event<Notification, Request, Response>) {
+        let destination = event.destination();
+        let entry = TraceEntry {
+            time: clock,
+            kind: event.kind(),
+            author: destination,
+        };
+        if let Some(trace) = &mut self.processing_trace {
+            trace.push(entry);
+        }
+        if let Some(trace) = &mut self.bounded_trace {
+            trace.record(TraceRecord::Event(entry));
+        }
+        let event = match self.accept_event(clock, event) {
+            Some(event) => event,
+            None => return,
+        };
+        debug!("@{:?} Processing event {:?}", clock, event);
+        match event {
+            Event::UpdateTimerEvent { author } => {
+                if clock <= self.simulated_node(author).ignore_scheduled_updates_until {
+                    // This scheduled update was invalidated in the meantime.
+                    debug!("@{:?} Timer was cancelled: {:?}", clock, event);
+                    return;
+                }
+                // Only draw when needed so that runs without timer loss are not affected.
+                let loss_rate = self.timer_loss_rates.get(&author).cloned().unwrap_or(0.0);
+                if loss_rate > 0.0 && self.rng.gen_bool(loss_rate) {
+                    debug!("@{:?} Missing timer: {:?}", clock, event);
+                    self.record_fault(FaultKind::MissedTimer);
+                    return;
+                }
+                let actions = self.simulated_node_mut(author).update(clock, 0);
+                trace!("Node state: {:?}", self.simulated_node(author));
+                self.process_node_actions(clock, author, actions);
+            }
+            Event::DataSyncNotifyEvent {
+                receiver,
+                sender,
+                notification,
+            } => {
+                let num_records = self
+                    .message_sizes
+                    .as_ref()
+                    .map_or(0, |sizes| (sizes.notification_records)(&notification));
+                let node = self.simulated_node_mut(receiver);
+                let result = node
+                    .node
+                    .handle_notification(notification, &mut node.context);
+                let actions = node.update(clock, num_records);
+                if let Some(request) = result {
+                    self.issue_sync_request(receiver, sender, request);
+                }
+                trace!(
+                    "Node state: {:?}, node index: {:?}",
+                    self.simulated_node(receiver),
+                    receiver
+                );
+                self.process_node_actions(clock, receiver, actions);
+            }
+            Event::DataSyncRequestEvent {
+                receiver,
+                sender,
+                request,
+            } => {
+                let node = &self.simulated_node(sender).node;
+                let response = node.handle_request_from(receiver, request);
+                let low_priority = node.deprioritizes_requests_from(receiver);
+                if node.refuses_requests_from(receiver) {
+                    self.simulated_node_mut(sender).refused_requests += 1;
+                }
+                let mut latency = self
+                    .latencies
+                    .sample_response_latency(sender, &mut self.rng);
+                if low_priority {
+                    latency += self.low_priority_response_delay;
+                }
+                self.latencies.record_response_latency(latency);
+                self.schedule_network_event_at(
+                    clock + latency,
+                    Event::DataSyncResponseEvent {
+                        sender,
+                        receiver,
+                        response,
+                    },
+                );
+            }
+            Event::DataSyncResponseEvent {
+                receiver,
+                sender,
+                response,
+            } => {
+                self.complete_sync_session(receiver, sender);
+                if let Some(response) = self.hold_sync_response(receiver, sender, response) {
+                    self.deliver_sync_response(clock, receiver, response);
+                }
+            }
+        }
+        self.release_sync_responses(clock, destination);
+        self.simulated_node_mut(destination).record_commits(clock);
+        self.send_queued_sync_requests(destination);
+    }
+
+    /// Keep a response with the oldest request of `requester` to `responder` that awaits its
+    /// response, if responses are ordered. Otherwise, return the response to deliver it now.
+    fn hold_sync_response(
+        &mut self,
+        requester: Author,
+        responder: Author,
+        response: Response,
+    ) -> Option<Response> {
+        let timeout = match self.sync.ordered_responses {
+            Some(timeout) => timeout,
+            None => return Some(response),
+        };
+        let clock = self.clock;
+        let ordered = &mut self.sync.sessions[requester.0].ordered;
+        let position = match ordered
+            .iter()
+            .position(|(author, _, response)| *author == responder && response.is_none())
+        {
+            Some(position) => position,
+            // Responses to expired requests can only help.
+            None => return Some(response),
+        };
+        let is_held_back = ordered
+            .iter()
+            .take(position)
+            .any(|(_, send_time, response)| response.is_none() && clock.0 - send_time.0 < timeout);
+        if is_held_back {
+            self.sync.stats.buffered_responses += 1;
+            self.sync.stats.buffered_bytes += self
+                .message_sizes
+                .as_ref()
+                .map_or(0, |sizes| (sizes.response_size)(&response));
+        }
+        ordered[position].2 = Some(response);
+        None
+    }
+
+    /// Deliver the responses of `requester` that no longer wait for older responses, after
+    /// giving up on the older requests that timed out.
+    fn release_sync_responses(&mut self, clock: GlobalTime, requester: Author) {
+        let timeout = match self.sync.ordered_responses {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        let ordered = &mut self.sync.sessions[requester.0].ordered;
+        let num_requests = ordered.len();
+        ordered.retain(|(_, send_time, response)| {
+            response.is_some() || clock.0 - send_time.0 < timeout
+        });
+        self.sync.stats.cancelled_sessions += num_requests - ordered.len();
+        let mut responses = Vec::new();
+        while let Some((_, _, Some(_))) = ordered.front() {
+            responses.extend(ordered.pop_front().and_then(|(_, _, response)| response));
+        }
+        for response in responses {
+            self.deliver_sync_response(clock, requester, response);
+        }
+    }
+
+    fn deliver_sync_response(&mut self, clock: GlobalTime, requester: Author, response: Response) {
+        let num_records = self
+            .message_sizes
+            .as_ref()
+            .map_or(0, |sizes| (sizes.response_records)(&response));
+        let node = self.simulated_node_mut(requester);
+        node.pending_requests = node.pending_requests.saturating_sub(1);
+        let local_clock = clock.to_node_time(node.startup_time);
+        node.node
+            .handle_response(response, &mut node.context, local_clock);
+        let actions = node.update(clock, num_records);
+        trace!("Node state: {:?}", node);
+        self.process_node_actions(clock, requester, actions);
+    }
+
+    /// Handle notifications delivered to the same node at the same time, then update the node
+    /// once.
+    fn process_notifications(
+        &mut self,
+        clock: GlobalTime,
+        receiver: Author,
+        events: Vec<Event<Notification, Request, Response>>,
+    ) {
+        let entries: Vec<_> = events
+            .iter()
+            .map(|event| TraceEntry {
+                time: clock,
+                kind: event.kind(),
+                author: receiver,
+            })
+            .collect();
+        if let Some(trace) = &mut self.processing_trace {
+            trace.extend(entries.iter().cloned());
+        }
+        if let Some(trace) = &mut self.bounded_trace {
+            for entry in entries {
+                trace.record(TraceRecord::Event(entry));
+            }
+        }
+        let mut senders = Vec::new();
+        let mut notifications = Vec::new();
+        for event in events {
+            if let Some(Event::DataSyncNotifyEvent {
+                sender,
+                notification,
+                ..
+            }) = self.accept_event(clock, event)
+            {
+                senders.push(sender);
+                notifications.push(notification);
+            }
+        }
+        if notifications.is_empty() {
+            return;
+        }
+        debug!(
+            "@{:?} Processing {} notifications for {:?}",
+            clock,
+            notifications.len(),
+            receiver
+        );
+        let num_records = self.message_sizes.as_ref().map_or(0, |sizes| {
+            notifications.iter().map(sizes.notification_records).sum()
+        });
+        let node = self.simulated_node_mut(receiver);
+        let results = node
+            .node
+            .handle_notifications(notifications, &mut node.context);
+        let actions = node.update(clock, num_records);
+        for (sender, result) in senders.into_iter().zip(results) {
+            if let Some(request) = result {
+                self.issue_sync_request(receiver, sender, request);
+            }
+        }
+        trace!("Node state: {:?}", self.simulated_node(receiver));
+        self.process_node_actions(clock, receiver, actions);
+        self.simulated_node_mut(receiver).record_commits(clock);
+        self.send_queued_sync_requests(receiver);
+    }
+
+    /// Take the next event to process, unless it is due after `max_clock`.
+    fn next_event(
+        &mut self,
+        max_clock: GlobalTime,
+    ) -> Option<(GlobalTime, Event<Notification, Request, Response>)> {
+        if !self.fair_scheduler.enabled {
+            self.cross_network_core(max_clock);
+            let clock = match self.pending_events.peek() {
+                Some(ScheduledEvent(std::cmp::Reverse(clock), ..)) if *clock <= max_clock => *clock,
+                // Later events stay scheduled, see `loop_until`.
+                _ => return None,
+            };
+            let event = self.deliver_event(clock);
+            return Some((clock, event));
+        }
+        if self.fair_scheduler.is_empty() {
+            self.cross_network_core(max_clock);
+            match self.pending_events.peek() {
+                Some(ScheduledEvent(std::cmp::Reverse(clock), ..)) if *clock <= max_clock => {
+                    self.fair_scheduler.start_round(*clock);
+                }
+                _ => return None,
+            }
+        }
+        // Take all the events due at the same time, including the ones scheduled meanwhile.
+        // Messages arriving later in the network core wait for the events served before them.
+        let fair_clock = self.fair_scheduler.clock;
+        self.cross_network_core(fair_clock);
+        while let Some(ScheduledEvent(std::cmp::Reverse(clock), ..)) = self.pending_events.peek() {
+            if *clock > fair_clock {
+                break;
+            }
+            // Queued events are served at `fair_clock`.
+            let event = self.deliver_event(fair_clock);
+            self.fair_scheduler.push(event);
+            self.cross_network_core(fair_clock);
+        }
+        self.fair_scheduler.pop()
+    }
+
+    /// Let the messages due first enter the network core, in their order of arrival until
+    /// `max_clock`, and re-schedule them once transmitted.
+    fn cross_network_core(&mut self, max_clock: GlobalTime) {
+        while let Some(ScheduledEvent(std::cmp::Reverse(arrival), .., true, _)) =
+            self.pending_events.peek()
+        {
+            if *arrival > max_clock {
+                break;
+            }
+            let ScheduledEvent(std::cmp::Reverse(arrival), event, _, injected, _, send_time) =
+                self.pop_event().unwrap();
+            let size = self
+                .message_sizes
+                .as_ref()
+                .map_or(0, |sizes| sizes.event_size(&event));
+            let core = self.network_core.as_mut().unwrap();
+            let deadline = arrival + core.transmit(arrival, size);
+            self.push_event(deadline, event, injected, false, send_time);
+        }
+    }
+
+    /// Pop the next event, to be delivered at `clock`, and count it if it is a message delivered
+    /// before it was sent.
+    fn deliver_event(&mut self, clock: GlobalTime) -> Event<Notification, Request, Response> {
+        let ScheduledEvent(_, event, _, _, _, send_time) = self.pop_event().unwrap();
+        if let Some(send_time) = send_time {
+            if clock < send_time {
+                warn!(
+                    "@{:?} Delivering message sent at {:?}: {:?}",
+                    clock, send_time, event
+                );
+                self.future_messages += 1;
+            }
+        }
+        event
+    }
+
+    fn pop_event(&mut self) -> Option<ScheduledEvent<Notification, Request, Response>> {
+        let scheduled_event = self.pending_events.pop()?;
+        if scheduled_event.1.source().is_some() {
+            self.scheduled_messages -= 1;
+        }
+        Some(scheduled_event)
+    }
+
+    /// Time of the next event to process, if any.
+    fn next_event_time(&self) -> Option<GlobalTime> {
+        if !self.fair_scheduler.is_empty() {
+            return Some(self.fair_scheduler.clock);
+        }
+        self.pending_events
+            .peek()
+            .map(|ScheduledEvent(std::cmp::Reverse(clock), ..)| *clock)
+    }
+
+    /// Take the next notification to `receiver` due at `clock`, if it would be processed next
+    /// by the destination, e.g. to coalesce it with the current one.
+    fn next_notification_to(
+        &mut self,
+        receiver: Author,
+        clock: GlobalTime,
+    ) -> Option<Event<Notification, Request, Response>> {
+        if self.fair_scheduler.enabled {
+            let queue = self.fair_scheduler.queues.get_mut(&receiver)?;
+            return match queue.front() {
+                Some(Event::DataSyncNotifyEvent { .. }) => queue.pop_front(),
+                _ => None,
+            };
+        }
+        self.cross_network_core(clock);
+        let is_batched = match self.pending_events.peek() {
+            Some(ScheduledEvent(
+                std::cmp::Reverse(next_clock),
+                Event::DataSyncNotifyEvent {
+                    receiver: next_receiver,
+                    ..
+                },
+                ..,
+            )) => *next_clock == clock && *next_receiver == receiver,
+            _ => false,
+        };
+        if is_batched {
+            Some(self.deliver_event(clock))
+        } else {
+            None
+        }
+    }
+
+    /// Deliver the events buffered while the node was frozen, in their original order.
+    fn thaw(&mut self, clock: GlobalTime, author: Author) {
+        let events = match self.freezes.buffered_events.remove(&author) {
+            Some(events) => events,
+            None => return,
+        };
+        debug!(
+            "@{:?} Delivering {} buffered events to {:?}",
+            clock,
+            events.len(),
+            author
+        );
+        for (index, event) in events.into_iter().enumerate() {
+            if index == 0 || self.freezes.thaw_processing_delay == 0 {
+                self.process_event(clock, event);
+            } else {
+                // Distinct deadlines preserve the order of the backlog.
+                let deadline = clock + (index as Duration) * self.freezes.thaw_processing_delay;
+                self.schedule_event(deadline, event);
+            }
+        }
+    }
+
+    /// Process the events due at or before `max_clock`. Later events stay scheduled, so that
+    /// calling `loop_until` again with a later `max_clock` resumes the same simulation.
+    pub fn loop_until(
+        &mut self,
+        max_clock: GlobalTime,
+        csv_path: Option<String>,
+    ) -> SimulationResult<'_, Context> {
+        let fingerprint = self.fingerprint();
+        let mut data_writer = { csv_path.map(|path| DataWriter::new(self.nodes.len(), path)) };
+
+        while self.step_with_data_writer(max_clock, data_writer.as_mut()) {}
+        debug_assert!(
+            self.verify_no_future_messages(),
+            "Some message was delivered before it was sent"
+        );
+
+        if let Some(data_writer_val) = data_writer {
+            data_writer_val.write_to_file();
+        }
+
+        self.result(fingerprint)
+    }
+
+    /// Process the next event due at or before `max_clock`, if any, through the same event
+    /// loop as `loop_until`. Return whether an event was processed. Useful to check invariants
+    /// between events in tests.
+    pub fn step(&mut self, max_clock: GlobalTime) -> bool {
+        self.step_with_data_writer(max_clock, None)
+    }
+
+    fn step_with_data_writer(
+        &mut self,
+        max_clock: GlobalTime,
+        mut data_writer: Option<&mut DataWriter>,
+    ) -> bool {
+        let (clock, event) = match self.next_event(max_clock) {
+            Some(next) => next,
+            None => return false,
+        };
+        if let Some(data_writer_val) = data_writer.as_mut() {
+            data_writer_val.update_round_number(self, &clock);
+            data_writer_val.add_message_counter(&event);
+        }
+
+        let destination = event.destination();
+        let mut events = vec![event];
+        if self.coalesce_notifications {
+            if let Event::DataSyncNotifyEvent { .. } = &events[0] {
+                while let Some(next_event) = self.next_notification_to(destination, clock) {
+                    if let Some(data_writer_val) = data_writer.as_mut() {
+                        data_writer_val.add_message_counter(&next_event);
+                    }
+                    events.push(next_event);
+                }
+            }
+        }
+
+        // Events scheduled in the past are fine but they do not move the clock.
+        let clock = std::cmp::max(clock, self.clock);
+        self.clock = clock;
+        self.apply_domain_crashes(clock);
+        if self.freezes.is_frozen(destination, clock) {
+            debug!(
+                "@{:?} Buffering events for frozen node: {:?}",
+                clock, events
+            );
+            for _ in &events {
+                self.record_fault(FaultKind::FrozenEvent);
+            }
+            self.freezes
+                .buffered_events
+                .entry(destination)
+                .or_default()
+                .extend(events);
+            return true;
+        }
+        if self.fair_scheduler.enabled
+            && self.freezes.thaw_processing_delay == 0
+            && self.freezes.buffered_events.contains_key(&destination)
+        {
+            // Serve the backlog in turn with the events of other nodes, followed by the
+            // new events.
+            let backlog = self.freezes.buffered_events.remove(&destination).unwrap();
+            debug!(
+                "@{:?} Queueing {} buffered events for {:?}",
+                clock,
+                backlog.len(),
+                destination
+            );
+            let queue = self.fair_scheduler.queues.entry(destination).or_default();
+            for event in backlog.into_iter().chain(events).rev() {
+                queue.push_front(event);
+            }
+            return true;
+        }
+        self.thaw(clock, destination);
+        let header = events[0].header();
+        let progress = self.round_progress(destination);
+        if events.len() == 1 {
+            self.process_event(clock, events.pop().unwrap());
+        } else {
+            self.process_notifications(clock, destination, events);
+        }
+        if let Some(data_writer_val) = data_writer {
+            let (num_commits, num_timeouts) = self.round_progress(destination);
+            data_writer_val.add_round_type_counter(
+                &header,
+                num_commits > progress.0,
+                num_timeouts > progress.1,
+            );
+        }
+        self.update_round_gap(clock, destination);
+        true
+    }
+
+    fn update_round_gap(&mut self, clock: GlobalTime, author: Author) {
+        let node = &self.simulated_node(author).node;
+        let committed_round = match node.committed_round() {
+            Some(round) => round,
+            None => return,
+        };
+        let active_round = node.active_round();
+        self.round_inflations
+            .update(clock, author, active_round, committed_round);
+    }
+
+    /// Number of committed entries and number of timeouts of a node, to classify rounds.
+    fn round_progress(&self, author: Author) -> (usize, usize) {
+        let node = self.simulated_node(author);
+        (
+            node.context.committed_chain().len(),
+            node.node.num_timeouts(),
+        )
+    }
+
+    fn result(&self, fingerprint: u64) -> SimulationResult<'_, Context> {
+        SimulationResult {
+            contexts: self.nodes.iter().map(|node| &node.context).collect(),
+            fingerprint,
+            latencies: self.latencies.stats.clone(),
+            sync: self.sync.stats.clone(),
+            summary: self.summary(),
+            pending: self.pending_census(),
+            expected_latency: self.expected_latency_under_good_network(),
+        }
+    }
+
+    /// Run as `loop_until` while `adversary` intercepts every notification sent on the
+    /// network, e.g. to check safety under active attacks. Notifications still held by the
+    /// adversary at the end are lost.
+    pub fn run_adversarial_sync(
+        &mut self,
+        adversary: Box<dyn AdversarySyncStrategy<Notification>>,
+        max_clock: GlobalTime,
+    ) -> SimulationResult<'_, Context> {
+        self.adversary = Some(adversary);
+        let fingerprint = self.loop_until(max_clock, None).fingerprint;
+        self.adversary = None;
+        self.result(fingerprint)
+    }
+
+    /// Run until the network is quiescent, that is, no message is in flight and no node has
+    /// changed rounds during `settling_window`, or until `max_clock` otherwise. Timers may
+    /// still be pending.
+    pub fn loop_until_quiescent(
+        &mut self,
+        max_clock: GlobalTime,
+        settling_window: Duration,
+    ) -> SimulationResult<'_, Context> {
+        let mut rounds = self.active_rounds();
+        let mut last_round_change = self.clock;
+        let mut until = max_clock;
+        while let Some(clock) = self.next_event_time() {
+            if !self.has_messages_in_flight() && clock >= last_round_change + settling_window {
+                until = self.clock;
+                break;
+            }
+            if !self.step(max_clock) {
+                break;
+            }
+            let new_rounds = self.active_rounds();
+            if new_rounds != rounds {
+                rounds = new_rounds;
+                last_round_change = self.clock;
+            }
+        }
+        self.loop_until(until, None)
+    }
+
+    fn active_rounds(&self) -> Vec<Round> {
+        self.nodes.iter().map(|node| node.active_round()).collect()
+    }
+
+    /// Whether every node, crashed or not, has reached `round`.
+    pub fn all_nodes_reached_round(&self, round: Round) -> bool {
+        self.nodes.iter().all(|node| node.active_round() >= round)
+    }
+
+    /// Whether some node has reached `round`.
+    pub fn any_node_reached_round(&self, round: Round) -> bool {
+        self.nodes.iter().any(|node| node.active_round() >= round)
+    }
+
+    /// Time at which the last message scheduled for delivery is due, if any. Messages buffered
+    /// by frozen nodes are not counted.
+    pub fn last_delivery_time(&self) -> Option<GlobalTime> {
+        self.pending_events
+            .iter()
+            .filter(|ScheduledEvent(_, event, ..)| event.source().is_some())
+            .map(|ScheduledEvent(std::cmp::Reverse(clock), ..)| *clock)
+            .max()
+    }
+
+    /// Whether messages are scheduled for delivery, queued in fair mode or buffered by frozen
+    /// nodes.
+    fn has_messages_in_flight(&self) -> bool {
+        self.scheduled_messages > 0
+            || self
+                .fair_scheduler
+                .queues
+                .values()
+                .flatten()
+                .any(|event| event.source().is_some())
+            || self
+                .freezes
+                .buffered_events
+                .values()
+                .any(|events| !events.is_empty())
+    }
+
+    /// Run `steps` simulations until `max_clock`, varying `param` linearly from the start of
+    /// `range` (included) to its end (excluded), while other parameters keep their values in
+    /// `baseline`. Simulators are created by `make_simulator` from a number of nodes and a
+    /// network delay.
+    pub fn run_sensitivity_analysis<F>(
+        baseline: SimulationParameters,
+        param: SensitivityParam,
+        range: Range<f64>,
+        steps: usize,
+        max_clock: GlobalTime,
+        make_simulator: F,
+    ) -> SensitivityResult
+    where
+        F: Fn(usize, RandomDelay) -> Self,
+    {
+        let mut result = SensitivityResult::default();
+        for step in 0..steps {
+            let value = range.start + (range.end - range.start) * step as f64 / steps as f64;
+            let parameters = baseline.with_value(param, value);
+            let network_delay = RandomDelay::new(
+                parameters.network_delay_mean,
+                parameters.network_delay_variance,
+            );
+            let mut sim = make_simulator(parameters.num_nodes, network_delay)
+                .with_message_loss_rate(parameters.message_loss_rate);
+            let summary = sim.loop_until(max_clock, None).summary;
+            let commit_rate = if summary.duration > 0 {
+                summary.committed_entries as f64 / summary.duration as f64
+            } else {
+                0.0
+            };
+            let latency_p95 =
+                percentile(&sim.commit_latencies(), 95).map_or(f64::NAN, |latency| latency as f64);
+            result.param_values.push(value);
+            result.commit_rates.push(commit_rate);
+            result.latencies_p95.push(latency_p95);
+        }
+        result
+    }
+
+    /// Run `runs` simulations until `max_clock`, with seeds `0..runs` given to
+    /// `make_simulator`, and return the mean and the sample standard deviation of
+    /// `target_property` over the results. At least one run is required.
+    pub fn run_monte_carlo<F, P>(
+        runs: u32,
+        max_clock: GlobalTime,
+        make_simulator: F,
+        target_property: P,
+    ) -> (f64, f64)
+    where
+        F: Fn(u64) -> Self,
+        P: Fn(&SimulationResult<Context>) -> f64,
+    {
+        assert!(runs > 0, "A Monte Carlo analysis needs at least one run.");
+        let values: Vec<_> = (0..runs)
+            .map(|seed| {
+                let mut sim = make_simulator(u64::from(seed));
+                target_property(&sim.loop_until(max_clock, None))
+            })
+            .collect();
+        let count = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / count;
+        let stddev = if values.len() > 1 {
+            let squares: f64 = values.iter().map(|value| (value - mean).powi(2)).sum();
+            (squares / (count - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+        (mean, stddev)
+    }
+
+    /// Theoretical minimum commit latency when every message takes the mean network delay: a
+    /// proposal, a vote and the delivery of the QC, whatever the number of nodes.
+    pub fn expected_latency_under_good_network(&self) -> Duration {
+        (3.0 * self.network_delay.mean).round() as Duration
+    }
+
+    /// Delays between the proposal and the commit of each committed entry, for all nodes, in
+    /// increasing order.
+    fn commit_latencies(&self) -> Vec<Duration> {
+        let mut commit_latencies = Vec::new();
+        for node in &self.nodes {
+            let chain = node.context.committed_chain();
+            for (entry, commit_time) in chain.iter().zip(node.commit_times.iter()) {
+                if let Some((author, time)) = node.context.proposal(entry) {
+                    let proposal_time =
+                        GlobalTime::from_node_time(time, self.simulated_node(author).startup_time);
+                    commit_latencies.push(commit_time.0 - proposal_time.0);
+                }
+            }
+        }
+        commit_latencies.sort_unstable();
+        commit_latencies
+    }
+
+    /// Fraction of the completed rounds in which some node detected an equivocation. Above
+    /// 1/3, the run exceeded the fault threshold of the protocol.
+    pub fn byzantine_fault_rate(&self) -> f64 {
+        let rounds_completed = self.rounds_completed();
+        if rounds_completed == 0 {
+            return 0.0;
+        }
+        let equivocation_rounds: BTreeSet<_> = self
+            .nodes
+            .iter()
+            .flat_map(|node| node.node.equivocation_rounds())
+            .filter(|round| round.0 <= rounds_completed)
+            .collect();
+        equivocation_rounds.len() as f64 / rounds_completed as f64
+    }
+
+    /// Number of rounds completed by the most advanced node.
+    fn rounds_completed(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| node.active_round().0.saturating_sub(1))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Length of the longest committed chain.
+    fn committed_entries(&self) -> usize {
+        self.nodes
+            .iter()
+            .map(|node| node.context.committed_chain().len())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Fraction of the completed rounds that ended with a view change, i.e. in which some node
+    /// timed out. Returns 0 if no node ever timed out and 1 if every round timed out. Values
+    /// above 0.33 suggest that the network conditions are below the liveness threshold of the
+    /// protocol.
+    pub fn measure_view_change_frequency(&self) -> f64 {
+        let rounds_completed = self.rounds_completed();
+        if rounds_completed == 0 {
+            return 0.0;
+        }
+        let view_changes = self
+            .timeouts_per_round
+            .keys()
+            .filter(|round| round.0 <= rounds_completed)
+            .count();
+        view_changes as f64 / rounds_completed as f64
+    }
+
+    /// Jain's fairness index of the number of entries proposed by each node in the longest
+    /// committed chain, i.e. `(sum x)^2 / (n * sum x^2)`. Returns 1 if every node proposed
+    /// as many entries, and `1/n` if a single node proposed all of them. Entries with an
+    /// unknown proposer are ignored. Returns 1 if no proposer is known.
+    pub fn proposer_fairness(&self) -> f64 {
+        let mut counts = vec![0usize; self.nodes.len()];
+        if let Some(node) = self
+            .nodes
+            .iter()
+            .max_by_key(|node| node.context.committed_chain().len())
+        {
+            for entry in node.context.committed_chain() {
+                if let Some((author, _)) = node.context.proposal(entry) {
+                    counts[author.0] += 1;
+                }
+            }
+        }
+        let sum: usize = counts.iter().sum();
+        if sum == 0 {
+            return 1.0;
+        }
+        let sum_of_squares: usize = counts.iter().map(|count| count * count).sum();
+        (sum * sum) as f64 / (counts.len() * sum_of_squares) as f64
+    }
+
+    /// Standard BFT test: partition the last `f` nodes away from the others during
+    /// `duration`, then check that no pair of nodes has diverging committed chains, and that
+    /// every live node of the majority committed new entries. The partition is healed at the
+    /// end.
+    pub fn run_network_partition_tolerance_test(
+        &mut self,
+        f: usize,
+        duration: Duration,
+    ) -> PartitionToleranceReport {
+        let num_nodes = self.nodes.len();
+        assert!(f < num_nodes, "The majority must not be empty.");
+        let (majority, minority): (Vec<_>, Vec<_>) = (0..num_nodes)
+            .map(Author)
+            .partition(|author| author.0 < num_nodes - f);
+        let chain_lengths = |sim: &Self| -> Vec<usize> {
+            sim.nodes
+                .iter()
+                .map(|node| node.context.committed_chain().len())
+                .collect()
+        };
+        let initial_lengths = chain_lengths(self);
+        self.set_partition(vec![majority.clone(), minority.clone()]);
+        self.loop_until(self.clock + duration, None);
+        self.heal_partition();
+        let new_commits: Vec<_> = chain_lengths(self)
+            .iter()
+            .zip(initial_lengths)
+            .map(|(length, initial_length)| length - initial_length)
+            .collect();
+        let mut safety_violations = Vec::new();
+        for (i, node1) in self.nodes.iter().enumerate() {
+            for (j, node2) in self.nodes.iter().enumerate().skip(i + 1) {
+                let chain1 = node1.context.committed_chain();
+                let chain2 = node2.context.committed_chain();
+                if let Some(index) = divergence_index(chain1, chain2) {
+                    safety_violations.push((Author(i), Author(j), index));
+                }
+            }
+        }
+        let stalled_nodes = majority
+            .iter()
+            .cloned()
+            .filter(|author| new_commits[author.0] == 0 && !self.simulated_node(*author).crashed)
+            .collect();
+        PartitionToleranceReport {
+            majority,
+            minority,
+            new_commits,
+            safety_violations,
+            stalled_nodes,
+        }
+    }
+
+    fn summary(&self) -> SimulationSummary {
+        let rounds_completed = self.rounds_completed();
+        let commit_latencies = self.commit_latencies();
+        let mean_commit_latency = if commit_latencies.is_empty() {
+            None
+        } else {
+            let total: Duration = commit_latencies.iter().sum();
+            Some(total as f64 / commit_latencies.len() as f64)
+        };
+        let p50_commit_latency = percentile(&commit_latencies, 50);
+        let p99_commit_latency = percentile(&commit_latencies, 99);
+        let timeout_rate = self.measure_view_change_frequency();
+        let mut safety_violations = 0;
+        for (i, node1) in self.nodes.iter().enumerate() {
+            for node2 in self.nodes.iter().skip(i + 1) {
+                let chain1 = node1.context.committed_chain();
+                let chain2 = node2.context.committed_chain();
+                if divergence_index(chain1, chain2).is_some() {
+                    safety_violations += 1;
+                }
+            }
+        }
+        SimulationSummary {
+            duration: self.clock.0,
+            rounds_completed,
+            in_flight_rounds: self
+                .nodes
+                .iter()
+                .filter(|node| !node.crashed)
+                .map(|node| node.node.in_flight_rounds())
+                .max()
+                .unwrap_or(0),
+            committed_entries: self.committed_entries(),
+            mean_commit_latency,
+            p50_commit_latency,
+            p99_commit_latency,
+            timeout_rate,
+            messages_sent: self.latencies.stats.network_messages,
+            messages_dropped: self.num_dropped_messages,
+            safety_violations,
+            crashed_nodes: self
+                .nodes_iter()
+                .filter(|(_, node)| node.crashed)
+                .map(|(author, _)| author)
+                .collect(),
+            partitioned: self.network_faults.is_partitioned(self.clock),
+            omitted_messages: self.replay.omitted_messages.len(),
+            fault_coverage: self.fault_coverage.clone(),
+            byzantine_fault_rate: if self.safety_checking {
+                Some(self.byzantine_fault_rate())
+            } else {
+                None
+            },
+            round_inflations: self
+                .round_inflations
+                .threshold
+                .map(|_| self.round_inflations.inflations.len()),
+            peak_core_utilization: self
+                .network_core
+                .as_ref()
+                .and_then(NetworkCore::peak_utilization),
+            core_utilization: self
+                .network_core
+                .as_ref()
+                .map_or_else(Vec::new, NetworkCore::utilization),
+        }
+    }
+}
+
+/// Smallest value such that at least `percent`% of the sorted values are lower or equal.
+pub(crate) fn percentile<T: Copy>(sorted_values: &[T], percent: usize) -> Option<T> {
+    let index = (sorted_values.len() * percent + 99) / 100;
+    index
+        .checked_sub(1)
+        .and_then(|index| sorted_values.get(index).cloned())
+}
+
+/// First index at which two committed chains differ, if any.
+fn divergence_index<Entry: Eq>(chain1: &[Entry], chain2: &[Entry]) -> Option<usize> {
+    (0..std::cmp::min(chain1.len(), chain2.len())).find(|index| chain1[*index] != chain2[*index])
+}
+
+impl<Node, Context, Notification, Request, Response>
+    Simulator<Node, Context, Notification, Request, Response>
+where
+    Context: CommittedChain,
+{
+    /// Check that all pairs of live nodes agree on their committed chains, and that no node
+    /// lags behind another by more than `tolerance_ms` of simulated time. Panics with the
+    /// divergence point and the conflicting tails otherwise.
+    pub fn assert_eventual_consistency(&self, tolerance_ms: Duration) {
+        let live_nodes: Vec<_> = self
+            .nodes_iter()
+            .filter(|(_, node)| !node.crashed)
+            .collect();
+        for (i, (author1, node1)) in live_nodes.iter().enumerate() {
+            for (author2, node2) in live_nodes.iter().skip(i + 1) {
+                let chain1 = node1.context.committed_chain();
+                let chain2 = node2.context.committed_chain();
+                if let Some(index) = divergence_index(chain1, chain2) {
+                    panic!(
+                        "Committed chains of {:?} and {:?} diverge at index {}: {:?} vs {:?}",
+                        author1,
+                        author2,
+                        index,
+                        &chain1[index..],
+                        &chain2[index..]
+                    );
+                }
+                let (lagging_author, leading_author, leading_node, index) =
+                    if chain1.len() < chain2.len() {
+                        (author1, author2, node2, chain1.len())
+                    } else {
+                        (author2, author1, node1, chain2.len())
+                    };
+                if let Some(commit_time) = leading_node.commit_times.get(index) {
+                    assert!(
+                        self.clock.0 - commit_time.0 <= tolerance_ms,
+                        "{:?} lags behind {:?}: entry {} was committed by the latter at {:?}",
+                        lagging_author,
+                        leading_author,
+                        index,
+                        commit_time
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Run the simulation until `within`, then check that all live nodes have agreed on a common,
+/// non-empty committed prefix. Panics with a diagnostic otherwise.
+pub fn assert_eventual_agreement<Node, Context, Notification, Request, Response>(
+    sim: &mut Simulator<Node, Context, Notification, Request, Response>,
+    within: GlobalTime,
+) where
+    Context: CommittedChain + std::fmt::Debug,
+    Node: BlockProposer<Context>
+        + DataSyncNode<Context, Notification = Notification, Request = Request, Response = Response>
+        + ActiveRound
+        + std::fmt::Debug,
+    Notification: std::cmp::Ord + std::fmt::Debug + std::clone::Clone + NotificationKind,
+    Request: std::cmp::Ord + std::fmt::Debug + std::clone::Clone,
+    Response: std::cmp::Ord + std::fmt::Debug,
+{
+    sim.loop_until(within, None);
+    sim.assert_eventual_consistency(Duration::MAX);
+    let num_commits: Vec<_> = sim
+        .nodes_iter()
+        .filter(|(_, node)| !node.crashed)
+        .map(|(author, node)| (author, node.context.committed_chain().len()))
+        .collect();
+    assert!(
+        num_commits.iter().all(|(_, n)| *n > 0),
+        "Live nodes did not agree on a common committed prefix by {:?}. \
+         Committed entries per node: {:?}",
+        within,
+        num_commits
+    );
+}
@@ -0,0 +1,551 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    base_types::{Author, Duration, Round},
+    schema,
+    simulator::{FaultKind, GlobalTime, TraceEntry},
+};
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fs, io,
+    path::Path,
+};
+
+#[cfg(test)]
+#[path = "unit_tests/trace_tests.rs"]
+mod trace_tests;
+
+/// An observation recorded by a `BoundedTrace`.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum TraceRecord {
+    /// An event was processed.
+    Event(TraceEntry),
+    /// A node entered a higher round.
+    RoundChange {
+        time: GlobalTime,
+        author: Author,
+        round: Round,
+    },
+    /// A node timed out a round.
+    Timeout {
+        time: GlobalTime,
+        author: Author,
+        round: Round,
+    },
+    /// A fault model was activated.
+    Fault { time: GlobalTime, kind: FaultKind },
+    /// An observer found that a node broke an invariant of the protocol.
+    Warning {
+        time: GlobalTime,
+        author: Author,
+        message: &'static str,
+    },
+    /// A node rejected a record, as reported by an observer.
+    Rejection { time: GlobalTime, author: Author },
+}
+
+impl TraceRecord {
+    pub fn time(&self) -> GlobalTime {
+        match self {
+            TraceRecord::Event(entry) => entry.time,
+            TraceRecord::RoundChange { time, .. }
+            | TraceRecord::Timeout { time, .. }
+            | TraceRecord::Fault { time, .. }
+            | TraceRecord::Warning { time, .. }
+            | TraceRecord::Rejection { time, .. } => *time,
+        }
+    }
+}
+
+impl TraceRecord {
+    /// Single-line JSON object following `schema::trace`.
+    pub fn to_json(&self) -> String {
+        let (record, author, round, kind) = match self {
+            TraceRecord::Event(entry) => (
+                "Event",
+                Some(entry.author),
+                None,
+                Some(entry.kind.to_string()),
+            ),
+            TraceRecord::RoundChange { author, round, .. } => {
+                ("RoundChange", Some(*author), Some(*round), None)
+            }
+            TraceRecord::Timeout { author, round, .. } => {
+                ("Timeout", Some(*author), Some(*round), None)
+            }
+            TraceRecord::Fault { kind, .. } => ("Fault", None, None, Some(format!("{:?}", kind))),
+            TraceRecord::Warning {
+                author, message, ..
+            } => ("Warning", Some(*author), None, Some(message.to_string())),
+            TraceRecord::Rejection { author, .. } => ("Rejection", Some(*author), None, None),
+        };
+        let null = || "null".to_string();
+        format!(
+            "{{\"time\": {}, \"record\": \"{}\", \"author\": {}, \"round\": {}, \"kind\": {}}}",
+            self.time().0,
+            record,
+            author.map_or_else(null, |author| author.0.to_string()),
+            round.map_or_else(null, |round| round.0.to_string()),
+            kind.map_or_else(null, |kind| format!("\"{}\"", kind)),
+        )
+    }
+}
+
+impl std::fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TraceRecord::Event(entry) => write!(f, "{}", entry),
+            TraceRecord::RoundChange {
+                time,
+                author,
+                round,
+            } => write!(f, "{} RoundChange {} {}", time.0, author.0, round),
+            TraceRecord::Timeout {
+                time,
+                author,
+                round,
+            } => write!(f, "{} Timeout {} {}", time.0, author.0, round),
+            TraceRecord::Fault { time, kind } => write!(f, "{} Fault {:?}", time.0, kind),
+            TraceRecord::Warning {
+                time,
+                author,
+                message,
+            } => write!(f, "{} Warning {} {}", time.0, author.0, message),
+            TraceRecord::Rejection { time, author } => {
+                write!(f, "{} Rejection {}", time.0, author.0)
+            }
+        }
+    }
+}
+
+/// Predicate that flags anomalies from the stream of records of a `BoundedTrace`.
+pub trait AnomalyTrigger: std::fmt::Debug {
+    /// Short description of the anomaly, reported by `BoundedTrace::anomalies`.
+    fn name(&self) -> &'static str;
+    /// Observe the next record, and tell whether it completes an anomaly.
+    fn observe(&mut self, record: &TraceRecord) -> bool;
+}
+
+/// Fires when a node times out `length` consecutive rounds.
+#[derive(Debug)]
+pub struct TimeoutStreak {
+    length: usize,
+    /// Last round timed out by each node, and the length of its current streak.
+    streaks: BTreeMap<Author, (Round, usize)>,
+}
+
+impl TimeoutStreak {
+    pub fn new(length: usize) -> Self {
+        TimeoutStreak {
+            length,
+            streaks: BTreeMap::new(),
+        }
+    }
+}
+
+impl AnomalyTrigger for TimeoutStreak {
+    fn name(&self) -> &'static str {
+        "timeout streak"
+    }
+
+    fn observe(&mut self, record: &TraceRecord) -> bool {
+        match record {
+            TraceRecord::Timeout { author, round, .. } => {
+                let streak = self.streaks.entry(*author).or_insert((*round, 0));
+                let (last_round, length) = *streak;
+                *streak = if length > 0 && round.follows(last_round) {
+                    (*round, length + 1)
+                } else {
+                    (*round, 1)
+                };
+                streak.1 == self.length
+            }
+            TraceRecord::RoundChange { author, round, .. } => {
+                // Entering a round other than the next one after a timeout ends the streak.
+                if let Some((last_round, length)) = self.streaks.get_mut(author) {
+                    if !round.follows(*last_round) {
+                        *length = 0;
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Fires when no node enters a higher round for `period`.
+#[derive(Debug)]
+pub struct RoundStall {
+    period: Duration,
+    latest_round_change: GlobalTime,
+    reported: bool,
+}
+
+impl RoundStall {
+    pub fn new(period: Duration) -> Self {
+        RoundStall {
+            period,
+            latest_round_change: GlobalTime(0),
+            reported: false,
+        }
+    }
+}
+
+impl AnomalyTrigger for RoundStall {
+    fn name(&self) -> &'static str {
+        "round stall"
+    }
+
+    fn observe(&mut self, record: &TraceRecord) -> bool {
+        if let TraceRecord::RoundChange { time, .. } = record {
+            self.latest_round_change = *time;
+            self.reported = false;
+            return false;
+        }
+        if !self.reported && record.time().0 >= self.latest_round_change.0 + self.period {
+            // Report each stall once.
+            self.reported = true;
+            return true;
+        }
+        false
+    }
+}
+
+/// Times of the latest records of some kind, to detect spikes.
+#[derive(Debug)]
+struct RecentTimes {
+    threshold: usize,
+    period: Duration,
+    times: VecDeque<GlobalTime>,
+}
+
+impl RecentTimes {
+    fn new(threshold: usize, period: Duration) -> Self {
+        RecentTimes {
+            threshold,
+            period,
+            times: VecDeque::new(),
+        }
+    }
+
+    /// Add a time and tell whether `threshold` times fall within `period`.
+    fn is_spike(&mut self, time: GlobalTime) -> bool {
+        while self
+            .times
+            .front()
+            .map_or(false, |first| first.0 + self.period <= time.0)
+        {
+            self.times.pop_front();
+        }
+        self.times.push_back(time);
+        if self.times.len() >= self.threshold {
+            // Report each spike once.
+            self.times.clear();
+            return true;
+        }
+        false
+    }
+}
+
+/// Fires when at least `threshold` faults are activated within `period`.
+#[derive(Debug)]
+pub struct FaultSpike {
+    recent_faults: RecentTimes,
+}
+
+impl FaultSpike {
+    pub fn new(threshold: usize, period: Duration) -> Self {
+        FaultSpike {
+            recent_faults: RecentTimes::new(threshold, period),
+        }
+    }
+}
+
+impl AnomalyTrigger for FaultSpike {
+    fn name(&self) -> &'static str {
+        "fault spike"
+    }
+
+    fn observe(&mut self, record: &TraceRecord) -> bool {
+        match record {
+            TraceRecord::Fault { time, .. } => self.recent_faults.is_spike(*time),
+            _ => false,
+        }
+    }
+}
+
+/// Fires when the nodes reject at least `threshold` records within `period`.
+#[derive(Debug)]
+pub struct RejectionSpike {
+    recent_rejections: RecentTimes,
+}
+
+impl RejectionSpike {
+    pub fn new(threshold: usize, period: Duration) -> Self {
+        RejectionSpike {
+            recent_rejections: RecentTimes::new(threshold, period),
+        }
+    }
+}
+
+impl AnomalyTrigger for RejectionSpike {
+    fn name(&self) -> &'static str {
+        "rejection spike"
+    }
+
+    fn observe(&mut self, record: &TraceRecord) -> bool {
+        match record {
+            TraceRecord::Rejection { time, .. } => self.recent_rejections.is_spike(*time),
+            _ => false,
+        }
+    }
+}
+
+/// Fires on every warning.
+#[derive(Debug, Default)]
+pub struct InvariantWarning;
+
+impl InvariantWarning {
+    pub fn new() -> Self {
+        InvariantWarning
+    }
+}
+
+impl AnomalyTrigger for InvariantWarning {
+    fn name(&self) -> &'static str {
+        "invariant warning"
+    }
+
+    fn observe(&mut self, record: &TraceRecord) -> bool {
+        matches!(record, TraceRecord::Warning { .. })
+    }
+}
+
+/// A record kept by a `BoundedTrace`.
+#[derive(Clone, Debug)]
+struct TracedRecord {
+    /// Position of the record among all the observed ones.
+    index: usize,
+    /// Position among the observed events, for events, or else among the other records.
+    sample_index: usize,
+    /// Whether the record is part of the full detail around an anomaly.
+    pinned: bool,
+    record: TraceRecord,
+}
+
+impl TracedRecord {
+    fn is_event(&self) -> bool {
+        matches!(self.record, TraceRecord::Event(_))
+    }
+}
+
+/// Trace of a long simulation with a target size. Only one event out of `sample_every()` is
+/// kept, and all the other records, e.g. round changes and faults. The last `window` records
+/// are also buffered at full detail: whenever a trigger reports an anomaly, they are flushed to
+/// the trace, together with the `window` records that follow. When the trace exceeds its
+/// budget, the sampling period of events doubles and the kept events are thinned accordingly.
+/// Once a single event is left, the other records are thinned the same way, then the full
+/// detail of the oldest anomalies is dropped, so that the trace never exceeds its budget.
+#[derive(Debug)]
+pub struct BoundedTrace {
+    budget: usize,
+    sample_every: usize,
+    /// Sampling period of the records other than events, once events alone cannot be thinned
+    /// further.
+    sample_others_every: usize,
+    window: usize,
+    triggers: Vec<Box<AnomalyTrigger>>,
+    records: Vec<TracedRecord>,
+    recent_records: VecDeque<TracedRecord>,
+    num_records: usize,
+    num_events: usize,
+    /// Number of upcoming records kept at full detail after an anomaly.
+    full_detail_remaining: usize,
+    anomalies: Vec<(GlobalTime, &'static str)>,
+    /// Latest round recorded for each node.
+    rounds: HashMap<Author, Round>,
+}
+
+impl BoundedTrace {
+    pub fn new(budget: usize, sample_every: usize, window: usize) -> Self {
+        assert!(sample_every > 0, "The sampling period must be positive");
+        BoundedTrace {
+            budget,
+            sample_every,
+            sample_others_every: 1,
+            window,
+            triggers: Vec::new(),
+            records: Vec::new(),
+            recent_records: VecDeque::new(),
+            num_records: 0,
+            num_events: 0,
+            full_detail_remaining: 0,
+            anomalies: Vec::new(),
+            rounds: HashMap::new(),
+        }
+    }
+
+    pub fn with_trigger(mut self, trigger: Box<AnomalyTrigger>) -> Self {
+        self.triggers.push(trigger);
+        self
+    }
+
+    pub fn record(&mut self, record: TraceRecord) {
+        let sample_index = match record {
+            TraceRecord::Event(_) => {
+                self.num_events += 1;
+                self.num_events - 1
+            }
+            _ => self.num_records - self.num_events,
+        };
+        let mut anomaly = None;
+        for trigger in &mut self.triggers {
+            if trigger.observe(&record) && anomaly.is_none() {
+                anomaly = Some(trigger.name());
+            }
+        }
+        let time = record.time();
+        let traced = TracedRecord {
+            index: self.num_records,
+            sample_index,
+            pinned: self.full_detail_remaining > 0,
+            record,
+        };
+        self.num_records += 1;
+        self.full_detail_remaining = self.full_detail_remaining.saturating_sub(1);
+        if self.window > 0 {
+            if self.recent_records.len() == self.window {
+                self.recent_records.pop_front();
+            }
+            self.recent_records.push_back(traced.clone());
+        }
+        if traced.pinned || self.is_sampled(&traced) {
+            self.records.push(traced);
+        }
+        if let Some(name) = anomaly {
+            self.anomalies.push((time, name));
+            self.flush_recent_records();
+            self.full_detail_remaining = self.window;
+        }
+        if self.records.len() > self.budget {
+            self.downsample();
+        }
+    }
+
+    /// Record that a node is at the given round, as a round change if the node entered a
+    /// higher round since the last call.
+    pub fn record_round(&mut self, time: GlobalTime, author: Author, round: Round) {
+        let last_round = self.rounds.insert(author, round).unwrap_or(Round(0));
+        if round > last_round {
+            self.record(TraceRecord::RoundChange {
+                time,
+                author,
+                round,
+            });
+        }
+    }
+
+    /// Forget the rounds of a node, e.g. after it lost its state.
+    pub fn forget_rounds(&mut self, author: Author) {
+        self.rounds.remove(&author);
+    }
+
+    /// Kept records, in order of observation.
+    pub fn records(&self) -> Vec<&TraceRecord> {
+        self.records.iter().map(|traced| &traced.record).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Current sampling period of the events outside of anomalies.
+    pub fn sample_every(&self) -> usize {
+        self.sample_every
+    }
+
+    /// Time and trigger of the anomalies reported so far.
+    pub fn anomalies(&self) -> &[(GlobalTime, &'static str)] {
+        &self.anomalies
+    }
+
+    /// Write the kept records to the file of `schema::trace` in the directory `path`, one
+    /// per line.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let schema = schema::trace();
+        let mut content = format!("{}\n", schema.version_line());
+        for traced in &self.records {
+            content.push_str(&format!("{}\n", traced.record.to_json()));
+        }
+        fs::write(path.join(schema.file_name), content)
+    }
+
+    fn is_sampled(&self, traced: &TracedRecord) -> bool {
+        let sample_every = if traced.is_event() {
+            self.sample_every
+        } else {
+            self.sample_others_every
+        };
+        traced.sample_index % sample_every == 0
+    }
+
+    /// Replace the tail of the trace by the buffered records, which cover it entirely.
+    fn flush_recent_records(&mut self) {
+        let start = match self.recent_records.front() {
+            Some(first) => first.index,
+            None => return,
+        };
+        while self
+            .records
+            .last()
+            .map_or(false, |last| last.index >= start)
+        {
+            self.records.pop();
+        }
+        self.records
+            .extend(self.recent_records.iter().cloned().map(|mut traced| {
+                traced.pinned = true;
+                traced
+            }));
+    }
+
+    /// Double the sampling period of events until the trace fits its budget, or only pinned
+    /// records, the first event and the other records are left. Then do the same with the other
+    /// records, then drop the oldest pinned records.
+    fn downsample(&mut self) {
+        while self.records.len() > self.budget && self.sample_every < self.num_events {
+            self.sample_every *= 2;
+            self.thin();
+        }
+        let num_others = self.num_records - self.num_events;
+        while self.records.len() > self.budget && self.sample_others_every < num_others {
+            self.sample_others_every *= 2;
+            self.thin();
+        }
+        let mut num_excess_records = self.records.len().saturating_sub(self.budget);
+        self.records.retain(|traced| {
+            if traced.pinned && num_excess_records > 0 {
+                num_excess_records -= 1;
+                return false;
+            }
+            true
+        });
+        // Only left with a budget below two records.
+        let num_excess_records = self.records.len().saturating_sub(self.budget);
+        self.records.drain(..num_excess_records);
+    }
+
+    fn thin(&mut self) {
+        let records = std::mem::take(&mut self.records);
+        self.records = records
+            .into_iter()
+            .filter(|traced| traced.pinned || self.is_sampled(traced))
+            .collect();
+    }
+}
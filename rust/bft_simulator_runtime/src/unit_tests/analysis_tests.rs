@@ -0,0 +1,142 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+fn equal_configuration(num_nodes: usize) -> EpochConfiguration {
+    let mut voting_rights = BTreeMap::new();
+    for index in 0..num_nodes {
+        voting_rights.insert(Author(index), 1);
+    }
+    EpochConfiguration::new(voting_rights)
+}
+
+fn round_robin(round: Round) -> Author {
+    Author(round.0 % 4)
+}
+
+/// Delays between 4 nodes, all equal to `delay` except for the given slow nodes.
+fn make_delays(delay: Duration, slow_nodes: &[Author], slow_delay: Duration) -> MessageDelays {
+    let mut delays = MessageDelays::new();
+    for sender in 0..4 {
+        for receiver in 0..4 {
+            let (sender, receiver) = (Author(sender), Author(receiver));
+            let delay = if slow_nodes.contains(&sender) || slow_nodes.contains(&receiver) {
+                slow_delay
+            } else {
+                delay
+            };
+            delays = delays.with_delay(sender, receiver, Round(0), delay);
+        }
+    }
+    delays
+}
+
+#[test]
+fn test_message_delays_from_log() {
+    let message = |send_time, deadline, round| LoggedMessage {
+        send_time: GlobalTime(send_time),
+        deadline: GlobalTime(deadline),
+        sender: Author(0),
+        receiver: Author(1),
+        round: Round(round),
+        kind: "DataSyncNotifyEvent",
+    };
+    let delays =
+        MessageDelays::from_log(&[message(0, 12, 1), message(5, 13, 1), message(20, 35, 2)]);
+    assert_eq!(delays.delay(Author(0), Author(1), Round(1)), Some(8));
+    assert_eq!(delays.delay(Author(0), Author(1), Round(2)), Some(15));
+    // Unrecorded rounds use the fastest delivery of the link.
+    assert_eq!(delays.delay(Author(0), Author(1), Round(3)), Some(8));
+    assert_eq!(delays.delay(Author(1), Author(0), Round(1)), None);
+    assert_eq!(delays.delay(Author(1), Author(1), Round(1)), Some(0));
+}
+
+#[test]
+fn test_ideal_commit_time() {
+    let configuration = equal_configuration(4);
+    // Leader 1 proposes at 100, votes of leader 1 and 2 reach leader 2 after 10, the other
+    // votes after 20, so that each QC takes 20.
+    let delays = make_delays(10, &[], 0);
+    assert_eq!(
+        ideal_commit_time(
+            &delays,
+            &configuration,
+            round_robin,
+            Round(1),
+            GlobalTime(100)
+        ),
+        Some(GlobalTime(160))
+    );
+    // A slow node 3 does not delay the QC of round 1, but the QCs of rounds 2 and 3, which
+    // involve node 3 as a leader, need a message on a slow link, i.e. 100 + 10.
+    let delays = make_delays(10, &[Author(3)], 100);
+    assert_eq!(
+        ideal_commit_time(
+            &delays,
+            &configuration,
+            round_robin,
+            Round(1),
+            GlobalTime(100)
+        ),
+        Some(GlobalTime(100 + 20 + 110 + 110))
+    );
+    // With nodes 0 and 3 slow, each quorum needs a slow node: the QCs of rounds 1 and 3 take
+    // 200, and the QC of round 2, formed by node 3, only takes 110.
+    let delays = make_delays(10, &[Author(0), Author(3)], 100);
+    assert_eq!(
+        ideal_commit_time(
+            &delays,
+            &configuration,
+            round_robin,
+            Round(1),
+            GlobalTime(0)
+        ),
+        Some(GlobalTime(200 + 110 + 200))
+    );
+    // Unknown delays prevent quorums.
+    assert_eq!(
+        ideal_commit_time(
+            &MessageDelays::new(),
+            &configuration,
+            round_robin,
+            Round(1),
+            GlobalTime(0)
+        ),
+        None
+    );
+}
+
+#[test]
+fn test_oracle_report() {
+    let configuration = equal_configuration(4);
+    let delays = make_delays(10, &[], 0);
+    let blocks = vec![
+        CommittedBlock {
+            round: Round(1),
+            proposal_time: GlobalTime(100),
+            commit_time: GlobalTime(220),
+        },
+        CommittedBlock {
+            round: Round(2),
+            proposal_time: GlobalTime(120),
+            commit_time: GlobalTime(180),
+        },
+    ];
+    let report = oracle_report(&delays, &configuration, round_robin, &blocks);
+    assert!(report.skipped_rounds.is_empty());
+    assert_eq!(
+        report.entries[0],
+        OracleEntry {
+            round: Round(1),
+            realized_latency: 120,
+            ideal_latency: 60,
+        }
+    );
+    assert_eq!(report.ratios(), vec![1.0, 2.0]);
+    assert_eq!(report.ratio_percentile(50), Some(1.0));
+    assert_eq!(report.ratio_percentile(100), Some(2.0));
+    let report = oracle_report(&MessageDelays::new(), &configuration, round_robin, &blocks);
+    assert!(report.entries.is_empty());
+    assert_eq!(report.skipped_rounds, vec![Round(1), Round(2)]);
+}
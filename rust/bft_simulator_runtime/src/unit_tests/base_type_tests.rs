@@ -8,6 +8,37 @@ fn test_round_plus_usize() {
     assert_eq!(Round(3) + 4, Round(7));
 }
 
+#[test]
+#[should_panic(expected = "Round overflow")]
+fn test_round_plus_usize_overflow() {
+    let _ = Round(usize::MAX - 1) + 2;
+}
+
+#[test]
+fn test_round_arithmetic_near_max() {
+    let max = Round(usize::MAX);
+    assert_eq!(Round(usize::MAX - 1).next(), max);
+    assert_eq!(max.checked_add(0), Some(max));
+    assert_eq!(max.checked_add(1), None);
+    assert_eq!(Round(3).checked_add(u64::MAX), None);
+    assert!(max.follows(Round(usize::MAX - 1)));
+    assert!(!Round(0).follows(max));
+    assert!(!Round(3).follows(Round(3)));
+}
+
+#[test]
+fn test_round_distance() {
+    assert_eq!(Round(7).distance(Round(3)), 4);
+    assert_eq!(Round(3).distance(Round(7)), 4);
+    assert_eq!(Round(5).distance(Round(5)), 0);
+    assert_eq!(Round(usize::MAX).distance(Round(0)), usize::MAX);
+}
+
+#[test]
+fn test_round_display() {
+    assert_eq!(Round(42).to_string(), "r42");
+}
+
 #[test]
 fn test_signature() {
     let sig = Signature::sign(35, Author(2));
@@ -15,3 +46,16 @@ fn test_signature() {
     assert!(sig.check(32, Author(2)).is_err());
     assert!(sig.check(35, Author(1)).is_err());
 }
+
+#[test]
+fn test_stable_hasher() {
+    let mut hasher = StableHasher::new();
+    hasher.write(b"a");
+    assert_eq!(hasher.finish(), 0xaf63_dc4c_8601_ec8c);
+
+    let mut h1 = StableHasher::new();
+    let mut h2 = StableHasher::new();
+    Author(3).hash(&mut h1);
+    3usize.hash(&mut h2);
+    assert_eq!(h1.finish(), h2.finish());
+}
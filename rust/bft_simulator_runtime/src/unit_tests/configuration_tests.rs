@@ -53,3 +53,32 @@ fn test_quorum() {
     assert_eq!(equal_configuration(5).quorum_threshold(), 4);
     assert_eq!(equal_configuration(6).quorum_threshold(), 5);
 }
+
+#[test]
+fn test_max_block_bytes() {
+    assert_eq!(equal_configuration(4).max_block_bytes(), None);
+    let config = equal_configuration(4).with_max_block_bytes(100);
+    assert_eq!(config.max_block_bytes(), Some(100));
+    assert_ne!(config, equal_configuration(4));
+}
+
+#[test]
+fn test_digest() {
+    let config = equal_configuration(4);
+    assert_eq!(config.digest(), equal_configuration(4).digest());
+    assert_ne!(config.digest(), equal_configuration(5).digest());
+    // The block size limit is not part of the digest.
+    assert_eq!(
+        config.digest(),
+        config.clone().with_max_block_bytes(10).digest()
+    );
+    let mut voting_rights = BTreeMap::new();
+    voting_rights.insert(Author(0), 2);
+    voting_rights.insert(Author(1), 1);
+    voting_rights.insert(Author(2), 1);
+    voting_rights.insert(Author(3), 1);
+    assert_ne!(
+        config.digest(),
+        EpochConfiguration::new(voting_rights).digest()
+    );
+}
@@ -0,0 +1,154 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use crate::{
+    base_types::{Author, Round},
+    data_writer::DataWriter,
+    simulator::{FaultKind, GlobalTime, SimulationSummary, TraceEntry},
+    trace::{BoundedTrace, TraceRecord},
+};
+use std::{fs, path::Path};
+
+// Major version and columns of the first release of this major version. Later releases may
+// only append columns.
+fn released_columns(name: &str) -> (u32, Vec<&'static str>) {
+    match name {
+        "round_switches" => (1, vec!["node {node}"]),
+        "number_of_messages" => (1, vec!["messages"]),
+        "report" => (
+            1,
+            vec![
+                "duration",
+                "rounds_completed",
+                "in_flight_rounds",
+                "committed_entries",
+                "mean_commit_latency",
+                "p50_commit_latency",
+                "p99_commit_latency",
+                "timeout_rate",
+                "messages_sent",
+                "messages_dropped",
+                "safety_violations",
+                "crashed_nodes",
+                "partitioned",
+                "omitted_messages",
+                "byzantine_fault_rate",
+                "round_inflations",
+                "peak_core_utilization",
+            ],
+        ),
+        "trace" => (1, vec!["time", "record", "author", "round", "kind"]),
+        "round_table" => (
+            1,
+            vec!["round", "leader", "outcome", "commit_latency", "votes"],
+        ),
+        "participation" => (
+            1,
+            vec![
+                "author",
+                "voted_rounds",
+                "missed_rounds",
+                "timed_out_rounds",
+                "qcs_included",
+            ],
+        ),
+        _ => panic!("Unknown artifact {}", name),
+    }
+}
+
+#[test]
+fn test_additive_changes() {
+    for schema in current() {
+        let (major, released) = released_columns(schema.name);
+        assert_eq!(schema.version.major, major);
+        let names: Vec<_> = schema.columns.iter().map(|column| column.name).collect();
+        assert_eq!(&names[..released.len()], &released[..]);
+    }
+}
+
+/// Names of the fields of a single-line JSON object, in order.
+fn json_fields(line: &str) -> Vec<&str> {
+    let parts: Vec<_> = line.split('"').collect();
+    parts
+        .windows(2)
+        .filter(|pair| pair[1].starts_with(": "))
+        .map(|pair| pair[0])
+        .collect()
+}
+
+fn column_names(schema: &ArtifactSchema) -> Vec<&'static str> {
+    schema.columns.iter().map(|column| column.name).collect()
+}
+
+#[test]
+fn test_emitted_headers() {
+    let path = std::env::temp_dir().join(format!("schema_tests_{}", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    DataWriter::new(3, path.clone()).write_to_file();
+    for schema in &[round_switches(), number_of_messages()] {
+        let content = fs::read_to_string(format!("{}/{}", path, schema.file_name)).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines[0], schema.version_line());
+        assert_eq!(lines[1], schema.header(3).join(","));
+    }
+
+    let schema = report();
+    SimulationSummary::default()
+        .write(Path::new(&path))
+        .unwrap();
+    let content = fs::read_to_string(format!("{}/{}", path, schema.file_name)).unwrap();
+    assert!(content.starts_with(&schema.version_line()));
+    let mut fields = vec!["schema_version"];
+    fields.extend(column_names(&schema));
+    assert_eq!(json_fields(&content), fields);
+
+    let schema = trace();
+    let mut bounded_trace = BoundedTrace::new(10, 1, 10);
+    bounded_trace.record(TraceRecord::Event(TraceEntry {
+        time: GlobalTime(1),
+        kind: "UpdateTimerEvent",
+        author: Author(0),
+    }));
+    bounded_trace.record(TraceRecord::Fault {
+        time: GlobalTime(2),
+        kind: FaultKind::Crash,
+    });
+    bounded_trace.record(TraceRecord::RoundChange {
+        time: GlobalTime(3),
+        author: Author(1),
+        round: Round(2),
+    });
+    bounded_trace.write(Path::new(&path)).unwrap();
+    let content = fs::read_to_string(format!("{}/{}", path, schema.file_name)).unwrap();
+    let lines: Vec<_> = content.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], schema.version_line());
+    for line in &lines[1..] {
+        assert_eq!(json_fields(line), column_names(&schema));
+    }
+    assert_eq!(
+        lines[2],
+        "{\"time\": 2, \"record\": \"Fault\", \"author\": null, \"round\": null, \"kind\": \"Crash\"}"
+    );
+    fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_header() {
+    assert_eq!(
+        round_switches().header(2),
+        vec!["node 0", "node 1", "round_type"]
+    );
+    assert_eq!(number_of_messages().header(2), vec!["messages"]);
+}
+
+#[test]
+fn test_to_json() {
+    assert_eq!(
+        to_json(&[number_of_messages()]),
+        "[{\"name\": \"number_of_messages\", \"file_name\": \"number_of_messages.txt\", \
+         \"format\": \"csv\", \"schema_version\": \"1.0\", \"columns\": [{\"name\": \"messages\", \
+         \"type\": \"integer\", \"per_node\": false}]}]"
+    );
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use super::*;
+use crate::{ConsensusNode, RoundActions};
 
 #[test]
 fn test_time_conversion() {
@@ -10,3 +11,1838 @@ fn test_time_conversion() {
     assert_eq!(x.to_node_time(start), NodeTime(12));
     assert_eq!(GlobalTime::from_node_time(NodeTime(12), start), x);
 }
+
+#[test]
+fn test_rng_fast_forward() {
+    let delay = RandomDelay::new(10.0, 25.0);
+    let mut rng = CountingRng::seed_from_u64(3);
+    for _ in 0..100 {
+        delay.sample(&mut rng);
+        rng.gen_bool(0.5);
+    }
+    let position = rng.position();
+    assert!(position >= 200);
+    let mut fast_forwarded = CountingRng::seed_from_u64(3);
+    fast_forwarded.advance(position);
+    assert_eq!(fast_forwarded.position(), position);
+    assert_eq!(delay.sample(&mut fast_forwarded), delay.sample(&mut rng));
+    assert_eq!(fast_forwarded.gen::<u64>(), rng.gen::<u64>());
+}
+
+#[derive(Debug, Default)]
+struct TestContext {
+    committed: Vec<u64>,
+    /// Proposers of the committed entries, when known.
+    proposers: HashMap<u64, Author>,
+    consensus_timeout: Option<ConsensusTimeoutHint>,
+}
+
+impl ConsensusTimeout for TestContext {
+    fn set_consensus_timeout_hint(&mut self, hint: ConsensusTimeoutHint) {
+        self.consensus_timeout = Some(hint);
+    }
+
+    fn consensus_timeout_hint(&self) -> Option<ConsensusTimeoutHint> {
+        self.consensus_timeout
+    }
+}
+
+impl CommittedChain for TestContext {
+    type Entry = u64;
+
+    fn committed_chain(&self) -> &[u64] {
+        &self.committed
+    }
+
+    fn proposal(&self, entry: &u64) -> Option<(Author, NodeTime)> {
+        self.proposers
+            .get(entry)
+            .map(|author| (*author, NodeTime(0)))
+    }
+}
+
+#[derive(Debug, Default)]
+struct TestNode {
+    received_responses: Vec<u64>,
+    last_response_clock: Option<NodeTime>,
+    received_notifications: Vec<u64>,
+    current_timeout: Duration,
+    should_broadcast: bool,
+    /// Whether updates ask to propose a block, valid or not.
+    proposals: Option<bool>,
+    /// Rounds of the blocks sent after validation.
+    sent_blocks: Vec<Round>,
+}
+
+impl ConsensusTimeout for TestNode {
+    fn set_consensus_timeout_hint(&mut self, hint: ConsensusTimeoutHint) {
+        self.current_timeout = hint.0;
+    }
+}
+
+/// Block proposed by a `TestNode`.
+#[derive(Debug)]
+struct TestBlock {
+    round: Round,
+    valid: bool,
+}
+
+impl Validate for TestBlock {
+    fn validate(&self) -> crate::base_types::Result<()> {
+        ensure!(self.valid, "Test blocks must be valid.");
+        Ok(())
+    }
+}
+
+impl ConsensusNode<TestContext> for TestNode {
+    fn update_node(&mut self, clock: NodeTime, _context: &mut TestContext) -> NodeUpdateActions {
+        let mut actions = NodeUpdateActions::new();
+        actions.next_scheduled_update = clock + 100;
+        actions.should_broadcast = self.should_broadcast;
+        actions
+    }
+}
+
+impl BlockProposer<TestContext> for TestNode {
+    type Block = TestBlock;
+    type Record = TestBlock;
+
+    fn create_block(&mut self, round: Round, _context: &mut TestContext) -> Option<TestBlock> {
+        self.proposals.map(|valid| TestBlock { round, valid })
+    }
+
+    fn send_block(&mut self, block: TestBlock, _context: &mut TestContext) {
+        self.sent_blocks.push(block.round);
+    }
+}
+
+impl DataSyncNode<TestContext> for TestNode {
+    type Notification = u64;
+    type Request = u64;
+    type Response = u64;
+
+    fn create_notification(&self) -> u64 {
+        0
+    }
+
+    fn create_notification_for(&self, receiver: Author) -> u64 {
+        // A different round for each receiver.
+        10 * receiver.0 as u64
+    }
+
+    fn create_request(&self) -> u64 {
+        0
+    }
+
+    fn handle_request(&self, request: u64) -> u64 {
+        request
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: u64,
+        _context: &mut TestContext,
+    ) -> Option<u64> {
+        self.received_notifications.push(notification);
+        None
+    }
+
+    fn handle_response(&mut self, response: u64, _context: &mut TestContext, clock: NodeTime) {
+        self.received_responses.push(response);
+        self.last_response_clock = Some(clock);
+    }
+}
+
+impl ActiveRound for TestNode {
+    fn active_round(&self) -> Round {
+        Round(0)
+    }
+
+    fn current_timeout(&self) -> Option<Duration> {
+        Some(self.current_timeout)
+    }
+
+    fn round_actions(&self) -> RoundActions {
+        RoundActions {
+            should_propose: self.proposals.is_some(),
+            ..RoundActions::default()
+        }
+    }
+}
+
+type TestSimulator = Simulator<TestNode, TestContext, u64, u64, u64>;
+
+fn make_simulator(num_nodes: usize) -> TestSimulator {
+    Simulator::new(
+        num_nodes,
+        RandomDelay::new(10.0, 4.0),
+        |_, _| TestContext::default(),
+        |_, _, _| TestNode::default(),
+    )
+}
+
+#[test]
+fn test_measure_rtt() {
+    let mut sim = make_simulator(3).with_seed(5);
+    let schedule = sim.dump_schedule();
+    let num_probes = 1000;
+    let total: Duration = (0..num_probes)
+        .map(|_| sim.measure_rtt(Author(0), Author(1)).unwrap())
+        .sum();
+    // Twice the mean one-way delay of 10.
+    let mean_rtt = total as f64 / num_probes as f64;
+    assert!((mean_rtt - 20.0).abs() < 2.0, "{}", mean_rtt);
+    // Probes do not alter the simulation.
+    assert_eq!(sim.dump_schedule(), schedule);
+    sim.set_partition(vec![vec![Author(0)], vec![Author(1), Author(2)]]);
+    assert_eq!(sim.measure_rtt(Author(0), Author(1)), None);
+    assert!(sim.measure_rtt(Author(1), Author(2)).is_some());
+}
+
+#[test]
+fn test_measure_rtt_through_inbox() {
+    // Probes toward a full inbox are delayed like messages, without counting as faults.
+    let mut sim = make_simulator(2)
+        .with_inbox_capacity(Author(1), 0)
+        .with_backpressure_policy(BackpressurePolicy::Delay(50));
+    sim.network_delay = RandomDelay::constant(10.0);
+    assert_eq!(sim.measure_rtt(Author(0), Author(1)), Some(70));
+    assert_eq!(sim.fault_coverage().count(FaultKind::BackpressureDelay), 0);
+}
+
+/// Counts the updates of each node.
+#[derive(Default)]
+struct UpdateCounter {
+    updates: BTreeMap<Author, usize>,
+}
+
+impl Observer<TestNode, TestContext> for UpdateCounter {
+    fn on_update(
+        &mut self,
+        _clock: GlobalTime,
+        author: Author,
+        _node: &TestNode,
+        _context: &TestContext,
+        _actions: &NodeUpdateActions,
+    ) {
+        *self.updates.entry(author).or_insert(0) += 1;
+    }
+}
+
+#[test]
+fn test_observer() {
+    let mut sim = make_simulator(3).with_seed(5);
+    let counter = Rc::new(RefCell::new(UpdateCounter::default()));
+    sim.add_observer(counter.clone());
+    sim.loop_until(GlobalTime(1000), None);
+    for (author, node) in sim.nodes_iter() {
+        assert!(node.num_updates() > 0);
+        assert_eq!(counter.borrow().updates[&author], node.num_updates());
+    }
+}
+
+#[test]
+fn test_message_complexity() {
+    let mut sim = make_simulator(3);
+    sim.enable_event_log();
+    for receiver in 1..3 {
+        sim.schedule_network_event(Event::DataSyncNotifyEvent {
+            sender: Author(0),
+            receiver: Author(receiver),
+            notification: 0,
+        });
+    }
+    sim.schedule_network_event(Event::DataSyncRequestEvent {
+        receiver: Author(1),
+        sender: Author(0),
+        request: 0,
+    });
+    sim.loop_until(GlobalTime(1000), None);
+    let leader = |_| Author(0);
+    let complexity: Vec<_> = sim.message_complexity(leader).into_iter().collect();
+    assert_eq!(
+        complexity,
+        vec![
+            (
+                (NodeRole::Leader, NodeRole::Validator, "DataSyncNotifyEvent"),
+                2
+            ),
+            (
+                (
+                    NodeRole::Leader,
+                    NodeRole::Validator,
+                    "DataSyncResponseEvent"
+                ),
+                1
+            ),
+            (
+                (
+                    NodeRole::Validator,
+                    NodeRole::Leader,
+                    "DataSyncRequestEvent"
+                ),
+                1
+            ),
+        ]
+    );
+    assert_eq!(
+        sim.message_complexity_table(leader),
+        "Leader->Validator    Notify          2\n\
+         Leader->Validator    Response        1\n\
+         Validator->Leader    Request         1\n"
+    );
+}
+
+// Schedule a message sent at time 110 to arrive at `deadline`, in the network core or not.
+fn schedule_message_sent_at_110(sim: &mut TestSimulator, deadline: i64, in_core: bool) {
+    let event = Event::DataSyncNotifyEvent {
+        sender: Author(0),
+        receiver: Author(1),
+        notification: 0,
+    };
+    sim.push_event(
+        GlobalTime(deadline),
+        event,
+        false,
+        in_core,
+        Some(GlobalTime(110)),
+    );
+}
+
+#[test]
+fn test_verify_no_future_messages() {
+    let mut sim = make_simulator(3);
+    sim.schedule_network_event(Event::DataSyncNotifyEvent {
+        sender: Author(0),
+        receiver: Author(1),
+        notification: 0,
+    });
+    sim.loop_until(GlobalTime(100), None);
+    assert!(sim.verify_no_future_messages());
+
+    sim.pending_events.clear();
+    schedule_message_sent_at_110(&mut sim, 109, false);
+    sim.step(GlobalTime(200));
+    assert!(!sim.verify_no_future_messages());
+}
+
+#[test]
+fn test_verify_no_future_messages_after_network_core() {
+    // Test messages take 8 bytes, that is, 8 time units at 1 byte per unit.
+    let mut sim = make_simulator(3).with_network_core(NetworkCore::new(1.0));
+    sim.pending_events.clear();
+    // Delivered at 113, once transmitted.
+    schedule_message_sent_at_110(&mut sim, 105, true);
+    sim.loop_until(GlobalTime(200), None);
+    assert!(sim.verify_no_future_messages());
+
+    // At 4 bytes per unit, the message is delivered at 107.
+    let mut sim = make_simulator(3).with_network_core(NetworkCore::new(4.0));
+    sim.pending_events.clear();
+    schedule_message_sent_at_110(&mut sim, 105, true);
+    sim.step(GlobalTime(200));
+    assert!(!sim.verify_no_future_messages());
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic(expected = "Some message was delivered before it was sent")]
+fn test_loop_until_checks_future_messages() {
+    let mut sim = make_simulator(3);
+    sim.loop_until(GlobalTime(100), None);
+    schedule_message_sent_at_110(&mut sim, 109, false);
+    sim.loop_until(GlobalTime(200), None);
+}
+
+#[test]
+fn test_request_to_crashed_node() {
+    let mut sim = make_simulator(2);
+    sim.crash_node(Author(1));
+    sim.schedule_network_event(Event::DataSyncRequestEvent {
+        receiver: Author(0),
+        sender: Author(1),
+        request: 7,
+    });
+    sim.loop_until(GlobalTime(1000), None);
+    assert_eq!(
+        sim.simulated_node(Author(0)).node.received_responses.len(),
+        0
+    );
+    assert!(sim.dead_letters().iter().any(|event| match event {
+        Event::DataSyncRequestEvent { sender, .. } => *sender == Author(1),
+        _ => false,
+    }));
+}
+
+#[test]
+fn test_request_to_crashed_node_with_respond_policy() {
+    let mut sim = make_simulator(2).with_crashed_sender_policy(CrashedSenderPolicy::Respond);
+    sim.crash_node(Author(1));
+    sim.schedule_network_event(Event::DataSyncRequestEvent {
+        receiver: Author(0),
+        sender: Author(1),
+        request: 7,
+    });
+    sim.loop_until(GlobalTime(1000), None);
+    assert_eq!(
+        sim.simulated_node(Author(0)).node.received_responses.len(),
+        1
+    );
+}
+
+#[test]
+fn test_message_latency_jitter() {
+    let mut sim = make_simulator(2).with_message_latency_jitter(3.0);
+    sim.network_delay = RandomDelay::constant(10.0);
+    sim.pending_events.clear();
+    for _ in 0..100 {
+        sim.schedule_network_event(Event::UpdateTimerEvent { author: Author(0) });
+    }
+    let deadlines: Vec<_> = sim
+        .pending_events
+        .iter()
+        .map(|ScheduledEvent(std::cmp::Reverse(deadline), ..)| deadline.0)
+        .collect();
+    assert!(deadlines
+        .iter()
+        .all(|deadline| *deadline >= 7 && *deadline <= 13));
+    assert!(deadlines.iter().any(|deadline| *deadline != 10));
+}
+
+#[test]
+fn test_fingerprint() {
+    let sim1 = make_simulator(3).with_seed(5);
+    let sim2 = make_simulator(3).with_seed(5);
+    assert_eq!(sim1.fingerprint(), sim2.fingerprint());
+    assert_ne!(
+        sim1.fingerprint(),
+        make_simulator(4).with_seed(5).fingerprint()
+    );
+    assert_ne!(
+        sim1.fingerprint(),
+        make_simulator(3).with_seed(6).fingerprint()
+    );
+    let sim3 = make_simulator(3)
+        .with_seed(5)
+        .with_message_latency_jitter(1.0);
+    assert_ne!(sim1.fingerprint(), sim3.fingerprint());
+    let mut sim4 = make_simulator(3).with_seed(5);
+    sim4.crash_node(Author(2));
+    assert_ne!(sim1.fingerprint(), sim4.fingerprint());
+}
+
+#[test]
+fn test_with_rng_position() {
+    let mut sim = make_simulator(3).with_seed(5);
+    sim.loop_until(GlobalTime(500), None);
+    let position = sim.rng_position();
+    assert!(position > 0);
+    let mut restored = make_simulator(3).with_seed(5).with_rng_position(position);
+    assert_eq!(restored.rng_position(), position);
+    assert_eq!(sim.rng.gen::<u64>(), restored.rng.gen::<u64>());
+}
+
+#[test]
+fn test_consensus_timeout() {
+    let mut sim = make_simulator(3).with_consensus_timeout(250);
+    let hint = Some(ConsensusTimeoutHint(250));
+    for author in (0..3).map(Author) {
+        let node = sim.simulated_node(author);
+        assert_eq!(node.context().consensus_timeout_hint(), hint);
+        assert_eq!(node.node().current_timeout, 250);
+    }
+    // Restarted contexts see the hint before their node is created.
+    sim.inject_reboot_with_full_state_loss(
+        Author(1),
+        |_, _| TestContext::default(),
+        |_, context: &TestContext, _| {
+            assert_eq!(
+                context.consensus_timeout_hint(),
+                Some(ConsensusTimeoutHint(250))
+            );
+            TestNode::default()
+        },
+    );
+    assert_eq!(sim.simulated_node(Author(1)).node().current_timeout, 250);
+    assert_eq!(
+        sim.simulated_node(Author(1))
+            .context()
+            .consensus_timeout_hint(),
+        hint
+    );
+    assert_ne!(
+        sim.fingerprint(),
+        make_simulator(3).with_seed(sim.seed()).fingerprint()
+    );
+}
+
+#[test]
+fn test_response_latency() {
+    let mut sim = make_simulator(2)
+        .with_response_latency(Author(1), ResponseLatency::Constant(25))
+        .with_seed(1);
+    sim.network_delay = RandomDelay::constant(10.0);
+    sim.schedule_network_event(Event::DataSyncRequestEvent {
+        receiver: Author(0),
+        sender: Author(1),
+        request: 7,
+    });
+    let result = sim.loop_until(GlobalTime(1000), None);
+    assert_eq!(result.latencies.network_messages, 2);
+    assert_eq!(result.latencies.total_network_delay, 20);
+    assert_eq!(result.latencies.responses, 1);
+    assert_eq!(result.latencies.total_response_latency, 25);
+    // Request (10) + response generation (25) + response (10).
+    let node = sim.simulated_node(Author(0));
+    let clock = node.node.last_response_clock.unwrap();
+    assert_eq!(
+        GlobalTime::from_node_time(clock, node.startup_time),
+        GlobalTime(45)
+    );
+}
+
+#[test]
+fn test_ordered_startup() {
+    let sim = make_simulator(4).with_ordered_startup(50).with_seed(3);
+    for index in 0..4 {
+        let node = sim.simulated_node(Author(index));
+        assert_eq!(node.startup_time, GlobalTime(50 * index as i64));
+    }
+    let mut deadlines: Vec<_> = sim
+        .pending_events
+        .iter()
+        .map(|ScheduledEvent(std::cmp::Reverse(deadline), ..)| deadline.0)
+        .collect();
+    deadlines.sort();
+    assert_eq!(deadlines, vec![0, 50, 100, 150]);
+}
+
+#[test]
+fn test_nodes_iter() {
+    let mut sim = make_simulator(5);
+    assert_eq!(sim.num_nodes(), 5);
+    assert_eq!(sim.current_clock(), GlobalTime(0));
+    let expected: Vec<_> = (0..5).map(Author).collect();
+    let authors: Vec<_> = sim.nodes_iter().map(|(author, _)| author).collect();
+    assert_eq!(authors, expected);
+    let authors: Vec<_> = sim.nodes_iter_mut().map(|(author, _)| author).collect();
+    assert_eq!(authors, expected);
+    for (author, node) in sim.nodes_iter() {
+        assert_eq!(node as *const _, sim.simulated_node(author) as *const _);
+    }
+}
+
+fn set_committed_chains(sim: &mut TestSimulator, chains: Vec<Vec<u64>>, clock: GlobalTime) {
+    for ((_, node), chain) in sim.nodes_iter_mut().zip(chains) {
+        node.context.committed = chain;
+        node.record_commits(GlobalTime(0));
+    }
+    sim.clock = clock;
+}
+
+#[test]
+fn test_eventual_consistency() {
+    let mut sim = make_simulator(3);
+    set_committed_chains(
+        &mut sim,
+        vec![vec![1, 2], vec![1, 2], vec![1]],
+        GlobalTime(100),
+    );
+    // Node 2 has been late by 100 time units.
+    sim.assert_eventual_consistency(100);
+    // Crashed nodes are ignored.
+    sim.crash_node(Author(2));
+    sim.assert_eventual_consistency(0);
+}
+
+#[test]
+#[should_panic(expected = "A Monte Carlo analysis needs at least one run")]
+fn test_monte_carlo_without_runs() {
+    TestSimulator::run_monte_carlo(0, GlobalTime(100), |_| make_simulator(2), |_| 0.0);
+}
+
+#[test]
+#[should_panic(expected = "The message loss rate must be a probability")]
+fn test_invalid_message_loss_rate() {
+    make_simulator(2).with_message_loss_rate(1.5);
+}
+
+#[test]
+#[should_panic(expected = "lags behind")]
+fn test_eventual_consistency_with_lagging_node() {
+    let mut sim = make_simulator(3);
+    set_committed_chains(
+        &mut sim,
+        vec![vec![1, 2], vec![1, 2], vec![1]],
+        GlobalTime(100),
+    );
+    sim.assert_eventual_consistency(99);
+}
+
+#[test]
+#[should_panic(expected = "diverge at index 1")]
+fn test_eventual_consistency_with_divergent_chains() {
+    let mut sim = make_simulator(2);
+    set_committed_chains(&mut sim, vec![vec![1, 2, 3], vec![1, 5]], GlobalTime(100));
+    sim.assert_eventual_consistency(1000);
+}
+
+fn set_proposers(sim: &mut TestSimulator, leader: impl Fn(u64) -> Author) {
+    let chain: Vec<u64> = (0..40).collect();
+    for (_, node) in sim.nodes_iter_mut() {
+        node.context.proposers = chain.iter().map(|entry| (*entry, leader(*entry))).collect();
+        node.context.committed = chain.clone();
+    }
+}
+
+#[test]
+fn test_proposer_fairness() {
+    let mut sim = make_simulator(4);
+    assert!((sim.proposer_fairness() - 1.0).abs() < 1e-9);
+    // Round-robin leaders.
+    set_proposers(&mut sim, |entry| Author(entry as usize % 4));
+    assert!(sim.proposer_fairness() > 0.99);
+    // One leader out of two is pinned to node 0.
+    set_proposers(&mut sim, |entry| {
+        if entry % 2 == 0 {
+            Author(0)
+        } else {
+            Author(entry as usize % 4)
+        }
+    });
+    let fairness = sim.proposer_fairness();
+    assert!(fairness > 0.25 && fairness < 0.99);
+    // A single pinned leader.
+    set_proposers(&mut sim, |_| Author(0));
+    assert!((sim.proposer_fairness() - 0.25).abs() < 1e-9);
+}
+
+fn sync_from_all_nodes(max_sync_sessions: usize) -> (TestSimulator, SyncStats) {
+    let mut sim = make_simulator(5).with_max_sync_sessions(max_sync_sessions, 1000);
+    sim.network_delay = RandomDelay::constant(10.0);
+    for i in 1..5 {
+        sim = sim.with_response_latency(Author(i), ResponseLatency::Constant(50));
+    }
+    for i in 1..5 {
+        sim.send_sync_request(Author(0), Author(i), 0);
+    }
+    let stats = sim.loop_until(GlobalTime(2000), None).sync;
+    (sim, stats)
+}
+
+#[test]
+fn test_max_sync_sessions() {
+    let (serial_sim, serial_stats) = sync_from_all_nodes(1);
+    assert_eq!(serial_stats.opened_sessions, 4);
+    assert_eq!(serial_stats.completed_sessions, 4);
+    assert_eq!(serial_stats.queued_requests, 3);
+    assert_eq!(serial_stats.max_active_sessions, 1);
+    let serial_node = &serial_sim.simulated_node(Author(0)).node;
+    assert_eq!(serial_node.received_responses.len(), 4);
+
+    let (parallel_sim, parallel_stats) = sync_from_all_nodes(4);
+    assert_eq!(parallel_stats.queued_requests, 0);
+    assert_eq!(parallel_stats.max_active_sessions, 4);
+    let parallel_node = &parallel_sim.simulated_node(Author(0)).node;
+    assert_eq!(parallel_node.received_responses.len(), 4);
+    assert!(parallel_node.last_response_clock < serial_node.last_response_clock);
+}
+
+#[test]
+fn test_sync_session_timeout() {
+    let mut sim = make_simulator(3).with_max_sync_sessions(1, 100);
+    sim.crash_node(Author(1));
+    sim.send_sync_request(Author(0), Author(1), 0);
+    sim.send_sync_request(Author(0), Author(2), 0);
+    let stats = sim.loop_until(GlobalTime(1000), None).sync;
+    assert_eq!(stats.timed_out_sessions, 1);
+    assert_eq!(stats.completed_sessions, 1);
+    assert_eq!(
+        sim.simulated_node(Author(0)).node.received_responses.len(),
+        1
+    );
+}
+
+#[test]
+fn test_lost_sync_sessions_are_abandoned() {
+    let mut sim = make_simulator(3);
+    sim.crash_node(Author(1));
+    for _ in 0..10 {
+        sim.send_sync_request(Author(0), Author(1), 0);
+        sim.send_sync_request(Author(0), Author(2), 0);
+    }
+    let stats = sim.loop_until(GlobalTime(1000), None).sync;
+    assert_eq!(stats.opened_sessions, 20);
+    assert_eq!(stats.completed_sessions, 10);
+    assert_eq!(stats.abandoned_sessions, 10);
+    // Without a maximal number of sessions, finished sessions are not kept either.
+    assert!(sim.sync.sessions[0].active.is_empty());
+}
+
+fn sync_with_delay_inversion(ordered: bool) -> (Vec<u64>, SyncStats) {
+    let mut sim = make_simulator(3)
+        .with_message_sizes()
+        .with_response_latency(Author(1), ResponseLatency::Constant(100))
+        .with_response_latency(Author(2), ResponseLatency::Constant(0));
+    if ordered {
+        sim = sim.with_ordered_sync_responses(1000);
+    }
+    sim.network_delay = RandomDelay::constant(10.0);
+    // The response to the second request overtakes the response to the first one.
+    sim.send_sync_request(Author(0), Author(1), 1);
+    sim.send_sync_request(Author(0), Author(2), 2);
+    let stats = sim.loop_until(GlobalTime(1000), None).sync;
+    let responses = sim
+        .simulated_node(Author(0))
+        .node
+        .received_responses
+        .clone();
+    (responses, stats)
+}
+
+#[test]
+fn test_ordered_sync_responses() {
+    let (responses, stats) = sync_with_delay_inversion(false);
+    assert_eq!(responses, vec![2, 1]);
+    assert_eq!(stats.buffered_responses, 0);
+
+    let (responses, stats) = sync_with_delay_inversion(true);
+    assert_eq!(responses, vec![1, 2]);
+    assert_eq!(stats.buffered_responses, 1);
+    assert_eq!(stats.buffered_bytes, 8);
+    assert_eq!(stats.cancelled_sessions, 0);
+}
+
+#[test]
+fn test_ordered_sync_responses_expire() {
+    let mut sim = make_simulator(3).with_ordered_sync_responses(200);
+    sim.crash_node(Author(1));
+    sim.send_sync_request(Author(0), Author(1), 1);
+    sim.send_sync_request(Author(0), Author(2), 2);
+    let stats = sim.loop_until(GlobalTime(1000), None).sync;
+    // The lost response does not hold back the other one forever.
+    let node = &sim.simulated_node(Author(0)).node;
+    assert_eq!(node.received_responses, vec![2]);
+    assert!(node.last_response_clock >= Some(NodeTime(200)));
+    assert_eq!(stats.buffered_responses, 1);
+    assert_eq!(stats.cancelled_sessions, 1);
+}
+
+#[test]
+fn test_summary_string() {
+    let mut fault_coverage = FaultCoverage::default();
+    fault_coverage.record(FaultKind::Crash);
+    fault_coverage.record(FaultKind::OmittedMessage);
+    fault_coverage.record(FaultKind::OmittedMessage);
+    let summary = SimulationSummary {
+        duration: 2000,
+        rounds_completed: 35,
+        in_flight_rounds: 2,
+        committed_entries: 22,
+        mean_commit_latency: Some(41.25),
+        p50_commit_latency: Some(40),
+        p99_commit_latency: Some(60),
+        timeout_rate: 0.125,
+        messages_sent: 1234,
+        messages_dropped: 56,
+        safety_violations: 0,
+        crashed_nodes: vec![Author(1)],
+        partitioned: true,
+        omitted_messages: 2,
+        fault_coverage,
+        byzantine_fault_rate: Some(0.25),
+        round_inflations: Some(3),
+        peak_core_utilization: Some(0.875),
+        core_utilization: vec![(GlobalTime(0), 0.875), (GlobalTime(100), 0.5)],
+    };
+    assert_eq!(
+        summary.to_string(),
+        "Simulated duration: 2000\n\
+         Rounds completed: 35\n\
+         Rounds in flight: 2\n\
+         Blocks committed: 22\n\
+         Commit latency: mean 41.2, p99 60\n\
+         Timeout rate: 0.12\n\
+         Messages sent: 1234\n\
+         Safety violations: 0\n\
+         Byzantine fault rate: 0.25\n\
+         Round inflations: 3\n\
+         Peak core utilization: 0.88\n\
+         Faults injected: crashed nodes [Author(1)], network partition, 2 message omissions\n\
+         Fault coverage: 2 omitted messages, 1 crashes"
+    );
+    assert!(summary
+        .to_json()
+        .ends_with("\"core_utilization\": [[0, 0.875], [100, 0.5]]}"));
+    let summary = SimulationSummary::default();
+    assert_eq!(
+        summary.to_string(),
+        "Simulated duration: 0\n\
+         Rounds completed: 0\n\
+         Rounds in flight: 0\n\
+         Blocks committed: 0\n\
+         Commit latency: n/a\n\
+         Timeout rate: 0.00\n\
+         Messages sent: 0\n\
+         Safety violations: 0\n\
+         Faults injected: none\n\
+         Fault coverage: none"
+    );
+}
+
+#[test]
+fn test_summary_of_run() {
+    let mut sim = make_simulator(3);
+    sim.crash_node(Author(2));
+    let result = sim.loop_until(GlobalTime(1000), None);
+    assert!(result.summary.duration > 900 && result.summary.duration <= 1000);
+    assert_eq!(result.summary.committed_entries, 0);
+    assert_eq!(result.summary.crashed_nodes, vec![Author(2)]);
+    assert_eq!(result.summary_string(), result.summary.to_string());
+}
+
+#[test]
+fn test_messages_per_commit_without_traffic() {
+    let mut sim = make_simulator(3);
+    let result = sim.loop_until(GlobalTime(1000), None);
+    assert_eq!(result.summary.messages_sent, 0);
+    assert_eq!(result.summary.committed_entries, 0);
+    assert_eq!(result.messages_per_commit(), f64::INFINITY);
+}
+
+// Test messages are integers.
+impl MessageSize for u64 {
+    fn message_size(&self) -> usize {
+        8
+    }
+}
+
+// Test notifications equal to 1 announce a QC.
+impl NotificationKind for u64 {
+    fn announces_quorum_certificate(&self) -> bool {
+        *self == 1
+    }
+}
+
+/// Delivery times of the pending notifications, in the order of the notifications.
+fn notification_deadlines(sim: &TestSimulator) -> Vec<(u64, i64)> {
+    let mut deadlines: Vec<_> = sim
+        .pending_events
+        .iter()
+        .map(
+            |ScheduledEvent(std::cmp::Reverse(deadline), event, ..)| match event {
+                Event::DataSyncNotifyEvent { notification, .. } => (*notification, deadline.0),
+                _ => unreachable!(),
+            },
+        )
+        .collect();
+    deadlines.sort_unstable();
+    deadlines
+}
+
+#[test]
+fn test_qc_extra_delay() {
+    let mut sim = make_simulator(2);
+    sim.network_delay = RandomDelay::constant(10.0);
+    sim.set_qc_extra_delay(30);
+    sim.pending_events.clear();
+    for notification in 0..2 {
+        sim.schedule_network_event(Event::DataSyncNotifyEvent {
+            sender: Author(0),
+            receiver: Author(1),
+            notification,
+        });
+    }
+    assert_eq!(notification_deadlines(&sim), vec![(0, 10), (1, 40)]);
+}
+
+#[test]
+fn test_network_core() {
+    // Test messages take 8 bytes, that is, 4 time units at 2 bytes per unit.
+    let mut sim =
+        make_simulator(3).with_network_core(NetworkCore::new(2.0).with_utilization_window(10));
+    sim.network_delay = RandomDelay::constant(10.0);
+    sim.pending_events.clear();
+    for notification in 0..4 {
+        sim.schedule_network_event(Event::DataSyncNotifyEvent {
+            sender: Author(0),
+            receiver: Author(1 + notification as usize % 2),
+            notification,
+        });
+    }
+    // Messages reach the core after the network delay.
+    assert_eq!(
+        notification_deadlines(&sim),
+        vec![(0, 10), (1, 10), (2, 10), (3, 10)]
+    );
+    sim.cross_network_core(GlobalTime(10));
+    // They are transmitted one after the other.
+    let mut deadlines: Vec<_> = notification_deadlines(&sim)
+        .into_iter()
+        .map(|(_, deadline)| deadline)
+        .collect();
+    deadlines.sort_unstable();
+    assert_eq!(deadlines, vec![14, 18, 22, 26]);
+    let core = sim.network_core().unwrap();
+    assert_eq!(
+        core.utilization(),
+        vec![(GlobalTime(10), 1.0), (GlobalTime(20), 0.6)]
+    );
+    assert_eq!(core.peak_utilization(), Some(1.0));
+    // The messages waited 0, 4, 8 and 12 time units.
+    assert_eq!(core.mean_queueing_delay(), 6.0);
+}
+
+#[test]
+fn test_network_core_is_fifo() {
+    let mut sim = make_simulator(2)
+        .with_seed(3)
+        .with_network_core(NetworkCore::new(2.0));
+    sim.pending_events.clear();
+    for notification in 0..20 {
+        sim.schedule_network_event(Event::DataSyncNotifyEvent {
+            sender: Author(0),
+            receiver: Author(1),
+            notification,
+        });
+    }
+    let arrivals: BTreeMap<_, _> = notification_deadlines(&sim).into_iter().collect();
+    let mut deliveries = Vec::new();
+    while let Some((clock, event)) = sim.next_event(GlobalTime(1000)) {
+        if let Event::DataSyncNotifyEvent { notification, .. } = event {
+            deliveries.push((arrivals[&notification], clock.0));
+        }
+    }
+    assert_eq!(deliveries.len(), 20);
+    // Messages leave the core in their order of arrival, each after its own transmission.
+    for pair in deliveries.windows(2) {
+        assert!(pair[0].0 <= pair[1].0);
+        assert!(pair[1].1 >= pair[0].1 + 4);
+    }
+    assert!(deliveries
+        .iter()
+        .all(|(arrival, delivery)| *delivery >= arrival + 4));
+}
+
+#[test]
+fn test_dump_schedule() {
+    let mut sim = make_simulator(2).with_ordered_startup(0);
+    sim.network_delay = RandomDelay::constant(10.0);
+    sim.loop_until(GlobalTime(0), None);
+    assert_eq!(
+        sim.dump_schedule(),
+        "@100 UpdateTimerEvent Author(1)\n@100 UpdateTimerEvent Author(0)"
+    );
+    // The response reschedules the timer of node 0 at 120.
+    sim.send_sync_request(Author(0), Author(1), 0);
+    sim.loop_until(GlobalTime(50), None);
+    let dump = sim.dump_schedule();
+    let timers: Vec<_> = dump
+        .lines()
+        .filter(|line| line.contains("UpdateTimerEvent"))
+        .collect();
+    assert_eq!(timers.len(), 3);
+    assert_eq!(
+        timers
+            .iter()
+            .filter(|line| line.ends_with("(cancelled)"))
+            .count(),
+        1
+    );
+    assert_eq!(
+        dump,
+        "@100 UpdateTimerEvent Author(1)\n\
+         @100 UpdateTimerEvent Author(0) (cancelled)\n\
+         @120 UpdateTimerEvent Author(0)"
+    );
+}
+
+#[test]
+fn test_dump_schedule_in_fair_mode() {
+    let mut sim = make_simulator(3)
+        .with_ordered_startup(0)
+        .with_fair_event_processing();
+    sim.network_delay = RandomDelay::constant(10.0);
+    sim.loop_until(GlobalTime(0), None);
+    for receiver in 1..3 {
+        for _ in 0..2 {
+            sim.schedule_network_event(Event::DataSyncNotifyEvent {
+                sender: Author(0),
+                receiver: Author(receiver),
+                notification: 0,
+            });
+        }
+    }
+    // The events due at 10 are queued per destination once the first one is served, and they
+    // are listed in the order of service.
+    assert!(sim.step(GlobalTime(10)));
+    assert!(sim.has_messages_in_flight());
+    assert_eq!(
+        sim.dump_schedule(),
+        "@10 DataSyncNotifyEvent Author(0) -> Author(2)\n\
+         @10 DataSyncNotifyEvent Author(0) -> Author(1)\n\
+         @10 DataSyncNotifyEvent Author(0) -> Author(2)\n\
+         @100 UpdateTimerEvent Author(2)\n\
+         @100 UpdateTimerEvent Author(1) (cancelled)\n\
+         @100 UpdateTimerEvent Author(0)\n\
+         @110 UpdateTimerEvent Author(1)"
+    );
+    sim.loop_until(GlobalTime(10), None);
+    assert!(!sim.has_messages_in_flight());
+}
+
+#[test]
+fn test_freeze_buffers_timer_events() {
+    let mut sim = make_simulator(2).with_ordered_startup(0);
+    sim.schedule_freeze(Author(0), GlobalTime(50), GlobalTime(250));
+    sim.loop_until(GlobalTime(200), None);
+    // The timer scheduled at 100 was buffered, not dropped.
+    assert_eq!(sim.freezes.buffered_events[&Author(0)].len(), 1);
+    assert!(sim.dead_letters().is_empty());
+    assert_eq!(
+        sim.dump_schedule(),
+        "@250 UpdateTimerEvent Author(0)\n@300 UpdateTimerEvent Author(1)"
+    );
+    sim.loop_until(GlobalTime(300), None);
+    assert!(sim.freezes.buffered_events.is_empty());
+    // The buffered timer updated the node at 250, which cancelled the wake-up timer.
+    assert_eq!(
+        sim.dump_schedule(),
+        "@350 UpdateTimerEvent Author(0)\n@400 UpdateTimerEvent Author(1)"
+    );
+}
+
+fn notify_frozen_node(thaw_processing_delay: Duration) -> TestSimulator {
+    let mut sim = make_simulator(2)
+        .with_ordered_startup(0)
+        .with_thaw_processing_delay(thaw_processing_delay);
+    sim.schedule_freeze(Author(0), GlobalTime(50), GlobalTime(200));
+    // Simultaneous events would be processed in decreasing order of notifications.
+    for (time, notification) in &[(60, 3), (70, 1), (80, 2)] {
+        sim.schedule_event(
+            GlobalTime(*time),
+            Event::DataSyncNotifyEvent {
+                sender: Author(1),
+                receiver: Author(0),
+                notification: *notification,
+            },
+        );
+    }
+    sim.loop_until(GlobalTime(199), None);
+    assert!(sim
+        .simulated_node(Author(0))
+        .node
+        .received_notifications
+        .is_empty());
+    sim
+}
+
+#[test]
+fn test_freeze_delivers_in_original_order() {
+    let mut sim = notify_frozen_node(0);
+    sim.loop_until(GlobalTime(200), None);
+    let node = &sim.simulated_node(Author(0)).node;
+    assert_eq!(node.received_notifications, vec![3, 1, 2]);
+}
+
+#[test]
+fn test_freeze_with_thaw_processing_delay() {
+    let mut sim = notify_frozen_node(10);
+    sim.loop_until(GlobalTime(215), None);
+    let node = &sim.simulated_node(Author(0)).node;
+    assert_eq!(node.received_notifications, vec![3, 1]);
+    sim.loop_until(GlobalTime(220), None);
+    let node = &sim.simulated_node(Author(0)).node;
+    assert_eq!(node.received_notifications, vec![3, 1, 2]);
+}
+
+fn thaw_among_busy_nodes(fair_event_processing: bool) -> TestSimulator {
+    let mut sim = make_simulator(3).with_ordered_startup(0);
+    if fair_event_processing {
+        sim = sim.with_fair_event_processing();
+    }
+    sim.enable_processing_trace();
+    sim.schedule_freeze(Author(0), GlobalTime(50), GlobalTime(200));
+    for (time, notification) in &[(60, 3), (70, 1), (80, 2)] {
+        sim.schedule_event(
+            GlobalTime(*time),
+            Event::DataSyncNotifyEvent {
+                sender: Author(1),
+                receiver: Author(0),
+                notification: *notification,
+            },
+        );
+    }
+    // Other nodes are busy when the frozen node thaws.
+    for receiver in 1..3 {
+        for notification in 0..3 {
+            sim.schedule_event(
+                GlobalTime(200),
+                Event::DataSyncNotifyEvent {
+                    sender: Author(0),
+                    receiver: Author(receiver),
+                    notification,
+                },
+            );
+        }
+    }
+    sim.loop_until(GlobalTime(200), None);
+    sim
+}
+
+/// Destinations of the events processed at the given time.
+fn destinations_at(sim: &TestSimulator, clock: GlobalTime) -> Vec<usize> {
+    sim.processing_trace()
+        .unwrap()
+        .iter()
+        .filter(|entry| entry.time == clock)
+        .map(|entry| entry.author.0)
+        .collect()
+}
+
+#[test]
+fn test_fair_event_processing_after_thaw() {
+    let sim = thaw_among_busy_nodes(false);
+    assert_eq!(
+        sim.simulated_node(Author(0)).node.received_notifications,
+        vec![3, 1, 2]
+    );
+    // The backlog of the thawed node is processed back-to-back.
+    assert!(destinations_at(&sim, GlobalTime(200))
+        .windows(3)
+        .any(|window| window.iter().all(|author| *author == 0)));
+
+    let sim = thaw_among_busy_nodes(true);
+    assert_eq!(
+        sim.simulated_node(Author(0)).node.received_notifications,
+        vec![3, 1, 2]
+    );
+    // The event that thaws the node is queued behind the backlog.
+    assert_eq!(
+        destinations_at(&sim, GlobalTime(200)),
+        vec![1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 0]
+    );
+    assert_eq!(
+        sim.processing_trace(),
+        thaw_among_busy_nodes(true).processing_trace()
+    );
+}
+
+#[test]
+fn test_inbox_capacity() {
+    let mut sim = make_simulator(3).with_inbox_capacity(Author(0), 3);
+    sim.network_delay = RandomDelay::constant(10.0);
+    for _ in 0..5 {
+        for sender in 1..3 {
+            sim.schedule_network_event(Event::DataSyncNotifyEvent {
+                sender: Author(sender),
+                receiver: Author(0),
+                notification: 0,
+            });
+        }
+    }
+    // Messages are only dropped on delivery.
+    assert_eq!(sim.simulated_node(Author(0)).dropped_messages(), 0);
+    assert_eq!(sim.simulated_node(Author(0)).in_flight_messages, 10);
+    sim.loop_until(GlobalTime(100), None);
+    assert_eq!(sim.simulated_node(Author(0)).dropped_messages(), 7);
+    assert_eq!(
+        sim.simulated_node(Author(0))
+            .node
+            .received_notifications
+            .len(),
+        3
+    );
+    assert_eq!(sim.simulated_node(Author(0)).in_flight_messages, 0);
+    // Once the inbox is drained, messages go through again.
+    sim.schedule_network_event(Event::DataSyncNotifyEvent {
+        sender: Author(1),
+        receiver: Author(0),
+        notification: 0,
+    });
+    sim.loop_until(GlobalTime(200), None);
+    let node = sim.simulated_node(Author(0));
+    assert_eq!(node.node.received_notifications.len(), 4);
+    assert_eq!(node.dropped_messages(), 7);
+}
+
+fn flood_with_backpressure(policy: BackpressurePolicy) -> TestSimulator {
+    let mut sim = make_simulator(3)
+        .with_backpressure(3)
+        .with_backpressure_policy(policy);
+    sim.network_delay = RandomDelay::constant(10.0);
+    for _ in 0..5 {
+        for sender in 1..3 {
+            sim.schedule_network_event(Event::DataSyncNotifyEvent {
+                sender: Author(sender),
+                receiver: Author(0),
+                notification: 0,
+            });
+        }
+    }
+    sim
+}
+
+#[test]
+fn test_backpressure_drop() {
+    let mut sim = flood_with_backpressure(BackpressurePolicy::Drop);
+    sim.loop_until(GlobalTime(100), None);
+    let node = sim.simulated_node(Author(0));
+    assert_eq!(node.dropped_messages(), 7);
+    assert_eq!(node.node.received_notifications.len(), 3);
+    assert_eq!(sim.fault_coverage().count(FaultKind::InboxOverflow), 7);
+}
+
+#[test]
+fn test_backpressure_delay() {
+    let mut sim = flood_with_backpressure(BackpressurePolicy::Delay(50));
+    sim.loop_until(GlobalTime(20), None);
+    assert_eq!(
+        sim.simulated_node(Author(0))
+            .node
+            .received_notifications
+            .len(),
+        3
+    );
+    sim.loop_until(GlobalTime(100), None);
+    let node = sim.simulated_node(Author(0));
+    assert_eq!(node.dropped_messages(), 0);
+    assert_eq!(node.node.received_notifications.len(), 10);
+    assert_eq!(node.in_flight_messages, 0);
+    assert_eq!(sim.fault_coverage().count(FaultKind::BackpressureDelay), 7);
+}
+
+#[test]
+fn test_loop_until_quiescent() {
+    let mut sim = make_simulator(3);
+    for _ in 0..5 {
+        for sender in 1..3 {
+            sim.schedule_network_event(Event::DataSyncNotifyEvent {
+                sender: Author(sender),
+                receiver: Author(0),
+                notification: 0,
+            });
+        }
+    }
+    sim.loop_until_quiescent(GlobalTime(10000), 200);
+    assert!(sim.current_clock() >= GlobalTime(100));
+    assert!(sim.current_clock() < GlobalTime(200));
+    let node = sim.simulated_node(Author(0));
+    assert_eq!(node.node.received_notifications.len(), 10);
+    assert_eq!(node.in_flight_messages, 0);
+
+    // Messages in flight delay quiescence beyond the settling window.
+    let mut sim = make_simulator(3);
+    sim.network_delay = RandomDelay::constant(500.0);
+    sim.schedule_network_event(Event::DataSyncNotifyEvent {
+        sender: Author(1),
+        receiver: Author(0),
+        notification: 0,
+    });
+    sim.loop_until_quiescent(GlobalTime(10000), 200);
+    assert!(sim.current_clock() > GlobalTime(400));
+    assert!(sim.current_clock() < GlobalTime(600));
+    assert_eq!(
+        sim.simulated_node(Author(0))
+            .node
+            .received_notifications
+            .len(),
+        1
+    );
+}
+
+fn notify_at_same_time(coalesce_notifications: bool) -> TestSimulator {
+    let mut sim = make_simulator(3).with_ordered_startup(0);
+    if coalesce_notifications {
+        sim = sim.with_notification_coalescing();
+    }
+    sim.loop_until(GlobalTime(0), None);
+    for (sender, notification) in &[(1, 5), (2, 6), (1, 7)] {
+        sim.schedule_event(
+            GlobalTime(50),
+            Event::DataSyncNotifyEvent {
+                sender: Author(*sender),
+                receiver: Author(0),
+                notification: *notification,
+            },
+        );
+    }
+    sim.loop_until(GlobalTime(50), None);
+    sim
+}
+
+#[test]
+fn test_notification_coalescing() {
+    let sim = notify_at_same_time(false);
+    let node = sim.simulated_node(Author(0));
+    assert_eq!(node.node.received_notifications.len(), 3);
+    assert_eq!(node.num_updates(), 4);
+
+    let sim = notify_at_same_time(true);
+    let node = sim.simulated_node(Author(0));
+    assert_eq!(node.node.received_notifications, vec![6, 7, 5]);
+    assert_eq!(node.num_updates(), 2);
+    // Other nodes are not affected.
+    assert_eq!(sim.simulated_node(Author(1)).num_updates(), 1);
+}
+
+fn first_update_times(sim: &mut TestSimulator) -> Vec<GlobalTime> {
+    sim.loop_until(GlobalTime(99), None);
+    sim.nodes_iter()
+        .map(|(_, node)| {
+            assert_eq!(node.num_updates(), 1);
+            node.startup_time
+        })
+        .collect()
+}
+
+#[test]
+fn test_startup_barrier() {
+    let mut sim = make_simulator(4).with_seed(3);
+    let times = first_update_times(&mut sim);
+    assert!(times.iter().any(|time| *time != times[0]));
+
+    let mut sim = make_simulator(4).with_seed(3).with_startup_barrier();
+    let times = first_update_times(&mut sim);
+    assert!(times.iter().all(|time| *time == times[0]));
+    assert!(times[0] > GlobalTime(0));
+}
+
+#[test]
+fn test_invalid_proposals_are_dropped() {
+    let mut sim = Simulator::new(
+        2,
+        RandomDelay::new(10.0, 4.0),
+        |_, _| TestContext::default(),
+        |author, _, _| TestNode {
+            proposals: Some(author == Author(0)),
+            ..TestNode::default()
+        },
+    );
+    sim.loop_until(GlobalTime(1000), None);
+    let node = sim.simulated_node(Author(0));
+    assert_eq!(node.node().sent_blocks.len(), node.num_updates());
+    assert_eq!(node.invalid_proposals(), 0);
+    // Invalid proposals are counted and never sent, without stopping the simulation.
+    let node = sim.simulated_node(Author(1));
+    assert!(node.node().sent_blocks.is_empty());
+    assert_eq!(node.invalid_proposals(), node.num_updates());
+    assert!(node.num_updates() > 1);
+}
+
+#[test]
+fn test_notifications_tailored_per_receiver() {
+    let mut sim = Simulator::new(
+        3,
+        RandomDelay::new(10.0, 4.0),
+        |_, _| TestContext::default(),
+        |author, _, _| TestNode {
+            should_broadcast: author == Author(0),
+            ..TestNode::default()
+        },
+    );
+    sim.loop_until(GlobalTime(1000), None);
+    assert!(sim
+        .simulated_node(Author(0))
+        .node
+        .received_notifications
+        .is_empty());
+    for index in 1..3 {
+        let received = &sim
+            .simulated_node(Author(index))
+            .node
+            .received_notifications;
+        assert!(!received.is_empty());
+        assert!(received.iter().all(|round| *round == 10 * index as u64));
+    }
+}
+
+#[test]
+fn test_eclipse() {
+    let mut sim = make_simulator(3);
+    sim.schedule_eclipse(Author(2), Author(1), GlobalTime(0), GlobalTime(100));
+    for sender in 0..2 {
+        sim.schedule_network_event(Event::DataSyncNotifyEvent {
+            sender: Author(sender),
+            receiver: Author(2),
+            notification: sender as u64,
+        });
+    }
+    sim.loop_until(GlobalTime(100), None);
+    assert_eq!(
+        sim.simulated_node(Author(2)).node.received_notifications,
+        vec![1]
+    );
+    // The eclipse has lifted.
+    sim.schedule_network_event_at(
+        GlobalTime(100),
+        Event::DataSyncNotifyEvent {
+            sender: Author(0),
+            receiver: Author(2),
+            notification: 0,
+        },
+    );
+    sim.loop_until(GlobalTime(200), None);
+    assert_eq!(
+        sim.simulated_node(Author(2)).node.received_notifications,
+        vec![1, 0]
+    );
+}
+
+fn received_simultaneous_notifications(mut sim: TestSimulator) -> Vec<u64> {
+    for notification in 1..4 {
+        sim.schedule_event(
+            GlobalTime(50),
+            Event::DataSyncNotifyEvent {
+                sender: Author(1),
+                receiver: Author(0),
+                notification,
+            },
+        );
+    }
+    sim.loop_until(GlobalTime(50), None);
+    sim.simulated_node(Author(0))
+        .node
+        .received_notifications
+        .clone()
+}
+
+#[test]
+fn test_custom_ord_for_events() {
+    let received = received_simultaneous_notifications(make_simulator(2));
+    assert_eq!(received, vec![3, 2, 1]);
+
+    let sim = make_simulator(2).with_custom_ord_for_events(|event1, event2| event1.cmp(event2));
+    let received = received_simultaneous_notifications(sim);
+    assert_eq!(received, vec![1, 2, 3]);
+}
+
+#[test]
+fn test_gradual_partition_recovery() {
+    let mut sim = make_simulator(2);
+    sim.set_partition(vec![vec![Author(0)], vec![Author(1)]]);
+    sim.heal_partition_gradually(4000);
+    // Send 100 messages during each quarter of the ramp, tagged with the quarter.
+    for time in 0..400 {
+        sim.schedule_network_event_at(
+            GlobalTime(10 * time),
+            Event::DataSyncNotifyEvent {
+                sender: Author(0),
+                receiver: Author(1),
+                notification: time as u64 / 100,
+            },
+        );
+    }
+    let result = sim.loop_until(GlobalTime(5000), None);
+    assert!(!result.summary.partitioned);
+    let received = &sim.simulated_node(Author(1)).node.received_notifications;
+    let delivered: Vec<_> = (0..4)
+        .map(|quarter| received.iter().filter(|n| **n == quarter).count())
+        .collect();
+    assert!(delivered[0] < 25);
+    assert!(delivered[3] > 75);
+    assert!(delivered.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn test_shard_assignment() {
+    let shards = ShardAssignment::contiguous(5, 2);
+    assert_eq!(shards.members(0), vec![Author(0), Author(1), Author(2)]);
+    assert_eq!(shards.members(1), vec![Author(3), Author(4)]);
+    assert!(shards.same_shard(Author(0), Author(2)));
+    assert!(!shards.same_shard(Author(2), Author(3)));
+    // Unassigned authors reach every shard.
+    assert_eq!(shards.shard(Author(5)), None);
+    assert!(shards.same_shard(Author(5), Author(0)));
+    assert!(shards.same_shard(Author(5), Author(4)));
+}
+
+#[test]
+fn test_node_view() {
+    let mut sim = Simulator::new(
+        3,
+        RandomDelay::new(10.0, 4.0),
+        |_, _| TestContext::default(),
+        |author, _, _| TestNode {
+            should_broadcast: author == Author(0),
+            ..TestNode::default()
+        },
+    )
+    .with_ordered_startup(100);
+    sim.loop_until(GlobalTime(1000), None);
+    let view = sim.node_view(Author(2));
+    assert_eq!(view.startup_time(), GlobalTime(200));
+    assert_eq!(view.uptime(GlobalTime(1000)), 800);
+    assert_eq!(view.uptime(GlobalTime(100)), 0);
+    assert_eq!(
+        view.active_round(),
+        sim.simulated_node(Author(2)).active_round()
+    );
+    // Only node 0 sends messages, and all of them were delivered or are still in flight.
+    let stats: Vec<_> = (0..3)
+        .map(|index| sim.node_view(Author(index)).message_stats())
+        .collect();
+    assert!(stats[0].sent > 0);
+    assert_eq!(stats[0].received, 0);
+    assert_eq!(stats[1].sent + stats[2].sent, 0);
+    let delivered: usize = stats
+        .iter()
+        .map(|stats| stats.received + stats.in_flight)
+        .sum();
+    assert_eq!(delivered, stats[0].sent);
+
+    // Crashed nodes stop receiving messages.
+    sim.crash_node(Author(1));
+    sim.loop_until(GlobalTime(2000), None);
+    let crashed_stats = sim.node_view(Author(1)).message_stats();
+    assert!(crashed_stats.received <= stats[1].received + stats[1].in_flight);
+    assert!(sim.node_view(Author(2)).message_stats().received > stats[2].received);
+}
+
+#[test]
+fn test_reboot_with_full_state_loss() {
+    let broadcaster = |author, _: &TestContext, _| TestNode {
+        should_broadcast: author == Author(0),
+        ..TestNode::default()
+    };
+    let mut sim = Simulator::new(
+        2,
+        RandomDelay::new(10.0, 4.0),
+        |_, _| TestContext::default(),
+        broadcaster,
+    )
+    .with_seed(3);
+    sim.loop_until(GlobalTime(1000), None);
+    sim.crash_node(Author(1));
+    sim.loop_until(GlobalTime(1005), None);
+    let received = sim.simulated_node(Author(1)).message_stats().received;
+    assert!(received > 0);
+    assert!(!sim
+        .simulated_node(Author(1))
+        .node
+        .received_notifications
+        .is_empty());
+
+    sim.inject_reboot_with_full_state_loss(Author(1), |_, _| TestContext::default(), broadcaster);
+    let clock = sim.current_clock();
+    let node = sim.simulated_node(Author(1));
+    assert!(!node.is_crashed());
+    assert_eq!(node.startup_time(), clock);
+    assert!(node.node.received_notifications.is_empty());
+    assert_eq!(node.message_stats().received, received);
+    assert_eq!(node.message_stats().in_flight, 0);
+    assert_eq!(sim.fault_coverage().count(FaultKind::StateLoss), 1);
+    // Only the startup of the rebooted node is pending for it.
+    let pending: Vec<_> = sim
+        .pending_events
+        .iter()
+        .filter(|ScheduledEvent(_, event, ..)| event.destination() == Author(1))
+        .map(|ScheduledEvent(std::cmp::Reverse(deadline), ..)| *deadline)
+        .collect();
+    assert_eq!(pending, vec![clock]);
+
+    sim.loop_until(GlobalTime(2000), None);
+    assert!(!sim
+        .simulated_node(Author(1))
+        .node
+        .received_notifications
+        .is_empty());
+}
+
+#[test]
+fn test_reboot_in_fair_mode() {
+    let mut sim = make_simulator(3)
+        .with_ordered_startup(0)
+        .with_fair_event_processing();
+    sim.network_delay = RandomDelay::constant(10.0);
+    sim.loop_until(GlobalTime(0), None);
+    for receiver in 1..3 {
+        for notification in 0..2 {
+            sim.schedule_network_event(Event::DataSyncNotifyEvent {
+                sender: Author(0),
+                receiver: Author(receiver),
+                notification,
+            });
+        }
+    }
+    // One notification to node 1 is served, the other one waits in its fair queue.
+    assert!(sim.step(GlobalTime(10)));
+    assert_eq!(sim.fair_scheduler.queues[&Author(1)].len(), 1);
+    let num_dead_letters = sim.dead_letters().len();
+
+    sim.inject_reboot_with_full_state_loss(
+        Author(1),
+        |_, _| TestContext::default(),
+        |_, _: &TestContext, _| TestNode::default(),
+    );
+    assert!(!sim.fair_scheduler.queues.contains_key(&Author(1)));
+    // The queued notification is a dead letter, like the other events pending for node 1.
+    let notifications: Vec<_> = sim.dead_letters()[num_dead_letters..]
+        .iter()
+        .filter_map(|event| match event {
+            Event::DataSyncNotifyEvent { receiver, .. } => Some(*receiver),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(notifications, vec![Author(1)]);
+    sim.loop_until(GlobalTime(50), None);
+    assert!(sim
+        .simulated_node(Author(1))
+        .node
+        .received_notifications
+        .is_empty());
+    assert_eq!(sim.simulated_node(Author(2)).message_stats().received, 2);
+}
+
+#[test]
+fn test_delay_bounds() {
+    let delay = RandomDelay::new(10.0, 100.0);
+    let bounded = delay.with_min_delay(5).with_max_delay(20);
+    let mut rng = StdRng::seed_from_u64(0);
+    let samples: Vec<_> = (0..10000).map(|_| delay.sample(&mut rng)).collect();
+    assert!(samples.iter().any(|delay| *delay < 5));
+    assert!(samples.iter().any(|delay| *delay > 20));
+    let mut rng = StdRng::seed_from_u64(0);
+    let bounded_samples: Vec<_> = (0..10000).map(|_| bounded.sample(&mut rng)).collect();
+    assert!(bounded_samples
+        .iter()
+        .all(|delay| *delay >= 5 && *delay <= 20));
+    assert!(bounded_samples.contains(&5));
+    assert!(bounded_samples.contains(&20));
+}
+
+#[test]
+fn test_constant_delay() {
+    let mut rng = StdRng::seed_from_u64(0);
+    assert_eq!(RandomDelay::constant(0.0).sample(&mut rng), 0);
+    assert_eq!(RandomDelay::constant(9.6).sample(&mut rng), 10);
+    assert_eq!(RandomDelay::constant(9.4).sample(&mut rng), 9);
+}
+
+#[test]
+fn test_delays_are_rounded() {
+    let delay = RandomDelay::new(2.0, 1.0);
+    let mut rng = StdRng::seed_from_u64(0);
+    let samples: Vec<_> = (0..10000).map(|_| delay.sample(&mut rng)).collect();
+    let mean = samples.iter().sum::<Duration>() as f64 / samples.len() as f64;
+    assert!((mean - 2.0).abs() < 0.1, "mean delay {}", mean);
+}
+
+/// Drops the notifications to node 1, and doubles and alters those to node 2.
+struct TamperingAdversary;
+
+impl AdversarySyncStrategy<u64> for TamperingAdversary {
+    fn intercept_notification(
+        &mut self,
+        _sender: Author,
+        receiver: Author,
+        notification: &mut u64,
+    ) -> Vec<(Author, u64)> {
+        match receiver.0 {
+            1 => Vec::new(),
+            2 => {
+                *notification += 1;
+                vec![(receiver, *notification), (receiver, *notification)]
+            }
+            _ => vec![(receiver, *notification)],
+        }
+    }
+}
+
+#[test]
+fn test_adversarial_sync() {
+    let broadcaster = |author, _: &TestContext, _| TestNode {
+        should_broadcast: author == Author(0),
+        ..TestNode::default()
+    };
+    let mut sim = Simulator::new(
+        4,
+        RandomDelay::new(10.0, 4.0),
+        |_, _| TestContext::default(),
+        broadcaster,
+    )
+    .with_seed(3);
+    sim.run_adversarial_sync(Box::new(TamperingAdversary), GlobalTime(1000));
+    let received = |sim: &TestSimulator, index| {
+        sim.simulated_node(Author(index))
+            .node
+            .received_notifications
+            .clone()
+    };
+    assert!(received(&sim, 1).is_empty());
+    assert!(!received(&sim, 2).is_empty());
+    assert!(received(&sim, 2).iter().all(|n| *n == 21));
+    assert!(received(&sim, 3).iter().all(|n| *n == 30));
+    assert_eq!(received(&sim, 2).len(), 2 * received(&sim, 3).len());
+    // The adversary is only active during the call.
+    sim.loop_until(GlobalTime(2000), None);
+    assert!(!received(&sim, 1).is_empty());
+}
+
+#[test]
+fn test_delay_and_replay_adversary() {
+    let mut adversary = DelayAndReplayAdversary::new(3, 2);
+    let mut intercept = |sender, receiver, mut notification| {
+        adversary.intercept_notification(Author(sender), Author(receiver), &mut notification)
+    };
+    assert!(intercept(0, 1, 1u64).is_empty());
+    assert!(intercept(0, 1, 2).is_empty());
+    // Links are delayed independently.
+    assert!(intercept(2, 1, 7).is_empty());
+    let deliveries: Vec<_> = intercept(0, 1, 3).into_iter().map(|(_, n)| n).collect();
+    assert_eq!(deliveries, vec![3, 3, 2, 2, 1, 1]);
+    assert!(intercept(0, 1, 4).is_empty());
+}
+
+/// Three nodes where node 0 broadcasts at every update.
+fn broadcast_simulator() -> TestSimulator {
+    let broadcaster = |author, _: &TestContext, _| TestNode {
+        should_broadcast: author == Author(0),
+        ..TestNode::default()
+    };
+    Simulator::new(
+        3,
+        RandomDelay::new(10.0, 4.0),
+        |_, _| TestContext::default(),
+        broadcaster,
+    )
+    .with_seed(9)
+}
+
+#[test]
+fn test_step() {
+    let mut sim1 = broadcast_simulator();
+    let mut sim2 = broadcast_simulator();
+    sim1.loop_until(GlobalTime(500), None);
+    let mut num_steps = 0;
+    while sim2.step(GlobalTime(500)) {
+        num_steps += 1;
+        assert!(sim2.current_clock() <= GlobalTime(500));
+    }
+    assert!(num_steps > 0);
+    assert!(!sim2.step(GlobalTime(500)));
+    // Stepping goes through the same event loop.
+    assert_eq!(sim1.current_clock(), sim2.current_clock());
+    for index in 0..3 {
+        assert_eq!(
+            sim1.simulated_node(Author(index))
+                .node
+                .received_notifications,
+            sim2.simulated_node(Author(index))
+                .node
+                .received_notifications
+        );
+    }
+}
+
+#[test]
+fn test_resume_loop_until() {
+    let mut sim1 = broadcast_simulator();
+    let mut sim2 = broadcast_simulator();
+    sim1.loop_until(GlobalTime(500), None);
+    for clock in (50..=500).step_by(50) {
+        sim2.loop_until(GlobalTime(clock), None);
+        assert!(sim2.current_clock() <= GlobalTime(clock));
+    }
+    // Events after each intermediate deadline were kept for the next call.
+    assert_eq!(sim1.current_clock(), sim2.current_clock());
+    for index in 0..3 {
+        assert_eq!(
+            sim1.simulated_node(Author(index))
+                .node
+                .received_notifications,
+            sim2.simulated_node(Author(index))
+                .node
+                .received_notifications
+        );
+    }
+}
+
+#[test]
+fn test_pending_census() {
+    let mut sim = make_simulator(3).with_seed(1);
+    sim.loop_until(GlobalTime(990), None);
+    // Updating the node now cancels its pending timer.
+    sim.schedule_event(
+        GlobalTime(991),
+        Event::DataSyncNotifyEvent {
+            sender: Author(0),
+            receiver: Author(1),
+            notification: 0,
+        },
+    );
+    // Leftover messages and the wake-up of a frozen node.
+    for _ in 0..2 {
+        sim.schedule_event(
+            GlobalTime(2000),
+            Event::DataSyncNotifyEvent {
+                sender: Author(0),
+                receiver: Author(2),
+                notification: 0,
+            },
+        );
+    }
+    sim.schedule_event(
+        GlobalTime(1500),
+        Event::DataSyncRequestEvent {
+            receiver: Author(2),
+            sender: Author(1),
+            request: 0,
+        },
+    );
+    sim.schedule_freeze(Author(0), GlobalTime(1200), GlobalTime(3000));
+    let census = sim.loop_until(GlobalTime(995), None).pending;
+    assert!(sim
+        .dump_schedule()
+        .contains("UpdateTimerEvent Author(1) (cancelled)"));
+    let expected: BTreeMap<_, _> = vec![
+        (("DataSyncNotifyEvent", Author(2)), 2),
+        (("DataSyncRequestEvent", Author(1)), 1),
+        // The next timer and the wake-up.
+        (("UpdateTimerEvent", Author(0)), 2),
+        (("UpdateTimerEvent", Author(1)), 1),
+        (("UpdateTimerEvent", Author(2)), 1),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(census.counts, expected);
+    assert_eq!(census.count_of_kind("UpdateTimerEvent"), 4);
+    assert_eq!(census.injected_events, 1);
+    assert_eq!(census.organic_events, 6);
+    assert_eq!(census.total(), 7);
+    assert!(census.earliest_deadline.unwrap() > GlobalTime(995));
+    assert!(census.earliest_deadline.unwrap() < GlobalTime(1500));
+}
+
+#[test]
+fn test_pending_census_of_queued_and_frozen_events() {
+    let mut sim = make_simulator(3).with_seed(1).with_fair_event_processing();
+    sim.loop_until(GlobalTime(990), None);
+    sim.schedule_freeze(Author(0), GlobalTime(991), GlobalTime(3000));
+    // Notifications due at the same time are served in turn, starting with node 0.
+    for receiver in 0..3 {
+        sim.schedule_event(
+            GlobalTime(995),
+            Event::DataSyncNotifyEvent {
+                sender: Author(1),
+                receiver: Author(receiver),
+                notification: 0,
+            },
+        );
+    }
+    while sim.current_clock() < GlobalTime(995) {
+        assert!(sim.step(GlobalTime(995)));
+    }
+    let census = sim.pending_census();
+    assert_eq!(census.frozen_events, 1);
+    assert_eq!(census.queued_events, 2);
+    assert_eq!(census.count_of_kind("DataSyncNotifyEvent"), 3);
+    assert_eq!(census.counts[&("DataSyncNotifyEvent", Author(0))], 1);
+    assert_eq!(census.earliest_deadline, Some(GlobalTime(995)));
+    // The wake-up of the frozen node and the next timers.
+    assert_eq!(census.injected_events, 1);
+    assert_eq!(
+        census.total(),
+        census.injected_events + census.organic_events + 3
+    );
+}
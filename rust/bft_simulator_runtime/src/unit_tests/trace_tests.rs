@@ -0,0 +1,156 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+fn event(time: i64) -> TraceRecord {
+    TraceRecord::Event(TraceEntry {
+        time: GlobalTime(time),
+        kind: "UpdateTimerEvent",
+        author: Author(0),
+    })
+}
+
+fn timeout(time: i64, round: usize) -> TraceRecord {
+    TraceRecord::Timeout {
+        time: GlobalTime(time),
+        author: Author(0),
+        round: Round(round),
+    }
+}
+
+fn round_change(time: i64, round: usize) -> TraceRecord {
+    TraceRecord::RoundChange {
+        time: GlobalTime(time),
+        author: Author(0),
+        round: Round(round),
+    }
+}
+
+#[test]
+fn test_bounded_trace_downsampling() {
+    let mut trace = BoundedTrace::new(100, 1, 10);
+    for time in 0..10000 {
+        if time % 500 == 0 {
+            trace.record(round_change(time, time as usize / 500 + 1));
+        }
+        trace.record(event(time));
+    }
+    assert!(trace.len() <= 100);
+    assert!(trace.sample_every() >= 128);
+    // Round changes are all kept.
+    let rounds: Vec<_> = trace
+        .records()
+        .into_iter()
+        .filter_map(|record| match record {
+            TraceRecord::RoundChange { round, .. } => Some(round.0),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(rounds, (1..=20).collect::<Vec<_>>());
+    assert!(trace.anomalies().is_empty());
+}
+
+#[test]
+fn test_bounded_trace_anomaly() {
+    let mut trace = BoundedTrace::new(100, 1, 10).with_trigger(Box::new(TimeoutStreak::new(3)));
+    let mut time = 0;
+    for _ in 0..5000 {
+        trace.record(event(time));
+        time += 1;
+    }
+    for round in 5..8 {
+        trace.record(timeout(time, round));
+        time += 1;
+        trace.record(event(time));
+        time += 1;
+    }
+    let incident_time = time - 2;
+    for _ in 0..5000 {
+        trace.record(event(time));
+        time += 1;
+    }
+    assert_eq!(
+        trace.anomalies(),
+        &[(GlobalTime(incident_time), "timeout streak")]
+    );
+    // Every record of the window before the anomaly and of the window after it is kept.
+    let times: Vec<_> = trace.records().into_iter().map(TraceRecord::time).collect();
+    let incident_times: Vec<_> = ((incident_time - 9)..(incident_time + 11))
+        .map(GlobalTime)
+        .collect();
+    let position = times
+        .iter()
+        .position(|time| *time == incident_times[0])
+        .unwrap();
+    assert_eq!(times[position..position + 20].to_vec(), incident_times);
+    assert!(trace.len() <= 100);
+}
+
+#[test]
+fn test_anomaly_triggers() {
+    let mut streak = TimeoutStreak::new(2);
+    assert!(!streak.observe(&timeout(0, 1)));
+    // Entering round 3 through a QC of round 2 ends the streak.
+    assert!(!streak.observe(&round_change(1, 3)));
+    assert!(!streak.observe(&timeout(2, 3)));
+    assert!(streak.observe(&timeout(3, 4)));
+
+    let mut stall = RoundStall::new(100);
+    assert!(!stall.observe(&round_change(50, 2)));
+    assert!(!stall.observe(&event(149)));
+    assert!(stall.observe(&event(150)));
+    assert!(!stall.observe(&event(300)));
+    assert!(!stall.observe(&round_change(310, 3)));
+    assert!(stall.observe(&event(410)));
+
+    let mut spike = FaultSpike::new(3, 10);
+    let fault = |time| TraceRecord::Fault {
+        time: GlobalTime(time),
+        kind: FaultKind::LostMessage,
+    };
+    assert!(!spike.observe(&fault(0)));
+    assert!(!spike.observe(&fault(5)));
+    assert!(!spike.observe(&fault(12)));
+    assert!(spike.observe(&fault(14)));
+    assert!(!spike.observe(&event(15)));
+
+    let mut spike = RejectionSpike::new(2, 10);
+    let rejection = |time| TraceRecord::Rejection {
+        time: GlobalTime(time),
+        author: Author(1),
+    };
+    assert!(!spike.observe(&rejection(0)));
+    assert!(!spike.observe(&fault(5)));
+    assert!(spike.observe(&rejection(9)));
+    assert!(!spike.observe(&rejection(30)));
+
+    let mut warning = InvariantWarning::new();
+    assert!(!warning.observe(&timeout(0, 1)));
+    assert!(warning.observe(&TraceRecord::Warning {
+        time: GlobalTime(1),
+        author: Author(1),
+        message: "divergent execution",
+    }));
+}
+
+#[test]
+fn test_bounded_trace_memory() {
+    // Every timeout completes an anomaly, since rounds are skipped in between.
+    let mut trace = BoundedTrace::new(100, 1, 10).with_trigger(Box::new(TimeoutStreak::new(1)));
+    let mut max_len = 0;
+    for time in 0..10000 {
+        if time % 10 == 0 {
+            let round = time as usize / 5 + 1;
+            trace.record(timeout(time, round));
+            trace.record(round_change(time, round + 2));
+        }
+        trace.record(event(time));
+        max_len = std::cmp::max(max_len, trace.len());
+    }
+    assert_eq!(max_len, 100);
+    assert_eq!(trace.anomalies().len(), 1000);
+    // The latest anomaly is kept at full detail.
+    let times: Vec<_> = trace.records().into_iter().map(TraceRecord::time).collect();
+    assert!((9981..9999).all(|time| times.contains(&GlobalTime(time))));
+}
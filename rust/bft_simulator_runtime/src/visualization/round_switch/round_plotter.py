@@ -10,7 +10,8 @@ import argparse
 
 def read_csv(csv_path):
     with open(csv_path) as csv_file:
-        data = list(csv.reader(csv_file))
+        # Skip the schema version.
+        data = [row for row in csv.reader(csv_file) if not row[0].startswith("#")]
     return data
 
 
@@ -0,0 +1,438 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use node::NodeState;
+use record::Record;
+use record_store::RecordStore;
+#[cfg(feature = "simulator")]
+use simulated_context::SimulatedContext;
+#[cfg(feature = "simulator")]
+use simulator::{GlobalTime, Observer};
+use std::{
+    cmp::max,
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    fmt,
+    hash::{Hash, Hasher},
+};
+
+#[cfg(all(test, feature = "simulator"))]
+#[path = "unit_tests/audit_tests.rs"]
+mod audit_tests;
+
+/// Why the voting rules refused a vote on a proposal.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Copy, Clone, Hash, Debug)]
+pub enum DenialReason {
+    /// The round of the block is not above the latest voted round.
+    RoundNotAboveLatestVote,
+    /// The block extends a certified block below the locked round.
+    PreviousRoundBelowLock,
+}
+
+/// Decision of a node that may change its voting constraints.
+#[derive(Eq, PartialEq, Clone, Hash, Debug)]
+pub enum Decision {
+    VoteGranted {
+        block_hash: BlockHash,
+    },
+    VoteDenied {
+        block_hash: BlockHash,
+        reasons: Vec<DenialReason>,
+    },
+    /// The node created a timeout, which prevents voting in the round.
+    Timeout,
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct AuditEntry {
+    /// Position of the entry in the log, so that removed entries are detected.
+    pub index: usize,
+    /// The current epoch.
+    pub epoch_id: EpochId,
+    /// The round of the proposed block, or the round that has timed out.
+    pub round: Round,
+    pub decision: Decision,
+    /// Highest round voted after the decision.
+    pub latest_voted_round: Round,
+    /// Locked round after the decision.
+    pub locked_round: Round,
+    /// Node that took the decision.
+    pub author: Author,
+    /// Signs the hash of the entry, that is, all the fields above.
+    pub signature: Signature,
+}
+
+impl Hash for AuditEntry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.epoch_id.hash(state);
+        self.round.hash(state);
+        self.decision.hash(state);
+        self.latest_voted_round.hash(state);
+        self.locked_round.hash(state);
+        self.author.hash(state);
+    }
+}
+
+impl AuditEntry {
+    pub fn digest(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+/// Voting decisions of a node, in order, each signed by the node.
+#[derive(Clone, Debug)]
+pub struct AuditLog {
+    author: Author,
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    pub fn new(author: Author) -> Self {
+        AuditLog {
+            author,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Copy of the signed entries, e.g. to check them with `verify` after the run.
+    pub fn export(&self) -> Vec<AuditEntry> {
+        self.entries.clone()
+    }
+
+    /// Block of the latest vote decision, if any.
+    pub fn latest_decided_block(&self) -> Option<BlockHash> {
+        self.entries
+            .iter()
+            .rev()
+            .find_map(|entry| match &entry.decision {
+                Decision::VoteGranted { block_hash } | Decision::VoteDenied { block_hash, .. } => {
+                    Some(*block_hash)
+                }
+                Decision::Timeout => None,
+            })
+    }
+
+    pub fn append(
+        &mut self,
+        epoch_id: EpochId,
+        round: Round,
+        decision: Decision,
+        latest_voted_round: Round,
+        locked_round: Round,
+    ) {
+        let mut entry = AuditEntry {
+            index: self.entries.len(),
+            epoch_id,
+            round,
+            decision,
+            latest_voted_round,
+            locked_round,
+            author: self.author,
+            signature: Signature(0),
+        };
+        entry.signature = Signature::sign(entry.digest(), self.author);
+        self.entries.push(entry);
+    }
+}
+
+/// Observer keeping the audit log of each node of a simulation. The decisions of an update are
+/// read from the voting constraints of the node before and after the update: a timeout comes
+/// first, then the vote on the proposed block is granted if the node now votes at its round, and
+/// denied otherwise. An update that starts a new epoch is not audited.
+#[cfg(feature = "simulator")]
+#[derive(Default, Debug)]
+pub struct AuditObserver {
+    logs: BTreeMap<Author, AuditLog>,
+    /// Epoch and voting constraints of each node after its last update.
+    constraints: BTreeMap<Author, (EpochId, Round, Round)>,
+}
+
+#[cfg(feature = "simulator")]
+impl AuditObserver {
+    pub fn new() -> Self {
+        AuditObserver::default()
+    }
+
+    /// Decisions of the voting rules of the given node so far, see `verify`.
+    pub fn log(&self, author: Author) -> Option<&AuditLog> {
+        self.logs.get(&author)
+    }
+}
+
+#[cfg(feature = "simulator")]
+impl Observer<NodeState, SimulatedContext> for AuditObserver {
+    fn on_update(
+        &mut self,
+        _clock: GlobalTime,
+        author: Author,
+        node: &NodeState,
+        _context: &SimulatedContext,
+        _actions: &NodeUpdateActions,
+    ) {
+        let epoch_id = node.epoch_id();
+        let (latest_voted_round, locked_round) = node.voting_constraints();
+        let previous = self
+            .constraints
+            .insert(author, (epoch_id, latest_voted_round, locked_round));
+        let (mut voted_round, locked_before) = match previous {
+            Some((previous_epoch_id, _, _)) if previous_epoch_id != epoch_id => return,
+            Some((_, voted_round, locked_round)) => (voted_round, locked_round),
+            None => (Round(0), Round(0)),
+        };
+        let log = self
+            .logs
+            .entry(author)
+            .or_insert_with(|| AuditLog::new(author));
+        if let Some(round) = node.round_actions().on_timeout {
+            voted_round.max_update(round);
+            log.append(
+                epoch_id,
+                round,
+                Decision::Timeout,
+                voted_round,
+                locked_before,
+            );
+        }
+        let store = node.record_store();
+        let (block_hash, round) = match store.proposed_block(node.pacemaker()) {
+            Some((block_hash, round, _)) if log.latest_decided_block() != Some(block_hash) => {
+                (block_hash, round)
+            }
+            _ => return,
+        };
+        if latest_voted_round == round && latest_voted_round > voted_round {
+            log.append(
+                epoch_id,
+                round,
+                Decision::VoteGranted { block_hash },
+                latest_voted_round,
+                locked_round,
+            );
+        } else {
+            let reasons = denial_reasons(
+                round,
+                store.previous_round(block_hash),
+                voted_round,
+                locked_before,
+            );
+            log.append(
+                epoch_id,
+                round,
+                Decision::VoteDenied {
+                    block_hash,
+                    reasons,
+                },
+                voted_round,
+                locked_before,
+            );
+        }
+    }
+}
+
+/// Why an audit log does not prove that its author followed the voting rules.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum AuditViolation {
+    /// Entries were removed or reordered before this position.
+    MissingEntry { index: usize },
+    /// The entry was not signed by the expected author.
+    InvalidSignature { index: usize },
+    /// The voted block is not in the record archive.
+    UnknownBlock { index: usize, block_hash: BlockHash },
+    /// The entry does not give the round of the voted block.
+    RoundMismatch { index: usize, expected: Round },
+    /// The voting rules call for another decision.
+    WrongDecision { index: usize, expected: Decision },
+    /// The voting constraints after the decision are not the ones given by the entry.
+    WrongConstraints {
+        index: usize,
+        latest_voted_round: Round,
+        locked_round: Round,
+    },
+    /// The archive contains a vote of the author that no entry granted.
+    UnloggedVote {
+        epoch_id: EpochId,
+        round: Round,
+        block_hash: BlockHash,
+    },
+}
+
+impl fmt::Display for AuditViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuditViolation::MissingEntry { index } => {
+                write!(f, "Entry {} is out of sequence", index)
+            }
+            AuditViolation::InvalidSignature { index } => {
+                write!(f, "Entry {} has an invalid signature", index)
+            }
+            AuditViolation::UnknownBlock { index, block_hash } => write!(
+                f,
+                "Entry {} refers to an unknown block {:?}",
+                index, block_hash
+            ),
+            AuditViolation::RoundMismatch { index, expected } => write!(
+                f,
+                "Entry {} gives the wrong round for its block, expected {}",
+                index, expected
+            ),
+            AuditViolation::WrongDecision { index, expected } => write!(
+                f,
+                "Entry {} breaks the voting rules, expected {:?}",
+                index, expected
+            ),
+            AuditViolation::WrongConstraints {
+                index,
+                latest_voted_round,
+                locked_round,
+            } => write!(
+                f,
+                "Entry {} gives the wrong voting constraints, expected latest voted round {} and locked round {}",
+                index, latest_voted_round, locked_round
+            ),
+            AuditViolation::UnloggedVote {
+                epoch_id,
+                round,
+                block_hash,
+            } => write!(
+                f,
+                "Vote on {:?} at {} in {} was never granted",
+                block_hash, round, epoch_id
+            ),
+        }
+    }
+}
+
+/// Records against which an audit log is checked.
+pub trait RecordArchive {
+    fn record_store_at(&self, epoch_id: EpochId) -> Option<&RecordStore>;
+}
+
+impl RecordArchive for NodeState {
+    fn record_store_at(&self, epoch_id: EpochId) -> Option<&RecordStore> {
+        NodeState::record_store_at(self, epoch_id)
+    }
+}
+
+/// Reasons to refuse a vote at `round` on a block extending a certified block of
+/// `previous_round`, given the current voting constraints. None means that the vote is allowed.
+pub fn denial_reasons(
+    round: Round,
+    previous_round: Round,
+    latest_voted_round: Round,
+    locked_round: Round,
+) -> Vec<DenialReason> {
+    let mut reasons = Vec::new();
+    if round <= latest_voted_round {
+        reasons.push(DenialReason::RoundNotAboveLatestVote);
+    }
+    if previous_round < locked_round {
+        reasons.push(DenialReason::PreviousRoundBelowLock);
+    }
+    reasons
+}
+
+/// Replay the decisions of an audit log against the records of `record_archive`, which must
+/// contain every block that `public_key` decided on, e.g. the node itself. Check that every
+/// entry is signed by `public_key`, follows the voting rules and gives the resulting voting
+/// constraints, and that every vote of `public_key` in the archive was granted.
+pub fn verify<A: RecordArchive>(
+    log: &[AuditEntry],
+    public_key: Author,
+    record_archive: &A,
+) -> std::result::Result<(), AuditViolation> {
+    let mut epoch_id = None;
+    let mut latest_voted_round = Round(0);
+    let mut locked_round = Round(0);
+    let mut granted_votes = BTreeSet::new();
+    for (index, entry) in log.iter().enumerate() {
+        if entry.index != index {
+            return Err(AuditViolation::MissingEntry { index });
+        }
+        if entry.author != public_key || entry.signature.check(entry.digest(), public_key).is_err()
+        {
+            return Err(AuditViolation::InvalidSignature { index });
+        }
+        // Voting constraints start afresh with each epoch.
+        if epoch_id != Some(entry.epoch_id) {
+            epoch_id = Some(entry.epoch_id);
+            latest_voted_round = Round(0);
+            locked_round = Round(0);
+        }
+        let block_hash = match &entry.decision {
+            Decision::Timeout => {
+                latest_voted_round = max(latest_voted_round, entry.round);
+                None
+            }
+            Decision::VoteGranted { block_hash } | Decision::VoteDenied { block_hash, .. } => {
+                Some(*block_hash)
+            }
+        };
+        if let Some(block_hash) = block_hash {
+            let store = record_archive
+                .record_store_at(entry.epoch_id)
+                .filter(|store| store.block(block_hash).is_some())
+                .ok_or(AuditViolation::UnknownBlock { index, block_hash })?;
+            let round = store.block(block_hash).unwrap().round;
+            if entry.round != round {
+                return Err(AuditViolation::RoundMismatch {
+                    index,
+                    expected: round,
+                });
+            }
+            let reasons = denial_reasons(
+                round,
+                store.previous_round(block_hash),
+                latest_voted_round,
+                locked_round,
+            );
+            let expected = if reasons.is_empty() {
+                latest_voted_round = round;
+                locked_round = max(locked_round, store.second_previous_round(block_hash));
+                granted_votes.insert((entry.epoch_id, round, block_hash));
+                Decision::VoteGranted { block_hash }
+            } else {
+                Decision::VoteDenied {
+                    block_hash,
+                    reasons,
+                }
+            };
+            if entry.decision != expected {
+                return Err(AuditViolation::WrongDecision { index, expected });
+            }
+        }
+        if entry.latest_voted_round != latest_voted_round || entry.locked_round != locked_round {
+            return Err(AuditViolation::WrongConstraints {
+                index,
+                latest_voted_round,
+                locked_round,
+            });
+        }
+    }
+    let epochs: BTreeSet<_> = log.iter().map(|entry| entry.epoch_id).collect();
+    for epoch_id in epochs {
+        let store = match record_archive.record_store_at(epoch_id) {
+            Some(store) => store,
+            None => continue,
+        };
+        for record in store.records() {
+            if let Record::Vote(vote) = record {
+                let key = (epoch_id, vote.round, vote.certified_block_hash);
+                if vote.author == public_key && !granted_votes.contains(&key) {
+                    return Err(AuditViolation::UnloggedVote {
+                        epoch_id,
+                        round: vote.round,
+                        block_hash: vote.certified_block_hash,
+                    });
+                }
+            }
+        }
+    }
+    Ok(())
+}
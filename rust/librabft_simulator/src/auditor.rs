@@ -0,0 +1,76 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::trace::TraceRecord;
+use node::NodeState;
+use simulated_context::SimulatedContext;
+use simulator::{GlobalTime, Observer};
+
+/// Observer auditing the nodes of a simulation from outside the protocol. It flags the nodes
+/// whose execution disagrees with the QCs that they receive, e.g. because they run a divergent
+/// implementation. Divergences and rejected blocks are also reported to the bounded trace of
+/// the simulation, if any.
+#[derive(Default, Debug)]
+pub struct Auditor {
+    /// Time at which each flagged node first saw a QC for a state other than its own.
+    divergences: BTreeMap<Author, GlobalTime>,
+    /// Number of blocks rejected by each node so far.
+    num_rejected_blocks: BTreeMap<Author, usize>,
+    /// Records for the bounded trace since the last update.
+    trace_records: Vec<TraceRecord>,
+}
+
+impl Auditor {
+    pub fn new() -> Self {
+        Auditor::default()
+    }
+
+    /// Flagged nodes, in order of authors.
+    pub fn divergent_authors(&self) -> Vec<Author> {
+        self.divergences.keys().cloned().collect()
+    }
+
+    pub fn divergence_time(&self, author: Author) -> Option<GlobalTime> {
+        self.divergences.get(&author).cloned()
+    }
+}
+
+impl Observer<NodeState, SimulatedContext> for Auditor {
+    fn on_update(
+        &mut self,
+        clock: GlobalTime,
+        author: Author,
+        node: &NodeState,
+        _context: &SimulatedContext,
+        _actions: &NodeUpdateActions,
+    ) {
+        if !self.divergences.contains_key(&author) && node.has_diverged() {
+            warn!(
+                "@{:?} {:?} executes commands differently from the QCs it receives",
+                clock, author
+            );
+            self.divergences.insert(author, clock);
+            self.trace_records.push(TraceRecord::Warning {
+                time: clock,
+                author,
+                message: "divergent execution",
+            });
+        }
+        let num_rejected_blocks = node.num_oversized_blocks() + node.num_epoch_config_mismatches();
+        let previous = self
+            .num_rejected_blocks
+            .insert(author, num_rejected_blocks)
+            .unwrap_or(0);
+        for _ in previous..num_rejected_blocks {
+            self.trace_records.push(TraceRecord::Rejection {
+                time: clock,
+                author,
+            });
+        }
+    }
+
+    fn take_trace_records(&mut self) -> Vec<TraceRecord> {
+        std::mem::take(&mut self.trace_records)
+    }
+}
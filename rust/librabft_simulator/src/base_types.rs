@@ -1,7 +1,8 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::Author;
+use crate::{Author, StableHasher};
+use std::{fmt, hash::Hasher};
 
 #[cfg(test)]
 #[path = "unit_tests/base_type_tests.rs"]
@@ -22,6 +23,41 @@ pub struct State(pub u64);
 pub struct Command {
     pub proposer: Author,
     pub index: usize,
+    /// Opaque content of the command.
+    pub payload: Vec<u8>,
+}
+
+impl Command {
+    /// A command without payload, which executes as a no-op. Blocks carrying it only advance
+    /// the commit rule.
+    pub fn empty(proposer: Author) -> Command {
+        Command {
+            proposer,
+            index: 0,
+            payload: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.payload.is_empty()
+    }
+}
+
+/// Hashing whose output only depends on the value, unlike `DefaultHasher`, so that nodes
+/// executing the same command always agree on its hash.
+pub trait DeterministicHash {
+    fn deterministic_hash(&self) -> u64;
+}
+
+impl DeterministicHash for Command {
+    fn deterministic_hash(&self) -> u64 {
+        let mut hasher = StableHasher::new();
+        hasher.write_u64(self.proposer.0 as u64);
+        hasher.write_u64(self.index as u64);
+        hasher.write_u64(self.payload.len() as u64);
+        hasher.write(&self.payload);
+        hasher.finish()
+    }
 }
 
 impl EpochId {
@@ -30,11 +66,22 @@ impl EpochId {
     }
 
     pub fn previous(self) -> Option<EpochId> {
-        if self.0 == 0 {
-            None
-        } else {
-            Some(EpochId(self.0))
-        }
+        self.0.checked_sub(1).map(EpochId)
+    }
+
+    /// The epoch after this one. Panics on overflow.
+    pub fn next(self) -> EpochId {
+        EpochId(
+            self.0
+                .checked_add(1)
+                .unwrap_or_else(|| panic!("Epoch overflow: {}", self)),
+        )
+    }
+}
+
+impl fmt::Display for EpochId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "e{}", self.0)
     }
 }
 
@@ -53,13 +100,13 @@ pub fn merge_sort<T: Eq, I: IntoIterator<Item = T>, F: Fn(&T, &T) -> std::cmp::O
     let mut head1 = iter1.next();
     let mut head2 = iter2.next();
     while let (Some(x1), Some(x2)) = (&head1, &head2) {
-        match cmp(&x1, &x2) {
+        match cmp(x1, x2) {
             std::cmp::Ordering::Less => {
                 result.push(head1.unwrap());
                 head1 = iter1.next();
             }
             std::cmp::Ordering::Equal => {
-                if head1 == head1 {
+                if head1 == head2 {
                     result.push(head1.unwrap());
                 } else {
                     result.push(head1.unwrap());
@@ -0,0 +1,193 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use data_sync::StaleFeed;
+#[cfg(feature = "simulator")]
+use data_sync::{BundledNotification, DataSyncRequest, DataSyncResponse};
+use node::NodeState;
+use record::{Block, Record};
+use simulated_context::{SimulatedContext, StateDigest};
+#[cfg(feature = "simulator")]
+use simulator::{RandomDelay, Simulator};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::TryFrom,
+};
+
+/// A deterministic fault of a Byzantine node.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum ByzantineFault {
+    /// When leading a round, also sign a block with a different payload and send it instead of
+    /// the first one to the given victims.
+    Equivocate { victims: BTreeSet<Author> },
+    /// Never send our votes on the blocks proposed by the given author.
+    Censor(Author),
+    /// Never send our votes, so that they only count toward the QCs of our own proposals.
+    WithholdVotes,
+    /// Claim in timeouts that no block was certified. Unlike higher claims, such timeouts are
+    /// still accepted by other nodes.
+    LieAboutCertifiedRound,
+    /// Feed the victim with the view that the node had `lag` ago, see `StaleFeed`.
+    StaleFeed { victim: Author, lag: Duration },
+    /// Append the given number of bytes to the commands of the blocks that we send, ignoring
+    /// the block size limit.
+    PadCommands(usize),
+    /// Compute execution states differently from other nodes.
+    DivergentExecution,
+}
+
+/// Faults of a node that are implemented by `NodeState` itself.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct ByzantineBehavior {
+    /// Nodes sent a conflicting version of our proposals, if we equivocate.
+    pub equivocation_victims: BTreeSet<Author>,
+    pub censored_authors: BTreeSet<Author>,
+    pub withhold_votes: bool,
+    pub lie_about_certified_round: bool,
+    /// Outdated view sent to a victim, if any.
+    pub stale_feed: Option<StaleFeed>,
+    /// Bytes appended to the command of our blocks when sending them.
+    pub block_padding: usize,
+}
+
+impl ByzantineBehavior {
+    /// Whether the node sends its votes on the blocks of the given proposer.
+    pub fn sends_votes_for(&self, proposer: Author) -> bool {
+        !self.withhold_votes && !self.censored_authors.contains(&proposer)
+    }
+
+    /// The block to send instead of our proposal to the given node, if it is a victim of our
+    /// equivocation: the same block with a different payload, signed again.
+    pub fn conflicting_block(&self, receiver: Author, block: &Block) -> Option<Block> {
+        if !self.equivocation_victims.contains(&receiver) {
+            return None;
+        }
+        let mut command = block.command.clone();
+        command.payload.push(1);
+        let record = Record::make_block(
+            command,
+            block.time,
+            block.previous_quorum_certificate_hash,
+            block.round,
+            block.epoch_configuration_digest,
+            block.author,
+        );
+        Some(Block::try_from(record).expect("make_block should create a block"))
+    }
+
+    /// The block to send instead of the given one, if we stuff our blocks: the same block with
+    /// a padded command, signed again. The original block is the one that we insert locally,
+    /// since our own record store would refuse the stuffed one.
+    pub fn stuffed_block(&self, block: &Block) -> Option<Block> {
+        if self.block_padding == 0 {
+            return None;
+        }
+        let mut command = block.command.clone();
+        command
+            .payload
+            .resize(command.payload.len() + self.block_padding, 0);
+        let record = Record::make_block(
+            command,
+            block.time,
+            block.previous_quorum_certificate_hash,
+            block.round,
+            block.epoch_configuration_digest,
+            block.author,
+        );
+        Some(Block::try_from(record).expect("make_block should create a block"))
+    }
+}
+
+/// Faults of each Byzantine node, so that an adversarial scenario is reproducible from a
+/// single value.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct ByzantineConfig {
+    faults: BTreeMap<Author, Vec<ByzantineFault>>,
+}
+
+impl ByzantineConfig {
+    pub fn new() -> Self {
+        ByzantineConfig::default()
+    }
+
+    pub fn with_fault(mut self, author: Author, fault: ByzantineFault) -> Self {
+        self.faults.entry(author).or_default().push(fault);
+        self
+    }
+
+    pub fn faults(&self, author: Author) -> &[ByzantineFault] {
+        self.faults.get(&author).map_or(&[], |faults| &faults[..])
+    }
+
+    pub fn byzantine_authors(&self) -> Vec<Author> {
+        self.faults.keys().cloned().collect()
+    }
+
+    /// Apply the faults of the author that are simulated by its execution context.
+    pub fn configure_context(&self, author: Author, context: SimulatedContext) -> SimulatedContext {
+        self.faults(author)
+            .iter()
+            .fold(context, |context, fault| match fault {
+                ByzantineFault::DivergentExecution => {
+                    context.with_state_digest(StateDigest::Salted(1))
+                }
+                _ => context,
+            })
+    }
+
+    /// Apply the faults of the author that are simulated by the node.
+    pub fn configure_node(&self, author: Author, node: NodeState) -> NodeState {
+        let mut behavior = ByzantineBehavior::default();
+        for fault in self.faults(author) {
+            match fault {
+                ByzantineFault::Equivocate { victims } => {
+                    behavior
+                        .equivocation_victims
+                        .extend(victims.iter().cloned());
+                }
+                ByzantineFault::Censor(victim) => {
+                    behavior.censored_authors.insert(*victim);
+                }
+                ByzantineFault::WithholdVotes => behavior.withhold_votes = true,
+                ByzantineFault::LieAboutCertifiedRound => behavior.lie_about_certified_round = true,
+                ByzantineFault::StaleFeed { victim, lag } => {
+                    behavior.stale_feed = Some(StaleFeed::new(*victim, *lag));
+                }
+                ByzantineFault::PadCommands(padding) => behavior.block_padding = *padding,
+                ByzantineFault::DivergentExecution => (),
+            }
+        }
+        node.with_byzantine_behavior(behavior)
+    }
+
+    /// Create a simulator whose nodes and contexts are made by the given factories, then
+    /// altered according to this configuration.
+    #[cfg(feature = "simulator")]
+    pub fn make_simulator<F, G>(
+        &self,
+        num_nodes: usize,
+        network_delay: RandomDelay,
+        context_factory: F,
+        node_factory: G,
+    ) -> Simulator<
+        NodeState,
+        SimulatedContext,
+        BundledNotification,
+        DataSyncRequest,
+        DataSyncResponse,
+    >
+    where
+        F: Fn(Author, usize) -> SimulatedContext,
+        G: Fn(Author, &SimulatedContext, NodeTime) -> NodeState,
+    {
+        Simulator::new(
+            num_nodes,
+            network_delay,
+            |author, num_nodes| self.configure_context(author, context_factory(author, num_nodes)),
+            |author, context: &SimulatedContext, clock| {
+                self.configure_node(author, node_factory(author, context, clock))
+            },
+        )
+    }
+}
@@ -6,9 +6,9 @@ use base_types::*;
 use node::*;
 use record::*;
 use smr_context::SMRContext;
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, VecDeque};
 
-#[cfg(test)]
+#[cfg(all(test, feature = "simulator"))]
 #[path = "unit_tests/data_sync_tests.rs"]
 mod data_sync_tests;
 
@@ -48,6 +48,185 @@ pub struct DataSyncResponse {
 }
 // -- END FILE --
 
+/// Notification sent by a `NodeState`, with the block certified by the highest QC attached if
+/// the notification carries timeouts and bundling is enabled, so that the next leader can insert
+/// the QC and the timeouts without a sync round trip. QCs are sent with aggregated signatures if
+/// the epoch configuration says so.
+#[derive(Eq, PartialEq, Ord, PartialOrd, Debug, Clone)]
+pub struct BundledNotification {
+    notification: DataSyncNotification,
+    highest_certified_block: Option<Block>,
+    /// Length of the bitmap of signers of the QCs, if they are sent with aggregated signatures.
+    aggregation_bitmap_len: Option<usize>,
+}
+
+impl DataSyncResponse {
+    /// Estimated number of bytes of the records once serialized.
+    fn wire_size(&self) -> usize {
+        self.records
+            .iter()
+            .flat_map(|(_, records)| records)
+            .map(Record::wire_size)
+            .sum()
+    }
+}
+
+/// How a node answers the requests of authors without voting rights in its current epoch, e.g.
+/// observers, nodes of another epoch, or Byzantine senders.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum UnknownAuthorPolicy {
+    /// Serve them as validators (open data availability).
+    Serve,
+    /// Serve them after the requests of validators.
+    Deprioritize,
+    /// Answer with an empty response and count the refusal.
+    Reject,
+}
+
+/// Byzantine behavior: feed a victim with an outdated view of the sender, made of validly
+/// signed but stale records.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct StaleFeed {
+    victim: Author,
+    /// Age of the view fed to the victim.
+    lag: Duration,
+    /// Notifications created at each update, oldest first. Only the most recent notification
+    /// that is at least `lag` old is kept among older ones.
+    history: VecDeque<(NodeTime, BundledNotification)>,
+}
+
+impl StaleFeed {
+    pub fn new(victim: Author, lag: Duration) -> Self {
+        StaleFeed {
+            victim,
+            lag,
+            history: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, clock: NodeTime, notification: BundledNotification) {
+        self.history.push_back((clock, notification));
+        while self.history.len() > 1 && self.history[1].0 + self.lag <= clock {
+            self.history.pop_front();
+        }
+    }
+
+    fn notification(&self) -> Option<&BundledNotification> {
+        self.history.front().map(|(_, notification)| notification)
+    }
+
+    /// Remove the records that are more recent than the view fed to the victim.
+    fn filter_response(&self, response: &mut DataSyncResponse) {
+        let notification = match self.notification() {
+            Some(bundle) => &bundle.notification,
+            None => return,
+        };
+        let highest_round = notification
+            .highest_quorum_certificate
+            .as_ref()
+            .map_or(Round(0), |qc| qc.round);
+        response
+            .records
+            .retain(|(epoch_id, _)| *epoch_id <= notification.current_epoch);
+        for (epoch_id, records) in &mut response.records {
+            if *epoch_id == notification.current_epoch {
+                records.retain(|record| record.round() <= highest_round);
+            }
+        }
+    }
+}
+
+impl NotificationKind for DataSyncNotification {
+    fn announces_quorum_certificate(&self) -> bool {
+        // Exclude votes, proposals, and timeouts, which also carry our highest QC.
+        match &self.highest_quorum_certificate {
+            Some(qc) => {
+                self.current_vote.is_none()
+                    && self.proposed_block.is_none()
+                    && self
+                        .timeouts
+                        .iter()
+                        .all(|timeout| timeout.round <= qc.round)
+            }
+            None => false,
+        }
+    }
+}
+
+// Epochs and rounds take 8 bytes each.
+impl MessageSize for DataSyncNotification {
+    fn message_size(&self) -> usize {
+        8 + self
+            .highest_commit_certificate
+            .iter()
+            .chain(&self.highest_quorum_certificate)
+            .map(QuorumCertificate::wire_size)
+            .sum::<usize>()
+            + self.timeouts.iter().map(Timeout::wire_size).sum::<usize>()
+            + self.current_vote.as_ref().map_or(0, Vote::wire_size)
+            + self.proposed_block.as_ref().map_or(0, Block::wire_size)
+    }
+
+    fn num_records(&self) -> usize {
+        self.highest_commit_certificate.iter().count()
+            + self.highest_quorum_certificate.iter().count()
+            + self.timeouts.len()
+            + self.current_vote.iter().count()
+            + self.proposed_block.iter().count()
+    }
+}
+
+impl NotificationKind for BundledNotification {
+    fn announces_quorum_certificate(&self) -> bool {
+        self.notification.announces_quorum_certificate()
+    }
+}
+
+impl MessageSize for BundledNotification {
+    fn message_size(&self) -> usize {
+        let size = self.notification.message_size()
+            + self
+                .highest_certified_block
+                .as_ref()
+                .map_or(0, Block::wire_size);
+        match self.aggregation_bitmap_len {
+            Some(num_voters) => {
+                let qcs = || {
+                    self.notification
+                        .highest_commit_certificate
+                        .iter()
+                        .chain(&self.notification.highest_quorum_certificate)
+                };
+                size + qcs()
+                    .map(|qc| qc.aggregated_wire_size(num_voters))
+                    .sum::<usize>()
+                    - qcs().map(QuorumCertificate::wire_size).sum::<usize>()
+            }
+            None => size,
+        }
+    }
+
+    fn num_records(&self) -> usize {
+        self.notification.num_records() + self.highest_certified_block.iter().count()
+    }
+}
+
+impl MessageSize for DataSyncRequest {
+    fn message_size(&self) -> usize {
+        8 + 8 * self.known_quorum_certificates.len() + 8
+    }
+}
+
+impl MessageSize for DataSyncResponse {
+    fn message_size(&self) -> usize {
+        8 + self.wire_size() + 8
+    }
+
+    fn num_records(&self) -> usize {
+        self.records.iter().map(|(_, records)| records.len()).sum()
+    }
+}
+
 impl NodeState {
     fn create_request_internal(&self) -> DataSyncRequest {
         DataSyncRequest {
@@ -55,17 +234,30 @@ impl NodeState {
             known_quorum_certificates: self.record_store().known_quorum_certificate_rounds(),
         }
     }
-}
 
-impl<Context> DataSyncNode<Context> for NodeState
-where
-    Context: SMRContext,
-{
-    type Notification = DataSyncNotification;
-    type Request = DataSyncRequest;
-    type Response = DataSyncResponse;
+    fn handle_request_internal(&self, request: DataSyncRequest) -> DataSyncResponse {
+        let mut records = Vec::new();
+        if let Some(store) = self.record_store_at(request.current_epoch) {
+            records.push((
+                request.current_epoch,
+                store.unknown_records(request.known_quorum_certificates),
+            ));
+        }
+        let mut epoch_id = request.current_epoch;
+        while epoch_id < self.epoch_id() {
+            epoch_id = epoch_id.next();
+            let store = self
+                .record_store_at(epoch_id)
+                .expect("All record stores up to the current epoch should exist.");
+            records.push((epoch_id, store.unknown_records(BTreeSet::new())));
+        }
+        DataSyncResponse {
+            current_epoch: self.epoch_id(),
+            records,
+        }
+    }
 
-    fn create_notification(&self) -> DataSyncNotification {
+    pub fn create_notification_internal(&self) -> DataSyncNotification {
         // Pass the latest (non-empty) commit certificate across epochs.
         let highest_commit_certificate = match self.record_store().highest_commit_certificate() {
             Some(hqc) => Some(hqc.clone()),
@@ -99,11 +291,87 @@ where
         }
     }
 
+    /// Attach the block certified by the highest QC to notifications carrying timeouts, if
+    /// enabled, and tell how the QCs are encoded in the current epoch.
+    pub fn create_bundled_notification(&self) -> BundledNotification {
+        let notification = self.create_notification_internal();
+        let highest_certified_block = match &notification.highest_quorum_certificate {
+            Some(qc)
+                if self.bundles_certified_block_with_timeouts()
+                    && !notification.timeouts.is_empty() =>
+            {
+                self.record_store().block(qc.certified_block_hash).cloned()
+            }
+            _ => None,
+        };
+        let configuration = self.configuration();
+        BundledNotification {
+            notification,
+            highest_certified_block,
+            aggregation_bitmap_len: if configuration.aggregates_signatures() {
+                Some(configuration.voters().count())
+            } else {
+                None
+            },
+        }
+    }
+}
+
+impl<Context> DataSyncNode<Context> for NodeState
+where
+    Context: SMRContext,
+{
+    type Notification = BundledNotification;
+    type Request = DataSyncRequest;
+    type Response = DataSyncResponse;
+
+    fn create_notification(&self) -> BundledNotification {
+        self.create_bundled_notification()
+    }
+
+    fn create_notification_for(&self, receiver: Author) -> BundledNotification {
+        match self.stale_feed() {
+            Some(feed) if feed.victim == receiver => match feed.notification() {
+                Some(bundle) => bundle.clone(),
+                None => self.create_bundled_notification(),
+            },
+            _ => {
+                let mut bundle = self.create_bundled_notification();
+                let notification = &mut bundle.notification;
+                let conflicting_block = notification
+                    .proposed_block
+                    .as_ref()
+                    .and_then(|block| self.conflicting_proposal_for(receiver, block));
+                if conflicting_block.is_some() {
+                    notification.proposed_block = conflicting_block;
+                }
+                if let Some(vote) = &notification.current_vote {
+                    if !self.sends_vote(vote) {
+                        notification.current_vote = None;
+                    }
+                }
+                for block in vec![
+                    &mut notification.proposed_block,
+                    &mut bundle.highest_certified_block,
+                ]
+                .into_iter()
+                .flatten()
+                {
+                    if let Some(stuffed_block) = self.stuffed_block(block) {
+                        *block = stuffed_block;
+                    }
+                }
+                bundle
+            }
+        }
+    }
+
     fn handle_notification(
         &mut self,
-        notification: DataSyncNotification,
+        bundle: BundledNotification,
         smr_context: &mut Context,
     ) -> Option<DataSyncRequest> {
+        let notification = bundle.notification;
         // Whether we should request more data because of a new epoch or missings records.
         let mut should_sync = false;
         // Note that malicious nodes can always lie to make us send a request, but they may as
@@ -123,6 +391,14 @@ where
                     && highest_commit_certificate.round
                         > self.record_store().highest_committed_round() + 2);
         }
+        // Insert the certified block of the highest QC first, if it was bundled.
+        if let Some(block) = bundle.highest_certified_block {
+            self.insert_network_record(
+                notification.current_epoch,
+                Record::Block(block),
+                smr_context,
+            );
+        }
         if let Some(highest_quorum_certificate) = &notification.highest_quorum_certificate {
             // Try to insert the QC.
             self.insert_network_record(
@@ -169,24 +445,43 @@ where
     }
 
     fn handle_request(&self, request: DataSyncRequest) -> DataSyncResponse {
-        let mut records = Vec::new();
-        if let Some(store) = self.record_store_at(request.current_epoch) {
-            records.push((
-                request.current_epoch,
-                store.unknown_records(request.known_quorum_certificates),
-            ));
+        self.handle_request_internal(request)
+    }
+
+    fn handle_request_from(&self, receiver: Author, request: DataSyncRequest) -> DataSyncResponse {
+        if <Self as DataSyncNode<Context>>::refuses_requests_from(self, receiver) {
+            return DataSyncResponse {
+                current_epoch: self.epoch_id(),
+                records: Vec::new(),
+            };
         }
-        for i in (request.current_epoch.0 + 1)..(self.epoch_id().0 + 1) {
-            let epoch_id = EpochId(i);
-            let store = self
-                .record_store_at(epoch_id)
-                .expect("All record stores up to the current epoch should exist.");
-            records.push((epoch_id, store.unknown_records(BTreeSet::new())));
+        let mut response = self.handle_request_internal(request);
+        if let Some(feed) = self.stale_feed() {
+            if feed.victim == receiver {
+                feed.filter_response(&mut response);
+            }
         }
-        DataSyncResponse {
-            current_epoch: self.epoch_id(),
-            records,
+        for (_, records) in &mut response.records {
+            for record in records {
+                let stuffed_block = match record {
+                    Record::Block(block) => self.stuffed_block(block),
+                    _ => None,
+                };
+                if let Some(block) = stuffed_block {
+                    *record = Record::Block(block);
+                }
+            }
         }
+        response
+    }
+
+    fn deprioritizes_requests_from(&self, receiver: Author) -> bool {
+        self.unknown_author_policy() == UnknownAuthorPolicy::Deprioritize
+            && !self.is_validator(receiver)
+    }
+
+    fn refuses_requests_from(&self, receiver: Author) -> bool {
+        self.unknown_author_policy() == UnknownAuthorPolicy::Reject && !self.is_validator(receiver)
     }
 
     fn handle_response(
@@ -221,3 +516,71 @@ where
         }
     }
 }
+
+/// What a node persists, see `DurableState`.
+#[derive(Debug)]
+pub struct DurableLog {
+    /// Committed records, replayed as a data sync response.
+    records: DataSyncResponse,
+    /// Epoch of the voting constraints below.
+    epoch_id: EpochId,
+    /// Highest round voted so far, so that the node never votes twice in a round.
+    latest_voted_round: Round,
+    locked_round: Round,
+}
+
+impl<Context> DurableState<Context> for NodeState
+where
+    Context: SMRContext,
+{
+    type Log = DurableLog;
+
+    fn highest_committed_round(&self) -> Round {
+        self.record_store().highest_committed_round()
+    }
+
+    fn durable_log(&self, last_committed_round: Round) -> DurableLog {
+        let mut log = self.handle_request_internal(DataSyncRequest {
+            current_epoch: EpochId(0),
+            known_quorum_certificates: BTreeSet::new(),
+        });
+        // The QCs that commit the last round are persisted as well.
+        let commit_certificates = self.commit_certificate_chain(last_committed_round);
+        for (epoch_id, records) in &mut log.records {
+            // Votes and timeouts are only kept in memory.
+            records.retain(|record| match record {
+                Record::Block(_) | Record::QuorumCertificate(_) => true,
+                Record::Vote(_) | Record::Timeout(_) => false,
+            });
+            if *epoch_id == self.epoch_id() {
+                records.retain(|record| {
+                    record.round() <= last_committed_round
+                        || match record {
+                            Record::Block(_) => commit_certificates
+                                .iter()
+                                .any(|qc| qc.certified_block_hash == BlockHash(record.digest())),
+                            Record::QuorumCertificate(qc) => commit_certificates.contains(&qc),
+                            _ => false,
+                        }
+                });
+            }
+        }
+        let (latest_voted_round, locked_round) = self.voting_constraints();
+        DurableLog {
+            records: log,
+            epoch_id: self.epoch_id(),
+            latest_voted_round,
+            locked_round,
+        }
+    }
+
+    fn recover(&mut self, log: DurableLog, smr_context: &mut Context, clock: NodeTime) {
+        self.handle_response(log.records, smr_context, clock);
+        // Unlike after a sync, commit the latest epoch right away.
+        self.process_commits(smr_context);
+        if self.epoch_id() == log.epoch_id {
+            self.restore_voting_constraints(log.latest_voted_round, log.locked_round);
+        }
+        self.update_tracker(clock);
+    }
+}
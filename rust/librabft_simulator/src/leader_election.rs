@@ -0,0 +1,70 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+#[cfg(all(test, feature = "simulator"))]
+#[path = "unit_tests/leader_election_tests.rs"]
+mod leader_election_tests;
+
+/// Maximal number of draws when looking for a leader outside of the inactive authors.
+const MAX_REPUTATION_DRAWS: u64 = 16;
+
+/// How the leader of each round is chosen. All honest nodes must agree on the leader of a
+/// round, so elections only depend on the configuration of the epoch.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LeaderElection {
+    /// Pick an author from a hash of the round, with chances proportional to voting rights.
+    Hashed,
+    /// Take turns, each author leading as many consecutive rounds as its voting rights.
+    RoundRobin,
+    /// Like `Hashed`, but skip the inactive authors of the epoch, see
+    /// `EpochConfiguration::inactive_authors`. The execution measures participation from the
+    /// voters of the QCs extended by committed blocks, so the schedule only changes at epoch
+    /// boundaries and every node that reaches the epoch, e.g. by data synchronization, computes
+    /// the same one.
+    Reputation,
+}
+
+/// Leader of each round of an epoch.
+pub trait LeaderSchedule {
+    fn leader(&self, round: Round) -> Author;
+}
+
+impl Default for LeaderElection {
+    fn default() -> Self {
+        LeaderElection::Hashed
+    }
+}
+
+impl LeaderElection {
+    pub fn leader(&self, configuration: &EpochConfiguration, round: Round) -> Author {
+        match self {
+            LeaderElection::Hashed => configuration.pick_author(Self::hash(round, 0)),
+            LeaderElection::RoundRobin => configuration.pick_author(round.0 as u64),
+            LeaderElection::Reputation => {
+                let inactive_authors = configuration.inactive_authors();
+                let mut author = configuration.pick_author(Self::hash(round, 0));
+                let mut draw = 1;
+                while inactive_authors.contains(&author) && draw < MAX_REPUTATION_DRAWS {
+                    author = configuration.pick_author(Self::hash(round, draw));
+                    draw += 1;
+                }
+                author
+            }
+        }
+    }
+
+    fn hash(round: Round, draw: u64) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        round.hash(&mut hasher);
+        if draw > 0 {
+            draw.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
@@ -0,0 +1,54 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(bare_trait_objects)]
+// Lints whose suggestions need a newer toolchain than the one of `rust-toolchain`.
+#![allow(
+    unknown_lints,
+    clippy::derivable_impls,
+    clippy::manual_div_ceil,
+    clippy::unnecessary_map_or
+)]
+// The code blocks of the consensus report are kept as published.
+#![allow(clippy::partialeq_to_none)]
+
+#[macro_use]
+extern crate failure;
+#[macro_use]
+extern crate log;
+extern crate bft_simulator_runtime;
+
+// Comments in the following form are used for code-block generation in the consensus report:
+//    "// -- BEGIN FILE name --"
+//    "// -- END FILE --"
+// Do not modify definitions without changing the report as well :)
+
+pub mod audit;
+#[cfg(feature = "simulator")]
+pub mod auditor;
+pub mod base_types;
+pub mod byzantine;
+pub mod data_sync;
+pub mod leader_election;
+pub mod node;
+pub mod pacemaker;
+pub mod record;
+pub mod record_store;
+pub mod simulated_context;
+pub mod smr_context;
+#[cfg(feature = "simulator")]
+pub mod testing;
+#[cfg(feature = "simulator")]
+pub mod visualisation;
+
+#[cfg(feature = "simulator")]
+use bft_simulator_runtime::simulator;
+use bft_simulator_runtime::{
+    base_types::*, ActiveRound, BlockProposer, CommittedChain, ConsensusNode, ConsensusTimeout,
+    ConsensusTimeoutHint, DataSyncNode, DurableState, EpochConfiguration, MessageSize,
+    NodeUpdateActions, NotificationKind, RoundActions, Validate,
+};
+
+use base_types::*;
+use data_sync::*;
+use std::{collections::BTreeMap, fmt::Debug};
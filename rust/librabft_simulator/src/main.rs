@@ -2,51 +2,104 @@
 // SPDX-License-Identifier: Apache-2.0
 
 #![allow(bare_trait_objects)]
+// Lints whose suggestions need a newer toolchain than the one of `rust-toolchain`.
+#![allow(unknown_lints, clippy::unnecessary_map_or)]
 
-#[macro_use]
 extern crate failure;
 extern crate rand;
 #[macro_use]
 extern crate log;
 extern crate bft_simulator_runtime;
 extern crate clap;
+extern crate csv;
 extern crate env_logger;
+extern crate librabft_simulator;
 
-use clap::{App, Arg};
-use std::{collections::BTreeMap, fmt::Debug};
+use clap::{App, Arg, SubCommand};
+use rand::Rng;
+use std::{cell::RefCell, collections::BTreeMap, fmt::Debug, rc::Rc};
 
 // Comments in the following form are used for code-block generation in the consensus report:
 //    "// -- BEGIN FILE name --"
 //    "// -- END FILE --"
 // Do not modify definitions without changing the report as well :)
 
-mod base_types;
-mod data_sync;
-mod node;
-mod pacemaker;
-mod record;
-mod record_store;
-mod simulated_context;
-mod smr_context;
-
-use bft_simulator_runtime::{
-    base_types::*, simulator, ActiveRound, ConsensusNode, DataSyncNode, EpochConfiguration,
-    NodeUpdateActions,
+mod participation;
+mod round_table;
+mod round_timing;
+
+use bft_simulator_runtime::{base_types::*, experiments, schema, simulator, trace};
+
+use librabft_simulator::{
+    auditor, base_types, byzantine, data_sync, leader_election, node, pacemaker, record,
+    simulated_context, visualisation,
 };
 
+use auditor::Auditor;
 use base_types::*;
+use byzantine::{ByzantineConfig, ByzantineFault};
 use data_sync::*;
+use leader_election::LeaderElection;
 use node::NodeState;
-use simulated_context::SimulatedContext;
+use simulated_context::{CommandGenerator, SimulatedContext};
+use visualisation::SimulatorVisualisation;
+
+#[cfg(test)]
+#[path = "unit_tests/simulation_tests.rs"]
+mod simulation_tests;
 
 fn main() {
     let args = get_arguments();
+    if args.print_schema {
+        println!("{}", schema::to_json(&schema::current()));
+        return;
+    }
 
     env_logger::init();
-    let context_factory =
-        |author, num_nodes| SimulatedContext::new(author, num_nodes, args.commands_per_epoch);
+    // Commands and network delays are derived from the same seed, so that a run can be replayed
+    // from the seed that it logs.
+    let seed = args.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    warn!("Seed: {}", seed);
+    let command_generator = CommandGenerator::new(seed);
+    assert!(
+        args.shards == 1 || args.observers == 0,
+        "Observers are not supported with shards"
+    );
+    assert!(
+        args.observers < args.nodes,
+        "At least one node must have voting rights"
+    );
+    // The last nodes are observers without voting rights.
+    let num_validators = args.nodes - args.observers;
+    let shard_assignment = simulator::ShardAssignment::contiguous(args.nodes, args.shards);
+    let context_factory = |author: Author, _num_nodes| {
+        let mut context = SimulatedContext::new(author, num_validators, args.commands_per_epoch)
+            .with_command_generator(command_generator)
+            .with_command_deduplication(args.deduplicate_commands)
+            .with_warm_start(args.warm_start_commands);
+        if let Some((_, cost)) = args.execution_costs.iter().find(|(x, _)| *x == author) {
+            context = context.with_execution_cost(*cost);
+        }
+        if args.shards > 1 {
+            let shard = shard_assignment.shard(author).unwrap();
+            context = context.with_validators(shard_assignment.members(shard));
+        }
+        if let Some(lag) = args.reinclusion_lag {
+            context = context.with_command_reinclusion(lag);
+        }
+        if args.aggregated_signatures {
+            context = context.with_aggregated_signatures();
+        }
+        if args.leader_election == LeaderElection::Reputation {
+            context = context.with_participation_window(args.reputation_window);
+        }
+        match args.max_block_bytes {
+            Some(max_block_bytes) => context.with_max_block_bytes(max_block_bytes),
+            None => context,
+        }
+    };
     let node_factory = |author: Author, context: &SimulatedContext, clock: NodeTime| {
-        NodeState::new(
+        let node = NodeState::new(
             author,
             context.last_committed_state(),
             clock,
@@ -55,46 +108,402 @@ fn main() {
             args.gamma,
             args.lambda,
             context,
-        )
+        );
+        let node = match args.vote_wait_timeout {
+            Some(timeout) => node.with_vote_wait_timeout(timeout),
+            None => node,
+        };
+        let node = match args.proposer_timeout {
+            Some(timeout) => node.with_proposer_timeout(timeout),
+            None => node,
+        };
+        let node = match args.follower_timeout {
+            Some(timeout) => node.with_follower_timeout(timeout),
+            None => node,
+        };
+        let node = if args.certified_block_in_timeouts {
+            node.with_certified_block_in_timeouts()
+        } else {
+            node
+        };
+        let node = match args.pipeline_depth {
+            Some(depth) => node.with_pipeline_depth(depth),
+            None => node,
+        };
+        node.with_unknown_author_policy(args.unknown_author_policy)
+            .with_leader_election(args.leader_election)
     };
-    let delay_distribution = simulator::RandomDelay::new(args.mean, args.variance);
-    let mut sim = simulator::Simulator::<
-        NodeState,
-        SimulatedContext,
-        DataSyncNotification,
-        DataSyncRequest,
-        DataSyncResponse,
-    >::new(
+    let byzantine_config = byzantine_config(&args);
+    warn!(
+        "Byzantine nodes: {:?}",
+        byzantine_config.byzantine_authors()
+    );
+    let mut delay_distribution = simulator::RandomDelay::new(args.mean, args.variance);
+    if let Some(min_delay) = args.min_delay {
+        delay_distribution = delay_distribution.with_min_delay(min_delay);
+    }
+    if let Some(max_delay) = args.max_delay {
+        delay_distribution = delay_distribution.with_max_delay(max_delay);
+    }
+    let sim = byzantine_config.make_simulator(
         args.nodes,
         delay_distribution,
         context_factory,
         node_factory,
     );
-    let contexts = sim.loop_until(
+    let sim = sim.with_low_priority_response_delay(args.low_priority_response_delay);
+    let sim = if args.shards > 1 {
+        warn!("Shards: {:?}", shard_assignment);
+        sim.with_shard_assignment(shard_assignment.clone(), args.cross_shard_delay)
+    } else {
+        sim
+    };
+    let sim = match args.node_cpu_budget {
+        Some(ops_per_ms) => sim.with_node_cpu_budget(ops_per_ms),
+        None => sim,
+    };
+    let mut sim = sim.with_seed(seed);
+    let auditor = Rc::new(RefCell::new(Auditor::new()));
+    sim.add_observer(auditor.clone());
+    let vote_tracker = Rc::new(RefCell::new(participation::VoteTracker::new()));
+    sim.add_observer(vote_tracker.clone());
+    let round_timings = Rc::new(RefCell::new(round_timing::RoundTimingObserver::new()));
+    sim.add_observer(round_timings.clone());
+    if let Some(budget) = args.trace_budget {
+        sim.enable_bounded_trace(
+            trace::BoundedTrace::new(budget, 1, /* window */ 1000)
+                .with_trigger(Box::new(trace::RoundStall::new(1000)))
+                .with_trigger(Box::new(trace::TimeoutStreak::new(10)))
+                .with_trigger(Box::new(trace::FaultSpike::new(100, 100)))
+                .with_trigger(Box::new(trace::InvariantWarning::new()))
+                .with_trigger(Box::new(trace::RejectionSpike::new(10, 1000))),
+        );
+    }
+    if let Some(eclipse) = args.eclipse {
+        sim.schedule_eclipse(
+            eclipse.victim,
+            eclipse.attacker,
+            simulator::GlobalTime(eclipse.from),
+            simulator::GlobalTime(eclipse.until),
+        );
+    }
+    if args.first_commit {
+        let config = experiments::FirstCommitConfig::new(num_validators)
+            .with_max_clock(simulator::GlobalTime(args.max_clock));
+        println!("{}", experiments::time_to_first_commit(&mut sim, config));
+        return;
+    }
+    let result = sim.loop_until(
         simulator::GlobalTime(args.max_clock),
-        args.output_data_files,
+        args.output_data_files.clone(),
     );
+    warn!("Configuration fingerprint: {:016x}", result.fingerprint);
+    println!("{}", result.summary_string());
+    if let Some(path) = &args.output_data_files {
+        result
+            .summary
+            .write(std::path::Path::new(path))
+            .expect("Writing did not succeed");
+    }
     warn!("Commands executed per node: {:#?}", {
-        let x: Vec<_> = contexts
+        let x: Vec<_> = result
+            .contexts
             .iter()
             .map(|context| context.committed_history().len())
             .collect();
         x
     });
-    info!("SMR contexts: {:#?}", contexts);
+    warn!("Duplicate commands skipped per node: {:?}", {
+        let x: Vec<_> = result
+            .contexts
+            .iter()
+            .map(|context| context.num_duplicate_commands())
+            .collect();
+        x
+    });
+    info!("SMR contexts: {:#?}", result.contexts);
+    warn!(
+        "Nodes with divergent execution states: {:?}",
+        auditor.borrow().divergent_authors()
+    );
+    warn!("Oversized blocks rejected per node: {:?}", {
+        let x: Vec<_> = sim
+            .nodes_iter()
+            .map(|(_, node)| node.node().num_oversized_blocks())
+            .collect();
+        x
+    });
+    let epoch_config_mismatches: Vec<_> = sim
+        .nodes_iter()
+        .map(|(_, node)| node.node().num_epoch_config_mismatches())
+        .collect();
+    if epoch_config_mismatches.iter().any(|n| *n > 0) {
+        error!(
+            "Blocks rejected for an epoch configuration mismatch per node: {:?}",
+            epoch_config_mismatches
+        );
+    }
+    warn!(
+        "Received proposals extending a stale parent per node: {:?}",
+        {
+            let x: Vec<_> = sim
+                .nodes_iter()
+                .map(|(_, node)| node.node().num_stale_proposals())
+                .collect();
+            x
+        }
+    );
+    if args.pipeline_depth.is_some() {
+        warn!("Proposals stalled by a full pipeline per node: {:?}", {
+            let x: Vec<_> = sim
+                .nodes_iter()
+                .map(|(_, node)| node.node().num_pipeline_stalls())
+                .collect();
+            x
+        });
+    }
+    warn!("Requests refused to non-validators per node: {:?}", {
+        let x: Vec<_> = sim
+            .nodes_iter()
+            .map(|(_, node)| node.refused_requests())
+            .collect();
+        x
+    });
+    let qc_sizes: Vec<_> = sim
+        .nodes_iter()
+        .filter_map(|(_, node)| {
+            let qc = node.node().record_store().highest_quorum_certificate()?;
+            let aggregate = qc.aggregate(node.node().configuration()).ok()?;
+            Some((qc.wire_size(), aggregate.wire_size()))
+        })
+        .collect();
+    warn!(
+        "Wire size of the highest QC per node, with per-vote and aggregated signatures: {:?}",
+        qc_sizes
+    );
+    warn!("Record store statistics per node: {:#?}", {
+        let x: Vec<_> = sim
+            .nodes_iter()
+            .map(|(_, node)| node.node().record_store().stats())
+            .collect();
+        x
+    });
+    warn!("Orphaned blocks per node: {:#?}", {
+        let x: Vec<_> = sim
+            .nodes_iter()
+            .map(|(_, node)| node.node().orphaned_blocks().len())
+            .collect();
+        x
+    });
+    for (author, node) in sim.nodes_iter() {
+        if let Some(qc) = node.node().record_store().highest_commit_certificate() {
+            info!(
+                "Last commit certificate of {:?}:\n{}",
+                author,
+                sim.visualise_qc_chain(qc)
+            );
+        }
+    }
+    let active_round = sim.simulated_node(Author(0)).active_round();
+    let participation = participation::participation_table(
+        &sim,
+        &vote_tracker.borrow(),
+        Author(0),
+        Round(1)..active_round,
+    );
+    for row in &participation {
+        warn!("Participation: {:?}", row);
+    }
+    let rounds = round_table::round_table(&sim, Author(0));
+    let timeout_cost = round_table::timeout_cost(&rounds);
+    warn!(
+        "Timeout cost: {:?}, delta {:?}",
+        timeout_cost,
+        timeout_cost.delta()
+    );
+    if !args.execution_costs.is_empty() {
+        warn!(
+            "Vote lag per node: {:#?}",
+            round_timings.borrow().lag_attributions(/* tolerance */ 5)
+        );
+    }
+    if let Some(trace) = sim.bounded_trace() {
+        warn!(
+            "Bounded trace: {} records, sampling 1/{}, anomalies: {:?}",
+            trace.len(),
+            trace.sample_every(),
+            trace.anomalies()
+        );
+    }
+    if let Some(path) = &args.output_data_files {
+        round_table::write_round_table(path, &rounds);
+        participation::write_participation_table(path, &participation);
+        if let Some(trace) = sim.bounded_trace() {
+            trace
+                .write(std::path::Path::new(path))
+                .expect("Writing did not succeed");
+        }
+    }
+}
+
+/// Collect the faults given on the command line.
+fn byzantine_config(args: &CliArguments) -> ByzantineConfig {
+    let mut config = ByzantineConfig::new();
+    for (author, fault) in &args.byzantine_faults {
+        config = config.with_fault(*author, fault.clone());
+    }
+    for index in 0..args.nodes {
+        let author = Author(index);
+        // The last nodes simulate a divergent implementation.
+        if index + args.buggy_nodes >= args.nodes {
+            config = config.with_fault(author, ByzantineFault::DivergentExecution);
+        }
+        // The first nodes simulate Byzantine leaders that ignore the block size limit.
+        if index < args.block_stuffing_nodes {
+            config = config.with_fault(
+                author,
+                ByzantineFault::PadCommands(args.block_stuffing_bytes),
+            );
+        }
+    }
+    if let Some(eclipse) = args.eclipse {
+        config = config.with_fault(
+            eclipse.attacker,
+            ByzantineFault::StaleFeed {
+                victim: eclipse.victim,
+                lag: args.stale_feed_lag,
+            },
+        );
+    }
+    config
+}
+
+/// Parse "author:fault" where fault is one of "equivocate=victim", "censor=victim",
+/// "withhold_votes", or "lie_about_certified_round".
+fn parse_byzantine_fault(value: &str) -> (Author, ByzantineFault) {
+    let fields: Vec<_> = value.split(':').map(str::trim).collect();
+    assert_eq!(fields.len(), 2, "Expected author:fault");
+    let author = Author(fields[0].parse::<usize>().unwrap());
+    let fault = match fields[1] {
+        fault if fault.starts_with("equivocate=") => ByzantineFault::Equivocate {
+            victims: vec![Author(
+                fault["equivocate=".len()..].parse::<usize>().unwrap(),
+            )]
+            .into_iter()
+            .collect(),
+        },
+        "withhold_votes" => ByzantineFault::WithholdVotes,
+        "lie_about_certified_round" => ByzantineFault::LieAboutCertifiedRound,
+        fault if fault.starts_with("censor=") => {
+            ByzantineFault::Censor(Author(fault["censor=".len()..].parse::<usize>().unwrap()))
+        }
+        fault => panic!("Unknown Byzantine fault: {}", fault),
+    };
+    (author, fault)
+}
+
+fn parse_execution_cost(value: &str) -> (Author, Duration) {
+    let fields: Vec<_> = value.split(':').map(str::trim).collect();
+    assert_eq!(fields.len(), 2, "Expected author:cost");
+    (
+        Author(fields[0].parse::<usize>().unwrap()),
+        fields[1].parse::<Duration>().unwrap(),
+    )
+}
+
+#[derive(Copy, Clone)]
+struct Eclipse {
+    victim: Author,
+    attacker: Author,
+    from: i64,
+    until: i64,
+}
+
+impl Eclipse {
+    /// Parse "victim,attacker,from,until", where victim and attacker are distinct nodes among
+    /// the first `num_nodes` and the time window is not empty.
+    fn parse(value: &str, num_nodes: usize) -> std::result::Result<Eclipse, String> {
+        let fields: Vec<_> = value.split(',').map(str::trim).collect();
+        if fields.len() != 4 {
+            return Err(format!(
+                "Expected victim,attacker,from,until, got {:?}",
+                value
+            ));
+        }
+        let parse_author = |field: &str| match field.parse::<usize>() {
+            Ok(index) if index < num_nodes => Ok(Author(index)),
+            _ => Err(format!(
+                "Expected a node index below {}, got {:?}",
+                num_nodes, field
+            )),
+        };
+        let parse_time = |field: &str| {
+            field
+                .parse::<i64>()
+                .map_err(|_| format!("Expected a time, got {:?}", field))
+        };
+        let eclipse = Eclipse {
+            victim: parse_author(fields[0])?,
+            attacker: parse_author(fields[1])?,
+            from: parse_time(fields[2])?,
+            until: parse_time(fields[3])?,
+        };
+        if eclipse.victim == eclipse.attacker {
+            return Err("The victim of an eclipse cannot be its attacker".to_string());
+        }
+        if eclipse.from < 0 || eclipse.from >= eclipse.until {
+            return Err(format!(
+                "Expected 0 <= from < until, got {}..{}",
+                eclipse.from, eclipse.until
+            ));
+        }
+        Ok(eclipse)
+    }
 }
 
 struct CliArguments {
     max_clock: i64,
     mean: f64,
     variance: f64,
+    min_delay: Option<Duration>,
+    max_delay: Option<Duration>,
     nodes: usize,
+    observers: usize,
+    unknown_author_policy: UnknownAuthorPolicy,
+    leader_election: LeaderElection,
+    reputation_window: usize,
+    low_priority_response_delay: Duration,
+    shards: usize,
+    cross_shard_delay: Duration,
+    node_cpu_budget: Option<f64>,
+    trace_budget: Option<usize>,
+    pipeline_depth: Option<usize>,
+    execution_costs: Vec<(Author, Duration)>,
     commands_per_epoch: usize,
     target_commit_interval: Duration,
     delta: Duration,
     gamma: f64,
     lambda: f64,
+    vote_wait_timeout: Option<Duration>,
+    proposer_timeout: Option<Duration>,
+    follower_timeout: Option<Duration>,
+    certified_block_in_timeouts: bool,
+    buggy_nodes: usize,
+    reinclusion_lag: Option<usize>,
+    deduplicate_commands: bool,
+    warm_start_commands: usize,
+    max_block_bytes: Option<usize>,
+    aggregated_signatures: bool,
+    block_stuffing_nodes: usize,
+    block_stuffing_bytes: usize,
+    eclipse: Option<Eclipse>,
+    stale_feed_lag: Duration,
+    byzantine_faults: Vec<(Author, ByzantineFault)>,
     output_data_files: Option<String>,
+    seed: Option<u64>,
+    print_schema: bool,
+    /// Stop at the first commit of a quorum of validators, see `experiments::time_to_first_commit`.
+    first_commit: bool,
 }
 
 fn get_arguments() -> CliArguments {
@@ -118,12 +527,99 @@ fn get_arguments() -> CliArguments {
                 .help("The variance of the normal distribution of the network delay")
                 .default_value("4.0"),
         )
+        .arg(
+            Arg::with_name("min_delay")
+                .long("min_delay")
+                .help("Lower bound on sampled network delays")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("max_delay")
+                .long("max_delay")
+                .help("Upper bound on sampled network delays")
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("nodes")
                 .long("nodes")
                 .help("The number of nodes to simulate")
                 .default_value("3"),
         )
+        .arg(
+            Arg::with_name("observers")
+                .long("observers")
+                .help("The number of simulated nodes without voting rights, taken last")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("unknown_author_policy")
+                .long("unknown_author_policy")
+                .help("How validators answer the requests of authors without voting rights")
+                .possible_values(&["serve", "deprioritize", "reject"])
+                .default_value("serve"),
+        )
+        .arg(
+            Arg::with_name("leader_election")
+                .long("leader_election")
+                .help("How the leader of each round is chosen")
+                .possible_values(&["hashed", "round_robin", "reputation"])
+                .default_value("hashed"),
+        )
+        .arg(
+            Arg::with_name("reputation_window")
+                .long("reputation_window")
+                .help("Number of latest commits over which the reputation election measures participation")
+                .default_value("10"),
+        )
+        .arg(
+            Arg::with_name("shards")
+                .long("shards")
+                .help("The number of shards running independent consensus instances")
+                .default_value("1"),
+        )
+        .arg(
+            Arg::with_name("cross_shard_delay")
+                .long("cross_shard_delay")
+                .help("Additional delay of the messages between shards")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("low_priority_response_delay")
+                .long("low_priority_response_delay")
+                .help("Additional delay of the responses to deprioritized requests")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("execution_cost")
+                .long("execution_cost")
+                .help("Time for a node to execute a command, given as author:cost")
+                .takes_value(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("pipeline_depth")
+                .long("pipeline_depth")
+                .help("Maximal number of uncommitted commands that a leader builds upon")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("trace_budget")
+                .long("trace_budget")
+                .help(
+                    "Record a downsampled trace of about this many records, with full detail \
+                     around anomalies",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("node_cpu_budget")
+                .long("node_cpu_budget")
+                .help(
+                    "Number of records that nodes handle per millisecond (processing is \
+                     instantaneous if not given)",
+                )
+                .takes_value(true),
+        )
         .arg(
             Arg::with_name("commands_per_epoch")
                 .long("commands_per_epoch")
@@ -154,11 +650,124 @@ fn get_arguments() -> CliArguments {
                 .help("Coefficient to control the frequency of query-all actions")
                 .default_value("0.5"),
         )
+        .arg(
+            Arg::with_name("vote_wait_timeout")
+                .long("vote_wait_timeout")
+                .help("Maximal time that a leader waits for votes (unlimited if not given)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("proposer_timeout")
+                .long("proposer_timeout")
+                .help("Maximal duration of the rounds led by a node (round duration if not given)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("follower_timeout")
+                .long("follower_timeout")
+                .help("Maximal time that a follower waits for a proposal (round duration if not given)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("certified_block_in_timeouts")
+                .long("certified_block_in_timeouts")
+                .help("Send the block of the highest QC along with timeouts"),
+        )
+        .arg(
+            Arg::with_name("reinclusion_lag")
+                .long("reinclusion_lag")
+                .help(
+                    "Number of proposals after which leaders propose again their commands that \
+                     are still not committed (never if not given)",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("warm_start_commands")
+                .long("warm_start_commands")
+                .help("Number of generated commands already committed when the simulation starts")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("deduplicate_commands")
+                .long("deduplicate_commands")
+                .help("Skip the commands that are committed twice instead of executing them again"),
+        )
+        .arg(
+            Arg::with_name("buggy_nodes")
+                .long("buggy_nodes")
+                .help("The number of nodes that compute execution states differently")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("max_block_bytes")
+                .long("max_block_bytes")
+                .help("Maximal size of the payload of a block (unlimited if not given)")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("aggregated_signatures")
+                .long("aggregated_signatures")
+                .help("Send and verify QCs with one aggregated signature instead of one per vote"),
+        )
+        .arg(
+            Arg::with_name("block_stuffing_nodes")
+                .long("block_stuffing_nodes")
+                .help("The number of Byzantine nodes that propose oversized blocks")
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("block_stuffing_bytes")
+                .long("block_stuffing_bytes")
+                .help("Bytes added to the payload of blocks by Byzantine nodes")
+                .default_value("1000000"),
+        )
+        .arg(
+            Arg::with_name("eclipse")
+                .long("eclipse")
+                .help(
+                    "Eclipse a victim node during a time window, given as victim,attacker,from,until: \
+                     only the attacker reaches the victim, and feeds it a stale view",
+                )
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("stale_feed_lag")
+                .long("stale_feed_lag")
+                .help("Age of the view fed by the attacker of an eclipse to the victim")
+                .default_value("500"),
+        )
+        .arg(
+            Arg::with_name("byzantine")
+                .long("byzantine")
+                .help(
+                    "Fault of a Byzantine node, given as author:fault where fault is one of \
+                     equivocate=victim, censor=victim, withhold_votes, or \
+                     lie_about_certified_round",
+                )
+                .takes_value(true)
+                .multiple(true),
+        )
         .arg(Arg::with_name("create_csv").long("create_csv").help(
             "If given this argument, csv files will be generated with data on the simulation"
         ).takes_value(true))
+        .arg(
+            Arg::with_name("seed")
+                .long("seed")
+                .help("Seed of the random number generator (random if not given)")
+                .takes_value(true),
+        )
+        .subcommand(
+            SubCommand::with_name("schema")
+                .about("Print the descriptors of the csv files as JSON, then exit"),
+        )
+        .subcommand(SubCommand::with_name("first_commit").about(
+            "Stop once a quorum of validators committed a block, at max_clock at the latest, \
+             and report the time, rounds and messages used",
+        ))
         .get_matches();
 
+    let nodes = matches.value_of("nodes").unwrap().parse::<usize>().unwrap();
     CliArguments {
         max_clock: matches
             .value_of("max_clock")
@@ -171,7 +780,62 @@ fn get_arguments() -> CliArguments {
             .unwrap()
             .parse::<f64>()
             .unwrap(),
-        nodes: matches.value_of("nodes").unwrap().parse::<usize>().unwrap(),
+        min_delay: matches
+            .value_of("min_delay")
+            .map(|x| x.parse::<Duration>().unwrap()),
+        max_delay: matches
+            .value_of("max_delay")
+            .map(|x| x.parse::<Duration>().unwrap()),
+        nodes,
+        observers: matches
+            .value_of("observers")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        unknown_author_policy: match matches.value_of("unknown_author_policy").unwrap() {
+            "deprioritize" => UnknownAuthorPolicy::Deprioritize,
+            "reject" => UnknownAuthorPolicy::Reject,
+            _ => UnknownAuthorPolicy::Serve,
+        },
+        leader_election: match matches.value_of("leader_election").unwrap() {
+            "round_robin" => LeaderElection::RoundRobin,
+            "reputation" => LeaderElection::Reputation,
+            _ => LeaderElection::Hashed,
+        },
+        reputation_window: matches
+            .value_of("reputation_window")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        low_priority_response_delay: matches
+            .value_of("low_priority_response_delay")
+            .unwrap()
+            .parse::<Duration>()
+            .unwrap(),
+        shards: matches
+            .value_of("shards")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        cross_shard_delay: matches
+            .value_of("cross_shard_delay")
+            .unwrap()
+            .parse::<Duration>()
+            .unwrap(),
+        trace_budget: matches
+            .value_of("trace_budget")
+            .map(|x| x.parse::<usize>().unwrap()),
+        execution_costs: matches
+            .values_of("execution_cost")
+            .map_or_else(Vec::new, |values| {
+                values.map(parse_execution_cost).collect()
+            }),
+        pipeline_depth: matches
+            .value_of("pipeline_depth")
+            .map(|x| x.parse::<usize>().unwrap()),
+        node_cpu_budget: matches
+            .value_of("node_cpu_budget")
+            .map(|x| x.parse::<f64>().unwrap()),
         commands_per_epoch: matches
             .value_of("commands_per_epoch")
             .unwrap()
@@ -189,6 +853,62 @@ fn get_arguments() -> CliArguments {
             .unwrap(),
         gamma: matches.value_of("gamma").unwrap().parse::<f64>().unwrap(),
         lambda: matches.value_of("lambda").unwrap().parse::<f64>().unwrap(),
+        vote_wait_timeout: matches
+            .value_of("vote_wait_timeout")
+            .map(|x| x.parse::<Duration>().unwrap()),
+        proposer_timeout: matches
+            .value_of("proposer_timeout")
+            .map(|x| x.parse::<Duration>().unwrap()),
+        follower_timeout: matches
+            .value_of("follower_timeout")
+            .map(|x| x.parse::<Duration>().unwrap()),
+        certified_block_in_timeouts: matches.is_present("certified_block_in_timeouts"),
+        buggy_nodes: matches
+            .value_of("buggy_nodes")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        reinclusion_lag: matches
+            .value_of("reinclusion_lag")
+            .map(|x| x.parse::<usize>().unwrap()),
+        deduplicate_commands: matches.is_present("deduplicate_commands"),
+        warm_start_commands: matches
+            .value_of("warm_start_commands")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        max_block_bytes: matches
+            .value_of("max_block_bytes")
+            .map(|x| x.parse::<usize>().unwrap()),
+        aggregated_signatures: matches.is_present("aggregated_signatures"),
+        block_stuffing_nodes: matches
+            .value_of("block_stuffing_nodes")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        block_stuffing_bytes: matches
+            .value_of("block_stuffing_bytes")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap(),
+        eclipse: matches.value_of("eclipse").map(|value| {
+            Eclipse::parse(value, nodes).unwrap_or_else(|error| {
+                clap::Error::with_description(&error, clap::ErrorKind::InvalidValue).exit()
+            })
+        }),
+        stale_feed_lag: matches
+            .value_of("stale_feed_lag")
+            .unwrap()
+            .parse::<Duration>()
+            .unwrap(),
+        byzantine_faults: matches
+            .values_of("byzantine")
+            .map_or_else(Vec::new, |values| {
+                values.map(parse_byzantine_fault).collect()
+            }),
         output_data_files: matches.value_of("create_csv").map(|x| x.to_string()),
+        seed: matches.value_of("seed").map(|x| x.parse::<u64>().unwrap()),
+        print_schema: matches.subcommand_matches("schema").is_some(),
+        first_commit: matches.subcommand_matches("first_commit").is_some(),
     }
 }
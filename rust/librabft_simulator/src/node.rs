@@ -3,6 +3,8 @@
 
 use super::*;
 use base_types::*;
+use byzantine::ByzantineBehavior;
+use leader_election::LeaderElection;
 use pacemaker::*;
 use record::*;
 use record_store::*;
@@ -10,7 +12,8 @@ use smr_context::SMRContext;
 
 use std::{
     cmp::{max, min},
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
+    convert::TryFrom,
 };
 
 #[cfg(test)]
@@ -38,9 +41,38 @@ pub struct NodeState {
     tracker: CommitTracker,
     /// Record stores from previous epochs.
     past_record_stores: HashMap<EpochId, RecordStoreState>,
+    /// Features added to the protocol of the report.
+    extensions: NodeExtensions,
 }
 // -- END FILE --
 
+/// Configuration and state of the features that the node adds to the protocol of the report.
+#[derive(Debug)]
+struct NodeExtensions {
+    /// Maximal time that a leader waits for votes on its proposal, if any.
+    vote_wait_timeout: Option<Duration>,
+    /// Time of the latest update.
+    latest_update_time: NodeTime,
+    /// Blocks that were not committed, in the order they were found.
+    orphaned_blocks: Vec<(BlockHash, Round)>,
+    /// Whether notifications carrying timeouts also carry the block of the highest QC.
+    bundle_certified_block_with_timeouts: bool,
+    /// How to answer the requests of authors outside the validator set.
+    unknown_author_policy: UnknownAuthorPolicy,
+    /// Faults applied by this node, if it is Byzantine.
+    byzantine_behavior: ByzantineBehavior,
+    /// Rounds for which we created a timeout, across epochs.
+    timed_out_rounds: BTreeSet<(EpochId, Round)>,
+    /// How the leader of each round is chosen, in every epoch.
+    leader_election: LeaderElection,
+    /// Maximal number of uncommitted commands on the branch of our proposals, if any.
+    pipeline_depth: Option<usize>,
+    /// Number of blocks proposed without a new command because the pipeline was full.
+    num_pipeline_stalls: usize,
+    /// Round changes made by the latest update.
+    round_actions: RoundActions,
+}
+
 // -- BEGIN FILE commit_tracker --
 #[derive(Debug)]
 pub struct CommitTracker {
@@ -67,6 +99,7 @@ impl CommitTracker {
 }
 
 impl NodeState {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         local_author: Author,
         initial_state: State,
@@ -95,7 +128,132 @@ impl NodeState {
             latest_query_all_time: node_time,
             tracker,
             past_record_stores: HashMap::new(),
+            extensions: NodeExtensions {
+                vote_wait_timeout: None,
+                latest_update_time: node_time,
+                orphaned_blocks: Vec::new(),
+                bundle_certified_block_with_timeouts: false,
+                unknown_author_policy: UnknownAuthorPolicy::Serve,
+                byzantine_behavior: ByzantineBehavior::default(),
+                timed_out_rounds: BTreeSet::new(),
+                leader_election: LeaderElection::default(),
+                pipeline_depth: None,
+                num_pipeline_stalls: 0,
+                round_actions: RoundActions::default(),
+            },
+        }
+    }
+
+    /// Give up forming a QC on our own proposal if not enough votes arrived within the given
+    /// duration after proposing, and time out the round instead.
+    pub fn with_vote_wait_timeout(mut self, vote_wait_timeout: Duration) -> Self {
+        self.extensions.vote_wait_timeout = Some(vote_wait_timeout);
+        self
+    }
+
+    /// Time out the rounds that we lead after the given duration, while waiting for votes.
+    pub fn with_proposer_timeout(mut self, proposer_timeout: Duration) -> Self {
+        self.pacemaker = self.pacemaker.with_proposer_timeout(proposer_timeout);
+        self
+    }
+
+    /// Time out the rounds that we do not lead after the given duration, while waiting for a
+    /// proposal.
+    pub fn with_follower_timeout(mut self, follower_timeout: Duration) -> Self {
+        self.pacemaker = self.pacemaker.with_follower_timeout(follower_timeout);
+        self
+    }
+
+    /// Behave as a Byzantine node toward `victim`: send it the view that this node had `lag`
+    /// ago instead of the current one.
+    pub fn with_stale_feed(mut self, victim: Author, lag: Duration) -> Self {
+        self.extensions.byzantine_behavior.stale_feed = Some(StaleFeed::new(victim, lag));
+        self
+    }
+
+    /// Send the block certified by our highest QC together with our timeouts, so that nodes
+    /// missing the block can still accept the QC and the timeouts right away.
+    pub fn with_certified_block_in_timeouts(mut self) -> Self {
+        self.extensions.bundle_certified_block_with_timeouts = true;
+        self
+    }
+
+    /// Decide how to answer the requests of authors without voting rights in the current epoch.
+    pub fn with_unknown_author_policy(mut self, policy: UnknownAuthorPolicy) -> Self {
+        self.extensions.unknown_author_policy = policy;
+        self
+    }
+
+    /// Choose the leaders of the current epoch and of the following ones.
+    pub fn with_leader_election(mut self, leader_election: LeaderElection) -> Self {
+        self.extensions.leader_election = leader_election;
+        self.record_store = self.record_store.with_leader_election(leader_election);
+        self
+    }
+
+    /// Only propose a new command when fewer than `depth` commands are uncommitted on the branch
+    /// of our proposal. Otherwise, propose an empty command, see `Command::empty`.
+    pub fn with_pipeline_depth(mut self, depth: usize) -> Self {
+        assert!(depth > 0, "The pipeline depth must be positive");
+        self.extensions.pipeline_depth = Some(depth);
+        self
+    }
+
+    pub fn num_pipeline_stalls(&self) -> usize {
+        self.extensions.num_pipeline_stalls
+    }
+
+    pub fn unknown_author_policy(&self) -> UnknownAuthorPolicy {
+        self.extensions.unknown_author_policy
+    }
+
+    /// Whether the given author has voting rights in the current epoch.
+    pub fn is_validator(&self, author: Author) -> bool {
+        self.record_store.configuration().weight(&author) > 0
+    }
+
+    /// Apply the given faults, see `ByzantineConfig`.
+    pub fn with_byzantine_behavior(mut self, behavior: ByzantineBehavior) -> Self {
+        self.extensions.byzantine_behavior = behavior;
+        self
+    }
+
+    /// The block to send to the given receiver instead of the given one, if it is our proposal
+    /// and the receiver is a victim of our equivocation.
+    pub fn conflicting_proposal_for(&self, receiver: Author, block: &Block) -> Option<Block> {
+        if block.author != self.local_author {
+            return None;
+        }
+        self.extensions
+            .byzantine_behavior
+            .conflicting_block(receiver, block)
+    }
+
+    /// Whether we send the given vote of ours, unless we withhold it.
+    pub fn sends_vote(&self, vote: &Vote) -> bool {
+        match self.record_store.block(vote.certified_block_hash) {
+            Some(block) => self
+                .extensions
+                .byzantine_behavior
+                .sends_votes_for(block.author),
+            None => true,
+        }
+    }
+
+    pub fn bundles_certified_block_with_timeouts(&self) -> bool {
+        self.extensions.bundle_certified_block_with_timeouts
+    }
+
+    /// The block to send instead of the given one, if it is ours and we stuff our blocks.
+    pub fn stuffed_block(&self, block: &Block) -> Option<Block> {
+        if block.author != self.local_author {
+            return None;
         }
+        self.extensions.byzantine_behavior.stuffed_block(block)
+    }
+
+    pub fn stale_feed(&self) -> Option<&StaleFeed> {
+        self.extensions.byzantine_behavior.stale_feed.as_ref()
     }
 
     pub fn epoch_id(&self) -> EpochId {
@@ -110,13 +268,96 @@ impl NodeState {
         &self.record_store
     }
 
+    /// Voting rights and protocol parameters of the current epoch.
+    pub fn configuration(&self) -> &EpochConfiguration {
+        self.record_store.configuration()
+    }
+
+    /// The QC of the current epoch with the given hash, if known.
+    pub fn quorum_certificate(&self, qc_hash: QuorumCertificateHash) -> Option<&QuorumCertificate> {
+        self.record_store.quorum_certificate(qc_hash)
+    }
+
+    /// Highest round voted so far and locked round, in the current epoch.
+    pub fn voting_constraints(&self) -> (Round, Round) {
+        (self.latest_voted_round, self.locked_round)
+    }
+
+    /// Restore the voting constraints of the current epoch, e.g. after a reboot.
+    pub fn restore_voting_constraints(&mut self, latest_voted_round: Round, locked_round: Round) {
+        self.latest_voted_round.max_update(latest_voted_round);
+        self.locked_round.max_update(locked_round);
+    }
+
+    /// See `RecordStoreState::commit_certificate_chain`.
+    pub fn commit_certificate_chain(&self, round: Round) -> Vec<&QuorumCertificate> {
+        self.record_store.commit_certificate_chain(round)
+    }
+
     pub fn record_store_at(&self, epoch_id: EpochId) -> Option<&RecordStore> {
         if epoch_id == self.epoch_id {
             return Some(&self.record_store);
         }
         self.past_record_stores
             .get(&epoch_id)
-            .map(|store| &*store as &RecordStore)
+            .map(|store| store as &RecordStore)
+    }
+
+    /// Whether this node has seen a QC for a state that differs from its own execution, which
+    /// denotes a divergent implementation on either side.
+    pub fn has_diverged(&self) -> bool {
+        self.record_store.num_state_mismatches() > 0
+            || self
+                .past_record_stores
+                .values()
+                .any(|store| store.num_state_mismatches() > 0)
+    }
+
+    /// Number of times that this node rejected a block for being too large, across all
+    /// epochs.
+    pub fn num_oversized_blocks(&self) -> usize {
+        self.record_store.num_oversized_blocks()
+            + self
+                .past_record_stores
+                .values()
+                .map(|store| store.num_oversized_blocks())
+                .sum::<usize>()
+    }
+
+    /// Number of proposals received on top of a QC older than our highest QC, across all
+    /// epochs.
+    pub fn num_stale_proposals(&self) -> usize {
+        self.record_store.num_stale_proposals()
+            + self
+                .past_record_stores
+                .values()
+                .map(|store| store.num_stale_proposals())
+                .sum::<usize>()
+    }
+
+    /// Equivocations detected by this node among the blocks of the current epoch.
+    pub fn equivocation_proofs(&self) -> &[EquivocationProof] {
+        self.record_store.equivocation_proofs()
+    }
+
+    /// Number of blocks that this node rejected for being proposed under another epoch
+    /// configuration, across all epochs.
+    pub fn num_epoch_config_mismatches(&self) -> usize {
+        self.record_store.num_epoch_config_mismatches()
+            + self
+                .past_record_stores
+                .values()
+                .map(|store| store.num_epoch_config_mismatches())
+                .sum::<usize>()
+    }
+
+    pub fn timed_out_rounds(&self) -> &BTreeSet<(EpochId, Round)> {
+        &self.extensions.timed_out_rounds
+    }
+
+    /// Blocks known to this node that lost to a committed block, across all epochs.
+    pub fn orphaned_blocks(&self) -> &[(BlockHash, Round)] {
+        &self.extensions.orphaned_blocks
     }
 
     pub fn pacemaker(&self) -> &Pacemaker {
@@ -133,6 +374,16 @@ impl NodeState {
         );
     }
 
+    /// Record the current view for the victim of the stale feed, if any.
+    fn update_stale_feed(&mut self, clock: NodeTime) {
+        if self.extensions.byzantine_behavior.stale_feed.is_some() {
+            let notification = self.create_bundled_notification();
+            if let Some(feed) = &mut self.extensions.byzantine_behavior.stale_feed {
+                feed.record(clock, notification);
+            }
+        }
+    }
+
     pub fn insert_network_record(
         &mut self,
         epoch_id: EpochId,
@@ -154,6 +405,40 @@ impl ActiveRound for NodeState {
     fn active_round(&self) -> Round {
         self.pacemaker.active_round()
     }
+
+    fn in_flight_rounds(&self) -> usize {
+        self.record_store
+            .num_rounds_with_blocks_after(self.record_store.highest_committed_round())
+    }
+
+    fn equivocation_rounds(&self) -> Vec<Round> {
+        self.equivocation_proofs()
+            .iter()
+            .map(|proof| proof.round)
+            .collect()
+    }
+
+    fn num_timeouts(&self) -> usize {
+        self.extensions.timed_out_rounds.len()
+    }
+
+    fn committed_round(&self) -> Option<Round> {
+        Some(self.record_store.highest_committed_round())
+    }
+
+    fn current_timeout(&self) -> Option<Duration> {
+        Some(self.pacemaker.active_round_duration())
+    }
+
+    fn round_actions(&self) -> RoundActions {
+        self.extensions.round_actions
+    }
+}
+
+impl ConsensusTimeout for NodeState {
+    fn set_consensus_timeout_hint(&mut self, hint: ConsensusTimeoutHint) {
+        self.pacemaker.set_delta(hint.0);
+    }
 }
 
 // -- BEGIN FILE process_pacemaker_actions --
@@ -161,7 +446,6 @@ impl NodeState {
     fn process_pacemaker_actions(
         &mut self,
         pacemaker_actions: PacemakerUpdateActions,
-        clock: NodeTime,
         smr_context: &mut SMRContext,
     ) -> NodeUpdateActions {
         let mut actions = NodeUpdateActions::new();
@@ -170,19 +454,14 @@ impl NodeState {
         actions.should_query_all = pacemaker_actions.should_query_all;
         actions.should_send = pacemaker_actions.should_send;
         if let Some(round) = pacemaker_actions.should_create_timeout {
-            self.record_store
-                .create_timeout(self.local_author, round, smr_context);
+            self.create_timeout(round, smr_context);
             // Prevent voting at a round for which we have created a timeout already.
             self.latest_voted_round.max_update(round);
+            self.extensions.round_actions.on_timeout = Some(round);
         }
-        if let Some(previous_qc_hash) = pacemaker_actions.should_propose_block {
-            self.record_store.propose_block(
-                self.local_author,
-                previous_qc_hash,
-                clock,
-                smr_context,
-            );
-        }
+        // The block itself is created by `create_block`.
+        self.extensions.round_actions.should_propose =
+            pacemaker_actions.should_propose_block.is_some();
         actions
     }
 }
@@ -191,6 +470,8 @@ impl NodeState {
 // -- BEGIN FILE consensus_node_impl --
 impl<Context: SMRContext> ConsensusNode<Context> for NodeState {
     fn update_node(&mut self, clock: NodeTime, smr_context: &mut Context) -> NodeUpdateActions {
+        self.extensions.latest_update_time = clock;
+        self.extensions.round_actions = RoundActions::default();
         // Update pacemaker state and process pacemaker actions (e.g., creating a timeout, proposing
         // a block).
         let pacemaker_actions = self.pacemaker.update_pacemaker(
@@ -200,36 +481,18 @@ impl<Context: SMRContext> ConsensusNode<Context> for NodeState {
             self.latest_query_all_time,
             clock,
         );
-        let mut actions = self.process_pacemaker_actions(pacemaker_actions, clock, smr_context);
+        let mut actions = self.process_pacemaker_actions(pacemaker_actions, smr_context);
         // Vote on a valid proposal block designated by the pacemaker, if any.
-        if let Some((block_hash, block_round, proposer)) =
-            self.record_store.proposed_block(&self.pacemaker)
-        {
-            // Enforce voting constraints.
-            if block_round > self.latest_voted_round
-                && self.record_store.previous_round(block_hash) >= self.locked_round
-            {
-                // Update the latest voted round.
-                self.latest_voted_round = block_round;
-                // Update the locked round.
-                self.locked_round = max(
-                    self.locked_round,
-                    self.record_store.second_previous_round(block_hash),
-                );
-                // Try to execute the command contained the a block and create a vote.
-                if self
-                    .record_store
-                    .create_vote(self.local_author, block_hash, smr_context)
-                {
-                    // Ask to notify and send our vote to the author of the block.
-                    actions.should_send = vec![proposer];
-                }
-            }
+        if let Some(proposer) = self.vote_on_proposed_block(smr_context) {
+            // Ask to notify and send our vote to the author of the block.
+            actions.should_send = vec![proposer];
         }
-        // Check if our last proposal has reached a quorum of votes and create a QC.
-        if self
-            .record_store
-            .check_for_new_quorum_certificate(self.local_author, smr_context)
+        // Check if our last proposal has reached a quorum of votes and create a QC, unless we
+        // stopped waiting for votes.
+        if !self.check_vote_wait_timeout(clock, smr_context, &mut actions)
+            && self
+                .record_store
+                .check_for_new_quorum_certificate(self.local_author, smr_context)
         {
             // Broadcast the QC to finish our work as a leader.
             actions.should_broadcast = true;
@@ -254,20 +517,152 @@ impl<Context: SMRContext> ConsensusNode<Context> for NodeState {
         if actions.should_query_all {
             self.latest_query_all_time = clock;
         }
+        self.update_stale_feed(clock);
         // Return desired actions to main handler.
         actions
     }
 }
 // -- END FILE --
 
+impl<Context: SMRContext> BlockProposer<Context> for NodeState {
+    type Block = Block;
+    type Record = Record;
+
+    fn create_block(&mut self, round: Round, smr_context: &mut Context) -> Option<Block> {
+        if PacemakerState::leader(&self.record_store, round) != self.local_author {
+            return None;
+        }
+        // Bundled QCs are inserted before the timeouts that they come with, so this is at least
+        // as fresh as the QC reported by any timeout that we accepted.
+        let previous_qc_hash = self.record_store.highest_quorum_certificate_hash();
+        let command = if self.is_pipeline_full() {
+            self.extensions.num_pipeline_stalls += 1;
+            Command::empty(self.local_author)
+        } else {
+            smr_context.fetch()?
+        };
+        let record = Record::make_block(
+            command,
+            self.extensions.latest_update_time,
+            previous_qc_hash,
+            round,
+            self.record_store.configuration().digest(),
+            self.local_author,
+        );
+        Some(Block::try_from(record).expect("make_block should create a block"))
+    }
+
+    fn send_block(&mut self, record: Record, smr_context: &mut Context) {
+        debug!("Inserting {:?}", record);
+        self.record_store.insert_network_record(record, smr_context);
+        // The leader votes for its own proposal in the update that created it.
+        self.vote_on_proposed_block(smr_context);
+    }
+}
+
+impl NodeState {
+    /// Vote on the proposal designated by the pacemaker, if the voting constraints allow it.
+    /// Return the author of the block if we voted.
+    fn vote_on_proposed_block(&mut self, smr_context: &mut SMRContext) -> Option<Author> {
+        let (block_hash, block_round, proposer) =
+            self.record_store.proposed_block(&self.pacemaker)?;
+        // Enforce voting constraints.
+        if block_round <= self.latest_voted_round
+            || self.record_store.previous_round(block_hash) < self.locked_round
+        {
+            return None;
+        }
+        // Update the latest voted round.
+        self.latest_voted_round = block_round;
+        // Update the locked round.
+        self.locked_round = max(
+            self.locked_round,
+            self.record_store.second_previous_round(block_hash),
+        );
+        // Try to execute the command contained the a block and create a vote.
+        if self
+            .record_store
+            .create_vote(self.local_author, block_hash, smr_context)
+        {
+            Some(proposer)
+        } else {
+            None
+        }
+    }
+
+    fn create_timeout(&mut self, round: Round, smr_context: &mut SMRContext) {
+        self.extensions
+            .timed_out_rounds
+            .insert((self.epoch_id, round));
+        if self.extensions.byzantine_behavior.lie_about_certified_round {
+            let timeout = Record::make_timeout(self.epoch_id, round, Round(0), self.local_author);
+            self.record_store
+                .insert_network_record(timeout, smr_context);
+        } else {
+            self.record_store
+                .create_timeout(self.local_author, round, smr_context);
+        }
+    }
+
+    /// Time out the round if we are the leader and the votes on our proposal did not arrive
+    /// within `vote_wait_timeout`. Return whether QC formation was abandoned.
+    fn check_vote_wait_timeout(
+        &mut self,
+        clock: NodeTime,
+        smr_context: &mut SMRContext,
+        actions: &mut NodeUpdateActions,
+    ) -> bool {
+        let vote_wait_timeout = match self.extensions.vote_wait_timeout {
+            Some(timeout) => timeout,
+            None => return false,
+        };
+        let (block_hash, round) = match self.record_store.proposed_block(&self.pacemaker) {
+            Some((block_hash, round, proposer)) if proposer == self.local_author => {
+                (block_hash, round)
+            }
+            _ => return false,
+        };
+        if self.record_store.has_timeout(self.local_author, round) {
+            return true;
+        }
+        if self.record_store.highest_quorum_certificate_round() >= round {
+            return false;
+        }
+        let deadline = self.record_store.block(block_hash).unwrap().time + vote_wait_timeout;
+        if clock < deadline {
+            actions.next_scheduled_update = min(actions.next_scheduled_update, deadline);
+            return false;
+        }
+        debug!(
+            "{:?}{:?} Abandoning the proposal of round {:?}",
+            self.local_author, clock, round
+        );
+        self.create_timeout(round, smr_context);
+        self.latest_voted_round.max_update(round);
+        actions.should_broadcast = true;
+        self.extensions.round_actions.on_timeout = Some(round);
+        true
+    }
+
+    /// Whether `pipeline_depth` commands are already uncommitted after our highest QC.
+    fn is_pipeline_full(&self) -> bool {
+        self.extensions.pipeline_depth.map_or(false, |depth| {
+            self.record_store.num_uncommitted_commands() >= depth
+        })
+    }
+}
+
 // -- BEGIN FILE process_commits --
 impl NodeState {
     pub fn process_commits(&mut self, smr_context: &mut SMRContext) {
-        // For all commits that have not been processed yet, according to the commit tracker..
-        for (round, state) in self
+        let commits = self
             .record_store
-            .committed_states_after(self.tracker.highest_committed_round)
-        {
+            .committed_states_after(self.tracker.highest_committed_round);
+        if !commits.is_empty() {
+            self.update_orphaned_blocks();
+        }
+        // For all commits that have not been processed yet, according to the commit tracker..
+        for (round, state) in commits {
             // .. deliver the committed state to the SMR layer, together with a commit certificate,
             // if any.
             if round == self.record_store.highest_committed_round() {
@@ -284,7 +679,8 @@ impl NodeState {
                     state.clone(),
                     new_epoch_id,
                     smr_context.configuration(&state),
-                );
+                )
+                .with_leader_election(self.extensions.leader_election);
                 let old_record_store = std::mem::replace(&mut self.record_store, new_record_store);
                 self.past_record_stores
                     .insert(self.epoch_id, old_record_store);
@@ -300,6 +696,19 @@ impl NodeState {
 }
 // -- END FILE --
 
+impl NodeState {
+    fn update_orphaned_blocks(&mut self) {
+        // Rounds up to the last processed commit were already checked.
+        let orphans = self
+            .record_store
+            .orphaned_blocks(self.tracker.highest_committed_round);
+        for orphan in orphans {
+            debug!("{:?} Found orphaned block: {:?}", self.local_author, orphan);
+            self.extensions.orphaned_blocks.push(orphan);
+        }
+    }
+}
+
 // -- BEGIN FILE commit_tracker_impl --
 #[derive(Debug)]
 pub struct CommitTrackerUpdateActions {
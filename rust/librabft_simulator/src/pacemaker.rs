@@ -1,11 +1,7 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
 
-use std::{
-    cmp::{max, min},
-    collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-};
+use std::cmp::{max, min};
 
 use super::*;
 use record_store::*;
@@ -53,6 +49,8 @@ pub trait Pacemaker: Debug {
     fn active_epoch(&self) -> EpochId;
     fn active_round(&self) -> Round;
     fn active_leader(&self) -> Option<Author>;
+    /// Maximal duration of the active round.
+    fn active_round_duration(&self) -> Duration;
 }
 // -- END FILE --
 
@@ -75,9 +73,22 @@ pub struct PacemakerState {
     gamma: f64,
     /// Coefficient to control the frequency of query-all actions.
     lambda: f64,
+    /// Timeouts of the phases of a round, if configured.
+    phase_timeouts: PhaseTimeouts,
 }
 // -- END FILE --
 
+/// Timeouts that replace the round duration for some phases of a round.
+#[derive(Default, Debug)]
+struct PhaseTimeouts {
+    /// Maximal duration of a round that we lead, while waiting for votes on our proposal.
+    proposer: Option<Duration>,
+    /// Maximal duration of a round that we do not lead, while waiting for its proposal.
+    follower: Option<Duration>,
+    /// Epoch and round of the latest proposal seen as a follower, with the time we first saw it.
+    proposal_time: Option<(EpochId, Round, NodeTime)>,
+}
+
 impl PacemakerState {
     pub fn new(
         epoch_id: EpochId,
@@ -95,14 +106,32 @@ impl PacemakerState {
             delta,
             gamma,
             lambda,
+            phase_timeouts: PhaseTimeouts::default(),
         }
     }
 
+    /// Time out the rounds that we lead after `proposer_timeout` instead of the round duration.
+    pub fn with_proposer_timeout(mut self, proposer_timeout: Duration) -> Self {
+        self.phase_timeouts.proposer = Some(proposer_timeout);
+        self
+    }
+
+    /// Time out the rounds that we do not lead after `follower_timeout` as long as no proposal
+    /// was received, instead of the round duration. Once the proposal is received, the round
+    /// lasts for its duration from then on.
+    pub fn with_follower_timeout(mut self, follower_timeout: Duration) -> Self {
+        self.phase_timeouts.follower = Some(follower_timeout);
+        self
+    }
+
+    /// Change the maximal duration of the first round after a commit rule, starting with the
+    /// next round.
+    pub fn set_delta(&mut self, delta: Duration) {
+        self.delta = delta;
+    }
+
     pub fn leader(record_store: &RecordStore, round: Round) -> Author {
-        let mut hasher = DefaultHasher::new();
-        round.hash(&mut hasher);
-        let author = record_store.pick_author(hasher.finish());
-        author
+        record_store.leader(round)
     }
 
     fn duration(&self, record_store: &RecordStore, round: Round) -> Duration {
@@ -112,15 +141,62 @@ impl PacemakerState {
         } else {
             Round(0)
         };
+        self.round_duration(round, highest_commit_certificate_round)
+    }
+
+    /// Time at which to time out the active round, given our role in it and whether its
+    /// proposal is known.
+    fn phase_deadline(
+        &mut self,
+        local_author: Author,
+        record_store: &RecordStore,
+        clock: NodeTime,
+    ) -> NodeTime {
+        let start = self.active_round_start_time;
+        let round_deadline = start + self.active_round_duration;
+        if self.active_leader == Some(local_author) {
+            return self
+                .phase_timeouts
+                .proposer
+                .map_or(round_deadline, |timeout| start + timeout);
+        }
+        let follower_timeout = match self.phase_timeouts.follower {
+            Some(timeout) => timeout,
+            None => return round_deadline,
+        };
+        if record_store.proposed_block(&*self).is_none() {
+            return start + follower_timeout;
+        }
+        // A proposal received after the round duration must still leave time to vote on it.
+        let (epoch_id, round) = (self.active_epoch, self.active_round);
+        let proposal_time = match self.phase_timeouts.proposal_time {
+            Some((e, r, time)) if (e, r) == (epoch_id, round) => time,
+            _ => {
+                self.phase_timeouts.proposal_time = Some((epoch_id, round, clock));
+                clock
+            }
+        };
+        proposal_time + self.active_round_duration
+    }
+
+    /// Maximal duration of `round`, growing with its distance to the round of the highest
+    /// commit certificate.
+    fn round_duration(&self, round: Round, highest_commit_certificate_round: Round) -> Duration {
         assert!(
             round > highest_commit_certificate_round,
             "Active round is higher than any QC round."
         );
-        let n = round.0 - highest_commit_certificate_round.0;
+        let n = round.distance(highest_commit_certificate_round);
         ((self.delta as f64) * (n as f64).powf(self.gamma)) as Duration
     }
 }
 
+impl Default for PacemakerUpdateActions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PacemakerUpdateActions {
     pub fn new() -> Self {
         PacemakerUpdateActions {
@@ -178,7 +254,7 @@ impl Pacemaker for PacemakerState {
             actions.next_scheduled_update = clock;
         }
         if !record_store.has_timeout(local_author, active_round) {
-            let timeout_deadline = self.active_round_start_time + self.active_round_duration;
+            let timeout_deadline = self.phase_deadline(local_author, record_store, clock);
             // If we have not created a timeout yet, check if the round has passed its maximal
             // duration. Then, either broadcast a new timeout now, or schedule an update
             // in the future.
@@ -215,4 +291,8 @@ impl Pacemaker for PacemakerState {
     fn active_leader(&self) -> Option<Author> {
         self.active_leader
     }
+
+    fn active_round_duration(&self) -> Duration {
+        self.active_round_duration
+    }
 }
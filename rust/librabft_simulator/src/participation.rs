@@ -0,0 +1,134 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::{schema, NodeUpdateActions};
+use record::Record;
+use simulator::{GlobalTime, Observer, Simulator};
+use std::{collections::BTreeSet, fs::File, io::Write, ops::Range};
+
+#[cfg(test)]
+#[path = "unit_tests/participation_tests.rs"]
+mod participation_tests;
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct ParticipationSummary {
+    pub author: Author,
+    /// Rounds in which the author voted.
+    pub voted_rounds: usize,
+    /// Rounds in which the author did not vote.
+    pub missed_rounds: usize,
+    /// Rounds for which the author created a timeout.
+    pub timed_out_rounds: usize,
+    /// QCs known to the observer that include a vote of the author.
+    pub qcs_included: usize,
+}
+
+/// Observer recording the rounds in which each node voted, across epochs.
+#[derive(Default, Debug)]
+pub struct VoteTracker {
+    voted_rounds: BTreeMap<Author, BTreeSet<(EpochId, Round)>>,
+}
+
+impl VoteTracker {
+    pub fn new() -> Self {
+        VoteTracker::default()
+    }
+
+    pub fn voted_rounds(&self, author: Author) -> BTreeSet<(EpochId, Round)> {
+        self.voted_rounds.get(&author).cloned().unwrap_or_default()
+    }
+}
+
+impl Observer<NodeState, SimulatedContext> for VoteTracker {
+    fn on_update(
+        &mut self,
+        _clock: GlobalTime,
+        author: Author,
+        node: &NodeState,
+        _context: &SimulatedContext,
+        _actions: &NodeUpdateActions,
+    ) {
+        // A vote stays current until the node leaves its round, so every vote is seen at
+        // least once.
+        if let Some(vote) = node.record_store().current_vote(author) {
+            self.voted_rounds
+                .entry(author)
+                .or_default()
+                .insert((vote.epoch_id, vote.round));
+        }
+    }
+}
+
+/// One row per node, counting the given rounds of the current epoch of `observer`. Votes are
+/// those seen by `votes`, timeouts are reported by each node, and QCs are those known to the
+/// observer.
+pub fn participation_table(
+    sim: &Simulator<
+        NodeState,
+        SimulatedContext,
+        BundledNotification,
+        DataSyncRequest,
+        DataSyncResponse,
+    >,
+    votes: &VoteTracker,
+    observer: Author,
+    rounds: Range<Round>,
+) -> Vec<ParticipationSummary> {
+    let observer_node = sim.simulated_node(observer).node();
+    let epoch_id = observer_node.epoch_id();
+    let in_range = |round: &Round| rounds.start <= *round && *round < rounds.end;
+    let count = |set: &BTreeSet<(EpochId, Round)>| {
+        set.iter()
+            .filter(|(epoch, round)| *epoch == epoch_id && in_range(round))
+            .count()
+    };
+    // Voters of the QCs of each round, known to the observer.
+    let mut qc_voters = BTreeSet::new();
+    for record in observer_node.record_store().records() {
+        if let Record::QuorumCertificate(qc) = record {
+            if in_range(&qc.round) {
+                for (author, _) in &qc.votes {
+                    qc_voters.insert((*author, qc.round));
+                }
+            }
+        }
+    }
+    let num_rounds = rounds.end.0.saturating_sub(rounds.start.0);
+    sim.nodes_iter()
+        .map(|(author, node)| {
+            let voted_rounds = count(&votes.voted_rounds(author));
+            ParticipationSummary {
+                author,
+                voted_rounds,
+                missed_rounds: num_rounds.saturating_sub(voted_rounds),
+                timed_out_rounds: count(node.node().timed_out_rounds()),
+                qcs_included: qc_voters
+                    .iter()
+                    .filter(|(voter, _)| *voter == author)
+                    .count(),
+            }
+        })
+        .collect()
+}
+
+/// Write the table in the directory `path`, following `schema::participation`.
+pub fn write_participation_table(path: &str, rows: &[ParticipationSummary]) {
+    let schema = schema::participation();
+    let mut file =
+        File::create(format!("{}/{}", path, schema.file_name)).expect("could not create data file");
+    writeln!(file, "{}", schema.version_line()).expect("Writing did not succeed");
+    let mut wtr = csv::Writer::from_writer(file);
+    wtr.write_record(schema.header(0))
+        .expect("Writing did not succeed");
+    for row in rows {
+        wtr.serialize((
+            row.author.0,
+            row.voted_rounds,
+            row.missed_rounds,
+            row.timed_out_rounds,
+            row.qcs_included,
+        ))
+        .expect("Writing did not succeed");
+    }
+}
@@ -3,8 +3,11 @@
 
 use super::*;
 use base_types::*;
+use bft_simulator_runtime::schema::SchemaVersion;
 use std::{
     collections::hash_map::DefaultHasher,
+    convert::TryFrom,
+    fmt,
     hash::{Hash, Hasher},
 };
 
@@ -41,6 +44,8 @@ pub struct Block {
     pub previous_quorum_certificate_hash: QuorumCertificateHash,
     /// Number used to identify repeated attempts to propose a block.
     pub round: Round,
+    /// Digest of the epoch configuration of the author, see `EpochConfiguration::digest`.
+    pub epoch_configuration_digest: u64,
     /// Creator of the block.
     pub author: Author,
     /// Signs the hash of the block, that is, all the fields above.
@@ -102,16 +107,61 @@ pub struct Timeout {
 }
 // -- END FILE --
 
+/// Version of the serialized format of blocks. It is hashed, hence signed, with every block so
+/// that blocks of different formats never share a hash. Version 2 added
+/// `Block::epoch_configuration_digest`.
+pub const BLOCK_FORMAT_VERSION: SchemaVersion = SchemaVersion { major: 2, minor: 0 };
+
 impl Hash for Block {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.command.hash(state);
+        BLOCK_FORMAT_VERSION.major.hash(state);
+        BLOCK_FORMAT_VERSION.minor.hash(state);
+        state.write_u64(self.command.deterministic_hash());
         self.time.hash(state);
         self.previous_quorum_certificate_hash.hash(state);
         self.round.hash(state);
+        self.epoch_configuration_digest.hash(state);
         self.author.hash(state);
     }
 }
 
+impl Block {
+    /// Stable hash of the command of the block.
+    pub fn command_hash(&self) -> u64 {
+        self.command.deterministic_hash()
+    }
+
+    /// Number of bytes of the block once serialized: format version, command, time, previous QC
+    /// hash, round, configuration digest, author, and signature.
+    pub fn wire_size(&self) -> usize {
+        4 + 4
+            + AUTHOR_BYTES
+            + 8
+            + self.command.payload.len()
+            + 8
+            + HASH_BYTES
+            + 8
+            + 8
+            + AUTHOR_BYTES
+            + SIGNATURE_BYTES
+    }
+}
+
+impl Vote {
+    /// Number of bytes of the vote once serialized, the same as the header of a QC.
+    pub fn wire_size(&self) -> usize {
+        QC_HEADER_BYTES
+    }
+}
+
+impl Timeout {
+    /// Number of bytes of the timeout once serialized: epoch, round, highest certified round,
+    /// author, and signature.
+    pub fn wire_size(&self) -> usize {
+        8 + 8 + 8 + AUTHOR_BYTES + SIGNATURE_BYTES
+    }
+}
+
 impl Hash for Vote {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.epoch_id.hash(state);
@@ -135,6 +185,61 @@ impl Hash for QuorumCertificate {
     }
 }
 
+impl QuorumCertificate {
+    /// Digest of the vote of `author` that was aggregated into this QC.
+    pub fn vote_digest(&self, author: Author) -> u64 {
+        Record::digest(&Record::Vote(Vote {
+            epoch_id: self.epoch_id,
+            round: self.round,
+            certified_block_hash: self.certified_block_hash,
+            state: self.state.clone(),
+            committed_state: self.committed_state.clone(),
+            author,
+            signature: Signature(0), // ignored
+        }))
+    }
+
+    /// Number of bytes of the QC once serialized, with one signature per vote.
+    pub fn wire_size(&self) -> usize {
+        QC_HEADER_BYTES + self.votes.len() * (AUTHOR_BYTES + SIGNATURE_BYTES)
+    }
+
+    /// Number of bytes of the QC once serialized with an aggregated signature, given the number
+    /// of voters of the epoch.
+    pub fn aggregated_wire_size(&self, num_voters: usize) -> usize {
+        QC_HEADER_BYTES + (num_voters + 7) / 8 + AGGREGATE_SIGNATURE_BYTES
+    }
+
+    /// Replace the signatures of the votes by a single aggregated signature and a bitmap of the
+    /// signers among the voters of the epoch. Fails if an author has no voting rights.
+    pub fn aggregate(
+        &self,
+        configuration: &EpochConfiguration,
+    ) -> Result<AggregateQuorumCertificate> {
+        let voters: Vec<_> = configuration.voters().cloned().collect();
+        let mut signers = vec![false; voters.len()];
+        for (author, _) in &self.votes {
+            match voters.binary_search(author) {
+                Ok(index) => signers[index] = true,
+                Err(_) => bail!("{:?} has no voting rights in {:?}", author, self.epoch_id),
+            }
+        }
+        let mut votes = self.votes.clone();
+        votes.sort();
+        Ok(AggregateQuorumCertificate {
+            epoch_id: self.epoch_id,
+            round: self.round,
+            certified_block_hash: self.certified_block_hash,
+            state: self.state.clone(),
+            committed_state: self.committed_state.clone(),
+            signers,
+            aggregate_signature: aggregate_signatures(&votes),
+            author: self.author,
+            signature: self.signature,
+        })
+    }
+}
+
 impl Hash for Timeout {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.epoch_id.hash(state);
@@ -143,6 +248,214 @@ impl Hash for Timeout {
     }
 }
 
+// Sizes used to compare the wire format of signature schemes.
+const AUTHOR_BYTES: usize = 8;
+const HASH_BYTES: usize = 32;
+const SIGNATURE_BYTES: usize = 64;
+const AGGREGATE_SIGNATURE_BYTES: usize = 96;
+// Epoch, round, certified block hash, state, optional committed state, author, and signature.
+const QC_HEADER_BYTES: usize =
+    8 + 8 + HASH_BYTES + HASH_BYTES + (1 + HASH_BYTES) + AUTHOR_BYTES + SIGNATURE_BYTES;
+
+/// A QC where the signatures of the votes are aggregated into one, as in BLS schemes.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct AggregateQuorumCertificate {
+    pub epoch_id: EpochId,
+    pub round: Round,
+    pub certified_block_hash: BlockHash,
+    pub state: State,
+    pub committed_state: Option<State>,
+    /// Bitmap of the authors of the votes, indexed by position among the voters of the epoch.
+    pub signers: Vec<bool>,
+    /// Aggregation of the signatures of the votes of the signers.
+    pub aggregate_signature: Signature,
+    pub author: Author,
+    /// Signature of the original QC by its author.
+    pub signature: Signature,
+}
+
+/// Simulates the aggregation of signatures, given in the order of the authors.
+fn aggregate_signatures(votes: &[(Author, Signature)]) -> Signature {
+    let mut hasher = StableHasher::new();
+    for (author, signature) in votes {
+        hasher.write_u64(author.0 as u64);
+        hasher.write_u64(signature.0);
+    }
+    Signature(hasher.finish())
+}
+
+impl AggregateQuorumCertificate {
+    pub fn signers(&self, configuration: &EpochConfiguration) -> Vec<Author> {
+        configuration
+            .voters()
+            .zip(&self.signers)
+            .filter(|(_, signed)| **signed)
+            .map(|(author, _)| *author)
+            .collect()
+    }
+
+    /// Number of bytes of the QC once serialized.
+    pub fn wire_size(&self) -> usize {
+        QC_HEADER_BYTES + (self.signers.len() + 7) / 8 + AGGREGATE_SIGNATURE_BYTES
+    }
+
+    /// Verify the aggregated signature against the signers, then check that the signers form a
+    /// quorum of the epoch.
+    pub fn check(&self, configuration: &EpochConfiguration) -> Result<()> {
+        let qc = QuorumCertificate {
+            epoch_id: self.epoch_id,
+            round: self.round,
+            certified_block_hash: self.certified_block_hash,
+            state: self.state.clone(),
+            committed_state: self.committed_state.clone(),
+            votes: Vec::new(),
+            author: self.author,
+            signature: self.signature,
+        };
+        ensure!(
+            self.signers.len() == configuration.voters().count(),
+            "The bitmap of signers must cover the voters of the epoch."
+        );
+        let signers = self.signers(configuration);
+        let votes: Vec<_> = signers
+            .iter()
+            .map(|author| (*author, Signature::sign(qc.vote_digest(*author), *author)))
+            .collect();
+        ensure!(
+            aggregate_signatures(&votes) == self.aggregate_signature,
+            "The aggregated signature must match the signers."
+        );
+        ensure!(
+            configuration.count_votes(&signers) >= configuration.quorum_threshold(),
+            "Signers of aggregated QCs must form a quorum"
+        );
+        Ok(())
+    }
+}
+
+impl From<Block> for Record {
+    fn from(block: Block) -> Record {
+        Record::Block(block)
+    }
+}
+
+impl From<Vote> for Record {
+    fn from(vote: Vote) -> Record {
+        Record::Vote(vote)
+    }
+}
+
+impl From<QuorumCertificate> for Record {
+    fn from(qc: QuorumCertificate) -> Record {
+        Record::QuorumCertificate(qc)
+    }
+}
+
+impl From<Timeout> for Record {
+    fn from(timeout: Timeout) -> Record {
+        Record::Timeout(timeout)
+    }
+}
+
+/// Error returned when extracting a value of the wrong type from a record.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct WrongRecordType {
+    pub expected: &'static str,
+    /// The record that was given, so that callers may try another type.
+    pub record: Record,
+}
+
+impl fmt::Display for WrongRecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Expected a {} record but found a {} record",
+            self.expected,
+            self.record.type_name()
+        )
+    }
+}
+
+impl std::error::Error for WrongRecordType {}
+
+impl TryFrom<Record> for Block {
+    type Error = WrongRecordType;
+
+    fn try_from(record: Record) -> std::result::Result<Block, WrongRecordType> {
+        match record {
+            Record::Block(block) => Ok(block),
+            record => Err(WrongRecordType {
+                expected: "block",
+                record,
+            }),
+        }
+    }
+}
+
+impl TryFrom<Record> for Vote {
+    type Error = WrongRecordType;
+
+    fn try_from(record: Record) -> std::result::Result<Vote, WrongRecordType> {
+        match record {
+            Record::Vote(vote) => Ok(vote),
+            record => Err(WrongRecordType {
+                expected: "vote",
+                record,
+            }),
+        }
+    }
+}
+
+impl TryFrom<Record> for QuorumCertificate {
+    type Error = WrongRecordType;
+
+    fn try_from(record: Record) -> std::result::Result<QuorumCertificate, WrongRecordType> {
+        match record {
+            Record::QuorumCertificate(qc) => Ok(qc),
+            record => Err(WrongRecordType {
+                expected: "quorum certificate",
+                record,
+            }),
+        }
+    }
+}
+
+impl TryFrom<Record> for Timeout {
+    type Error = WrongRecordType;
+
+    fn try_from(record: Record) -> std::result::Result<Timeout, WrongRecordType> {
+        match record {
+            Record::Timeout(timeout) => Ok(timeout),
+            record => Err(WrongRecordType {
+                expected: "timeout",
+                record,
+            }),
+        }
+    }
+}
+
+impl Validate for Record {
+    fn validate(&self) -> Result<()> {
+        let hash = self.digest();
+        match self {
+            Record::Block(block) => {
+                block.signature.check(hash, block.author)?;
+                ensure!(block.round > Round(0), "Rounds must start at 1");
+            }
+            Record::Vote(vote) => vote.signature.check(hash, vote.author)?,
+            Record::QuorumCertificate(qc) => {
+                qc.signature.check(hash, qc.author)?;
+                ensure!(
+                    !qc.votes.is_empty(),
+                    "A quorum certificate must contain votes"
+                );
+            }
+            Record::Timeout(timeout) => timeout.signature.check(hash, timeout.author)?,
+        }
+        Ok(())
+    }
+}
+
 impl Record {
     pub fn digest(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
@@ -155,6 +468,7 @@ impl Record {
         time: NodeTime,
         previous_quorum_certificate_hash: QuorumCertificateHash,
         round: Round,
+        epoch_configuration_digest: u64,
         author: Author,
     ) -> Record {
         let mut value = Record::Block(Block {
@@ -162,6 +476,7 @@ impl Record {
             time,
             previous_quorum_certificate_hash,
             round,
+            epoch_configuration_digest,
             author,
             signature: Signature(0),
         });
@@ -246,7 +561,35 @@ impl Record {
         value
     }
 
-    #[cfg(test)]
+    /// Human-readable name of the type of record.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Record::Block(_) => "block",
+            Record::Vote(_) => "vote",
+            Record::QuorumCertificate(_) => "quorum certificate",
+            Record::Timeout(_) => "timeout",
+        }
+    }
+
+    pub fn round(&self) -> Round {
+        match self {
+            Record::Block(x) => x.round,
+            Record::Vote(x) => x.round,
+            Record::QuorumCertificate(x) => x.round,
+            Record::Timeout(x) => x.round,
+        }
+    }
+
+    /// Estimated number of bytes of the record once serialized.
+    pub fn wire_size(&self) -> usize {
+        match self {
+            Record::Block(x) => x.wire_size(),
+            Record::Vote(x) => x.wire_size(),
+            Record::QuorumCertificate(x) => x.wire_size(),
+            Record::Timeout(x) => x.wire_size(),
+        }
+    }
+
     pub fn author(&self) -> Author {
         match self {
             Record::Block(x) => x.author,
@@ -3,12 +3,15 @@
 
 use super::*;
 use base_types::*;
+use leader_election::{LeaderElection, LeaderSchedule};
 use pacemaker::{Pacemaker, PacemakerState};
 use record::*;
 use smr_context::SMRContext;
 use std::{
-    collections::{BTreeSet, HashMap},
-    fmt::Debug,
+    cmp::{max, min},
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    convert::TryFrom,
+    fmt::{self, Debug},
 };
 
 #[cfg(test)]
@@ -16,7 +19,7 @@ use std::{
 mod record_store_tests;
 
 // -- BEGIN FILE record_store --
-pub trait RecordStore: Debug {
+pub trait RecordStore: Debug + LeaderSchedule {
     /// Return the hash of a QC at the highest round, or the initial hash.
     fn highest_quorum_certificate_hash(&self) -> QuorumCertificateHash;
     /// Query the round of the highest QC.
@@ -43,14 +46,15 @@ pub trait RecordStore: Debug {
 
     /// Create a timeout.
     fn create_timeout(&mut self, author: Author, round: Round, smr_context: &mut SMRContext);
-    /// Fetch a command from mempool and propose a block.
+    /// Fetch a command from mempool and propose a block. Return the block if it was inserted.
     fn propose_block(
         &mut self,
         local_author: Author,
         previous_qc_hash: QuorumCertificateHash,
+        round: Round,
         clock: NodeTime,
         smr_context: &mut SMRContext,
-    );
+    ) -> Option<Block>;
     /// Execute the command contained in a block and vote for the resulting state.
     /// Return false if the execution failed.
     fn create_vote(
@@ -71,17 +75,98 @@ pub trait RecordStore: Debug {
     fn second_previous_round(&self, block_hash: BlockHash) -> Round;
     /// Pick an author based on a seed, with chances proportional to voting rights.
     fn pick_author(&self, seed: u64) -> Author;
+    /// Known blocks of the rounds after `after_round` that cannot be committed anymore because a
+    /// block of the same or a higher round was committed instead, ordered by rounds.
+    fn orphaned_blocks(&self, after_round: Round) -> Vec<(BlockHash, Round)>;
 
     /// APIs supporting data synchronization.
     fn timeouts(&self) -> Vec<Timeout>;
     fn current_vote(&self, local_author: Author) -> Option<&Vote>;
     fn block(&self, block_hash: BlockHash) -> Option<&Block>;
     fn known_quorum_certificate_rounds(&self) -> BTreeSet<Round>;
+    /// Number of votes in the known QC of each certified round.
+    fn quorum_certificate_votes(&self) -> BTreeMap<Round, usize>;
     fn unknown_records(&self, known_qc_rounds: BTreeSet<Round>) -> Vec<Record>;
+    /// All the records inserted in the store, in order of insertion, including the votes and
+    /// timeouts of past rounds. The order does not depend on the internal hash maps, hence is
+    /// stable across runs with the same seed.
+    fn records(&self) -> &[Record];
+    /// All the records of the given author and round, in order of insertion. Records of
+    /// committed rounds are kept, like in `records`.
+    fn find_by_author_and_round(&self, author: Author, round: Round) -> Vec<&Record>;
+    /// Summary of the records of the store, for debugging and reporting memory usage.
+    fn stats(&self) -> RecordStoreStats;
     fn insert_network_record(&mut self, record: Record, smr_context: &mut SMRContext);
 }
 // -- END FILE --
 
+/// Number of records of each kind in a store, with an estimate of their size.
+#[derive(Eq, PartialEq, Clone, Default, Debug)]
+pub struct RecordStoreStats {
+    pub block_count: usize,
+    pub vote_count: usize,
+    pub qc_count: usize,
+    pub timeout_count: usize,
+    /// Sum of the serialized sizes of the records, see `Record::wire_size`.
+    pub total_size_estimate_bytes: usize,
+    /// Lowest and highest rounds among the records, if any.
+    pub oldest_round: Option<Round>,
+    pub newest_round: Option<Round>,
+}
+
+/// Two distinct blocks signed by the same author for the same round, in order of insertion.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct EquivocationProof {
+    pub author: Author,
+    pub round: Round,
+    pub first_block: BlockHash,
+    pub second_block: BlockHash,
+}
+
+/// Why a record was rejected, when the reason matters beyond logging.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum RejectReason {
+    /// The payload of the block exceeds the `max_block_bytes` of the epoch.
+    BlockTooLarge { size: usize, max_block_bytes: usize },
+    /// The block was proposed under a different validator set or voting rights.
+    EpochConfigMismatch { expected: u64, found: u64 },
+}
+
+impl<'a> RecordStore + 'a {
+    pub fn iter(&self) -> std::slice::Iter<'_, Record> {
+        self.records().iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a RecordStore {
+    type Item = &'a Record;
+    type IntoIter = std::slice::Iter<'a, Record>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.records().iter()
+    }
+}
+
+impl fmt::Display for RejectReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RejectReason::BlockTooLarge {
+                size,
+                max_block_bytes,
+            } => write!(
+                f,
+                "Block payload of {} bytes exceeds the maximum of {} bytes",
+                size, max_block_bytes
+            ),
+            RejectReason::EpochConfigMismatch { expected, found } => write!(
+                f,
+                "Block was proposed with epoch configuration {:016x} instead of {:016x}",
+                found, expected
+            ),
+        }
+    }
+}
+
 // -- BEGIN FILE record_store_state --
 #[derive(Debug)]
 pub struct RecordStoreState {
@@ -93,6 +178,10 @@ pub struct RecordStoreState {
     /// Storage of verified blocks and QCs.
     blocks: HashMap<BlockHash, Block>,
     quorum_certificates: HashMap<QuorumCertificateHash, QuorumCertificate>,
+    /// Positions in `inserted_records` of the records of each author and round.
+    records_by_author_and_round: HashMap<(Author, Round), Vec<usize>>,
+    /// Hashes of the inserted blocks of each round, in order of insertion.
+    blocks_by_round: BTreeMap<Round, Vec<BlockHash>>,
     current_proposed_block: Option<BlockHash>,
     /// Computed round values.
     highest_quorum_certificate_round: Round,
@@ -109,6 +198,22 @@ pub struct RecordStoreState {
     /// Computed weight values.
     current_timeouts_weight: usize,
     current_election: ElectionState,
+    /// Number of QCs whose state differs from the result of our own execution.
+    num_state_mismatches: usize,
+    /// Number of times that a block was rejected for being too large.
+    num_oversized_blocks: usize,
+    /// Number of times that a block was rejected for an epoch configuration other than ours.
+    num_epoch_config_mismatches: usize,
+    /// Highest certified round claimed by a verified timeout or QC of this epoch.
+    highest_reported_certified_round: Round,
+    /// Number of received proposals whose previous round is lower than our highest QC round.
+    num_stale_proposals: usize,
+    /// Equivocations detected among the inserted blocks, in order of detection.
+    equivocation_proofs: Vec<EquivocationProof>,
+    /// Every record inserted in the store, in order of insertion.
+    inserted_records: Vec<Record>,
+    /// How the leader of each round is chosen.
+    leader_election: LeaderElection,
 }
 
 /// Counting votes for a proposed block and its execution state.
@@ -163,7 +268,7 @@ impl RecordStoreState {
         epoch_id: EpochId,
         configuration: EpochConfiguration,
     ) -> RecordStoreState {
-        warn!("Creating new record store for epoch: {:?}, initial_hash: {:?}, initial_state: {:?}, configuration: {:?}", epoch_id, initial_hash, initial_state, configuration);
+        warn!("Creating new record store for epoch: {}, initial_hash: {:?}, initial_state: {:?}, configuration: {:?}", epoch_id, initial_hash, initial_state, configuration);
         RecordStoreState {
             configuration,
             initial_hash,
@@ -171,6 +276,8 @@ impl RecordStoreState {
             epoch_id,
             blocks: HashMap::new(),
             quorum_certificates: HashMap::new(),
+            records_by_author_and_round: HashMap::new(),
+            blocks_by_round: BTreeMap::new(),
             current_proposed_block: None,
             highest_quorum_certificate_round: Round(0),
             highest_quorum_certificate_hash: initial_hash,
@@ -185,6 +292,124 @@ impl RecordStoreState {
             current_election: ElectionState::Ongoing {
                 ballot: HashMap::new(),
             },
+            num_state_mismatches: 0,
+            num_oversized_blocks: 0,
+            num_epoch_config_mismatches: 0,
+            highest_reported_certified_round: Round(0),
+            num_stale_proposals: 0,
+            equivocation_proofs: Vec::new(),
+            inserted_records: Vec::new(),
+            leader_election: LeaderElection::default(),
+        }
+    }
+
+    pub fn with_leader_election(mut self, leader_election: LeaderElection) -> Self {
+        self.leader_election = leader_election;
+        self
+    }
+
+    pub fn num_state_mismatches(&self) -> usize {
+        self.num_state_mismatches
+    }
+
+    pub fn num_oversized_blocks(&self) -> usize {
+        self.num_oversized_blocks
+    }
+
+    pub fn num_stale_proposals(&self) -> usize {
+        self.num_stale_proposals
+    }
+
+    pub fn num_epoch_config_mismatches(&self) -> usize {
+        self.num_epoch_config_mismatches
+    }
+
+    pub fn equivocation_proofs(&self) -> &[EquivocationProof] {
+        &self.equivocation_proofs
+    }
+
+    pub fn configuration(&self) -> &EpochConfiguration {
+        &self.configuration
+    }
+
+    /// Records of the given author and round, in order of insertion.
+    fn indexed_records<'a>(
+        &'a self,
+        author: Author,
+        round: Round,
+    ) -> impl Iterator<Item = &'a Record> + 'a {
+        self.records_by_author_and_round
+            .get(&(author, round))
+            .into_iter()
+            .flatten()
+            .map(move |position| &self.inserted_records[*position])
+    }
+
+    /// Hashes of the blocks of the given author and round, in order of insertion.
+    fn indexed_block_hashes<'a>(
+        &'a self,
+        author: Author,
+        round: Round,
+    ) -> impl Iterator<Item = BlockHash> + 'a {
+        self.indexed_records(author, round)
+            .filter_map(|record| match record {
+                Record::Block(_) => Some(BlockHash(record.digest())),
+                _ => None,
+            })
+    }
+
+    /// Number of non-empty blocks certified on the branch of the highest QC after the highest
+    /// committed round.
+    pub fn num_uncommitted_commands(&self) -> usize {
+        BackwardQuorumCertificateIterator::new(self, self.highest_quorum_certificate_hash)
+            .take_while(|qc| qc.round > self.highest_committed_round)
+            .filter(|qc| {
+                self.blocks
+                    .get(&qc.certified_block_hash)
+                    .map_or(false, |block| !block.command.is_empty())
+            })
+            .count()
+    }
+
+    /// Number of rounds after `after_round` with at least one known block.
+    pub fn num_rounds_with_blocks_after(&self, after_round: Round) -> usize {
+        self.blocks_by_round.range(after_round.next()..).count()
+    }
+
+    /// The commit certificate of the highest committed round up to `round`, and the QC between
+    /// them, if any. Together with the records of the rounds up to `round`, they are enough to
+    /// commit these rounds again.
+    pub fn commit_certificate_chain(&self, round: Round) -> Vec<&QuorumCertificate> {
+        let cc_hash = self
+            .highest_commit_certificate_hash
+            .unwrap_or(self.initial_hash);
+        let qcs: Vec<_> = BackwardQuorumCertificateIterator::new(self, cc_hash).collect();
+        (0..qcs.len().saturating_sub(2))
+            .find(|i| qcs[*i].committed_state.is_some() && qcs[i + 2].round <= round)
+            .map_or_else(Vec::new, |i| qcs[i..i + 2].to_vec())
+    }
+
+    fn check_block_size(&self, block: &Block) -> std::result::Result<(), RejectReason> {
+        match self.configuration.max_block_bytes() {
+            Some(max_block_bytes) if block.command.payload.len() > max_block_bytes => {
+                Err(RejectReason::BlockTooLarge {
+                    size: block.command.payload.len(),
+                    max_block_bytes,
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn check_epoch_configuration(&self, block: &Block) -> std::result::Result<(), RejectReason> {
+        let expected = self.configuration.digest();
+        if block.epoch_configuration_digest == expected {
+            Ok(())
+        } else {
+            Err(RejectReason::EpochConfigMismatch {
+                expected,
+                found: block.epoch_configuration_digest,
+            })
         }
     }
 
@@ -218,7 +443,7 @@ impl RecordStoreState {
             (r1, r2, r3)
         };
         if let (Some(r1), Some(r2), Some(r3)) = rounds {
-            if r3 == r2 + 1 && r2 == r1 + 1 && r1 > self.highest_committed_round {
+            if r3.follows(r2) && r2.follows(r1) && r1 > self.highest_committed_round {
                 self.highest_committed_round = r1;
                 self.highest_commit_certificate_hash = Some(qc_hash);
             }
@@ -229,7 +454,7 @@ impl RecordStoreState {
         let block = self.block(block_hash).unwrap();
         let r3 = block.round;
         let qc2_hash = block.previous_quorum_certificate_hash;
-        let mut iter = BackwardQuorumCertificateIterator::new(&self, qc2_hash);
+        let mut iter = BackwardQuorumCertificateIterator::new(self, qc2_hash);
         let opt_qc2 = iter.next();
         let opt_qc1 = iter.next();
         if let (Some(qc1), Some(qc2)) = (opt_qc1, opt_qc2) {
@@ -331,24 +556,21 @@ impl RecordStoreState {
                     self.vote_committed_state(qc.certified_block_hash) == qc.committed_state,
                     "The committed_state value of a QC must follow the commit rule."
                 );
-                let mut weight = 0;
-                for (author, signature) in &qc.votes {
-                    let original_vote_digest = Record::digest(&Record::Vote(Vote {
-                        epoch_id: self.epoch_id,
-                        round: qc.round,
-                        certified_block_hash: qc.certified_block_hash,
-                        state: qc.state.clone(),
-                        committed_state: qc.committed_state.clone(),
-                        author: *author,
-                        signature: Signature(0), // ignored
-                    }));
-                    signature.check(original_vote_digest, *author)?;
-                    weight += self.configuration.weight(author);
+                if self.configuration.aggregates_signatures() {
+                    // Only the aggregated signature and the bitmap of signers are checked.
+                    qc.aggregate(&self.configuration)?
+                        .check(&self.configuration)?;
+                } else {
+                    let mut weight = 0;
+                    for (author, signature) in &qc.votes {
+                        signature.check(qc.vote_digest(*author), *author)?;
+                        weight += self.configuration.weight(author);
+                    }
+                    ensure!(
+                        weight >= self.configuration.quorum_threshold(),
+                        "Votes in QCs must form a quorum"
+                    );
                 }
-                ensure!(
-                    weight >= self.configuration.quorum_threshold(),
-                    "Votes in QCs must form a quorum"
-                );
                 // TODO: do not recompute hash
                 qc.signature
                     .check(Record::QuorumCertificate(qc.clone()).digest(), qc.author)?;
@@ -381,7 +603,7 @@ impl RecordStoreState {
         Ok(hash)
     }
 
-    fn quorum_certificate(&self, qc_hash: QuorumCertificateHash) -> Option<&QuorumCertificate> {
+    pub fn quorum_certificate(&self, qc_hash: QuorumCertificateHash) -> Option<&QuorumCertificate> {
         self.quorum_certificates.get(&qc_hash)
     }
 
@@ -412,19 +634,75 @@ impl RecordStoreState {
         record: Record,
         smr_context: &mut SMRContext,
     ) -> Result<()> {
+        // Oversized blocks are counted, including our own, which are therefore never proposed.
+        if let Record::Block(block) = &record {
+            if let Err(reason) = self.check_block_size(block) {
+                self.num_oversized_blocks += 1;
+                bail!("{}", reason);
+            }
+            // This denotes a setup bug or an attack rather than a network issue. Later
+            // mismatches are only counted, see `num_epoch_config_mismatches`.
+            if let Err(reason) = self.check_epoch_configuration(block) {
+                self.num_epoch_config_mismatches += 1;
+                if self.num_epoch_config_mismatches == 1 {
+                    warn!("{:?} {}", block.author, reason);
+                }
+                bail!("{}", reason);
+            }
+        }
         // First, check that the record is "relevant" and that invariants of "verified records",
         // such as chaining, are respected.
         let hash = self.verify_network_record(&record)?;
+        // Verified timeouts and QCs tell us about the certified rounds known to other nodes.
+        let reported_certified_round = match &record {
+            Record::Timeout(timeout) => timeout.highest_certified_block_round,
+            Record::QuorumCertificate(qc) => qc.round,
+            _ => Round(0),
+        };
+        self.highest_reported_certified_round = max(
+            self.highest_reported_certified_round,
+            reported_certified_round,
+        );
+        self.records_by_author_and_round
+            .entry((record.author(), record.round()))
+            .or_default()
+            .push(self.inserted_records.len());
+        self.inserted_records.push(record.clone());
+        if let Record::Block(block) = &record {
+            self.blocks_by_round
+                .entry(block.round)
+                .or_default()
+                .push(BlockHash(hash));
+        }
         // Second, insert the record. In the case of QC, this is where check execution states.
         match record {
             Record::Block(block) => {
                 let block_hash = BlockHash(hash);
+                let first_block = self
+                    .indexed_block_hashes(block.author, block.round)
+                    .find(|first_block| *first_block != block_hash);
+                // Only the first proposal of the leader counts. Later ones are equivocations.
                 if block.round == self.current_round
                     && PacemakerState::leader(&*self, block.round) == block.author
+                    && first_block.is_none()
                 {
                     // If we use a VRF, this assumes that we have inserted the highest commit rule
                     // beforehand.
                     self.current_proposed_block = Some(block_hash);
+                    let previous_round = self
+                        .quorum_certificate(block.previous_quorum_certificate_hash)
+                        .map_or(Round(0), |qc| qc.round);
+                    if previous_round < self.highest_quorum_certificate_round() {
+                        self.num_stale_proposals += 1;
+                    }
+                }
+                if let Some(first_block) = first_block {
+                    self.equivocation_proofs.push(EquivocationProof {
+                        author: block.author,
+                        round: block.round,
+                        first_block,
+                        second_block: block_hash,
+                    });
                 }
                 self.blocks.insert(block_hash, block);
             }
@@ -460,11 +738,13 @@ impl RecordStoreState {
                 // Make sure that the state in the QC is known to execution.
                 match self.compute_state(block_hash, smr_context) {
                     Some(state) => {
-                        ensure!(
-                            state == qc_state,
-                            "I computed a different state for a QC. This is very bad: {:?}",
-                            qc_state
-                        );
+                        if state != qc_state {
+                            self.num_state_mismatches += 1;
+                            bail!(
+                                "I computed a different state for a QC. This is very bad: {:?}",
+                                qc_state
+                            );
+                        }
                     }
                     None => {
                         bail!("I failed to execute a block with a QC at {:?} while my last commit is at {:?}", qc_round, self.highest_committed_round);
@@ -475,7 +755,7 @@ impl RecordStoreState {
                     self.highest_quorum_certificate_round = qc_round;
                     self.highest_quorum_certificate_hash = qc_hash;
                 }
-                self.update_current_round(qc_round + 1);
+                self.update_current_round(qc_round.next());
                 self.update_commit_3chain_round(qc_hash);
             }
             Record::Timeout(timeout) => {
@@ -487,7 +767,7 @@ impl RecordStoreState {
                         self.current_timeouts.iter().map(|x| x.1.clone()).collect();
                     self.highest_timeout_certificate = Some(timeout_certificate);
                     self.highest_timeout_certificate_round = self.current_round;
-                    self.update_current_round(self.current_round + 1);
+                    self.update_current_round(self.current_round.next());
                 }
             }
         }
@@ -495,6 +775,12 @@ impl RecordStoreState {
     }
 }
 
+impl LeaderSchedule for RecordStoreState {
+    fn leader(&self, round: Round) -> Author {
+        self.leader_election.leader(&self.configuration, round)
+    }
+}
+
 impl RecordStore for RecordStoreState {
     fn current_round(&self) -> Round {
         self.current_round
@@ -520,13 +806,36 @@ impl RecordStore for RecordStoreState {
             if qc.round <= after_round {
                 break;
             }
-            info!("Delivering committed state for round {:?}", qc.round);
+            info!("Delivering committed state for round {}", qc.round);
             commits.push((qc.round, qc.state.clone()));
         }
         commits.reverse();
         commits
     }
 
+    fn orphaned_blocks(&self, after_round: Round) -> Vec<(BlockHash, Round)> {
+        if self.highest_committed_round <= after_round {
+            return Vec::new();
+        }
+        let cc_hash = self
+            .highest_commit_certificate_hash
+            .unwrap_or(self.initial_hash);
+        // Only visit the commits and the blocks of the new rounds.
+        let committed_blocks: HashSet<_> = BackwardQuorumCertificateIterator::new(self, cc_hash)
+            .skip(2)
+            .take_while(|qc| qc.round > after_round)
+            .map(|qc| qc.certified_block_hash)
+            .collect();
+        let mut orphans: Vec<_> = self
+            .blocks_by_round
+            .range(after_round.next()..self.highest_committed_round.next())
+            .flat_map(|(round, hashes)| hashes.iter().map(move |hash| (*hash, *round)))
+            .filter(|(hash, _)| !committed_blocks.contains(hash))
+            .collect();
+        orphans.sort_by_key(|(hash, round)| (*round, *hash));
+        orphans
+    }
+
     fn highest_quorum_certificate_round(&self) -> Round {
         self.highest_quorum_certificate_round
     }
@@ -604,18 +913,27 @@ impl RecordStore for RecordStoreState {
         &mut self,
         local_author: Author,
         previous_qc_hash: QuorumCertificateHash,
+        round: Round,
         clock: NodeTime,
         smr_context: &mut SMRContext,
-    ) {
-        if let Some(command) = smr_context.fetch() {
-            let block = Record::make_block(
-                command,
-                clock,
-                previous_qc_hash,
-                self.current_round,
-                local_author,
-            );
-            self.insert_network_record(block, smr_context)
+    ) -> Option<Block> {
+        let command = smr_context.fetch()?;
+        let record = Record::make_block(
+            command,
+            clock,
+            previous_qc_hash,
+            round,
+            self.configuration.digest(),
+            local_author,
+        );
+        let block = Block::try_from(record.clone()).expect("make_block should create a block");
+        debug!("Inserting {:?}", record);
+        match self.try_insert_network_record(record, smr_context) {
+            Err(err) => {
+                debug!("=> Skipped: {}", err);
+                None
+            }
+            Ok(()) => Some(block),
         }
     }
 
@@ -656,8 +974,8 @@ impl RecordStore for RecordStoreState {
                 let committed_state = self.vote_committed_state(*block_hash);
                 let authors_and_signatures = self
                     .current_votes
-                    .iter()
-                    .filter_map(|(_, vote)| {
+                    .values()
+                    .filter_map(|vote| {
                         if vote.state == *state {
                             Some((vote.author, vote.signature))
                         } else {
@@ -665,7 +983,7 @@ impl RecordStore for RecordStoreState {
                         }
                     })
                     .collect();
-                let quorum_certificate = Record::make_quorum_certificate(
+                Record::make_quorum_certificate(
                     self.epoch_id,
                     self.current_round,
                     *block_hash,
@@ -673,8 +991,7 @@ impl RecordStore for RecordStoreState {
                     authors_and_signatures,
                     committed_state,
                     local_author,
-                );
-                quorum_certificate
+                )
             }
             _ => {
                 return false;
@@ -699,7 +1016,7 @@ impl RecordStore for RecordStoreState {
         if let Some(highest_tc) = &self.highest_timeout_certificate {
             timeouts.extend(highest_tc.iter().cloned());
         }
-        timeouts.extend(self.current_timeouts.iter().map(|(_, tc)| tc.clone()));
+        timeouts.extend(self.current_timeouts.values().cloned());
         timeouts
     }
 
@@ -734,6 +1051,15 @@ impl RecordStore for RecordStoreState {
         result
     }
 
+    fn quorum_certificate_votes(&self) -> BTreeMap<Round, usize> {
+        let mut votes = BTreeMap::new();
+        for qc in self.quorum_certificates.values() {
+            let entry = votes.entry(qc.round).or_insert(0);
+            *entry = std::cmp::max(*entry, qc.votes.len());
+        }
+        votes
+    }
+
     fn unknown_records(&self, known_qc_rounds: BTreeSet<Round>) -> Vec<Record> {
         let highest_qc_hash = self.highest_quorum_certificate_hash;
         let highest_cc_hash = self
@@ -745,9 +1071,7 @@ impl RecordStore for RecordStoreState {
         let chain2: Vec<_> = BackwardQuorumCertificateIterator::new(self, highest_cc_hash)
             .take_while(|qc| !known_qc_rounds.contains(&qc.round))
             .collect();
-        let qcs = merge_sort(chain1.into_iter(), chain2.into_iter(), |qc1, qc2| {
-            qc2.round.cmp(&qc1.round)
-        });
+        let qcs = merge_sort(chain1, chain2, |qc1, qc2| qc2.round.cmp(&qc1.round));
         let mut result = Vec::new();
         for n in (0..qcs.len()).rev() {
             let qc = qcs[n];
@@ -766,14 +1090,55 @@ impl RecordStore for RecordStoreState {
         result
     }
 
+    fn records(&self) -> &[Record] {
+        &self.inserted_records
+    }
+
+    fn find_by_author_and_round(&self, author: Author, round: Round) -> Vec<&Record> {
+        self.indexed_records(author, round).collect()
+    }
+
+    fn stats(&self) -> RecordStoreStats {
+        let timeouts = self.timeouts();
+        let rounds_and_sizes = self
+            .blocks
+            .values()
+            .map(|block| (block.round, block.wire_size()))
+            .chain(
+                self.quorum_certificates
+                    .values()
+                    .map(|qc| (qc.round, qc.wire_size())),
+            )
+            .chain(
+                self.current_votes
+                    .values()
+                    .map(|vote| (vote.round, vote.wire_size())),
+            )
+            .chain(
+                timeouts
+                    .iter()
+                    .map(|timeout| (timeout.round, timeout.wire_size())),
+            );
+        let mut stats = RecordStoreStats {
+            block_count: self.blocks.len(),
+            vote_count: self.current_votes.len(),
+            qc_count: self.quorum_certificates.len(),
+            timeout_count: timeouts.len(),
+            ..RecordStoreStats::default()
+        };
+        for (round, size) in rounds_and_sizes {
+            stats.total_size_estimate_bytes += size;
+            stats.oldest_round = Some(stats.oldest_round.map_or(round, |r| min(r, round)));
+            stats.newest_round = Some(stats.newest_round.map_or(round, |r| max(r, round)));
+        }
+        stats
+    }
+
     fn insert_network_record(&mut self, record: Record, smr_context: &mut SMRContext) {
         debug!("Inserting {:?}", record);
-        match self.try_insert_network_record(record, smr_context) {
-            Err(err) => {
-                debug!("=> Skipped: {}", err);
-            }
-            Ok(()) => (),
-        };
+        if let Err(err) = self.try_insert_network_record(record, smr_context) {
+            debug!("=> Skipped: {}", err);
+        }
         // TODO: discard unneeded records from self.blocks and self.quorum_certificates
     }
 }
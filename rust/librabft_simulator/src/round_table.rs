@@ -0,0 +1,186 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::schema;
+use pacemaker::PacemakerState;
+use simulator::{GlobalTime, Simulator};
+use std::{fs::File, io::Write};
+
+#[cfg(test)]
+#[path = "unit_tests/round_table_tests.rs"]
+mod round_table_tests;
+
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum RoundOutcome {
+    /// The block of the round was committed.
+    Committed,
+    /// Some node timed out at this round and its block, if any, was not committed.
+    TimedOut,
+    /// Neither committed nor timed out, e.g. a recent round or a certified block on a branch
+    /// that was not extended.
+    Uncommitted,
+}
+
+impl RoundOutcome {
+    fn name(self) -> &'static str {
+        match self {
+            RoundOutcome::Committed => "committed",
+            RoundOutcome::TimedOut => "timed_out",
+            RoundOutcome::Uncommitted => "uncommitted",
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct RoundSummary {
+    pub round: Round,
+    pub leader: Author,
+    pub outcome: RoundOutcome,
+    /// Delay between the proposal and the commit of the block of the round, if committed.
+    pub commit_latency: Option<Duration>,
+    /// Time of the proposal of the block of the round, if committed. Not written to the table.
+    pub proposal_time: Option<GlobalTime>,
+    /// Number of votes in the QC of the round, if any.
+    pub votes: usize,
+}
+
+/// One row per round of the current epoch of `author`, up to its active round (excluded).
+/// Timeouts are counted across all nodes.
+pub fn round_table(
+    sim: &Simulator<
+        NodeState,
+        SimulatedContext,
+        BundledNotification,
+        DataSyncRequest,
+        DataSyncResponse,
+    >,
+    author: Author,
+) -> Vec<RoundSummary> {
+    let node = sim.simulated_node(author);
+    let record_store = node.node().record_store();
+    let timeouts_per_round = sim.count_timeouts_per_round();
+    let votes = record_store.quorum_certificate_votes();
+    // The commits of the current epoch are the last entries of the committed history.
+    let commits = record_store.committed_states_after(Round(0));
+    let history = node.context().committed_history();
+    let offset = history.len().saturating_sub(commits.len());
+    let mut commit_latencies = BTreeMap::new();
+    let mut proposal_times = BTreeMap::new();
+    for (index, (round, _)) in commits.iter().enumerate() {
+        let (command, time) = &history[offset + index];
+        if let Some(commit_time) = node.commit_times().get(offset + index) {
+            let proposal_time = sim.simulated_node(command.proposer).startup_time().0 + time.0;
+            commit_latencies.insert(*round, commit_time.0 - proposal_time);
+            proposal_times.insert(*round, GlobalTime(proposal_time));
+        }
+    }
+    (1..node.active_round().0)
+        .map(Round)
+        .map(|round| {
+            let commit_latency = commit_latencies.get(&round).cloned();
+            let outcome = if commits.iter().any(|(committed, _)| *committed == round) {
+                RoundOutcome::Committed
+            } else if timeouts_per_round.contains_key(&round) {
+                RoundOutcome::TimedOut
+            } else {
+                RoundOutcome::Uncommitted
+            };
+            RoundSummary {
+                round,
+                leader: PacemakerState::leader(record_store, round),
+                outcome,
+                commit_latency,
+                proposal_time: proposal_times.get(&round).cloned(),
+                votes: votes.get(&round).cloned().unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+/// Commit latencies of the committed rounds, split by the outcome of the previous round. To
+/// account for the time lost before the proposal, the latency of a block is measured from the
+/// proposal of the previous committed block: on the happy path, this adds one round to the
+/// commit latency, while after a timeout, it also includes the wait for the timeout.
+#[derive(PartialEq, Clone, Default, Debug)]
+pub struct TimeoutCost {
+    /// Committed rounds right after a committed round.
+    pub happy_path_rounds: usize,
+    /// Committed rounds right after a timed-out round.
+    pub after_timeout_rounds: usize,
+    pub mean_happy_path_latency: Option<f64>,
+    pub mean_after_timeout_latency: Option<f64>,
+}
+
+impl TimeoutCost {
+    /// Mean latency added by a timeout to the commit of the next block.
+    pub fn delta(&self) -> Option<f64> {
+        match (
+            self.mean_happy_path_latency,
+            self.mean_after_timeout_latency,
+        ) {
+            (Some(happy_path), Some(after_timeout)) => Some(after_timeout - happy_path),
+            _ => None,
+        }
+    }
+}
+
+/// Compare the latency of the committed rounds preceded by a timeout with the happy path.
+/// Rounds preceded by an uncommitted round, or without a previous committed block, are ignored.
+pub fn timeout_cost(rows: &[RoundSummary]) -> TimeoutCost {
+    let mut happy_path = Vec::new();
+    let mut after_timeout = Vec::new();
+    let mut previous_proposal_time = None;
+    for (previous, row) in rows.iter().zip(rows.iter().skip(1)) {
+        if previous.outcome == RoundOutcome::Committed {
+            previous_proposal_time = previous.proposal_time;
+        }
+        let latency = match (
+            previous_proposal_time,
+            row.proposal_time,
+            row.commit_latency,
+        ) {
+            (Some(previous_time), Some(time), Some(latency)) => time.0 - previous_time.0 + latency,
+            _ => continue,
+        };
+        match previous.outcome {
+            RoundOutcome::Committed => happy_path.push(latency),
+            RoundOutcome::TimedOut => after_timeout.push(latency),
+            RoundOutcome::Uncommitted => (),
+        }
+    }
+    let mean = |latencies: &[Duration]| {
+        if latencies.is_empty() {
+            None
+        } else {
+            Some(latencies.iter().sum::<Duration>() as f64 / latencies.len() as f64)
+        }
+    };
+    TimeoutCost {
+        happy_path_rounds: happy_path.len(),
+        after_timeout_rounds: after_timeout.len(),
+        mean_happy_path_latency: mean(&happy_path),
+        mean_after_timeout_latency: mean(&after_timeout),
+    }
+}
+
+/// Write the table in the directory `path`, following `schema::round_table`.
+pub fn write_round_table(path: &str, rows: &[RoundSummary]) {
+    let schema = schema::round_table();
+    let mut file =
+        File::create(format!("{}/{}", path, schema.file_name)).expect("could not create data file");
+    writeln!(file, "{}", schema.version_line()).expect("Writing did not succeed");
+    let mut wtr = csv::Writer::from_writer(file);
+    wtr.write_record(schema.header(0))
+        .expect("Writing did not succeed");
+    for row in rows {
+        wtr.serialize((
+            row.round.0,
+            row.leader.0,
+            row.outcome.name(),
+            row.commit_latency,
+            row.votes,
+        ))
+        .expect("Writing did not succeed");
+    }
+}
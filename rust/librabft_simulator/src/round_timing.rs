@@ -0,0 +1,171 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::{ActiveRound, CommittedChain, NodeUpdateActions};
+use simulator::{GlobalTime, Observer};
+
+#[cfg(test)]
+#[path = "unit_tests/round_timing_tests.rs"]
+mod round_timing_tests;
+
+/// Timing of the vote of a node at a round.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub struct VoteTiming {
+    pub epoch_id: EpochId,
+    pub round: Round,
+    pub author: Author,
+    /// Delay between the proposal of the leader and its delivery to the node.
+    pub network_delay: Duration,
+    /// Delay between the delivery of the proposal and the vote of the node.
+    pub execution_delay: Duration,
+}
+
+/// Main cause of the delay of the votes of a node, compared to the other nodes.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum LagAttribution {
+    OnTime,
+    /// Proposals reach the node late.
+    NetworkLate,
+    /// The node votes late after receiving proposals.
+    ExecutionLate,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct NodeLag {
+    pub author: Author,
+    /// Number of votes measured.
+    pub votes: usize,
+    pub mean_network_delay: f64,
+    pub mean_execution_delay: f64,
+    pub attribution: LagAttribution,
+}
+
+/// Observer timing the proposals and the votes of each round. A proposal is sent once the
+/// leader completes the update that asked for it, a node receives it at its first update that
+/// knows it, and votes once the execution of this update completes. The leader is not timed.
+#[derive(Default, Debug)]
+pub struct RoundTimingObserver {
+    /// Total execution time of each node, as of its last update.
+    execution_times: BTreeMap<Author, Duration>,
+    /// Time at which the proposal of each round was sent.
+    proposals: BTreeMap<(EpochId, Round), GlobalTime>,
+    /// Time at which each node first knew the proposal of a round.
+    deliveries: BTreeMap<(Author, EpochId, Round), GlobalTime>,
+    timings: BTreeMap<(Author, EpochId, Round), VoteTiming>,
+}
+
+impl RoundTimingObserver {
+    pub fn new() -> Self {
+        RoundTimingObserver::default()
+    }
+
+    /// Attribute the lag of each node to the network or to the execution, by comparing its
+    /// mean delays with the median over all nodes. Nodes whose mean delays exceed the medians
+    /// by at most `tolerance` are on time.
+    pub fn lag_attributions(&self, tolerance: Duration) -> Vec<NodeLag> {
+        let mut delays = BTreeMap::new();
+        for timing in self.timings.values() {
+            delays
+                .entry(timing.author)
+                .or_insert_with(Vec::new)
+                .push((timing.network_delay, timing.execution_delay));
+        }
+        let means: Vec<_> = delays
+            .into_iter()
+            .map(|(author, delays)| {
+                let mean = |delay: fn(&(Duration, Duration)) -> Duration| {
+                    delays.iter().map(delay).sum::<Duration>() as f64 / delays.len() as f64
+                };
+                (author, delays.len(), mean(|d| d.0), mean(|d| d.1))
+            })
+            .collect();
+        let median = |mean: fn(&(Author, usize, f64, f64)) -> f64| {
+            let mut values: Vec<_> = means.iter().map(mean).collect();
+            values.sort_by(|x, y| x.partial_cmp(y).unwrap());
+            values.get(values.len() / 2).cloned().unwrap_or(0.0)
+        };
+        let median_network_delay = median(|m| m.2);
+        let median_execution_delay = median(|m| m.3);
+        means
+            .iter()
+            .map(|(author, votes, network_delay, execution_delay)| {
+                let network_excess = network_delay - median_network_delay;
+                let execution_excess = execution_delay - median_execution_delay;
+                let attribution = if f64::max(network_excess, execution_excess) <= tolerance as f64
+                {
+                    LagAttribution::OnTime
+                } else if execution_excess > network_excess {
+                    LagAttribution::ExecutionLate
+                } else {
+                    LagAttribution::NetworkLate
+                };
+                NodeLag {
+                    author: *author,
+                    votes: *votes,
+                    mean_network_delay: *network_delay,
+                    mean_execution_delay: *execution_delay,
+                    attribution,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Observer<NodeState, SimulatedContext> for RoundTimingObserver {
+    fn on_update(
+        &mut self,
+        clock: GlobalTime,
+        author: Author,
+        node: &NodeState,
+        context: &SimulatedContext,
+        _actions: &NodeUpdateActions,
+    ) {
+        // Messages of the update are sent once its execution completes.
+        let total_execution_time = context.execution_time();
+        let previous = self
+            .execution_times
+            .insert(author, total_execution_time)
+            .unwrap_or(0);
+        let send_time = clock + (total_execution_time - previous);
+        let epoch_id = node.epoch_id();
+        if node.round_actions().should_propose {
+            self.proposals
+                .entry((epoch_id, node.active_round()))
+                .or_insert(send_time);
+        }
+        let record_store = node.record_store();
+        if let Some((_, round, proposer)) = record_store.proposed_block(node.pacemaker()) {
+            if proposer != author {
+                self.deliveries
+                    .entry((author, epoch_id, round))
+                    .or_insert(clock);
+            }
+        }
+        let vote = match record_store.current_vote(author) {
+            Some(vote) => vote,
+            None => return,
+        };
+        let key = (author, vote.epoch_id, vote.round);
+        if self.timings.contains_key(&key) {
+            return;
+        }
+        let (proposal, delivery) = match (
+            self.proposals.get(&(vote.epoch_id, vote.round)),
+            self.deliveries.get(&key),
+        ) {
+            (Some(proposal), Some(delivery)) => (*proposal, *delivery),
+            _ => return,
+        };
+        self.timings.insert(
+            key,
+            VoteTiming {
+                epoch_id: vote.epoch_id,
+                round: vote.round,
+                author,
+                network_delay: delivery.0 - proposal.0,
+                execution_delay: send_time.0 - delivery.0,
+            },
+        );
+    }
+}
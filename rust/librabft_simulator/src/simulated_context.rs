@@ -6,7 +6,7 @@ use base_types::*;
 use record::*;
 use smr_context::*;
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    collections::{hash_map::DefaultHasher, BTreeSet, HashMap, VecDeque},
     hash::{Hash, Hasher},
 };
 
@@ -14,40 +14,166 @@ use std::{
 #[path = "unit_tests/simulated_context_tests.rs"]
 mod simulated_context_tests;
 
+/// How a ledger summarizes its execution history into a `State`.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub enum StateDigest {
+    Standard,
+    /// Mix the given value into the digest. Nodes using different digests never agree on
+    /// execution results, e.g. to simulate an implementation bug.
+    Salted(u64),
+}
+
+impl StateDigest {
+    fn digest(self, ledger_state: &SimulatedLedgerState) -> State {
+        let mut hasher = DefaultHasher::new();
+        if let StateDigest::Salted(salt) = self {
+            salt.hash(&mut hasher);
+        }
+        ledger_state.execution_history.hash(&mut hasher);
+        ledger_state.skipped_commands.hash(&mut hasher);
+        // Only counted when blocks without commands are proposed.
+        if ledger_state.empty_blocks > 0 {
+            ledger_state.empty_blocks.hash(&mut hasher);
+        }
+        // Only measured when a participation window is configured.
+        if !ledger_state.recent_voters.is_empty() {
+            ledger_state.recent_voters.hash(&mut hasher);
+            ledger_state.epoch_voters.hash(&mut hasher);
+        }
+        State(hasher.finish())
+    }
+}
+
 #[derive(Eq, PartialEq, Clone, Debug, Hash)]
 pub struct SimulatedLedgerState {
     /// All the executed commands and theirs consensus times of execution.
     /// TODO: use linked lists with sharing
     execution_history: Vec<(Command, NodeTime)>,
+    /// Number of blocks whose command was skipped because it was already executed.
+    skipped_commands: usize,
+    /// Number of blocks without a command, see `Command::empty`.
+    empty_blocks: usize,
+    /// Voters of the QCs extended by the latest executed blocks, oldest first.
+    recent_voters: VecDeque<Vec<Author>>,
+    /// Value of `recent_voters` when the current epoch started, which decides the inactive
+    /// authors of the epoch.
+    epoch_voters: VecDeque<Vec<Author>>,
 }
 
 impl SimulatedLedgerState {
     fn new() -> SimulatedLedgerState {
         SimulatedLedgerState {
             execution_history: Vec::new(),
+            skipped_commands: 0,
+            empty_blocks: 0,
+            recent_voters: VecDeque::new(),
+            epoch_voters: VecDeque::new(),
         }
     }
 
-    fn key(&self) -> State {
-        let mut hasher = DefaultHasher::new();
-        self.execution_history.hash(&mut hasher);
-        State(hasher.finish())
+    fn key(&self, state_digest: StateDigest) -> State {
+        state_digest.digest(self)
+    }
+
+    /// Generated commands embed their identity, so equal commands are the same command.
+    fn has_executed(&self, command: &Command) -> bool {
+        self.execution_history
+            .iter()
+            .any(|(executed_command, _)| executed_command == command)
     }
 
-    fn execute(&mut self, command: Command, time: NodeTime) {
+    /// Execute the command of a block, unless `deduplicate` is set and the same command was
+    /// already executed.
+    fn execute(&mut self, command: Command, time: NodeTime, deduplicate: bool) {
+        if deduplicate && self.has_executed(&command) {
+            self.skipped_commands += 1;
+            return;
+        }
         self.execution_history.push((command, time));
     }
 
+    /// Remember the voters of the QC extended by the last executed block, keeping `window`
+    /// blocks at most. The first block of an epoch does not extend a QC.
+    fn record_voters(&mut self, voters: Vec<Author>, window: usize) {
+        if voters.is_empty() {
+            return;
+        }
+        self.recent_voters.push_back(voters);
+        while self.recent_voters.len() > window {
+            self.recent_voters.pop_front();
+        }
+    }
+
+    /// Authors whose votes were missing from more than half of the recent QCs when the
+    /// current epoch started.
+    fn inactive_authors<'a, I>(&self, validators: I) -> BTreeSet<Author>
+    where
+        I: Iterator<Item = &'a Author>,
+    {
+        validators
+            .filter(|author| {
+                let num_votes = self
+                    .epoch_voters
+                    .iter()
+                    .filter(|voters| voters.contains(author))
+                    .count();
+                2 * num_votes < self.epoch_voters.len()
+            })
+            .cloned()
+            .collect()
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.execution_history.len() + self.skipped_commands + self.empty_blocks
+    }
+
     fn happened_just_before(&self, other: &SimulatedLedgerState) -> bool {
-        if self.execution_history.len() + 1 != other.execution_history.len() {
-            return false;
+        self.num_blocks() + 1 == other.num_blocks()
+            && other.execution_history.starts_with(&self.execution_history)
+    }
+}
+
+/// Deterministic source of commands. Each command is derived from the seed of the generator,
+/// the author of the command, and a sequence number. This triple is embedded in the payload so
+/// that commit logs can be matched across simulations.
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
+pub struct CommandGenerator {
+    seed: u64,
+}
+
+impl CommandGenerator {
+    pub fn new(seed: u64) -> Self {
+        CommandGenerator { seed }
+    }
+
+    pub fn generate(&self, author: Author, sequence: usize) -> Command {
+        let mut payload = Vec::new();
+        payload.extend_from_slice(&self.seed.to_le_bytes());
+        payload.extend_from_slice(&(author.0 as u64).to_le_bytes());
+        payload.extend_from_slice(&(sequence as u64).to_le_bytes());
+        let mut hasher = StableHasher::new();
+        hasher.write(&payload);
+        payload.extend_from_slice(&hasher.finish().to_le_bytes());
+        Command {
+            proposer: author,
+            index: sequence,
+            payload,
         }
-        for i in 0..self.execution_history.len() {
-            if self.execution_history[i] != other.execution_history[i] {
-                return false;
-            }
+    }
+
+    /// Recover the seed, the author, and the sequence number of a generated command, e.g. to
+    /// align the commit logs of two simulations in tests.
+    #[cfg(test)]
+    pub fn identify(command: &Command) -> Option<(u64, Author, usize)> {
+        if command.payload.len() < 24 {
+            return None;
         }
-        true
+        let read = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&command.payload[8 * i..8 * (i + 1)]);
+            u64::from_le_bytes(bytes)
+        };
+        Some((read(0), Author(read(1) as usize), read(2) as usize))
     }
 }
 
@@ -55,10 +181,36 @@ impl SimulatedLedgerState {
 pub struct SimulatedContext {
     author: Author,
     num_nodes: usize,
+    /// Validators of every epoch, if not all the nodes, e.g. the members of a shard.
+    validators: Option<Vec<Author>>,
     max_command_per_epoch: usize,
     next_fetched_command_index: usize,
+    command_generator: CommandGenerator,
+    state_digest: StateDigest,
+    /// Maximal size of the payload of a block, as configured for all epochs.
+    max_block_bytes: Option<usize>,
+    /// Whether QCs use aggregated signatures, as configured for all epochs.
+    aggregated_signatures: bool,
+    /// Bytes appended to the payload of fetched commands, e.g. to simulate large commands.
+    command_padding: usize,
+    /// Skip the execution of commands that were already executed, e.g. after a re-inclusion.
+    deduplicate_commands: bool,
+    /// Time needed to execute a command.
+    execution_cost: Duration,
+    /// Total time spent executing commands.
+    execution_time: Duration,
+    /// Number of later proposals after which a command of the node that is still not committed
+    /// is proposed again, if any.
+    reinclusion_lag: Option<usize>,
+    num_proposals: usize,
+    /// Commands proposed by the node and not committed yet, with the index of their last proposal.
+    mempool: VecDeque<(usize, Command)>,
     last_committed_ledger_state: SimulatedLedgerState,
     pending_ledger_states: HashMap<State, SimulatedLedgerState>,
+    /// Base round duration chosen by the simulator, used by the node instead of its own.
+    consensus_timeout: Option<ConsensusTimeoutHint>,
+    /// Number of latest blocks over which the participation of validators is measured, if any.
+    participation_window: Option<usize>,
 }
 
 impl SimulatedContext {
@@ -66,15 +218,117 @@ impl SimulatedContext {
         SimulatedContext {
             author,
             num_nodes,
+            validators: None,
             max_command_per_epoch,
             next_fetched_command_index: 0,
+            command_generator: CommandGenerator::new(0),
+            state_digest: StateDigest::Standard,
+            max_block_bytes: None,
+            aggregated_signatures: false,
+            command_padding: 0,
+            deduplicate_commands: false,
+            execution_cost: 0,
+            execution_time: 0,
+            reinclusion_lag: None,
+            num_proposals: 0,
+            mempool: VecDeque::new(),
             last_committed_ledger_state: SimulatedLedgerState::new(),
             pending_ledger_states: HashMap::new(),
+            consensus_timeout: None,
+            participation_window: None,
         }
     }
 
+    pub fn with_command_generator(mut self, command_generator: CommandGenerator) -> Self {
+        self.command_generator = command_generator;
+        self
+    }
+
+    pub fn with_state_digest(mut self, state_digest: StateDigest) -> Self {
+        self.state_digest = state_digest;
+        self
+    }
+
+    /// Spend `cost` to execute each command, e.g. to simulate a slow node.
+    pub fn with_execution_cost(mut self, cost: Duration) -> Self {
+        self.execution_cost = cost;
+        self
+    }
+
+    /// Give voting rights to the given authors only, instead of all the nodes.
+    pub fn with_validators(mut self, validators: Vec<Author>) -> Self {
+        self.validators = Some(validators);
+        self
+    }
+
+    pub fn with_max_block_bytes(mut self, max_block_bytes: usize) -> Self {
+        self.max_block_bytes = Some(max_block_bytes);
+        self
+    }
+
+    pub fn with_aggregated_signatures(mut self) -> Self {
+        self.aggregated_signatures = true;
+        self
+    }
+
+    pub fn with_command_padding(mut self, padding: usize) -> Self {
+        self.command_padding = padding;
+        self
+    }
+
+    pub fn with_command_deduplication(mut self, deduplicate_commands: bool) -> Self {
+        self.deduplicate_commands = deduplicate_commands;
+        self
+    }
+
+    /// Propose again the commands that are still not committed `lag` proposals later, as a
+    /// mempool would after a block looks abandoned.
+    pub fn with_command_reinclusion(mut self, lag: usize) -> Self {
+        self.reinclusion_lag = Some(lag);
+        self
+    }
+
+    /// Measure the participation of validators over the QCs extended by the latest `window`
+    /// executed blocks, and mark the ones missing from more than half of them as inactive in
+    /// the configuration of the next epoch, see `EpochConfiguration::inactive_authors`. Every
+    /// node must use the same window since this changes the execution states.
+    pub fn with_participation_window(mut self, window: usize) -> Self {
+        self.participation_window = Some(window);
+        self
+    }
+
+    /// Start from a ledger where `num_commands` generated commands are already committed, as if
+    /// the nodes were restarted after a warm-up phase. Commands are taken from all the nodes in
+    /// turn, so every node must use the same generator, and this must be called after
+    /// `with_command_generator`.
+    pub fn with_warm_start(mut self, num_commands: usize) -> Self {
+        assert!(
+            num_commands < self.max_command_per_epoch,
+            "Warm-start commands should not change the epoch"
+        );
+        let mut ledger_state = SimulatedLedgerState::new();
+        for index in 0..num_commands {
+            let author = Author(index % self.num_nodes);
+            let command = self
+                .command_generator
+                .generate(author, index / self.num_nodes);
+            if author == self.author {
+                // Fetch the next commands of the node after its warm-start commands.
+                self.next_fetched_command_index += 1;
+            }
+            ledger_state.execute(command, NodeTime(0), self.deduplicate_commands);
+        }
+        self.last_committed_ledger_state = ledger_state;
+        self
+    }
+
+    /// Number of committed blocks whose command was skipped as a duplicate.
+    pub fn num_duplicate_commands(&self) -> usize {
+        self.last_committed_ledger_state.skipped_commands
+    }
+
     pub fn last_committed_state(&self) -> State {
-        self.last_committed_ledger_state.key()
+        self.last_committed_ledger_state.key(self.state_digest)
     }
 
     pub fn committed_history(&self) -> &Vec<(Command, NodeTime)> {
@@ -82,7 +336,7 @@ impl SimulatedContext {
     }
 
     fn get_ledger_state(&self, state: &State) -> Option<&SimulatedLedgerState> {
-        if state == &self.last_committed_ledger_state.key() {
+        if state == &self.last_committed_ledger_state.key(self.state_digest) {
             Some(&self.last_committed_ledger_state)
         } else {
             self.pending_ledger_states.get(state)
@@ -92,11 +346,39 @@ impl SimulatedContext {
 
 impl CommandFetcher for SimulatedContext {
     fn fetch(&mut self) -> Option<Command> {
-        let command = Command {
-            proposer: self.author,
-            index: self.next_fetched_command_index,
-        };
+        self.num_proposals += 1;
+        if let Some(lag) = self.reinclusion_lag {
+            let committed = &self.last_committed_ledger_state;
+            self.mempool
+                .retain(|(_, command)| !committed.has_executed(command));
+            match self.mempool.front() {
+                Some((proposal, _)) if proposal + lag <= self.num_proposals => {
+                    let (_, command) = self.mempool.pop_front().unwrap();
+                    self.mempool
+                        .push_back((self.num_proposals, command.clone()));
+                    return Some(command);
+                }
+                _ => (),
+            }
+        }
+        let mut command = self
+            .command_generator
+            .generate(self.author, self.next_fetched_command_index);
+        command
+            .payload
+            .resize(command.payload.len() + self.command_padding, 0);
+        // Keep a command that does not fit in a block rather than losing it in a block that
+        // would be rejected.
+        if let Some(max_block_bytes) = self.max_block_bytes {
+            if command.payload.len() > max_block_bytes {
+                return None;
+            }
+        }
         self.next_fetched_command_index += 1;
+        if self.reinclusion_lag.is_some() {
+            self.mempool
+                .push_back((self.num_proposals, command.clone()));
+        }
         Some(command)
     }
 }
@@ -108,13 +390,27 @@ impl StateComputer for SimulatedContext {
         command: Command,
         time: NodeTime,
         _previous_author: Option<Author>,
-        _previous_voters: Vec<Author>,
+        previous_voters: Vec<Author>,
     ) -> Option<State> {
         match self.get_ledger_state(base_state) {
             Some(ledger_state) => {
                 let mut new_ledger_state = ledger_state.clone();
-                new_ledger_state.execute(command.clone(), time);
-                let new_state = new_ledger_state.key();
+                if command.is_empty() {
+                    new_ledger_state.empty_blocks += 1;
+                } else {
+                    new_ledger_state.execute(command.clone(), time, self.deduplicate_commands);
+                }
+                if let Some(window) = self.participation_window {
+                    new_ledger_state.record_voters(previous_voters, window);
+                    let epoch_id = |ledger_state: &SimulatedLedgerState| {
+                        ledger_state.execution_history.len() / self.max_command_per_epoch
+                    };
+                    if epoch_id(&new_ledger_state) > epoch_id(ledger_state) {
+                        new_ledger_state.epoch_voters = new_ledger_state.recent_voters.clone();
+                    }
+                }
+                self.execution_time += self.execution_cost;
+                let new_state = new_ledger_state.key(self.state_digest);
                 self.pending_ledger_states
                     .insert(new_state.clone(), new_ledger_state);
                 info!(
@@ -179,14 +475,61 @@ impl EpochReader for SimulatedContext {
         EpochId(num_commands / self.max_command_per_epoch)
     }
 
-    fn configuration(&self, _state: &State) -> EpochConfiguration {
+    fn configuration(&self, state: &State) -> EpochConfiguration {
         // We do not simulate changes in the voting rights yet.
         let mut voting_rights = BTreeMap::new();
-        for index in 0..self.num_nodes {
-            voting_rights.insert(Author(index), 1);
+        match &self.validators {
+            Some(validators) => {
+                for author in validators {
+                    voting_rights.insert(*author, 1);
+                }
+            }
+            None => {
+                for index in 0..self.num_nodes {
+                    voting_rights.insert(Author(index), 1);
+                }
+            }
+        }
+        let mut configuration = EpochConfiguration::new(voting_rights);
+        if let Some(max_block_bytes) = self.max_block_bytes {
+            configuration = configuration.with_max_block_bytes(max_block_bytes);
+        }
+        if self.aggregated_signatures {
+            configuration = configuration.with_aggregated_signatures();
         }
-        EpochConfiguration::new(voting_rights)
+        if let Some(ledger_state) = self.get_ledger_state(state) {
+            let inactive_authors = ledger_state.inactive_authors(configuration.voters());
+            configuration = configuration.with_inactive_authors(inactive_authors);
+        }
+        configuration
     }
 }
 
 impl SMRContext for SimulatedContext {}
+
+impl ConsensusTimeout for SimulatedContext {
+    fn set_consensus_timeout_hint(&mut self, hint: ConsensusTimeoutHint) {
+        self.consensus_timeout = Some(hint);
+    }
+
+    fn consensus_timeout_hint(&self) -> Option<ConsensusTimeoutHint> {
+        self.consensus_timeout
+    }
+}
+
+impl CommittedChain for SimulatedContext {
+    type Entry = (Command, NodeTime);
+
+    fn committed_chain(&self) -> &[(Command, NodeTime)] {
+        &self.last_committed_ledger_state.execution_history
+    }
+
+    fn proposal(&self, entry: &(Command, NodeTime)) -> Option<(Author, NodeTime)> {
+        let (command, time) = entry;
+        Some((command.proposer, *time))
+    }
+
+    fn execution_time(&self) -> Duration {
+        self.execution_time
+    }
+}
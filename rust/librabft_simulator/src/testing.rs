@@ -0,0 +1,183 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use node::NodeState;
+use simulated_context::SimulatedContext;
+use simulator::{RandomDelay, Simulator};
+
+#[cfg(test)]
+#[path = "unit_tests/testing_tests.rs"]
+mod testing_tests;
+
+pub type TestSimulator =
+    Simulator<NodeState, SimulatedContext, BundledNotification, DataSyncRequest, DataSyncResponse>;
+
+/// Network delay between any two nodes.
+const NETWORK_DELAY: f64 = 10.0;
+/// Simulated time after which a single call gives up, by default.
+const DEFAULT_TIME_LIMIT: Duration = 100_000;
+/// Commands per epoch, enough for epochs to never change during a test.
+const EPOCH_LENGTH: usize = 30000;
+
+/// Context of a validator, with the epoch length used by tests.
+pub fn make_context(author: Author, num_nodes: usize) -> SimulatedContext {
+    SimulatedContext::new(author, num_nodes, EPOCH_LENGTH)
+}
+
+/// Node with the pacemaker parameters used by tests.
+pub fn make_node(author: Author, context: &SimulatedContext, clock: NodeTime) -> NodeState {
+    NodeState::new(
+        author,
+        context.last_committed_state(),
+        clock,
+        /* target_commit_interval */ 100000,
+        /* delta */ 20,
+        /* gamma */ 2.0,
+        /* lambda */ 0.5,
+        context,
+    )
+}
+
+/// Simulator of `num_nodes` validators over a network with random delays, after applying the
+/// given options to the context of every author and to every node.
+pub fn make_configured_simulator<F, G>(
+    num_nodes: usize,
+    seed: u64,
+    configure_context: F,
+    configure_node: G,
+) -> TestSimulator
+where
+    F: Fn(Author, SimulatedContext) -> SimulatedContext,
+    G: Fn(NodeState) -> NodeState,
+{
+    let context_factory =
+        |author, num_nodes| configure_context(author, make_context(author, num_nodes));
+    let node_factory = |author, context: &SimulatedContext, clock| {
+        configure_node(make_node(author, context, clock))
+    };
+    Simulator::new(
+        num_nodes,
+        RandomDelay::new(NETWORK_DELAY, 4.0),
+        context_factory,
+        node_factory,
+    )
+    .with_seed(seed)
+}
+
+/// A small network of honest validators for unit tests: constant network delays, a fixed seed
+/// and no observers. Events go through the same event loop as `Simulator::loop_until`, one step
+/// at a time. Methods panic with a readable message when the expected progress is not made.
+pub struct TestNet {
+    simulator: TestSimulator,
+    time_limit: Duration,
+}
+
+impl TestNet {
+    pub fn new(num_nodes: usize) -> Self {
+        Self::custom(num_nodes, |context| context, |node| node)
+    }
+
+    /// Same as `new`, after applying the given options to every context and every node.
+    pub fn custom<F, G>(num_nodes: usize, configure_context: F, configure_node: G) -> Self
+    where
+        F: Fn(SimulatedContext) -> SimulatedContext,
+        G: Fn(NodeState) -> NodeState,
+    {
+        Self::custom_with_epoch_length(num_nodes, EPOCH_LENGTH, configure_context, configure_node)
+    }
+
+    /// Same as `custom`, with epochs of `epoch_length` commands.
+    pub fn custom_with_epoch_length<F, G>(
+        num_nodes: usize,
+        epoch_length: usize,
+        configure_context: F,
+        configure_node: G,
+    ) -> Self
+    where
+        F: Fn(SimulatedContext) -> SimulatedContext,
+        G: Fn(NodeState) -> NodeState,
+    {
+        let context_factory = |author, num_nodes| {
+            configure_context(SimulatedContext::new(author, num_nodes, epoch_length))
+        };
+        let node_factory = |author, context: &SimulatedContext, clock| {
+            configure_node(make_node(author, context, clock))
+        };
+        let simulator = Simulator::new(
+            num_nodes,
+            RandomDelay::constant(NETWORK_DELAY),
+            context_factory,
+            node_factory,
+        )
+        .with_seed(0);
+        TestNet {
+            simulator,
+            time_limit: DEFAULT_TIME_LIMIT,
+        }
+    }
+
+    /// Simulated time after which `run_rounds` gives up.
+    pub fn with_time_limit(mut self, time_limit: Duration) -> Self {
+        self.time_limit = time_limit;
+        self
+    }
+
+    /// Run until every node is `num_rounds` rounds past the lowest active round.
+    pub fn run_rounds(&mut self, num_rounds: usize) {
+        let start = self
+            .simulator
+            .nodes_iter()
+            .map(|(_, node)| node.active_round())
+            .min()
+            .unwrap_or(Round(0));
+        let target = start + num_rounds;
+        let deadline = self.simulator.current_clock() + self.time_limit;
+        while !self.simulator.all_nodes_reached_round(target) {
+            assert!(
+                self.simulator.step(deadline),
+                "Some node did not reach {} before {:?}",
+                target,
+                deadline
+            );
+        }
+    }
+
+    /// Deliver the messages currently in flight, together with the timers and messages due
+    /// before the last of them.
+    pub fn deliver_all_pending(&mut self) {
+        if let Some(deadline) = self.simulator.last_delivery_time() {
+            while self.simulator.step(deadline) {}
+        }
+    }
+
+    pub fn node(&self, author: Author) -> &NodeState {
+        self.simulator.simulated_node(author).node()
+    }
+
+    pub fn context(&self, author: Author) -> &SimulatedContext {
+        self.simulator.simulated_node(author).context()
+    }
+
+    pub fn simulator(&self) -> &TestSimulator {
+        &self.simulator
+    }
+
+    /// Mutable variant of `simulator`, e.g. to inject faults.
+    pub fn simulator_mut(&mut self) -> &mut TestSimulator {
+        &mut self.simulator
+    }
+
+    pub fn assert_all_committed_through(&self, round: Round) {
+        for (author, node) in self.simulator.nodes_iter() {
+            let committed_round = node.node().record_store().highest_committed_round();
+            assert!(
+                committed_round >= round,
+                "{:?} only committed through {}, expected {}",
+                author,
+                committed_round,
+                round
+            );
+        }
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use std::{cell::RefCell, rc::Rc};
+use testing::TestNet;
+
+fn make_audited_net(num_rounds: usize) -> (TestNet, Rc<RefCell<AuditObserver>>) {
+    let mut net = TestNet::new(4);
+    let observer = Rc::new(RefCell::new(AuditObserver::new()));
+    net.simulator_mut().add_observer(observer.clone());
+    net.run_rounds(num_rounds);
+    (net, observer)
+}
+
+fn make_log() -> (TestNet, Vec<AuditEntry>) {
+    let (net, observer) = make_audited_net(10);
+    let log = observer.borrow().log(Author(1)).unwrap().export();
+    (net, log)
+}
+
+/// Sign again an entry that was modified, as its author could.
+fn resign(entry: &mut AuditEntry) {
+    entry.signature = Signature::sign(entry.digest(), entry.author);
+}
+
+fn first_granted_vote(log: &[AuditEntry]) -> usize {
+    log.iter()
+        .position(|entry| matches!(entry.decision, Decision::VoteGranted { .. }))
+        .unwrap()
+}
+
+#[test]
+fn test_honest_run_verifies() {
+    let (net, observer) = make_audited_net(10);
+    for index in 0..4 {
+        let author = Author(index);
+        let node = net.node(author);
+        let observer = observer.borrow();
+        let log = observer.log(author).unwrap().entries();
+        assert!(log.len() >= 10);
+        assert_eq!(verify(log, author, node), Ok(()));
+        // Nobody else signed these entries.
+        assert_eq!(
+            verify(log, Author((index + 1) % 4), node),
+            Err(AuditViolation::InvalidSignature { index: 0 })
+        );
+    }
+}
+
+#[test]
+fn test_tampered_entry_is_detected() {
+    let (net, mut log) = make_log();
+    let node = net.node(Author(1));
+    let index = first_granted_vote(&log);
+    log[index].locked_round = log[index].locked_round + 1;
+    assert_eq!(
+        verify(&log, Author(1), node),
+        Err(AuditViolation::InvalidSignature { index })
+    );
+    // A node signing a false account of its constraints is caught by the replay.
+    resign(&mut log[index]);
+    match verify(&log, Author(1), node) {
+        Err(AuditViolation::WrongConstraints {
+            index: found_index, ..
+        }) => assert_eq!(found_index, index),
+        result => panic!("Unexpected result {:?}", result),
+    }
+}
+
+#[test]
+fn test_hidden_vote_is_detected() {
+    let (net, mut log) = make_log();
+    let node = net.node(Author(1));
+    // Claiming that a vote was denied breaks the voting rules.
+    let index = first_granted_vote(&log);
+    let block_hash = match log[index].decision {
+        Decision::VoteGranted { block_hash } => block_hash,
+        _ => unreachable!(),
+    };
+    let mut forged_log = log.clone();
+    forged_log[index].decision = Decision::VoteDenied {
+        block_hash,
+        reasons: vec![DenialReason::RoundNotAboveLatestVote],
+    };
+    resign(&mut forged_log[index]);
+    assert_eq!(
+        verify(&forged_log, Author(1), node),
+        Err(AuditViolation::WrongDecision {
+            index,
+            expected: Decision::VoteGranted { block_hash },
+        })
+    );
+    // Removing an entry breaks the sequence.
+    log.remove(index);
+    assert_eq!(
+        verify(&log, Author(1), node),
+        Err(AuditViolation::MissingEntry { index })
+    );
+}
+
+#[test]
+fn test_unlogged_vote_is_detected() {
+    let (mut net, observer) = make_audited_net(5);
+    // Votes are only kept by the leader who collects them, until the next round.
+    let holder = |net: &TestNet| {
+        (0..4).map(Author).find(|author| {
+            net.node(*author)
+                .record_store()
+                .current_vote(Author(1))
+                .is_some()
+        })
+    };
+    let deadline = net.simulator().current_clock() + 1000;
+    while holder(&net).is_none() {
+        assert!(net.simulator_mut().step(deadline));
+    }
+    let node = net.node(holder(&net).unwrap());
+    let vote = node.record_store().current_vote(Author(1)).unwrap().clone();
+    let mut log = observer.borrow().log(Author(1)).unwrap().export();
+    let granted = Decision::VoteGranted {
+        block_hash: vote.certified_block_hash,
+    };
+    let index = log
+        .iter()
+        .position(|entry| entry.decision == granted)
+        .unwrap();
+    log.truncate(index);
+    assert_eq!(
+        verify(&log, Author(1), node),
+        Err(AuditViolation::UnloggedVote {
+            epoch_id: vote.epoch_id,
+            round: vote.round,
+            block_hash: vote.certified_block_hash,
+        })
+    );
+}
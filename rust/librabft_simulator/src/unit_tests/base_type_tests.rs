@@ -19,3 +19,38 @@ fn test_merge_sort() {
         merge_sort(vec![0, 2, 6, 9], vec![2, 5, 6], u64::cmp),
     );
 }
+
+#[test]
+fn test_command_deterministic_hash() {
+    let command = |proposer, payload: &[u8]| Command {
+        proposer: Author(proposer),
+        index: 2,
+        payload: payload.to_vec(),
+    };
+    assert_eq!(
+        command(1, &[1, 2]).deterministic_hash(),
+        command(1, &[1, 2]).deterministic_hash()
+    );
+    assert_ne!(
+        command(1, &[1, 2]).deterministic_hash(),
+        command(2, &[1, 2]).deterministic_hash()
+    );
+    assert_ne!(
+        command(1, &[1, 2]).deterministic_hash(),
+        command(1, &[1, 3]).deterministic_hash()
+    );
+}
+
+#[test]
+fn test_epoch_id_arithmetic() {
+    assert_eq!(EpochId(0).previous(), None);
+    assert_eq!(EpochId(3).previous(), Some(EpochId(2)));
+    assert_eq!(EpochId(3).next(), EpochId(4));
+    assert_eq!(EpochId(3).to_string(), "e3");
+}
+
+#[test]
+#[should_panic(expected = "Epoch overflow")]
+fn test_epoch_id_overflow() {
+    EpochId(usize::MAX).next();
+}
@@ -1,2 +1,86 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use bft_simulator_runtime::base_types::*;
+use simulator::GlobalTime;
+use testing::{make_configured_simulator, TestNet};
+
+#[test]
+fn test_certified_block_sent_with_timeouts() {
+    let mut sim = make_configured_simulator(
+        4,
+        5,
+        |_, context| context,
+        |node| node.with_certified_block_in_timeouts(),
+    );
+    // Rounds led by the crashed node time out.
+    sim.crash_node(Author(3));
+    let mut num_bundled_blocks = 0;
+    for clock in 1..20 {
+        sim.loop_until(GlobalTime(100 * clock), None);
+        for (_, node) in sim.nodes_iter().take(3) {
+            let bundle = node.node().create_bundled_notification();
+            let notification = &bundle.notification;
+            match (
+                &notification.highest_quorum_certificate,
+                &bundle.highest_certified_block,
+            ) {
+                (Some(qc), Some(block)) => {
+                    assert!(!notification.timeouts.is_empty());
+                    assert_eq!(block.round, qc.round);
+                    num_bundled_blocks += 1;
+                }
+                (_, None) => assert!(
+                    notification.timeouts.is_empty()
+                        || notification.highest_quorum_certificate.is_none()
+                ),
+                (None, Some(_)) => unreachable!(),
+            }
+        }
+    }
+    assert!(num_bundled_blocks > 0);
+}
+
+#[test]
+fn test_aggregated_signatures() {
+    let mut net = TestNet::custom(
+        4,
+        |context| context.with_aggregated_signatures(),
+        |node| node,
+    );
+    net.run_rounds(10);
+    net.assert_all_committed_through(Round(5));
+    let bundle = net.node(Author(0)).create_bundled_notification();
+    assert!(bundle.notification.highest_quorum_certificate.is_some());
+    assert!(bundle.message_size() < bundle.notification.message_size());
+}
+
+#[test]
+fn test_commit_certificate_of_previous_epoch_is_notified() {
+    let mut net = TestNet::custom_with_epoch_length(
+        4,
+        /* commands */ 10,
+        |context| context,
+        |node| node,
+    );
+    let mut num_checks = 0;
+    for clock in 1..100 {
+        net.simulator_mut().loop_until(GlobalTime(20 * clock), None);
+        for (_, node) in net.simulator().nodes_iter() {
+            let node = node.node();
+            if node.epoch_id() > EpochId(0)
+                && node.record_store().highest_commit_certificate().is_none()
+            {
+                // Nodes that just entered an epoch pass on the last commit of the previous one.
+                let certificate = node
+                    .create_notification_internal()
+                    .highest_commit_certificate
+                    .unwrap();
+                assert_eq!(Some(certificate.epoch_id), node.epoch_id().previous());
+                num_checks += 1;
+            }
+        }
+    }
+    assert!(num_checks > 0);
+}
@@ -0,0 +1,139 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use node::NodeState;
+use record_store::RecordStoreState;
+use simulated_context::SimulatedContext;
+use smr_context::EpochReader;
+use simulator::{GlobalTime, RandomDelay, Simulator};
+use std::collections::{BTreeMap, BTreeSet};
+use testing::{make_node, TestSimulator};
+
+/// Commands per epoch, so that the reputation election is recomputed a few times per run.
+const EPOCH_LENGTH: usize = 20;
+
+fn make_simulator(leader_election: LeaderElection) -> TestSimulator {
+    let context_factory = |author, num_nodes| {
+        SimulatedContext::new(author, num_nodes, EPOCH_LENGTH)
+            .with_participation_window(/* window */ 10)
+    };
+    let node_factory = move |author: Author, context: &SimulatedContext, clock: NodeTime| {
+        make_node(author, context, clock).with_leader_election(leader_election)
+    };
+    Simulator::new(
+        4,
+        RandomDelay::new(10.0, 4.0),
+        context_factory,
+        node_factory,
+    )
+    .with_seed(3)
+}
+
+#[test]
+fn test_round_robin() {
+    let context = SimulatedContext::new(Author(0), 4, 30);
+    let state = context.last_committed_state();
+    let store = RecordStoreState::new(
+        QuorumCertificateHash(0),
+        state.clone(),
+        EpochId(0),
+        context.configuration(&state),
+    );
+    let hashed: Vec<_> = (1..9).map(|round| store.leader(Round(round))).collect();
+    let store = store.with_leader_election(LeaderElection::RoundRobin);
+    let leaders: Vec<_> = (1..9).map(|round| store.leader(Round(round)).0).collect();
+    assert_eq!(leaders, vec![1, 2, 3, 0, 1, 2, 3, 0]);
+    // Without inactive authors, the reputation election is the hashed one.
+    let store = store.with_leader_election(LeaderElection::Reputation);
+    let leaders: Vec<_> = (1..9).map(|round| store.leader(Round(round))).collect();
+    assert_eq!(leaders, hashed);
+}
+
+#[test]
+fn test_reputation_skips_inactive_authors() {
+    let mut voting_rights = BTreeMap::new();
+    for index in 0..4 {
+        voting_rights.insert(Author(index), 1);
+    }
+    let inactive_authors = vec![Author(1), Author(3)].into_iter().collect();
+    let configuration =
+        EpochConfiguration::new(voting_rights).with_inactive_authors(inactive_authors);
+    let leaders: BTreeSet<_> = (1..100)
+        .map(|round| LeaderElection::Reputation.leader(&configuration, Round(round)))
+        .collect();
+    assert_eq!(leaders, vec![Author(0), Author(2)].into_iter().collect());
+}
+
+#[test]
+fn test_reputation_consistency() {
+    let mut sim = make_simulator(LeaderElection::Reputation);
+    sim.loop_until(GlobalTime(1000), None);
+    sim.crash_node(Author(3));
+    let crash_epoch = sim.simulated_node(Author(0)).node().epoch_id();
+    sim.loop_until(GlobalTime(8000), None);
+    let live_nodes = [Author(0), Author(1), Author(2)];
+    let nodes: Vec<_> = live_nodes
+        .iter()
+        .map(|author| sim.simulated_node(*author).node())
+        .collect();
+    let current_epoch = nodes.iter().map(|node| node.epoch_id()).min().unwrap();
+    assert!(current_epoch.0 >= crash_epoch.0 + 3);
+    // Leaders only depend on the configuration of the epoch, hence are the same at all honest
+    // nodes.
+    let rounds = (1..100).map(Round);
+    for epoch_id in 0..=current_epoch.0 {
+        let stores: Vec<_> = nodes
+            .iter()
+            .map(|node| node.record_store_at(EpochId(epoch_id)).unwrap())
+            .collect();
+        for round in rounds.clone() {
+            let leader = stores[0].leader(round);
+            assert!(stores.iter().all(|store| store.leader(round) == leader));
+        }
+        // The crashed node is not elected anymore once an epoch starts after a full window
+        // without its votes.
+        if epoch_id >= crash_epoch.0 + 2 {
+            assert!(rounds
+                .clone()
+                .all(|round| stores[0].leader(round) != Author(3)));
+        }
+    }
+}
+
+/// Rounds that ended without a QC at the given node, up to its last commit in each epoch.
+fn num_skipped_rounds(node: &NodeState) -> usize {
+    (0..=node.epoch_id().0)
+        .filter_map(|epoch_id| node.record_store_at(EpochId(epoch_id)))
+        .map(|store| {
+            let committed_round = store.highest_committed_round();
+            let num_certified_rounds = store
+                .quorum_certificate_votes()
+                .range(..=committed_round)
+                .count();
+            committed_round.0 - num_certified_rounds
+        })
+        .sum()
+}
+
+#[test]
+fn test_reputation_avoids_crashed_leader() {
+    let run = |leader_election| {
+        let mut sim = make_simulator(leader_election);
+        sim.loop_until(GlobalTime(1000), None);
+        sim.crash_node(Author(3));
+        sim.loop_until(GlobalTime(12000), None);
+        let node = sim.simulated_node(Author(0));
+        (
+            num_skipped_rounds(node.node()),
+            node.context().committed_history().len(),
+        )
+    };
+    let (round_robin_skipped, round_robin_commits) = run(LeaderElection::RoundRobin);
+    let (reputation_skipped, reputation_commits) = run(LeaderElection::Reputation);
+    // Round-robin skips a round every time the crashed node leads, reputation only until an
+    // epoch starts without the crashed node.
+    assert!(reputation_skipped < round_robin_skipped);
+    assert!(reputation_commits > round_robin_commits);
+}
+
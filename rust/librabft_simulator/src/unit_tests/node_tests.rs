@@ -6,6 +6,7 @@ use simulated_context::*;
 use smr_context::*;
 use std::{
     collections::hash_map::DefaultHasher,
+    convert::TryFrom,
     hash::{Hash, Hasher},
 };
 
@@ -32,7 +33,14 @@ fn test_node() {
 
     // Make a sequence of blocks / QCs
     let cmd = context.fetch().unwrap();
-    let b0 = Record::make_block(cmd.clone(), NodeTime(1), initial_hash, Round(1), Author(0));
+    let b0 = Record::make_block(
+        cmd.clone(),
+        NodeTime(1),
+        initial_hash,
+        Round(1),
+        context.configuration(&initial_state).digest(),
+        Author(0),
+    );
 
     let mut hasher = DefaultHasher::new();
     b0.hash(&mut hasher);
@@ -42,17 +50,15 @@ fn test_node() {
         .compute(&initial_state, cmd, NodeTime(1), None, Vec::new())
         .unwrap();
 
-    let v0 = match Record::make_vote(
+    let v0 = Vote::try_from(Record::make_vote(
         epoch_id,
         Round(1),
         block_hash,
         state.clone(),
         Author(0),
         /* commitment */ None,
-    ) {
-        Record::Vote(x) => x,
-        _ => unreachable!(),
-    };
+    ))
+    .unwrap();
     let qc0 = Record::make_quorum_certificate(
         epoch_id,
         Round(1),
@@ -71,3 +77,125 @@ fn test_node() {
         qc_hash
     );
 }
+
+fn make_node(author: Author, context: &SimulatedContext) -> NodeState {
+    NodeState::new(
+        author,
+        context.last_committed_state(),
+        NodeTime(0),
+        1000,
+        30,
+        2.0,
+        0.5,
+        context,
+    )
+}
+
+#[test]
+fn test_create_block() {
+    let mut context = SimulatedContext::new(
+        Author(0),
+        /* num_nodes */ 1,
+        /* max commands per epoch */ 2,
+    );
+    let mut node = make_node(Author(0), &context);
+    let block = node.create_block(Round(5), &mut context).unwrap();
+    assert_eq!(block.round, Round(5));
+    assert_eq!(block.author, Author(0));
+    assert_eq!(
+        block.previous_quorum_certificate_hash,
+        EpochId(0).initial_hash()
+    );
+    // The block is only inserted once sent.
+    let block_hash = BlockHash(Record::Block(block.clone()).digest());
+    assert_eq!(node.record_store().block(block_hash), None);
+    node.send_block(Record::Block(block.clone()), &mut context);
+    assert_eq!(node.record_store().block(block_hash), Some(&block));
+}
+
+#[test]
+fn test_create_block_when_not_leader() {
+    let mut context = SimulatedContext::new(
+        Author(0),
+        /* num_nodes */ 2,
+        /* max commands per epoch */ 2,
+    );
+    let mut node = make_node(Author(0), &context);
+    let round = (1..100)
+        .map(Round)
+        .find(|round| PacemakerState::leader(node.record_store(), *round) == Author(1))
+        .unwrap();
+    assert_eq!(node.create_block(round, &mut context), None);
+}
+
+#[test]
+fn test_update_node_should_propose() {
+    let mut context = SimulatedContext::new(
+        Author(0),
+        /* num_nodes */ 1,
+        /* max commands per epoch */ 2,
+    );
+    let mut node = make_node(Author(0), &context);
+    node.update_node(NodeTime(1), &mut context);
+    assert!(node.round_actions().should_propose);
+    // The proposal is left to `create_block`.
+    let round = node.active_round();
+    assert_eq!(node.record_store().proposed_block(&node.pacemaker), None);
+    let block = node.create_block(round, &mut context).unwrap();
+    let block_hash = BlockHash(Record::Block(block.clone()).digest());
+    node.send_block(Record::Block(block), &mut context);
+    assert_eq!(
+        node.record_store().proposed_block(&node.pacemaker),
+        Some((block_hash, round, Author(0)))
+    );
+    // The leader votes for its proposal before its next update, as when it proposed from
+    // `update_node`.
+    let vote = node.record_store().current_vote(Author(0)).unwrap();
+    assert_eq!(vote.certified_block_hash, block_hash);
+    assert_eq!(node.voting_constraints().0, round);
+}
+
+#[test]
+fn test_proposer_and_follower_timeouts() {
+    let make_timed_node = |author| {
+        let context = SimulatedContext::new(author, /* num_nodes */ 4, 10);
+        let node = make_node(author, &context)
+            .with_proposer_timeout(50)
+            .with_follower_timeout(80);
+        (node, context)
+    };
+    let leader = PacemakerState::leader(make_timed_node(Author(0)).0.record_store(), Round(1));
+    // Without a proposal, the follower times out after 80 instead of the round duration of 30.
+    let (mut follower, mut context) = make_timed_node(Author((leader.0 + 1) % 4));
+    let actions = follower.update_node(NodeTime(0), &mut context);
+    assert_eq!(actions.next_scheduled_update, NodeTime(80));
+    follower.update_node(NodeTime(79), &mut context);
+    assert_eq!(follower.round_actions().on_timeout, None);
+    follower.update_node(NodeTime(80), &mut context);
+    assert_eq!(follower.round_actions().on_timeout, Some(Round(1)));
+    // Without a quorum of votes, the leader times out after 50.
+    let (mut node, mut leader_context) = make_timed_node(leader);
+    node.update_node(NodeTime(0), &mut leader_context);
+    assert!(node.round_actions().should_propose);
+    let block = node.create_block(Round(1), &mut leader_context).unwrap();
+    node.send_block(Record::Block(block.clone()), &mut leader_context);
+    let actions = node.update_node(NodeTime(0), &mut leader_context);
+    assert_eq!(actions.next_scheduled_update, NodeTime(50));
+    node.update_node(NodeTime(49), &mut leader_context);
+    assert_eq!(node.round_actions().on_timeout, None);
+    node.update_node(NodeTime(50), &mut leader_context);
+    assert_eq!(node.round_actions().on_timeout, Some(Round(1)));
+    // A proposal received after the round duration leaves the round duration to vote on it.
+    let author = Author((leader.0 + 2) % 4);
+    let (mut follower, mut context) = make_timed_node(author);
+    follower.update_node(NodeTime(0), &mut context);
+    follower.insert_network_record(EpochId(0), Record::Block(block), &mut context);
+    let actions = follower.update_node(NodeTime(70), &mut context);
+    assert_eq!(follower.round_actions().on_timeout, None);
+    assert_eq!(actions.next_scheduled_update, NodeTime(100));
+    assert!(follower.record_store().current_vote(author).is_some());
+    follower.update_node(NodeTime(99), &mut context);
+    assert_eq!(follower.round_actions().on_timeout, None);
+    follower.update_node(NodeTime(100), &mut context);
+    assert_eq!(follower.round_actions().on_timeout, Some(Round(1)));
+}
@@ -1,2 +1,27 @@
 // Copyright (c) Calibra Research
 // SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_round_duration_near_max_round() {
+    let pacemaker = PacemakerState::new(
+        EpochId(0),
+        NodeTime(0),
+        /* delta */ 20,
+        /* gamma */ 2.0,
+        /* lambda */ 0.5,
+    );
+    assert_eq!(pacemaker.round_duration(Round(3), Round(2)), 20);
+    assert_eq!(pacemaker.round_duration(Round(5), Round(2)), 180);
+    let max = usize::MAX;
+    assert_eq!(pacemaker.round_duration(Round(max), Round(max - 1)), 20);
+    assert_eq!(pacemaker.round_duration(Round(max), Round(max - 3)), 180);
+}
+
+#[test]
+#[should_panic(expected = "Active round is higher than any QC round.")]
+fn test_round_duration_of_old_round() {
+    let pacemaker = PacemakerState::new(EpochId(0), NodeTime(0), 20, 2.0, 0.5);
+    pacemaker.round_duration(Round(2), Round(2));
+}
@@ -0,0 +1,72 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use librabft_simulator::testing::TestNet;
+use simulator::GlobalTime;
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn test_participation_with_restarted_node() {
+    let mut net = TestNet::new(4);
+    let votes = Rc::new(RefCell::new(VoteTracker::new()));
+    let sim = net.simulator_mut();
+    sim.add_observer(votes.clone());
+    let active_round =
+        |sim: &Simulator<_, _, _, _, _>| sim.simulated_node(Author(0)).active_round();
+    sim.loop_until(GlobalTime(1000), None);
+    sim.crash_node(Author(3));
+    let crash_round = active_round(sim);
+    sim.loop_until(GlobalTime(3000), None);
+    sim.recover_node(Author(3));
+    let recovery_round = active_round(sim);
+    sim.loop_until(GlobalTime(6000), None);
+    let end_round = active_round(sim);
+
+    // No participation while crashed.
+    let downtime = Round(crash_round.0 + 1)..recovery_round;
+    let table = participation_table(sim, &votes.borrow(), Author(0), downtime.clone());
+    let num_rounds = downtime.end.0 - downtime.start.0;
+    assert!(num_rounds > 0);
+    assert_eq!(
+        table[3],
+        ParticipationSummary {
+            author: Author(3),
+            voted_rounds: 0,
+            missed_rounds: num_rounds,
+            timed_out_rounds: 0,
+            qcs_included: 0,
+        }
+    );
+    assert!(table[..3].iter().all(|row| row.qcs_included > 0));
+    // Participation resumes after the restart.
+    let table = participation_table(sim, &votes.borrow(), Author(0), recovery_round..end_round);
+    assert!(table[3].voted_rounds > 0);
+    assert!(table[3].qcs_included > 0);
+}
+
+#[test]
+fn test_write_participation_table() {
+    let path = std::env::temp_dir().join(format!(
+        "librabft_participation_test_{}",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&path).unwrap();
+    let rows = vec![ParticipationSummary {
+        author: Author(2),
+        voted_rounds: 5,
+        missed_rounds: 1,
+        timed_out_rounds: 2,
+        qcs_included: 4,
+    }];
+    write_participation_table(path.to_str().unwrap(), &rows);
+    let content = std::fs::read_to_string(path.join("participation.txt")).unwrap();
+    let mut lines = content.lines();
+    assert_eq!(lines.next(), Some("# schema_version: 1.0"));
+    assert_eq!(
+        lines.next(),
+        Some("author,voted_rounds,missed_rounds,timed_out_rounds,qcs_included")
+    );
+    assert!(lines.next().is_some());
+    std::fs::remove_dir_all(path).unwrap();
+}
@@ -21,11 +21,7 @@ impl SharedRecordStore {
                 SimulatedContext::new(Author(i), num_nodes, epoch_ttl),
             );
         }
-        let state = contexts
-            .get(&Author(0))
-            .unwrap()
-            .last_committed_state()
-            ;
+        let state = contexts.get(&Author(0)).unwrap().last_committed_state();
         SharedRecordStore {
             store: RecordStoreState::new(
                 initial_hash,
@@ -50,9 +46,11 @@ impl SharedRecordStore {
         clock: NodeTime,
     ) {
         let author = Author(author_id);
+        let round = self.store.current_round();
         self.store.propose_block(
             author,
             previous_qc_hash,
+            round,
             clock,
             self.contexts.get_mut(&author).unwrap(),
         );
@@ -77,9 +75,11 @@ impl SharedRecordStore {
     fn make_round(&mut self, clock: NodeTime) {
         let author = self.leader(self.store.current_round());
         let previous_qc_hash = self.store.highest_quorum_certificate_hash();
+        let round = self.store.current_round();
         self.store.propose_block(
             author,
             previous_qc_hash,
+            round,
             clock,
             self.contexts.get_mut(&author).unwrap(),
         );
@@ -291,3 +291,256 @@ fn test_commit() {
             .as_ref()
     );
 }
+
+#[test]
+fn test_orphaned_blocks() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    // Both nodes propose a block at round 1 but only the leader's proposal can be certified.
+    shared_store.propose_block(0, QuorumCertificateHash(0), NodeTime(1));
+    shared_store.propose_block(1, QuorumCertificateHash(0), NodeTime(2));
+    let proposed_hash = shared_store.store.current_proposed_block.unwrap();
+    let losing_hash = *shared_store
+        .store
+        .blocks
+        .keys()
+        .find(|hash| **hash != proposed_hash)
+        .unwrap();
+    assert!(shared_store.create_vote(0, proposed_hash));
+    assert!(shared_store.create_vote(1, proposed_hash));
+    assert!(shared_store.check_for_new_quorum_certificate());
+    // Nothing is orphaned before the first commit.
+    assert_eq!(shared_store.store.orphaned_blocks(Round(0)), vec![]);
+    shared_store.make_round(NodeTime(20));
+    shared_store.make_round(NodeTime(30));
+    assert_eq!(shared_store.store.highest_committed_round(), Round(1));
+    assert_eq!(
+        shared_store.store.orphaned_blocks(Round(0)),
+        vec![(losing_hash, Round(1))]
+    );
+    // Rounds that were already checked are skipped.
+    assert_eq!(shared_store.store.orphaned_blocks(Round(1)), vec![]);
+}
+
+#[test]
+fn test_oversized_block() {
+    let mut context = SimulatedContext::new(Author(0), 1, 30).with_max_block_bytes(40);
+    let state = context.last_committed_state();
+    let initial_hash = QuorumCertificateHash(0);
+    let mut store = RecordStoreState::new(
+        initial_hash,
+        state.clone(),
+        EpochId(0),
+        context.configuration(&state),
+    );
+    let mut stuffing_context = SimulatedContext::new(Author(0), 1, 30).with_command_padding(100);
+    let block = store.propose_block(
+        Author(0),
+        initial_hash,
+        Round(1),
+        NodeTime(1),
+        &mut stuffing_context,
+    );
+    assert_eq!(block, None);
+    assert_eq!(store.num_oversized_blocks(), 1);
+
+    let command = stuffing_context.fetch().unwrap();
+    let size = command.payload.len();
+    let block = Block::try_from(Record::make_block(
+        command,
+        NodeTime(1),
+        initial_hash,
+        Round(1),
+        store.configuration().digest(),
+        Author(0),
+    ))
+    .unwrap();
+    assert_eq!(
+        store.check_block_size(&block),
+        Err(RejectReason::BlockTooLarge {
+            size,
+            max_block_bytes: 40
+        })
+    );
+    // Regular commands fit.
+    assert!(store
+        .propose_block(Author(0), initial_hash, Round(1), NodeTime(1), &mut context)
+        .is_some());
+}
+
+#[test]
+fn test_epoch_config_mismatch() {
+    let mut context = SimulatedContext::new(Author(0), 1, 30);
+    let state = context.last_committed_state();
+    let initial_hash = QuorumCertificateHash(0);
+    let configuration = context.configuration(&state);
+    let mut store = RecordStoreState::new(
+        initial_hash,
+        state.clone(),
+        EpochId(0),
+        configuration.clone(),
+    );
+    // A proposer that believes in a second validator.
+    let other_configuration = SimulatedContext::new(Author(0), 2, 30).configuration(&state);
+    let command = context.fetch().unwrap();
+    let block = Block::try_from(Record::make_block(
+        command,
+        NodeTime(1),
+        initial_hash,
+        Round(1),
+        other_configuration.digest(),
+        Author(0),
+    ))
+    .unwrap();
+    assert_eq!(
+        store.check_epoch_configuration(&block),
+        Err(RejectReason::EpochConfigMismatch {
+            expected: configuration.digest(),
+            found: other_configuration.digest(),
+        })
+    );
+    store.insert_network_record(Record::Block(block), &mut context);
+    assert_eq!(store.num_epoch_config_mismatches(), 1);
+    assert_eq!(store.current_proposed_block, None);
+    // Our own proposals carry our configuration.
+    assert!(store
+        .propose_block(Author(0), initial_hash, Round(1), NodeTime(1), &mut context)
+        .is_some());
+    assert_eq!(store.num_epoch_config_mismatches(), 1);
+}
+
+#[test]
+fn test_iterate_records() {
+    let make_store = || {
+        let mut shared_store = SharedRecordStore::new(2, 20);
+        shared_store.make_round(NodeTime(10));
+        shared_store.make_tc();
+        shared_store.make_round(NodeTime(30));
+        shared_store.create_timeout(0, Round(4));
+        shared_store.store
+    };
+    let store = make_store();
+    let store: &RecordStore = &store;
+    let rounds: Vec<_> = store.iter().map(|record| record.round()).collect();
+    let mut sorted_rounds = rounds.clone();
+    sorted_rounds.sort();
+    assert_eq!(rounds, sorted_rounds);
+    // Block, two votes and QC at rounds 1 and 3, two timeouts at round 2, one timeout at
+    // round 4, in order of insertion.
+    assert_eq!(rounds.len(), 11);
+    assert_eq!(
+        store
+            .iter()
+            .filter(|record| record.round() == Round(2))
+            .count(),
+        2
+    );
+    let mut num_blocks = 0;
+    for record in store {
+        if let Record::Block(_) = record {
+            num_blocks += 1;
+        }
+    }
+    assert_eq!(num_blocks, 2);
+
+    // The order only depends on the insertions.
+    let other_store = make_store();
+    let other_store: &RecordStore = &other_store;
+    let authors_and_rounds =
+        |store: &RecordStore| -> Vec<_> { store.iter().map(|r| (r.author(), r.round())).collect() };
+    assert_eq!(authors_and_rounds(store), authors_and_rounds(other_store));
+}
+
+#[test]
+fn test_stats() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    assert_eq!(shared_store.store.stats(), RecordStoreStats::default());
+    shared_store.make_round(NodeTime(10));
+    shared_store.make_tc();
+    shared_store.make_round(NodeTime(30));
+    shared_store.create_timeout(0, Round(4));
+    let stats = shared_store.store.stats();
+    assert_eq!(stats.block_count, 2);
+    assert_eq!(stats.vote_count, 0);
+    assert_eq!(stats.qc_count, 2);
+    assert_eq!(stats.timeout_count, 3);
+    assert_eq!(stats.oldest_round, Some(Round(1)));
+    assert_eq!(stats.newest_round, Some(Round(4)));
+    // Votes of past rounds are not in store anymore.
+    let total_size: usize = shared_store
+        .store
+        .records()
+        .iter()
+        .filter(|record| !matches!(record, Record::Vote(_)))
+        .map(Record::wire_size)
+        .sum();
+    assert_eq!(stats.total_size_estimate_bytes, total_size);
+
+    // Votes of the current round are counted too.
+    let author = shared_store.leader(shared_store.store.current_round());
+    shared_store.propose_block(
+        author.0,
+        shared_store.store.highest_quorum_certificate_hash(),
+        NodeTime(40),
+    );
+    let proposed_hash = shared_store.store.current_proposed_block.unwrap();
+    assert!(shared_store.create_vote(0, proposed_hash));
+    let stats = shared_store.store.stats();
+    assert_eq!(stats.block_count, 3);
+    assert_eq!(stats.vote_count, 1);
+    assert_eq!(stats.newest_round, Some(Round(4)));
+}
+
+#[test]
+fn test_find_by_author_and_round() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    let leader = shared_store.leader(Round(1));
+    shared_store.make_round(NodeTime(10));
+    shared_store.make_tc();
+    shared_store.create_timeout(0, Round(3));
+    let store: &RecordStore = &shared_store.store;
+    // Block, vote and QC of the leader at round 1.
+    let records = store.find_by_author_and_round(leader, Round(1));
+    assert_eq!(records.len(), 3);
+    assert_eq!(Block::try_from(records[0].clone()).unwrap().author, leader);
+    assert!(Vote::try_from(records[1].clone()).is_ok());
+    assert!(QuorumCertificate::try_from(records[2].clone()).is_ok());
+    // Timeouts of round 2 and of the current round.
+    for author in 0..2 {
+        let records = store.find_by_author_and_round(Author(author), Round(2));
+        assert_eq!(records.len(), 1);
+        assert!(Timeout::try_from(records[0].clone()).is_ok());
+    }
+    assert_eq!(store.find_by_author_and_round(Author(0), Round(3)).len(), 1);
+    assert!(store
+        .find_by_author_and_round(Author(1), Round(3))
+        .is_empty());
+    assert!(store
+        .find_by_author_and_round(Author(0), Round(5))
+        .is_empty());
+
+    // Committed rounds stay indexed.
+    for i in 0..3 {
+        shared_store.make_round(NodeTime(20 + 10 * i));
+    }
+    let store: &RecordStore = &shared_store.store;
+    assert!(store.highest_committed_round() >= Round(1));
+    assert_eq!(store.find_by_author_and_round(leader, Round(1)).len(), 3);
+}
+
+#[test]
+fn test_equivocating_proposal_is_not_current() {
+    let mut shared_store = SharedRecordStore::new(2, 20);
+    shared_store.make_round(NodeTime(10));
+    // A second block of the leader at the same round does not replace the first proposal.
+    let round = shared_store.store.current_round();
+    let leader = shared_store.leader(round);
+    let previous_qc_hash = shared_store.store.highest_quorum_certificate_hash();
+    shared_store.propose_block(leader.0, previous_qc_hash, NodeTime(30));
+    let proposed_hash = shared_store.store.current_proposed_block.unwrap();
+    shared_store.propose_block(leader.0, previous_qc_hash, NodeTime(40));
+    assert_eq!(
+        shared_store.store.current_proposed_block,
+        Some(proposed_hash)
+    );
+    assert_eq!(shared_store.store.equivocation_proofs().len(), 1);
+}
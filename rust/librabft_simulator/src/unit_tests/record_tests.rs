@@ -9,10 +9,12 @@ fn test_block_signing() {
         Command {
             proposer: Author(1),
             index: 2,
+            payload: Vec::new(),
         },
         NodeTime(2),
         QuorumCertificateHash(47),
         Round(3),
+        /* epoch configuration digest */ 0,
         Author(2),
     );
     assert!(b.signature().check(b.digest(), b.author()).is_ok());
@@ -21,11 +23,161 @@ fn test_block_signing() {
         Command {
             proposer: Author(3),
             index: 2,
+            payload: Vec::new(),
         },
         NodeTime(2),
         QuorumCertificateHash(47),
         Round(3),
+        /* epoch configuration digest */ 0,
         Author(2),
     );
     assert!(b.signature().check(b2.digest(), b.author()).is_err());
+    match (&b, &b2) {
+        (Record::Block(block), Record::Block(block2)) => {
+            assert_eq!(block.command_hash(), block.command.deterministic_hash());
+            assert_ne!(block.command_hash(), block2.command_hash());
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_validate_block() {
+    let command = Command {
+        proposer: Author(1),
+        index: 2,
+        payload: Vec::new(),
+    };
+    let b = Record::make_block(
+        command.clone(),
+        NodeTime(2),
+        QuorumCertificateHash(47),
+        Round(3),
+        /* epoch configuration digest */ 0,
+        Author(2),
+    );
+    assert!(b.validate().is_ok());
+    let mut forged = Block::try_from(b).unwrap();
+    forged.author = Author(1);
+    assert!(Record::from(forged).validate().is_err());
+    let b0 = Record::make_block(
+        command,
+        NodeTime(2),
+        QuorumCertificateHash(47),
+        Round(0),
+        /* epoch configuration digest */ 0,
+        Author(2),
+    );
+    assert!(b0.validate().is_err());
+}
+
+#[test]
+fn test_aggregate_quorum_certificate() {
+    let num_nodes = 100;
+    let mut voting_rights = BTreeMap::new();
+    for index in 0..num_nodes {
+        voting_rights.insert(Author(index), 1);
+    }
+    let configuration = EpochConfiguration::new(voting_rights);
+    let make_qc = |num_votes| {
+        let mut qc = QuorumCertificate {
+            epoch_id: EpochId(0),
+            round: Round(3),
+            certified_block_hash: BlockHash(47),
+            state: State(11),
+            committed_state: None,
+            votes: Vec::new(),
+            author: Author(2),
+            signature: Signature(0),
+        };
+        qc.votes = (0..num_votes)
+            .map(|index| {
+                let author = Author(index);
+                (author, Signature::sign(qc.vote_digest(author), author))
+            })
+            .collect();
+        qc
+    };
+
+    let qc = make_qc(configuration.quorum_threshold());
+    let aggregate = qc.aggregate(&configuration).unwrap();
+    assert!(aggregate.check(&configuration).is_ok());
+    assert_eq!(aggregate.signers(&configuration).len(), qc.votes.len());
+    assert!(10 * aggregate.wire_size() < qc.wire_size());
+    assert_eq!(aggregate.wire_size(), qc.aggregated_wire_size(num_nodes));
+
+    // Signers must form a quorum.
+    let aggregate = make_qc(configuration.quorum_threshold() - 1)
+        .aggregate(&configuration)
+        .unwrap();
+    assert!(aggregate.check(&configuration).is_err());
+
+    // The signature must match the signers.
+    let mut forged = qc.aggregate(&configuration).unwrap();
+    forged.signers[num_nodes - 1] = true;
+    assert!(forged.check(&configuration).is_err());
+
+    // Signers must have voting rights.
+    assert!(make_qc(num_nodes + 1).aggregate(&configuration).is_err());
+}
+
+#[test]
+fn test_record_from() {
+    let records = vec![
+        Record::make_vote(
+            EpochId(1),
+            Round(3),
+            BlockHash(47),
+            State(5),
+            Author(2),
+            None,
+        ),
+        Record::make_timeout(EpochId(1), Round(3), Round(2), Author(2)),
+        Record::make_quorum_certificate(
+            EpochId(1),
+            Round(3),
+            BlockHash(47),
+            State(5),
+            vec![(Author(2), Signature(0))],
+            None,
+            Author(2),
+        ),
+    ];
+    for record in records {
+        let wrapped: Record = match record.clone() {
+            Record::Block(block) => block.into(),
+            Record::Vote(vote) => vote.into(),
+            Record::QuorumCertificate(qc) => qc.into(),
+            Record::Timeout(timeout) => timeout.into(),
+        };
+        assert_eq!(wrapped, record);
+    }
+}
+
+#[test]
+fn test_record_try_from() {
+    let vote = Record::make_vote(
+        EpochId(1),
+        Round(3),
+        BlockHash(47),
+        State(5),
+        Author(2),
+        None,
+    );
+    let timeout = Record::make_timeout(EpochId(1), Round(3), Round(2), Author(2));
+    assert_eq!(Record::from(Vote::try_from(vote.clone()).unwrap()), vote);
+    assert_eq!(
+        Record::from(Timeout::try_from(timeout.clone()).unwrap()),
+        timeout
+    );
+    let err = Block::try_from(vote.clone()).unwrap_err();
+    assert_eq!(err.record, vote);
+    assert_eq!(
+        err.to_string(),
+        "Expected a block record but found a vote record"
+    );
+    assert!(QuorumCertificate::try_from(timeout).is_err());
+    // Errors propagate with `?`.
+    let extract = |record| -> Result<Round> { Ok(Block::try_from(record)?.round) };
+    assert!(extract(vote).is_err());
 }
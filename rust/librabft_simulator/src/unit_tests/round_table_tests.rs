@@ -0,0 +1,105 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use librabft_simulator::testing::make_configured_simulator;
+use simulator::GlobalTime;
+
+#[test]
+fn test_round_table_with_crashed_leader() {
+    let mut sim = make_configured_simulator(4, 5, |_, context| context, |node| node);
+    sim.crash_node(Author(3));
+    sim.loop_until(GlobalTime(2000), None);
+
+    let table = round_table(&sim, Author(0));
+    assert!(!table.is_empty());
+    for (index, row) in table.iter().enumerate() {
+        assert_eq!(row.round, Round(index + 1));
+        assert_eq!(
+            row.commit_latency.is_some(),
+            row.outcome == RoundOutcome::Committed
+        );
+        if row.leader == Author(3) {
+            assert_eq!(row.outcome, RoundOutcome::TimedOut);
+            assert_eq!(row.votes, 0);
+        }
+        if row.outcome == RoundOutcome::Committed {
+            assert!(row.votes >= 3);
+        }
+    }
+    assert!(table
+        .iter()
+        .any(|row| row.outcome == RoundOutcome::Committed));
+    assert!(table
+        .iter()
+        .any(|row| row.outcome == RoundOutcome::TimedOut));
+}
+
+#[test]
+fn test_write_round_table() {
+    let path = std::env::temp_dir().join(format!("librabft_round_table_{}", std::process::id()));
+    std::fs::create_dir_all(&path).unwrap();
+    let rows = vec![RoundSummary {
+        round: Round(1),
+        leader: Author(2),
+        outcome: RoundOutcome::Committed,
+        commit_latency: Some(42),
+        proposal_time: Some(GlobalTime(100)),
+        votes: 3,
+    }];
+    write_round_table(path.to_str().unwrap(), &rows);
+    let content = std::fs::read_to_string(path.join("round_table.txt")).unwrap();
+    let mut lines = content.lines();
+    assert_eq!(lines.next(), Some("# schema_version: 1.0"));
+    assert_eq!(
+        lines.next(),
+        Some("round,leader,outcome,commit_latency,votes")
+    );
+    assert!(lines.next().is_some());
+    std::fs::remove_dir_all(path).unwrap();
+}
+
+#[test]
+fn test_timeout_cost_with_flaky_leader() {
+    let mut sim = make_configured_simulator(4, 7, |_, context| context, |node| node);
+    // Node 3 is alternately down and up.
+    for period in 0..10 {
+        sim.loop_until(GlobalTime(period * 1000 + 500), None);
+        sim.crash_node(Author(3));
+        sim.loop_until(GlobalTime(period * 1000 + 1000), None);
+        sim.recover_node(Author(3));
+    }
+
+    let cost = timeout_cost(&round_table(&sim, Author(0)));
+    assert!(cost.happy_path_rounds > 0);
+    assert!(cost.after_timeout_rounds > 0);
+    let delta = cost.delta().unwrap();
+    assert!(delta > cost.mean_happy_path_latency.unwrap() / 2.0);
+}
+
+#[test]
+fn test_timeout_cost() {
+    let row = |round, outcome, proposal_time: Option<GlobalTime>, commit_latency| RoundSummary {
+        round: Round(round),
+        leader: Author(0),
+        outcome,
+        commit_latency,
+        proposal_time,
+        votes: 3,
+    };
+    let rows = vec![
+        row(1, RoundOutcome::Committed, Some(GlobalTime(0)), Some(30)),
+        row(2, RoundOutcome::Committed, Some(GlobalTime(10)), Some(30)),
+        row(3, RoundOutcome::TimedOut, None, None),
+        row(4, RoundOutcome::Committed, Some(GlobalTime(60)), Some(30)),
+        row(5, RoundOutcome::Uncommitted, None, None),
+        row(6, RoundOutcome::Committed, Some(GlobalTime(100)), Some(30)),
+    ];
+    let cost = timeout_cost(&rows);
+    assert_eq!(cost.happy_path_rounds, 1);
+    assert_eq!(cost.after_timeout_rounds, 1);
+    assert_eq!(cost.mean_happy_path_latency, Some(40.0));
+    assert_eq!(cost.mean_after_timeout_latency, Some(80.0));
+    assert_eq!(cost.delta(), Some(40.0));
+    assert_eq!(timeout_cost(&rows[..2]).delta(), None);
+}
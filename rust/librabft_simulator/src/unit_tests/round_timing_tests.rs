@@ -0,0 +1,58 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use librabft_simulator::testing::TestNet;
+use std::{cell::RefCell, rc::Rc};
+
+#[test]
+fn test_lag_attributions() {
+    let mut observer = RoundTimingObserver::new();
+    for round in 1..4 {
+        let leader = round % 4;
+        for node in (0..4).filter(|node| *node != leader) {
+            // Node 1 receives proposals late, node 2 votes late.
+            let key = (Author(node), EpochId(0), Round(round));
+            observer.timings.insert(
+                key,
+                VoteTiming {
+                    epoch_id: EpochId(0),
+                    round: Round(round),
+                    author: Author(node),
+                    network_delay: if node == 1 { 40 } else { 10 },
+                    execution_delay: if node == 2 { 30 } else { 2 },
+                },
+            );
+        }
+    }
+    let lags = observer.lag_attributions(5);
+    let attribution = |author| {
+        lags.iter()
+            .find(|lag| lag.author == Author(author))
+            .unwrap()
+            .attribution
+    };
+    assert_eq!(attribution(0), LagAttribution::OnTime);
+    assert_eq!(attribution(1), LagAttribution::NetworkLate);
+    assert_eq!(attribution(2), LagAttribution::ExecutionLate);
+    assert_eq!(attribution(3), LagAttribution::OnTime);
+}
+
+#[test]
+fn test_vote_timings() {
+    let mut net = TestNet::new(4);
+    let observer = Rc::new(RefCell::new(RoundTimingObserver::new()));
+    let sim = net.simulator_mut();
+    sim.add_observer(observer.clone());
+    sim.loop_until(GlobalTime(1000), None);
+    let timings: Vec<_> = observer.borrow().timings.values().cloned().collect();
+    assert!(!timings.is_empty());
+    // Without execution costs, nodes vote as soon as they receive proposals.
+    assert!(timings.iter().all(|timing| timing.execution_delay == 0));
+    assert!(timings.iter().all(|timing| timing.network_delay > 0));
+    // Leaders are not timed.
+    let record_store = sim.simulated_node(Author(0)).node().record_store();
+    assert!(timings
+        .iter()
+        .all(|timing| record_store.leader(timing.round) != timing.author));
+}
@@ -12,8 +12,10 @@ fn test_happened_before() {
         Command {
             proposer: Author(0),
             index: 0,
+            payload: Vec::new(),
         },
         NodeTime(1),
+        /* deduplicate */ true,
     );
     assert!(!s1.happened_just_before(&s2));
     assert!(s2.happened_just_before(&s1));
@@ -21,15 +23,19 @@ fn test_happened_before() {
         Command {
             proposer: Author(1),
             index: 0,
+            payload: Vec::new(),
         },
         NodeTime(1),
+        /* deduplicate */ true,
     );
     s2.execute(
         Command {
             proposer: Author(1),
             index: 0,
+            payload: Vec::new(),
         },
         NodeTime(1),
+        /* deduplicate */ true,
     );
     assert!(!s2.happened_just_before(&s1));
 }
@@ -68,20 +74,165 @@ fn test_simulated_context() {
     assert_eq!(
         context.last_committed_ledger_state.execution_history,
         vec![
-            (
-                Command {
-                    proposer: Author(0),
-                    index: 0,
-                },
-                NodeTime(1)
-            ),
-            (
-                Command {
-                    proposer: Author(0),
-                    index: 1,
-                },
-                NodeTime(4)
-            ),
+            (CommandGenerator::new(0).generate(Author(0), 0), NodeTime(1)),
+            (CommandGenerator::new(0).generate(Author(0), 1), NodeTime(4)),
         ]
     )
 }
+
+#[test]
+fn test_inactive_authors() {
+    let mut context = SimulatedContext::new(
+        Author(0),
+        /* num_nodes */ 3,
+        /* max commands per epoch */ 2,
+    )
+    .with_participation_window(2);
+    let s0 = context.last_committed_state();
+    let voters = vec![Author(0), Author(1)];
+    let c1 = context.fetch().unwrap();
+    let s1 = context
+        .compute(&s0, c1, NodeTime(1), None, voters.clone())
+        .unwrap();
+    let c2 = context.fetch().unwrap();
+    let s2 = context
+        .compute(&s1, c2, NodeTime(2), None, voters.clone())
+        .unwrap();
+    assert_eq!(context.read_epoch_id(&s2), EpochId(1));
+    let inactive_authors = vec![Author(2)].into_iter().collect();
+    assert_eq!(
+        context.configuration(&s2).inactive_authors(),
+        &inactive_authors
+    );
+    // Later votes only count toward the next epoch.
+    let c3 = context.fetch().unwrap();
+    let s3 = context
+        .compute(&s2, c3, NodeTime(3), None, vec![Author(1), Author(2)])
+        .unwrap();
+    assert_eq!(
+        context.configuration(&s3).inactive_authors(),
+        &inactive_authors
+    );
+    // Without a window, everyone is active.
+    let context = SimulatedContext::new(Author(0), 3, 2);
+    assert!(context
+        .configuration(&context.last_committed_state())
+        .inactive_authors()
+        .is_empty());
+}
+
+#[test]
+fn test_command_generator() {
+    let generator = CommandGenerator::new(7);
+    let command = generator.generate(Author(2), 5);
+    assert_eq!(command.proposer, Author(2));
+    assert_eq!(command.index, 5);
+    assert_eq!(command, CommandGenerator::new(7).generate(Author(2), 5));
+    assert_ne!(
+        command.payload,
+        CommandGenerator::new(8).generate(Author(2), 5).payload
+    );
+    assert_eq!(
+        CommandGenerator::identify(&command),
+        Some((7, Author(2), 5))
+    );
+
+    let mut context = SimulatedContext::new(Author(2), 3, 10).with_command_generator(generator);
+    assert_eq!(context.fetch(), Some(generator.generate(Author(2), 0)));
+    assert_eq!(context.fetch(), Some(generator.generate(Author(2), 1)));
+}
+
+#[test]
+fn test_warm_start() {
+    let generator = CommandGenerator::new(7);
+    let mut context = SimulatedContext::new(Author(1), 3, 10)
+        .with_command_generator(generator)
+        .with_warm_start(5);
+    let other = SimulatedContext::new(Author(2), 3, 10)
+        .with_command_generator(generator)
+        .with_warm_start(5);
+    assert_eq!(context.last_committed_state(), other.last_committed_state());
+    let identities: Vec<_> = context
+        .committed_history()
+        .iter()
+        .map(|(command, _)| CommandGenerator::identify(command).unwrap())
+        .collect();
+    assert_eq!(
+        identities,
+        vec![
+            (7, Author(0), 0),
+            (7, Author(1), 0),
+            (7, Author(2), 0),
+            (7, Author(0), 1),
+            (7, Author(1), 1),
+        ]
+    );
+    // Fetched commands follow the warm-start commands of the node.
+    assert_eq!(context.fetch(), Some(generator.generate(Author(1), 2)));
+}
+
+#[test]
+fn test_fetch_within_max_block_bytes() {
+    let mut context = SimulatedContext::new(Author(0), 1, 30)
+        .with_command_padding(10)
+        .with_max_block_bytes(40);
+    assert_eq!(context.fetch(), None);
+    // The command that did not fit is still the next one.
+    let mut context = context.with_max_block_bytes(50);
+    let command = context.fetch().unwrap();
+    assert_eq!(command.index, 0);
+    assert_eq!(command.payload.len(), 42);
+}
+
+#[test]
+fn test_duplicate_commands() {
+    let mut context = SimulatedContext::new(Author(0), 2, 10).with_command_deduplication(true);
+    let s0 = context.last_committed_state();
+    let c1 = context.fetch().unwrap();
+    let s1 = context
+        .compute(&s0, c1.clone(), NodeTime(1), None, Vec::new())
+        .unwrap();
+    let s2 = context
+        .compute(&s1, c1.clone(), NodeTime(2), None, Vec::new())
+        .unwrap();
+    assert_ne!(s1, s2);
+    context.commit(&s1, None);
+    context.commit(&s2, None);
+    assert_eq!(
+        context.committed_history(),
+        &vec![(c1.clone(), NodeTime(1))]
+    );
+    assert_eq!(context.num_duplicate_commands(), 1);
+
+    // Commands are executed again by default.
+    let mut context = SimulatedContext::new(Author(0), 2, 10);
+    let s1 = context
+        .compute(&s0, c1.clone(), NodeTime(1), None, Vec::new())
+        .unwrap();
+    let s2 = context
+        .compute(&s1, c1, NodeTime(2), None, Vec::new())
+        .unwrap();
+    context.commit(&s1, None);
+    context.commit(&s2, None);
+    assert_eq!(context.committed_history().len(), 2);
+    assert_eq!(context.num_duplicate_commands(), 0);
+}
+
+#[test]
+fn test_command_reinclusion() {
+    let generator = CommandGenerator::new(0);
+    let mut context = SimulatedContext::new(Author(0), 2, 10).with_command_reinclusion(2);
+    let s0 = context.last_committed_state();
+    let c0 = context.fetch().unwrap();
+    assert_eq!(context.fetch(), Some(generator.generate(Author(0), 1)));
+    // The first command is still not committed two proposals later.
+    assert_eq!(context.fetch(), Some(c0.clone()));
+    let s1 = context
+        .compute(&s0, c0, NodeTime(1), None, Vec::new())
+        .unwrap();
+    context.commit(&s1, None);
+    // The second command is proposed again, but not the committed one.
+    assert_eq!(context.fetch(), Some(generator.generate(Author(0), 1)));
+    assert_eq!(context.fetch(), Some(generator.generate(Author(0), 2)));
+    assert_eq!(context.fetch(), Some(generator.generate(Author(0), 1)));
+}
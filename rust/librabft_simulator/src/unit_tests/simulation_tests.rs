@@ -0,0 +1,1732 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use auditor::Auditor;
+use bft_simulator_runtime::{
+    experiments,
+    trace::{BoundedTrace, InvariantWarning, RejectionSpike, RoundStall, TraceRecord},
+    ActiveRound, CommittedChain, ConsensusTimeout, ConsensusTimeoutHint, Validate,
+};
+use byzantine::{ByzantineConfig, ByzantineFault};
+use librabft_simulator::testing::{make_configured_simulator, make_context, make_node, TestNet};
+use record::{Block, Record};
+use simulated_context::StateDigest;
+use simulator::{
+    assert_eventual_agreement, DelayAndReplayAdversary, FaultKind, GlobalTime, LoggedMessage,
+    NetworkCore, RandomDelay, SensitivityParam, ShardAssignment, SimulationParameters,
+    SimulationResult, Simulator,
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeSet, HashSet},
+    rc::Rc,
+};
+
+type LibraSimulator =
+    Simulator<NodeState, SimulatedContext, BundledNotification, DataSyncRequest, DataSyncResponse>;
+
+fn make_simulator(num_nodes: usize, seed: u64) -> LibraSimulator {
+    make_configured_simulator(num_nodes, seed, |_, context| context, |node| node)
+}
+
+fn make_custom_simulator(
+    num_nodes: usize,
+    seed: u64,
+    network_delay: RandomDelay,
+    vote_wait_timeout: Option<Duration>,
+) -> LibraSimulator {
+    let node_factory = |author: Author, context: &SimulatedContext, clock: NodeTime| {
+        let node = make_node(author, context, clock);
+        match vote_wait_timeout {
+            Some(timeout) => node.with_vote_wait_timeout(timeout),
+            None => node,
+        }
+    };
+    Simulator::new(num_nodes, network_delay, make_context, node_factory).with_seed(seed)
+}
+
+fn num_commits(sim: &mut LibraSimulator, max_clock: GlobalTime) -> Vec<usize> {
+    sim.loop_until(max_clock, None)
+        .contexts
+        .iter()
+        .map(|context| context.committed_history().len())
+        .collect()
+}
+
+#[test]
+fn test_gst() {
+    let gst = GlobalTime(2000);
+    let mut sim = make_simulator(4, 17).with_gst(gst);
+    assert!(num_commits(&mut sim, GlobalTime(gst.0 - 1))
+        .iter()
+        .all(|n| *n == 0));
+    assert!(num_commits(&mut sim, GlobalTime(gst.0 + 1000))
+        .iter()
+        .all(|n| *n > 0));
+}
+
+#[test]
+fn test_eventual_consistency() {
+    let mut net = TestNet::new(4);
+    net.run_rounds(20);
+    net.simulator_mut().assert_eventual_consistency(200);
+}
+
+#[test]
+fn test_eventual_agreement() {
+    let mut sim = make_simulator(4, 5);
+    assert_eventual_agreement(&mut sim, GlobalTime(1000));
+}
+
+#[test]
+#[should_panic(expected = "did not agree on a common committed prefix")]
+fn test_eventual_agreement_with_partition() {
+    let mut sim = make_simulator(4, 5);
+    sim.set_partition(vec![vec![Author(0), Author(1)], vec![Author(2), Author(3)]]);
+    assert_eventual_agreement(&mut sim, GlobalTime(1000));
+}
+
+fn committed_chains(
+    sim: &mut LibraSimulator,
+    max_clock: GlobalTime,
+) -> Vec<Vec<(Command, NodeTime)>> {
+    sim.loop_until(max_clock, None)
+        .contexts
+        .iter()
+        .map(|context| context.committed_chain().to_vec())
+        .collect()
+}
+
+#[test]
+fn test_replay_without_omission() {
+    let mut sim = make_simulator(4, 11);
+    sim.enable_event_log();
+    let chains = committed_chains(&mut sim, GlobalTime(1000));
+    let log = sim.event_log().unwrap().to_vec();
+    assert!(!log.is_empty());
+
+    let mut replay = make_simulator(4, 11).replay_with_omission(&log, &[]);
+    replay.enable_event_log();
+    assert_eq!(committed_chains(&mut replay, GlobalTime(1000)), chains);
+    assert_eq!(replay.event_log().unwrap(), &log[..]);
+}
+
+#[test]
+fn test_replay_with_omission() {
+    let mut sim = make_simulator(4, 11);
+    sim.enable_event_log();
+    sim.loop_until(GlobalTime(1000), None);
+    let log = sim.event_log().unwrap().to_vec();
+    let omitted = log[0];
+    let omit = [(omitted.sender, omitted.receiver, omitted.round)];
+
+    let mut replay = make_simulator(4, 11).replay_with_omission(&log, &omit);
+    replay.enable_event_log();
+    replay.loop_until(GlobalTime(1000), None);
+    let replayed_log = replay.event_log().unwrap();
+    assert!(replayed_log
+        .iter()
+        .all(|message| { (message.sender, message.receiver, message.round) != omit[0] }));
+    // Messages sent before the omitted one are delivered as in the original run.
+    let earlier = |message: &&LoggedMessage| message.send_time < omitted.send_time;
+    assert!(log
+        .iter()
+        .filter(earlier)
+        .eq(replayed_log.iter().filter(earlier)));
+}
+
+fn committed_commands(sim: &mut LibraSimulator, max_clock: GlobalTime) -> Vec<Vec<Command>> {
+    committed_chains(sim, max_clock)
+        .into_iter()
+        .map(|chain| chain.into_iter().map(|(command, _)| command).collect())
+        .collect()
+}
+
+#[test]
+fn test_reproducible_commands() {
+    let commands = committed_commands(&mut make_simulator(4, 13), GlobalTime(1000));
+    assert!(commands.iter().all(|chain| !chain.is_empty()));
+    assert_eq!(
+        committed_commands(&mut make_simulator(4, 13), GlobalTime(1000)),
+        commands
+    );
+}
+
+#[test]
+fn test_reproducible_commands_with_fair_event_processing() {
+    let make_fair_simulator = || make_simulator(4, 13).with_fair_event_processing();
+    let commands = committed_commands(&mut make_fair_simulator(), GlobalTime(1000));
+    assert!(commands.iter().all(|chain| !chain.is_empty()));
+    assert_eq!(
+        committed_commands(&mut make_fair_simulator(), GlobalTime(1000)),
+        commands
+    );
+}
+
+#[test]
+fn test_align_commands_across_runs() {
+    // Same commands, different network delays.
+    let commands1 = committed_commands(&mut make_simulator(4, 13), GlobalTime(1000));
+    let commands2 = committed_commands(&mut make_simulator(4, 14), GlobalTime(1000));
+    let identities: BTreeSet<_> = commands1[0]
+        .iter()
+        .map(|command| (command.proposer, command.index))
+        .collect();
+    let aligned: Vec<_> = commands2[0]
+        .iter()
+        .filter(|command| identities.contains(&(command.proposer, command.index)))
+        .collect();
+    assert!(!aligned.is_empty());
+    for command in aligned {
+        assert!(commands1[0].contains(command));
+    }
+}
+
+#[test]
+fn test_warm_start() {
+    let mut sim =
+        make_configured_simulator(4, 13, |_, context| context.with_warm_start(10), |node| node);
+    let commands = committed_commands(&mut sim, GlobalTime(1000));
+    for chain in commands {
+        assert!(chain.len() > 10);
+        // Commands committed after the warm start are new.
+        let identities: BTreeSet<_> = chain
+            .iter()
+            .map(|command| (command.proposer, command.index))
+            .collect();
+        assert_eq!(identities.len(), chain.len());
+    }
+}
+
+// With a constant network delay of 10, votes reach the leader 20 after its proposal.
+#[test]
+fn test_votes_after_vote_wait_timeout() {
+    let mut sim = make_custom_simulator(4, 3, RandomDelay::constant(10.0), Some(19));
+    assert!(num_commits(&mut sim, GlobalTime(1000))
+        .iter()
+        .all(|n| *n == 0));
+    // Rounds are still completed by timeout certificates.
+    assert!(sim.simulated_node(Author(0)).active_round() > Round(1));
+}
+
+#[test]
+fn test_votes_before_vote_wait_timeout() {
+    let mut sim = make_custom_simulator(4, 3, RandomDelay::constant(10.0), Some(21));
+    assert!(num_commits(&mut sim, GlobalTime(1000))
+        .iter()
+        .all(|n| *n > 0));
+}
+
+#[test]
+fn test_buggy_state_digest_minority() {
+    let configure_context = |author, context: SimulatedContext| {
+        if author == Author(3) {
+            context.with_state_digest(StateDigest::Salted(1))
+        } else {
+            context
+        }
+    };
+    let mut sim = make_configured_simulator(4, 9, configure_context, |node| node);
+    let auditor = Rc::new(RefCell::new(Auditor::new()));
+    sim.add_observer(auditor.clone());
+    sim.enable_bounded_trace(
+        BoundedTrace::new(1000, 1, 10).with_trigger(Box::new(InvariantWarning::new())),
+    );
+    let commits = num_commits(&mut sim, GlobalTime(2000));
+    assert_eq!(commits[3], 0);
+    assert!(commits[..3].iter().all(|n| *n > 0));
+    assert_eq!(auditor.borrow().divergent_authors(), vec![Author(3)]);
+    // The divergence is reported to the trace as an anomaly.
+    let divergence_time = auditor.borrow().divergence_time(Author(3)).unwrap();
+    assert_eq!(
+        sim.bounded_trace().unwrap().anomalies(),
+        &[(divergence_time, "invariant warning")]
+    );
+}
+
+#[test]
+fn test_count_timeouts_per_round() {
+    // Leaders give up on all their proposals so every round ends with a timeout certificate.
+    let mut sim = make_custom_simulator(4, 3, RandomDelay::constant(10.0), Some(19));
+    sim.loop_until(GlobalTime(1000), None);
+    let timeouts = sim.count_timeouts_per_round();
+    let active_round = sim.simulated_node(Author(0)).active_round();
+    for round in 1..active_round.0 {
+        let count = timeouts[&Round(round)];
+        assert!((1..=4).contains(&count));
+    }
+}
+
+#[test]
+fn test_count_timeouts_per_round_with_fast_network() {
+    // Votes arrive well before the end of the rounds.
+    let mut sim = make_custom_simulator(4, 3, RandomDelay::constant(2.0), None);
+    sim.loop_until(GlobalTime(1000), None);
+    assert!(sim.simulated_node(Author(0)).active_round() > Round(100));
+    assert!(sim.count_timeouts_per_round().is_empty());
+}
+
+#[test]
+fn test_qc_extra_delay() {
+    let mut sim = make_custom_simulator(4, 3, RandomDelay::constant(2.0), None);
+    let summary = sim.loop_until(GlobalTime(1000), None).summary;
+    let mut slow_sim = make_custom_simulator(4, 3, RandomDelay::constant(2.0), None);
+    slow_sim.set_qc_extra_delay(5);
+    let slow_summary = slow_sim.loop_until(GlobalTime(1000), None).summary;
+    assert!(slow_summary.committed_entries > 0);
+    assert!(slow_summary.mean_commit_latency.unwrap() > summary.mean_commit_latency.unwrap());
+}
+
+#[test]
+fn test_verify_pacemaker_safety() {
+    // Without commits, round durations grow as `delta * n^gamma` with `gamma = 2`.
+    let mut sim = make_custom_simulator(4, 3, RandomDelay::constant(10.0), Some(19));
+    sim.loop_until(GlobalTime(1000), None);
+    assert!(sim
+        .nodes_iter()
+        .all(|(_, node)| node.timeouts().windows(2).any(|pair| pair[1] > pair[0])));
+    sim.verify_pacemaker_safety(4.0);
+}
+
+#[test]
+#[should_panic(expected = "more than a factor")]
+fn test_verify_pacemaker_safety_violation() {
+    let mut sim = make_custom_simulator(4, 3, RandomDelay::constant(10.0), Some(19));
+    sim.loop_until(GlobalTime(1000), None);
+    sim.verify_pacemaker_safety(2.0);
+}
+
+#[test]
+fn test_freeze_recovers_faster_than_crash() {
+    let (from, until) = (GlobalTime(300), GlobalTime(700));
+    let mut frozen_sim = make_simulator(4, 7);
+    frozen_sim.schedule_freeze(Author(3), from, until);
+    frozen_sim.loop_until(GlobalTime(800), None);
+
+    let mut crashed_sim = make_simulator(4, 7);
+    crashed_sim.loop_until(from, None);
+    crashed_sim.crash_node(Author(3));
+    crashed_sim.loop_until(until, None);
+    crashed_sim.recover_node(Author(3));
+    crashed_sim.loop_until(GlobalTime(800), None);
+
+    // First commit of the node after the end of the outage.
+    let recovery_time = |sim: &LibraSimulator| {
+        *sim.simulated_node(Author(3))
+            .commit_times()
+            .iter()
+            .find(|time| **time >= until)
+            .unwrap()
+    };
+    assert!(frozen_sim.dead_letters().is_empty());
+    assert!(!crashed_sim.dead_letters().is_empty());
+    assert!(recovery_time(&frozen_sim) < recovery_time(&crashed_sim));
+}
+
+#[test]
+fn test_saturated_node() {
+    let mut sim = make_simulator(4, 5).with_inbox_capacity(Author(3), 1);
+    let commits = num_commits(&mut sim, GlobalTime(1000));
+    assert!(sim.simulated_node(Author(3)).dropped_messages() > 0);
+    // The rest of the cluster is not affected.
+    for (index, num_commits) in commits.iter().enumerate().take(3) {
+        assert_eq!(sim.simulated_node(Author(index)).dropped_messages(), 0);
+        assert!(*num_commits > 0);
+    }
+}
+
+#[test]
+fn test_notification_coalescing() {
+    // With a constant delay, votes reach the leader at the same time. With 3 nodes, quorums
+    // need all the votes, so batching them does not change the certificates.
+    let mut sim = make_custom_simulator(3, 3, RandomDelay::constant(10.0), None);
+    let mut coalescing_sim = make_custom_simulator(3, 3, RandomDelay::constant(10.0), None)
+        .with_notification_coalescing();
+    let commands = committed_commands(&mut sim, GlobalTime(1000));
+    assert!(commands.iter().all(|chain| !chain.is_empty()));
+    assert_eq!(
+        committed_commands(&mut coalescing_sim, GlobalTime(1000)),
+        commands
+    );
+    let num_updates = |sim: &LibraSimulator| -> usize {
+        sim.nodes_iter().map(|(_, node)| node.num_updates()).sum()
+    };
+    assert!(num_updates(&coalescing_sim) < num_updates(&sim));
+}
+
+/// Runs the same sensitivity analysis with several seeds and returns the parameter values with
+/// the mean commit rates and p95 latencies of each step, so that comparisons between steps do not
+/// hinge on a single run.
+fn mean_sensitivity(
+    baseline: SimulationParameters,
+    param: SensitivityParam,
+    range: std::ops::Range<f64>,
+    steps: usize,
+) -> (Vec<f64>, Vec<f64>, Vec<f64>) {
+    let num_seeds = 5;
+    let mut param_values = Vec::new();
+    let mut commit_rates = vec![0.0; steps];
+    let mut latencies_p95 = vec![0.0; steps];
+    for seed in 0..num_seeds {
+        let result = LibraSimulator::run_sensitivity_analysis(
+            baseline,
+            param,
+            range.clone(),
+            steps,
+            GlobalTime(5000),
+            |num_nodes, network_delay| make_custom_simulator(num_nodes, seed, network_delay, None),
+        );
+        param_values = result.param_values;
+        for step in 0..steps {
+            commit_rates[step] += result.commit_rates[step] / num_seeds as f64;
+            latencies_p95[step] += result.latencies_p95[step] / num_seeds as f64;
+        }
+    }
+    (param_values, commit_rates, latencies_p95)
+}
+
+#[test]
+fn test_sensitivity_to_network_delay() {
+    let baseline = SimulationParameters {
+        num_nodes: 4,
+        network_delay_mean: 10.0,
+        network_delay_variance: 0.0,
+        message_loss_rate: 0.0,
+    };
+    let (param_values, commit_rates, latencies_p95) =
+        mean_sensitivity(baseline, SensitivityParam::NetworkDelayMean, 5.0..25.0, 2);
+    assert_eq!(param_values, vec![5.0, 15.0]);
+    assert!(commit_rates[0] > commit_rates[1]);
+    assert!(latencies_p95[0] < latencies_p95[1]);
+}
+
+#[test]
+fn test_sensitivity_to_message_loss() {
+    let baseline = SimulationParameters {
+        num_nodes: 4,
+        network_delay_mean: 10.0,
+        network_delay_variance: 4.0,
+        message_loss_rate: 0.0,
+    };
+    let (_, commit_rates, _) =
+        mean_sensitivity(baseline, SensitivityParam::MessageLossRate, 0.0..1.0, 2);
+    assert!(commit_rates[0] > 0.0);
+    // With half of the messages lost, progress is expected to be slower.
+    assert!(commit_rates[1] < commit_rates[0]);
+}
+
+#[test]
+fn test_eclipse_with_stale_feed() {
+    let (victim, attacker) = (Author(3), Author(0));
+    let (from, until) = (GlobalTime(500), GlobalTime(1500));
+    let config =
+        ByzantineConfig::new().with_fault(attacker, ByzantineFault::StaleFeed { victim, lag: 300 });
+    let mut sim = byzantine_simulator(&config, 3);
+    sim.schedule_eclipse(victim, attacker, from, until);
+    sim.loop_until(until, None);
+    let num_commits =
+        |sim: &LibraSimulator, author| sim.simulated_node(author).commit_times().len();
+    let target = num_commits(&sim, Author(1));
+    // The victim fell behind.
+    assert!(target > 0);
+    assert!(num_commits(&sim, victim) < target);
+
+    let summary = sim.loop_until(GlobalTime(5000), None).summary;
+    assert_eq!(summary.safety_violations, 0);
+    // The victim recovers with data sync once the eclipse lifts.
+    let catch_up_time = *sim
+        .simulated_node(victim)
+        .commit_times()
+        .get(target - 1)
+        .expect("The victim should catch up with the other nodes");
+    let recovery_time = catch_up_time.0 - until.0;
+    assert!(recovery_time > 0);
+    assert!(recovery_time < 1000, "Recovery took {}", recovery_time);
+    // Stale records are still valid, and do not pass for a divergent execution.
+    for (_, node) in sim.nodes_iter() {
+        assert!(!node.node().has_diverged());
+    }
+    let records = sim
+        .simulated_node(victim)
+        .node()
+        .record_store()
+        .unknown_records(BTreeSet::new());
+    assert!(records.iter().all(|record| record.validate().is_ok()));
+}
+
+#[test]
+fn test_first_commit_time_of_late_node() {
+    let join_time = GlobalTime(1000);
+    let mut sim = make_simulator(4, 3);
+    // The last node joins late: until then, messages sent to it are lost.
+    sim.crash_node(Author(3));
+    sim.loop_until(join_time, None);
+    assert_eq!(sim.simulated_node(Author(3)).first_commit_time(), None);
+    sim.recover_node(Author(3));
+    sim.loop_until(GlobalTime(2000), None);
+    let first_commit_time = |author| sim.simulated_node(author).first_commit_time().unwrap();
+    assert!(first_commit_time(Author(3)) >= join_time);
+    for index in 0..3 {
+        assert!(first_commit_time(Author(index)) < GlobalTime(join_time.0 / 2));
+    }
+}
+
+#[test]
+fn test_gst_liveness() {
+    let seeds = [1, 2, 3, 4, 5];
+    let gst_liveness = |max_rounds| {
+        experiments::gst_liveness(
+            |seed| make_simulator(4, seed),
+            GlobalTime(1000),
+            max_rounds,
+            &seeds,
+            /* timeout */ 2000,
+        )
+    };
+    let report = gst_liveness(5);
+    assert!(report.failures().is_empty());
+    assert!(report
+        .outcomes
+        .iter()
+        .all(|outcome| outcome.rounds_to_commit.is_some()));
+    // A bound that is too tight is reported.
+    let report = gst_liveness(1);
+    assert_eq!(report.failures(), seeds.to_vec());
+}
+
+#[test]
+fn test_run_monte_carlo() {
+    let committed_entries =
+        |result: &SimulationResult<SimulatedContext>| result.summary.committed_entries as f64;
+    let (mean, stddev) = LibraSimulator::run_monte_carlo(
+        10,
+        GlobalTime(1000),
+        |seed| make_simulator(4, seed),
+        committed_entries,
+    );
+    assert!(mean > 0.0);
+    assert!(stddev > 0.0);
+    // Constant properties do not vary.
+    let (mean, stddev) = LibraSimulator::run_monte_carlo(
+        3,
+        GlobalTime(100),
+        |seed| make_simulator(4, seed),
+        |_| 2.0,
+    );
+    assert_eq!((mean, stddev), (2.0, 0.0));
+}
+
+#[test]
+fn test_block_stuffing_leader() {
+    let stuffing_leader = Author(0);
+    let config =
+        ByzantineConfig::new().with_fault(stuffing_leader, ByzantineFault::PadCommands(1000));
+    let mut sim = config
+        .make_simulator(
+            4,
+            RandomDelay::new(10.0, 4.0),
+            |author, num_nodes| make_context(author, num_nodes).with_max_block_bytes(100),
+            make_node,
+        )
+        .with_seed(3);
+    sim.add_observer(Rc::new(RefCell::new(Auditor::new())));
+    sim.enable_bounded_trace(
+        BoundedTrace::new(1000, 1, 10).with_trigger(Box::new(RejectionSpike::new(3, 1000))),
+    );
+    let result = sim.loop_until(GlobalTime(3000), None);
+    assert_eq!(result.summary.safety_violations, 0);
+    for context in &result.contexts[1..] {
+        let history = context.committed_history();
+        // Liveness proceeds without the blocks of the Byzantine leader.
+        assert!(!history.is_empty());
+        assert!(history
+            .iter()
+            .all(|(command, _)| command.proposer != stuffing_leader));
+    }
+    for index in 1..4 {
+        assert!(
+            sim.simulated_node(Author(index))
+                .node()
+                .num_oversized_blocks()
+                > 0
+        );
+    }
+    // The Byzantine leader shares the epoch configuration of the other nodes.
+    for (_, node) in sim.nodes_iter() {
+        assert_eq!(node.node().num_epoch_config_mismatches(), 0);
+    }
+    // Rounds led by the Byzantine leader time out.
+    assert!(!sim.count_timeouts_per_round().is_empty());
+    // The rejected blocks are reported to the trace.
+    let trace = sim.bounded_trace().unwrap();
+    assert!(trace
+        .anomalies()
+        .iter()
+        .any(|(_, name)| *name == "rejection spike"));
+}
+
+/// Run a lossy network and return the mean commit latency.
+fn commit_latency_in_lossy_network(seed: u64, certified_block_in_timeouts: bool) -> f64 {
+    let configure_node = |node: NodeState| {
+        if certified_block_in_timeouts {
+            node.with_certified_block_in_timeouts()
+        } else {
+            node
+        }
+    };
+    let mut sim = make_configured_simulator(4, seed, |_, context| context, configure_node)
+        .with_message_loss_rate(0.1);
+    let summary = sim.loop_until(GlobalTime(5000), None).summary;
+    assert_eq!(summary.safety_violations, 0);
+    summary
+        .mean_commit_latency
+        .expect("Commands should be committed despite message losses")
+}
+
+#[test]
+fn test_certified_block_in_timeouts() {
+    // Leaders receive the freshest QC of the timeouts without waiting for a sync round trip.
+    let plain: f64 = (0..4)
+        .map(|seed| commit_latency_in_lossy_network(seed, false))
+        .sum();
+    let bundled: f64 = (0..4)
+        .map(|seed| commit_latency_in_lossy_network(seed, true))
+        .sum();
+    assert!(bundled < plain);
+}
+
+#[test]
+fn test_view_change_frequency() {
+    // Rounds last long enough for a QC to arrive before the timeout.
+    let fast_network = RandomDelay::new(4.0, 1.0);
+    let mut sim = make_custom_simulator(4, 5, fast_network, None);
+    sim.loop_until(GlobalTime(2000), None);
+    assert!(sim.measure_view_change_frequency() < 0.05);
+
+    // Rounds led by the crashed node time out.
+    let mut sim = make_custom_simulator(4, 5, fast_network, None);
+    sim.crash_node(Author(3));
+    sim.loop_until(GlobalTime(2000), None);
+    let frequency = sim.measure_view_change_frequency();
+    assert!((0.2..=1.0).contains(&frequency));
+}
+
+fn lossy_simulator_expecting_losses() -> LibraSimulator {
+    let mut sim = make_simulator(4, 3).with_message_loss_rate(0.01);
+    sim.expect_fault_activations(FaultKind::LostMessage, 1);
+    sim
+}
+
+#[test]
+#[should_panic(expected = "expected at least 1 lost messages, got 0")]
+fn test_fault_coverage_of_short_run() {
+    let mut sim = lossy_simulator_expecting_losses();
+    sim.loop_until(GlobalTime(30), None);
+    sim.assert_fault_coverage();
+}
+
+#[test]
+fn test_fault_coverage_of_long_run() {
+    let mut sim = lossy_simulator_expecting_losses();
+    sim.crash_node(Author(3));
+    sim.set_partition(vec![vec![Author(0)], vec![Author(1), Author(2)]]);
+    let summary = sim.loop_until(GlobalTime(1000), None).summary;
+    sim.assert_fault_coverage();
+    let coverage = sim.fault_coverage();
+    assert_eq!(&summary.fault_coverage, coverage);
+    assert!(coverage.count(FaultKind::LostMessage) > 0);
+    assert!(coverage.count(FaultKind::PartitionDrop) > 0);
+    assert_eq!(coverage.count(FaultKind::Crash), 1);
+    assert_eq!(coverage.count(FaultKind::Partition), 1);
+    assert_eq!(coverage.count(FaultKind::EclipseDrop), 0);
+}
+
+#[test]
+fn test_pending_requests_of_lagging_node() {
+    let lagging_node = Author(3);
+    let mut sim = make_simulator(4, 7);
+    sim.set_partition(vec![
+        vec![Author(0), Author(1), Author(2)],
+        vec![lagging_node],
+    ]);
+    sim.loop_until(GlobalTime(300), None);
+    sim.heal_partition();
+    let mut has_requested = false;
+    let mut has_caught_up = false;
+    for clock in 31..100 {
+        sim.loop_until(GlobalTime(10 * clock), None);
+        let node = sim.simulated_node(lagging_node);
+        has_requested |= node.pending_requests() > 0;
+        let num_commits = node.commit_times().len();
+        if has_requested
+            && num_commits > 0
+            && num_commits >= sim.simulated_node(Author(0)).commit_times().len()
+            && node.pending_requests() == 0
+        {
+            has_caught_up = true;
+            break;
+        }
+    }
+    assert!(has_requested);
+    assert!(has_caught_up);
+}
+
+fn reinclusion_simulator(seed: u64, deduplicate_commands: bool, lag: usize) -> LibraSimulator {
+    let configure_context = |_, context: SimulatedContext| {
+        context
+            .with_command_reinclusion(lag)
+            .with_command_deduplication(deduplicate_commands)
+    };
+    make_configured_simulator(4, seed, configure_context, |node| node)
+}
+
+fn num_duplicates(history: &[(Command, NodeTime)]) -> usize {
+    let ids: BTreeSet<_> = history
+        .iter()
+        .map(|(command, _)| (command.proposer, command.index))
+        .collect();
+    history.len() - ids.len()
+}
+
+fn committed_histories_after_leader_crash(
+    deduplicate_commands: bool,
+) -> (Vec<Vec<(Command, NodeTime)>>, Vec<usize>) {
+    let mut sim = reinclusion_simulator(1, deduplicate_commands, /* lag */ 3);
+    sim.loop_until(GlobalTime(300), None);
+    // The crash delays commits, so that leaders propose again commands whose first block ends
+    // up being committed as well.
+    sim.crash_node(Author(1));
+    let result = sim.loop_until(GlobalTime(2000), None);
+    let histories = result
+        .contexts
+        .iter()
+        .map(|context| context.committed_history().clone())
+        .collect();
+    let duplicates = result
+        .contexts
+        .iter()
+        .map(|context| context.num_duplicate_commands())
+        .collect();
+    (histories, duplicates)
+}
+
+#[test]
+fn test_command_deduplication_after_leader_crash() {
+    let (histories, duplicates) = committed_histories_after_leader_crash(false);
+    assert!(histories.iter().any(|history| num_duplicates(history) > 0));
+    assert!(duplicates.iter().all(|n| *n == 0));
+
+    let (histories, duplicates) = committed_histories_after_leader_crash(true);
+    assert!(histories.iter().all(|history| num_duplicates(history) == 0));
+    assert!(duplicates.iter().any(|n| *n > 0));
+    let longest = histories
+        .iter()
+        .max_by_key(|history| history.len())
+        .unwrap();
+    for history in &histories {
+        assert!(longest.starts_with(history));
+    }
+}
+
+#[test]
+fn test_network_partition_tolerance() {
+    let mut sim = make_simulator(4, 9);
+    sim.loop_until(GlobalTime(500), None);
+    let report = sim.run_network_partition_tolerance_test(1, 2000);
+    assert_eq!(report.majority, vec![Author(0), Author(1), Author(2)]);
+    assert_eq!(report.minority, vec![Author(3)]);
+    assert!(report.is_success());
+    assert!(report.new_commits[..3].iter().all(|n| *n > 0));
+
+    // Without a quorum, neither group can commit, but safety still holds.
+    let mut sim = make_simulator(4, 9);
+    sim.loop_until(GlobalTime(500), None);
+    let report = sim.run_network_partition_tolerance_test(2, 2000);
+    assert!(report.is_safe());
+    assert!(!report.is_live());
+    assert_eq!(report.stalled_nodes, vec![Author(0), Author(1)]);
+}
+
+#[test]
+fn test_missed_timers() {
+    // The node misses all its timers, but messages keep updating it.
+    let mut sim = make_simulator(4, 21).with_timer_loss_rate(Author(3), 1.0);
+    let commits = num_commits(&mut sim, GlobalTime(3000));
+    assert!(sim.fault_coverage().count(FaultKind::MissedTimer) > 0);
+    assert!(commits[3] > 0);
+    assert!(commits[3] + 1 >= commits[0]);
+}
+
+/// Simulate 4 validators and an observer without voting rights, Author(4), which only learns
+/// the chain through sync requests.
+fn observer_simulator(policy: UnknownAuthorPolicy, low_priority_delay: Duration) -> LibraSimulator {
+    let context_factory = |author, num_nodes| make_context(author, num_nodes - 1);
+    let node_factory = |author: Author, context: &SimulatedContext, clock: NodeTime| {
+        make_node(author, context, clock).with_unknown_author_policy(policy)
+    };
+    Simulator::new(
+        5,
+        RandomDelay::new(10.0, 4.0),
+        context_factory,
+        node_factory,
+    )
+    .with_seed(5)
+    .with_low_priority_response_delay(low_priority_delay)
+}
+
+fn num_refused_requests(sim: &LibraSimulator) -> Vec<usize> {
+    sim.nodes_iter()
+        .map(|(_, node)| node.refused_requests())
+        .collect()
+}
+
+/// Isolate the observer until it must sync the missed chain from validators.
+fn isolate_observer(sim: &mut LibraSimulator) {
+    sim.set_partition(vec![(0..4).map(Author).collect(), vec![Author(4)]]);
+    sim.loop_until(GlobalTime(1000), None);
+    sim.heal_partition();
+}
+
+#[test]
+fn test_requests_from_observer() {
+    let mut sim = observer_simulator(UnknownAuthorPolicy::Serve, 200);
+    isolate_observer(&mut sim);
+    let commits = num_commits(&mut sim, GlobalTime(1100));
+    assert!(commits[4] > 0);
+    assert_eq!(commits[4], commits[0]);
+    assert_eq!(num_refused_requests(&sim), vec![0; 5]);
+
+    let mut sim = observer_simulator(UnknownAuthorPolicy::Deprioritize, 200);
+    isolate_observer(&mut sim);
+    assert_eq!(num_commits(&mut sim, GlobalTime(1100))[4], 0);
+    assert!(num_commits(&mut sim, GlobalTime(1500))[4] > 0);
+    assert_eq!(num_refused_requests(&sim), vec![0; 5]);
+
+    let mut sim = observer_simulator(UnknownAuthorPolicy::Reject, 200);
+    isolate_observer(&mut sim);
+    let commits = num_commits(&mut sim, GlobalTime(2000));
+    assert_eq!(commits[4], 0);
+    assert!(commits[..4].iter().all(|n| *n > 0));
+    let refusals = num_refused_requests(&sim);
+    assert!(refusals[..4].iter().all(|n| *n > 0));
+    assert_eq!(refusals[4], 0);
+}
+
+/// Synthetic violation: node 3 learns a QC.
+fn knows_quorum_certificate(sim: &LibraSimulator) -> bool {
+    sim.simulated_node(Author(3))
+        .node()
+        .record_store()
+        .highest_quorum_certificate_round()
+        >= Round(1)
+}
+
+/// Seed of the run whose trace is minimized.
+const MINIMIZED_SEED: u64 = 8;
+
+fn replay_trace(trace: &experiments::EventTrace) -> LibraSimulator {
+    let mut sim =
+        make_simulator(4, MINIMIZED_SEED).replay_with_omission(&trace.messages, &trace.omitted);
+    sim.loop_until(trace.end, None);
+    sim
+}
+
+#[test]
+fn test_minimize_trace() {
+    let end = GlobalTime(1000);
+    let mut sim = make_simulator(4, MINIMIZED_SEED);
+    sim.enable_event_log();
+    sim.loop_until(end, None);
+    assert!(knows_quorum_certificate(&sim));
+    let trace = experiments::EventTrace::new(sim.event_log().unwrap().to_vec(), end);
+    assert!(trace.messages.len() > 900);
+
+    let budget = experiments::MinimizationBudget {
+        max_replays: 1000,
+        max_duration: None,
+    };
+    let minimized = experiments::minimize(
+        || make_simulator(4, MINIMIZED_SEED),
+        &trace,
+        knows_quorum_certificate,
+        budget,
+    );
+    // A proposal, a quorum of votes, and the QC reaching node 3.
+    assert!(minimized.messages.len() <= 20);
+    assert!(knows_quorum_certificate(&replay_trace(&minimized)));
+    // Dropping any remaining message fixes the run.
+    for message in &minimized.messages {
+        let mut trace = minimized.clone();
+        trace
+            .omitted
+            .push((message.sender, message.receiver, message.round));
+        assert!(!knows_quorum_certificate(&replay_trace(&trace)));
+    }
+
+    // Without budget, the trace is returned as is.
+    let budget = experiments::MinimizationBudget {
+        max_replays: 0,
+        max_duration: None,
+    };
+    let unchanged = experiments::minimize(
+        || make_simulator(4, MINIMIZED_SEED),
+        &trace,
+        knows_quorum_certificate,
+        budget,
+    );
+    assert_eq!(unchanged, trace);
+}
+
+#[test]
+fn test_proposer_fairness() {
+    // Leaders are picked by hashing rounds, hence evenly in the long run.
+    let mut sim = make_simulator(4, 3);
+    sim.loop_until(GlobalTime(5000), None);
+    assert!(sim.proposer_fairness() > 0.9);
+}
+
+fn byzantine_simulator(config: &ByzantineConfig, seed: u64) -> LibraSimulator {
+    config
+        .make_simulator(4, RandomDelay::new(10.0, 4.0), make_context, make_node)
+        .with_seed(seed)
+}
+
+/// Blocks of the given proposer known to any node, with their rounds.
+fn known_blocks(sim: &LibraSimulator, proposer: Author) -> BTreeSet<(Round, Block)> {
+    sim.nodes_iter()
+        .flat_map(|(_, node)| node.node().record_store().records())
+        .filter_map(|record| match record {
+            Record::Block(block) if block.author == proposer => Some((block.round, block.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Authors of the votes in the QCs of other leaders than `leader` known to any node.
+fn certifying_voters(sim: &LibraSimulator, leader: Author) -> BTreeSet<Author> {
+    sim.nodes_iter()
+        .flat_map(|(_, node)| node.node().record_store().records())
+        .filter_map(|record| match record {
+            Record::QuorumCertificate(qc) if qc.author != leader => Some(&qc.votes),
+            _ => None,
+        })
+        .flatten()
+        .map(|(author, _)| *author)
+        .collect()
+}
+
+fn equivocate_to(victims: &[usize]) -> ByzantineFault {
+    ByzantineFault::Equivocate {
+        victims: victims.iter().map(|index| Author(*index)).collect(),
+    }
+}
+
+#[test]
+fn test_byzantine_config() {
+    let config = ByzantineConfig::new()
+        .with_fault(Author(1), equivocate_to(&[2, 3]))
+        .with_fault(Author(2), ByzantineFault::WithholdVotes);
+    assert_eq!(config.byzantine_authors(), vec![Author(1), Author(2)]);
+    let mut sim = byzantine_simulator(&config, 4);
+    let commits = num_commits(&mut sim, GlobalTime(3000));
+    assert!(commits.iter().all(|n| *n > 0));
+    // Nodes were sent two different blocks by the equivocating leader at the same round.
+    let blocks = known_blocks(&sim, Author(1));
+    let rounds: BTreeSet<_> = blocks.iter().map(|(round, _)| *round).collect();
+    assert!(!rounds.is_empty());
+    assert!(blocks.len() > rounds.len());
+    // The withholding node only counts its votes toward its own QCs.
+    let voters = certifying_voters(&sim, Author(2));
+    assert!(voters.contains(&Author(1)));
+    assert!(!voters.contains(&Author(2)));
+
+    let config = ByzantineConfig::new()
+        .with_fault(Author(2), ByzantineFault::LieAboutCertifiedRound)
+        .with_fault(Author(3), ByzantineFault::Censor(Author(0)));
+    let mut sim = byzantine_simulator(&config, 4);
+    let mut claimed_rounds = BTreeMap::new();
+    let mut censored_votes = Vec::new();
+    for clock in 1..60 {
+        sim.loop_until(GlobalTime(50 * clock), None);
+        let store = sim.simulated_node(Author(0)).node().record_store();
+        for record in store.records() {
+            match record {
+                Record::Timeout(timeout) => {
+                    claimed_rounds
+                        .entry(timeout.author)
+                        .or_insert_with(BTreeSet::new)
+                        .insert(timeout.highest_certified_block_round);
+                }
+                Record::QuorumCertificate(qc)
+                    if store.block(qc.certified_block_hash).unwrap().author == Author(0) =>
+                {
+                    censored_votes.push(qc.votes.iter().any(|(author, _)| *author == Author(3)));
+                }
+                _ => (),
+            }
+        }
+    }
+    // Timeouts of the lying node claim that nothing was certified, unlike honest ones.
+    assert_eq!(
+        claimed_rounds[&Author(2)],
+        vec![Round(0)].into_iter().collect()
+    );
+    assert!(claimed_rounds[&Author(0)]
+        .iter()
+        .any(|round| *round > Round(0)));
+    // Blocks of the censored leader are certified without the censoring node.
+    assert!(!censored_votes.is_empty());
+    assert!(censored_votes.iter().all(|voted| !voted));
+}
+
+#[test]
+fn test_byzantine_fault_rate() {
+    let mut sim = make_simulator(4, 21);
+    sim.enable_safety_checking();
+    let summary = sim.loop_until(GlobalTime(3000), None).summary;
+    assert_eq!(summary.byzantine_fault_rate, Some(0.0));
+
+    let config = ByzantineConfig::new().with_fault(Author(1), equivocate_to(&[3]));
+    let mut sim = byzantine_simulator(&config, 4);
+    assert_eq!(
+        sim.loop_until(GlobalTime(3000), None)
+            .summary
+            .byzantine_fault_rate,
+        None
+    );
+    let proofs = sim
+        .simulated_node(Author(1))
+        .node()
+        .equivocation_proofs()
+        .to_vec();
+    assert!(!proofs.is_empty());
+    assert!(proofs.iter().all(|proof| proof.author == Author(1)));
+    // Only the victim is sent conflicting proposals.
+    let node = sim.simulated_node(Author(1)).node();
+    let (_, block) = known_blocks(&sim, Author(1)).into_iter().next().unwrap();
+    assert!(node.conflicting_proposal_for(Author(3), &block).is_some());
+    assert!(node.conflicting_proposal_for(Author(0), &block).is_none());
+    assert!(node.conflicting_proposal_for(Author(2), &block).is_none());
+    // The equivocating node leads at most one round out of four.
+    let rate = sim.byzantine_fault_rate();
+    assert!(rate > 0.0);
+    assert!(rate < 1.0 / 3.0);
+}
+
+#[test]
+fn test_misconfigured_proposer() {
+    // Node 3 believes that a fifth node has voting rights.
+    let context_factory = |author: Author, num_nodes| {
+        let num_validators = if author == Author(3) { 5 } else { num_nodes };
+        make_context(author, num_validators)
+    };
+    let mut sim =
+        Simulator::new(4, RandomDelay::new(10.0, 4.0), context_factory, make_node).with_seed(2);
+    let commits = num_commits(&mut sim, GlobalTime(3000));
+    assert!(commits[..3].iter().all(|n| *n > 0));
+    // Node 3 rejects the blocks of the others, hence never certifies nor commits anything.
+    assert_eq!(commits[3], 0);
+    let node = sim.simulated_node(Author(3)).node();
+    assert!(node.num_epoch_config_mismatches() > 0);
+    assert!(known_blocks(&sim, Author(3)).is_empty());
+    for index in 0..3 {
+        let node = sim.simulated_node(Author(index)).node();
+        assert_eq!(node.num_epoch_config_mismatches(), 0);
+    }
+}
+
+#[test]
+fn test_node_cpu_budget() {
+    let max_clock = GlobalTime(5000);
+    let mut sim = make_simulator(4, 3);
+    num_commits(&mut sim, max_clock);
+    let fast_updates: usize = sim.nodes_iter().map(|(_, node)| node.num_updates()).sum();
+    // Handling 10 records takes 1ms.
+    let mut sim = make_simulator(4, 3).with_node_cpu_budget(0.1);
+    let slow_commits = num_commits(&mut sim, max_clock);
+    let slow_updates: usize = sim.nodes_iter().map(|(_, node)| node.num_updates()).sum();
+    assert!(slow_updates < fast_updates);
+    // The pacemaker copes with the additional delays.
+    assert!(slow_commits.iter().all(|n| *n > 0));
+}
+
+#[test]
+fn test_golden_trace() {
+    let path = std::env::temp_dir().join(format!("golden_trace_{}", std::process::id()));
+    let run = |seed| {
+        let mut sim = make_simulator(4, seed);
+        sim.enable_processing_trace();
+        sim.loop_until(GlobalTime(1000), None);
+        sim
+    };
+    run(11).write_golden_trace(&path).unwrap();
+    run(11).assert_trace_matches(&path);
+    let divergence = run(12).golden_trace_divergence(&path).unwrap().unwrap();
+    assert_ne!(divergence.expected, divergence.found);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_in_flight_rounds() {
+    let mut sim = make_simulator(4, 6);
+    let summary = sim.loop_until(GlobalTime(1003), None).summary;
+    // Under the 3-chain rule, the last proposed rounds are not committed yet.
+    assert!(summary.in_flight_rounds > 0);
+    assert!(summary.in_flight_rounds <= summary.rounds_completed + 1);
+}
+
+#[test]
+fn test_shards() {
+    let shards = ShardAssignment::contiguous(8, 2);
+    let configure_context = |author, context: SimulatedContext| {
+        let shard = shards.shard(author).unwrap();
+        context.with_validators(shards.members(shard))
+    };
+    let mut sim = make_configured_simulator(8, 7, configure_context, |node| node)
+        .with_shard_assignment(shards.clone(), /* cross_shard_delay */ 50);
+    sim.enable_event_log();
+    let commits_before = num_commits(&mut sim, GlobalTime(1000));
+    assert!(commits_before.iter().all(|n| *n > 0));
+    // Shard 1 loses its quorum while shard 0 carries on.
+    sim.crash_node(Author(6));
+    sim.crash_node(Author(7));
+    let commits_after = num_commits(&mut sim, GlobalTime(3000));
+    for author in 0..4 {
+        assert!(commits_after[author] > commits_before[author] + 10);
+    }
+    for author in 4..6 {
+        assert!(commits_after[author] <= commits_before[author] + 2);
+    }
+    // Broadcasts stay within shards.
+    assert!(sim
+        .event_log()
+        .unwrap()
+        .iter()
+        .all(|message| shards.same_shard(message.sender, message.receiver)));
+}
+
+#[test]
+fn test_domain_crash() {
+    // f = 2 out of 7 nodes share a rack that loses power.
+    let mut sim = make_simulator(7, 3);
+    let domain: HashSet<_> = vec![Author(5), Author(6)].into_iter().collect();
+    sim.schedule_domain_crash(domain.clone(), GlobalTime(1000));
+    let commits_before = num_commits(&mut sim, GlobalTime(999));
+    assert!(commits_before.iter().all(|n| *n > 0));
+    assert!(domain
+        .iter()
+        .all(|author| !sim.simulated_node(*author).is_crashed()));
+    let commits_after = num_commits(&mut sim, GlobalTime(10000));
+    assert!(domain
+        .iter()
+        .all(|author| sim.simulated_node(*author).is_crashed()));
+    assert_eq!(sim.fault_coverage().count(FaultKind::Crash), 2);
+    // The remaining quorum keeps committing.
+    for author in 0..5 {
+        assert!(commits_after[author] > commits_before[author] + 10);
+    }
+    for author in 5..7 {
+        assert!(commits_after[author] <= commits_before[author] + 2);
+    }
+}
+
+#[test]
+fn test_bounded_trace() {
+    let make_trace =
+        || BoundedTrace::new(5000, 1, 500).with_trigger(Box::new(RoundStall::new(200)));
+    // Clean run.
+    let mut sim = make_simulator(4, 11);
+    sim.enable_bounded_trace(make_trace());
+    sim.loop_until(GlobalTime(10000), None);
+    let trace = sim.bounded_trace().unwrap();
+    assert!(trace.len() <= 5000);
+    assert!(trace.sample_every() > 1);
+    assert!(trace.anomalies().is_empty());
+
+    // Incident: the network loses its quorum for a while.
+    let mut sim = make_simulator(4, 11);
+    sim.enable_bounded_trace(make_trace());
+    sim.enable_processing_trace();
+    sim.loop_until(GlobalTime(5000), None);
+    sim.crash_node(Author(2));
+    sim.crash_node(Author(3));
+    sim.loop_until(GlobalTime(6000), None);
+    sim.recover_node(Author(2));
+    sim.recover_node(Author(3));
+    sim.loop_until(GlobalTime(10000), None);
+    let trace = sim.bounded_trace().unwrap();
+    assert!(!trace.anomalies().is_empty());
+    let (anomaly_time, _) = trace.anomalies()[0];
+    assert!(anomaly_time > GlobalTime(5000));
+    // All the events shortly before the anomaly are kept.
+    let is_recent = |time: GlobalTime| anomaly_time.0 - 10 <= time.0 && time <= anomaly_time;
+    let recent_events: Vec<_> = sim
+        .processing_trace()
+        .unwrap()
+        .iter()
+        .filter(|entry| is_recent(entry.time))
+        .cloned()
+        .collect();
+    let traced_events: Vec<_> = trace
+        .records()
+        .into_iter()
+        .filter_map(|record| match record {
+            TraceRecord::Event(entry) if is_recent(entry.time) => Some(*entry),
+            _ => None,
+        })
+        .collect();
+    assert!(!recent_events.is_empty());
+    assert_eq!(traced_events, recent_events);
+}
+
+#[test]
+fn test_nodes_reached_round() {
+    let mut sim = make_simulator(4, 17).with_ordered_startup(200);
+    let target = Round(10);
+    assert!(!sim.any_node_reached_round(Round(1)));
+    let mut clock = GlobalTime(0);
+    while !sim.any_node_reached_round(target) {
+        clock = clock + 10;
+        assert!(clock < GlobalTime(5000), "No node reached {}", target);
+        sim.loop_until(clock, None);
+    }
+    // The last node to start lags behind the first one.
+    assert!(!sim.all_nodes_reached_round(target));
+    while !sim.all_nodes_reached_round(target) {
+        clock = clock + 10;
+        assert!(
+            clock < GlobalTime(5000),
+            "Some node did not reach {}",
+            target
+        );
+        sim.loop_until(clock, None);
+    }
+    assert!(sim
+        .nodes_iter()
+        .all(|(_, node)| node.active_round() >= target));
+}
+
+#[test]
+fn test_pipeline_depth() {
+    let run = |depth| {
+        let mut sim = make_configured_simulator(
+            4,
+            23,
+            |_, context| context,
+            |node| node.with_pipeline_depth(depth),
+        );
+        sim.loop_until(GlobalTime(3000), None);
+        sim
+    };
+    let unpipelined = run(1);
+    let pipelined = run(3);
+    let num_commits = |sim: &LibraSimulator| {
+        sim.simulated_node(Author(0))
+            .context()
+            .committed_history()
+            .len()
+    };
+    let stalls = |sim: &LibraSimulator| {
+        sim.nodes_iter()
+            .map(|(_, node)| node.node().num_pipeline_stalls())
+            .sum::<usize>()
+    };
+    assert!(stalls(&unpipelined) > stalls(&pipelined));
+    assert!(num_commits(&unpipelined) > 0);
+    assert!(num_commits(&pipelined) > 2 * num_commits(&unpipelined));
+    // Each command is committed once, in the same order at every node.
+    for sim in &[unpipelined, pipelined] {
+        sim.assert_eventual_consistency(500);
+        let history = sim.simulated_node(Author(0)).context().committed_history();
+        let commands: BTreeSet<_> = history.iter().map(|(command, _)| command).collect();
+        assert_eq!(commands.len(), history.len());
+    }
+}
+
+#[test]
+fn test_slow_executor() {
+    let slow = Author(2);
+    let configure_context = |author, context: SimulatedContext| {
+        let cost = if author == slow { 20 } else { 2 };
+        context.with_execution_cost(cost)
+    };
+    let mut sim = make_configured_simulator(4, 31, configure_context, |node| node);
+    let votes = Rc::new(RefCell::new(participation::VoteTracker::new()));
+    sim.add_observer(votes.clone());
+    let round_timings = Rc::new(RefCell::new(round_timing::RoundTimingObserver::new()));
+    sim.add_observer(round_timings.clone());
+    sim.loop_until(GlobalTime(3000), None);
+
+    // The slow node is attributed the lag of its votes, not the network.
+    let lags = round_timings.borrow().lag_attributions(/* tolerance */ 5);
+    assert_eq!(lags.len(), 4);
+    for lag in &lags {
+        let expected = if lag.author == slow {
+            round_timing::LagAttribution::ExecutionLate
+        } else {
+            round_timing::LagAttribution::OnTime
+        };
+        assert_eq!(lag.attribution, expected);
+    }
+    assert!(
+        sim.simulated_node(slow).execution_time()
+            > 5 * sim.simulated_node(Author(0)).execution_time()
+    );
+
+    // Its votes sometimes miss the QCs, but it still commits the same blocks.
+    let active_round = sim.simulated_node(Author(0)).active_round();
+    let participation = participation::participation_table(
+        &sim,
+        &votes.borrow(),
+        Author(0),
+        Round(1)..active_round,
+    );
+    let qcs_included = |author: Author| participation[author.0].qcs_included;
+    assert!(qcs_included(slow) < qcs_included(Author(0)));
+    assert!(qcs_included(slow) > 0);
+    assert!(!sim
+        .simulated_node(slow)
+        .context()
+        .committed_history()
+        .is_empty());
+    sim.assert_eventual_consistency(500);
+}
+
+#[test]
+fn test_reboot_with_full_state_loss() {
+    let mut sim = make_simulator(4, 41);
+    let victim = Author(3);
+    sim.loop_until(GlobalTime(2000), None);
+    let num_commits = |sim: &LibraSimulator, author| {
+        sim.simulated_node(author)
+            .context()
+            .committed_history()
+            .len()
+    };
+    let commits_before = num_commits(&sim, victim);
+    assert!(commits_before > 0);
+
+    sim.inject_reboot_with_full_state_loss(victim, make_context, make_node);
+    assert_eq!(num_commits(&sim, victim), 0);
+    assert_eq!(sim.simulated_node(victim).active_round(), Round(0));
+    // Data synchronization brings the node back up to date.
+    sim.loop_until(GlobalTime(4000), None);
+    assert!(num_commits(&sim, victim) > commits_before);
+    assert!(
+        sim.simulated_node(victim).active_round() + 5
+            >= sim.simulated_node(Author(0)).active_round()
+    );
+    sim.assert_eventual_consistency(500);
+}
+
+#[test]
+fn test_inspect_node_internals() {
+    let mut net = TestNet::new(4);
+    net.run_rounds(10);
+    let node = net.node(Author(1));
+    let store = node.record_store();
+    let qc = store.highest_commit_certificate().unwrap();
+    // The block certified by the commit certificate is stored along with it.
+    let block = store.block(qc.certified_block_hash).unwrap();
+    assert_eq!(block.round, qc.round);
+    assert!(store
+        .find_by_author_and_round(block.author, block.round)
+        .contains(&&Record::Block(block.clone())));
+    // The committed state of the certificate was delivered to the context.
+    let history = net.context(Author(1)).committed_history();
+    assert!(!history.is_empty());
+}
+
+#[test]
+fn test_reboot_with_partial_state() {
+    let mut sim = make_simulator(4, 47);
+    let victim = Author(2);
+    sim.loop_until(GlobalTime(2000), None);
+    let history = |sim: &LibraSimulator| {
+        sim.simulated_node(victim)
+            .context()
+            .committed_history()
+            .clone()
+    };
+    let before = history(&sim);
+    let committed_round = sim
+        .simulated_node(victim)
+        .node()
+        .record_store()
+        .highest_committed_round();
+    assert!(!before.is_empty());
+
+    sim.inject_reboot_with_partial_state(victim, committed_round, make_context, make_node);
+    assert_eq!(sim.fault_coverage().count(FaultKind::PartialStateLoss), 1);
+    // The committed chain is recovered from the log, without waiting for data synchronization.
+    assert_eq!(history(&sim), before);
+    // The node votes again above the committed round without breaking safety.
+    let votes = Rc::new(RefCell::new(participation::VoteTracker::new()));
+    sim.add_observer(votes.clone());
+    sim.loop_until(GlobalTime(4000), None);
+    assert!(votes
+        .borrow()
+        .voted_rounds(victim)
+        .iter()
+        .all(|(_, round)| *round > committed_round));
+    assert!(history(&sim).len() > before.len());
+    sim.assert_eventual_consistency(500);
+}
+
+#[test]
+fn test_no_revote_after_reboot_with_partial_state() {
+    let victim = Author(1);
+    for seed in 50..55 {
+        let mut sim = make_simulator(4, seed);
+        let votes_before = Rc::new(RefCell::new(participation::VoteTracker::new()));
+        sim.add_observer(votes_before.clone());
+        sim.loop_until(GlobalTime(1000), None);
+        // Reboot right after a vote whose round is not certified yet, so that the node is
+        // asked to vote on the same proposal again.
+        let has_pending_vote = |sim: &LibraSimulator| {
+            let store = sim.simulated_node(victim).node().record_store();
+            store.current_vote(victim).map_or(false, |vote| {
+                vote.round > store.highest_quorum_certificate_round()
+            })
+        };
+        while !has_pending_vote(&sim) {
+            assert!(sim.step(GlobalTime(2000)));
+        }
+        let voted_before = votes_before.borrow().voted_rounds(victim);
+        let committed_round = sim
+            .simulated_node(victim)
+            .node()
+            .record_store()
+            .highest_committed_round();
+        sim.inject_reboot_with_partial_state(victim, committed_round, make_context, make_node);
+        // A rebooted node has no current vote, so the new tracker only sees new votes.
+        let votes_after = Rc::new(RefCell::new(participation::VoteTracker::new()));
+        sim.add_observer(votes_after.clone());
+        sim.loop_until(GlobalTime(3000), None);
+        let voted_after = votes_after.borrow().voted_rounds(victim);
+        assert!(!voted_after.is_empty());
+        assert!(voted_before.is_disjoint(&voted_after), "seed {}", seed);
+    }
+}
+
+#[test]
+fn test_restart_with_committed_state_only() {
+    let victim = Author(2);
+    // Blocks after the committed round, except those of the commit certificate.
+    let uncommitted_blocks = |sim: &LibraSimulator| {
+        let node = sim.simulated_node(victim).node();
+        let committed_round = node.record_store().highest_committed_round();
+        let commit_certificates = node.commit_certificate_chain(committed_round);
+        node.record_store()
+            .records()
+            .iter()
+            .filter(|record| match record {
+                Record::Block(block) => {
+                    block.round > committed_round
+                        && commit_certificates
+                            .iter()
+                            .all(|qc| qc.certified_block_hash != BlockHash(record.digest()))
+                }
+                _ => false,
+            })
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let num_known_records = |sim: &LibraSimulator, records: &[Record]| {
+        let known = sim.simulated_node(victim).node().record_store().records();
+        records
+            .iter()
+            .filter(|record| known.contains(record))
+            .count()
+    };
+    let crashed_simulator = || {
+        let mut sim = make_simulator(4, 53);
+        sim.loop_until(GlobalTime(2000), None);
+        sim.crash_node(victim);
+        sim
+    };
+
+    let mut sim = crashed_simulator();
+    let before = uncommitted_blocks(&sim);
+    assert!(!before.is_empty());
+    sim.recover_node(victim);
+    assert_eq!(num_known_records(&sim, &before), before.len());
+
+    let mut sim = crashed_simulator();
+    let before = uncommitted_blocks(&sim);
+    let history = |sim: &LibraSimulator| {
+        sim.simulated_node(victim)
+            .context()
+            .committed_history()
+            .clone()
+    };
+    let committed = history(&sim);
+    sim.restart_with_committed_state(victim, make_context, make_node);
+    assert_eq!(
+        sim.fault_coverage().count(FaultKind::UncommittedStateLoss),
+        1
+    );
+    assert_eq!(history(&sim), committed);
+    assert_eq!(num_known_records(&sim, &before), 0);
+    // The in-progress rounds are fetched again from other nodes.
+    sim.loop_until(GlobalTime(2500), None);
+    assert_eq!(num_known_records(&sim, &before), before.len());
+    sim.loop_until(GlobalTime(4000), None);
+    sim.assert_eventual_consistency(500);
+}
+
+#[test]
+fn test_peer_discovery_of_late_joiner() {
+    let join_time = GlobalTime(1000);
+    let joiner = Author(3);
+    let bootstrap_peers = vec![Author(0), Author(1)];
+    // Return the time to full connectivity and the sync time of the joiner.
+    let run = |discovery_period: Option<Option<Duration>>| {
+        let mut sim = make_simulator(4, 0);
+        sim.crash_node(joiner);
+        sim.loop_until(join_time, None);
+        sim.recover_node(joiner);
+        if let Some(period) = discovery_period {
+            sim.start_peer_discovery(joiner, bootstrap_peers.clone(), period);
+            assert_eq!(
+                sim.known_peers(joiner),
+                bootstrap_peers.iter().cloned().collect()
+            );
+        }
+        sim.loop_until(GlobalTime(3000), None);
+        let sync_time = sim.simulated_node(joiner).first_commit_time().unwrap().0 - join_time.0;
+        (sim.time_to_full_connectivity(joiner), sync_time)
+    };
+    let (connectivity, baseline_sync_time) = run(None);
+    assert_eq!(connectivity, Some(0));
+    // The joiner learns the remaining peer when it first hears from it.
+    let (by_notifications, sync_time) = run(Some(None));
+    assert!(by_notifications.unwrap() > 0);
+    assert!(sync_time >= baseline_sync_time);
+    // Discovery ticks bound the time to full connectivity.
+    let (by_ticks, sync_time) = run(Some(Some(100)));
+    assert!(by_ticks.unwrap() <= std::cmp::min(100, by_notifications.unwrap()));
+    assert!(sync_time >= baseline_sync_time);
+    assert!(sync_time < baseline_sync_time + 100);
+}
+
+#[test]
+fn test_adversarial_sync_with_delay_and_replay() {
+    let mut sim = make_simulator(4, 48);
+    let result = sim.run_adversarial_sync(
+        Box::new(DelayAndReplayAdversary::new(2, 2)),
+        GlobalTime(3000),
+    );
+    // Reordered and replayed notifications slow down synchronization but never break safety.
+    assert_eq!(result.summary.safety_violations, 0);
+    let rounds_under_attack = result.summary.rounds_completed;
+    assert!(rounds_under_attack > 0);
+    // Nodes catch up once the adversary is gone.
+    let result = sim.loop_until(GlobalTime(6000), None);
+    assert!(result.summary.rounds_completed > 2 * rounds_under_attack);
+    sim.assert_eventual_consistency(500);
+}
+
+#[test]
+fn test_messages_per_commit() {
+    let num_nodes = 4;
+    let messages_per_commit = |loss_rate| {
+        let mut sim = make_simulator(num_nodes, 50).with_message_loss_rate(loss_rate);
+        sim.loop_until(GlobalTime(5000), None).messages_per_commit()
+    };
+    // Each commit needs at least a proposal and a vote per other node, and each of them takes a
+    // notification, a request and a response.
+    let minimum = 6.0 * (num_nodes - 1) as f64;
+    let healthy = messages_per_commit(0.0);
+    assert!(healthy >= minimum);
+    assert!(healthy < 2.0 * minimum);
+    // Lost messages cause timeouts and rounds without commits.
+    let lossy = messages_per_commit(0.2);
+    assert!(lossy > 1.25 * healthy);
+}
+
+#[test]
+fn test_latency_efficiency() {
+    let run = |loss_rate| {
+        let mut sim = make_simulator(4, 50).with_message_loss_rate(loss_rate);
+        assert_eq!(sim.expected_latency_under_good_network(), 30);
+        sim.loop_until(GlobalTime(5000), None).latency_efficiency()
+    };
+    // Committing takes a few rounds beyond the minimum.
+    let healthy = run(0.0);
+    assert!(healthy > 0.25 && healthy <= 1.0);
+    // Even without variance, the minimum is not beaten.
+    for seed in 0..4 {
+        let mut sim = make_custom_simulator(4, seed, RandomDelay::constant(10.0), None);
+        let efficiency = sim.loop_until(GlobalTime(2000), None).latency_efficiency();
+        assert!(efficiency > 0.0 && efficiency <= 1.0, "{}", efficiency);
+    }
+    let lossy = run(0.25);
+    assert!(lossy > 0.0 && lossy < healthy);
+    // Nothing was committed yet.
+    let mut sim = make_simulator(4, 50);
+    assert_eq!(
+        sim.loop_until(GlobalTime(10), None).latency_efficiency(),
+        0.0
+    );
+}
+
+fn current_timeouts(sim: &mut LibraSimulator, hint: Option<ConsensusTimeoutHint>) -> Vec<Duration> {
+    sim.loop_until(GlobalTime(2000), None);
+    (0..4)
+        .map(|index| {
+            let node = sim.simulated_node(Author(index));
+            assert_eq!(node.context().consensus_timeout_hint(), hint);
+            node.node().current_timeout().unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn test_time_to_first_commit() {
+    // No time limit: the run stops on its own at the first commit.
+    let config = experiments::FirstCommitConfig::new(4);
+    let mut sim = make_simulator(4, 2);
+    let report = experiments::time_to_first_commit(&mut sim, config);
+    let elapsed = report.elapsed.unwrap();
+    assert!(elapsed > 0 && elapsed < 1000, "{}", report);
+    assert_eq!(sim.current_clock(), GlobalTime(elapsed));
+    let num_committed = sim
+        .nodes_iter()
+        .filter(|(_, node)| node.first_commit_time().is_some())
+        .count();
+    assert!(num_committed >= 3);
+    // A commit needs a chain of QCs over a few rounds.
+    assert!(report.rounds >= 3, "{}", report);
+    assert!(report.messages > 0);
+    // Rounds led by a crashed node are burned by timeouts.
+    let mut sim = make_simulator(4, 2);
+    sim.crash_node(Author(0));
+    let slow_report = experiments::time_to_first_commit(&mut sim, config);
+    assert!(slow_report.elapsed.unwrap() < 10000, "{}", slow_report);
+    // Without a quorum of live nodes, the run gives up at `max_clock`.
+    let mut sim = make_simulator(4, 2);
+    sim.crash_node(Author(0));
+    sim.crash_node(Author(1));
+    let report =
+        experiments::time_to_first_commit(&mut sim, config.with_max_clock(GlobalTime(2000)));
+    assert_eq!(report.elapsed, None);
+    assert!(report.to_string().starts_with("No commit"));
+}
+
+#[test]
+fn test_consensus_timeout() {
+    // Nodes are created with a delta of 20.
+    let mut sim = make_simulator(4, 1);
+    let timeouts = current_timeouts(&mut sim, None);
+    assert!(timeouts.iter().all(|timeout| *timeout < 500));
+    let mut sim = make_simulator(4, 1).with_consensus_timeout(500);
+    let timeouts = current_timeouts(&mut sim, Some(ConsensusTimeoutHint(500)));
+    assert!(timeouts.iter().all(|timeout| *timeout >= 500));
+}
+
+#[test]
+fn test_network_core() {
+    const MAX_CLOCK: i64 = 1500;
+    // Median commit latency and utilization of a shared core of 30 kB per time unit.
+    let run = |num_nodes, relay_to_all| {
+        let mut sim = make_simulator(num_nodes, /* seed */ 1)
+            .with_consensus_timeout(100)
+            .with_network_core(NetworkCore::new(30000.0));
+        if relay_to_all {
+            sim = sim.with_relay_to_all();
+        }
+        let summary = sim.loop_until(GlobalTime(MAX_CLOCK), None).summary;
+        (
+            summary.p50_commit_latency.unwrap() as f64,
+            summary.peak_core_utilization.unwrap(),
+            summary.core_utilization,
+        )
+    };
+    // Broadcasting votes saturates the core: doubling the nodes more than doubles the latency.
+    let (broadcast_25, _, _) = run(25, true);
+    let (broadcast_50, broadcast_utilization, utilization) = run(50, true);
+    assert!(broadcast_50 > 2.0 * broadcast_25);
+    assert!(broadcast_utilization > 0.99);
+    // The core stays saturated from the first round on.
+    assert!(utilization
+        .iter()
+        .filter(|(time, _)| time.0 >= 100 && time.0 < MAX_CLOCK)
+        .all(|(_, utilization)| *utilization > 0.99));
+    // Relaying votes through the leader keeps the traffic within the capacity of the core.
+    let (leader_25, _, _) = run(25, false);
+    let (leader_50, leader_utilization, _) = run(50, false);
+    assert!(leader_50 < 1.5 * leader_25);
+    assert!(leader_utilization < 0.99);
+    assert!(leader_50 < broadcast_50 / 2.0);
+}
+
+#[test]
+fn test_round_types() {
+    let run = |crash| {
+        let path =
+            std::env::temp_dir().join(format!("round_types_{}_{}", crash, std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        // Long enough for rounds to complete before timing out.
+        let mut sim = make_simulator(4, 3).with_consensus_timeout(100);
+        if crash {
+            sim.crash_node(Author(3));
+        }
+        sim.loop_until(GlobalTime(3000), Some(path.clone()));
+        let content = std::fs::read_to_string(format!("{}/round_switches.txt", path)).unwrap();
+        std::fs::remove_dir_all(path).unwrap();
+        // Skip the version and the header.
+        let round_types: Vec<_> = content
+            .lines()
+            .skip(2)
+            .map(|line| line.rsplit(',').next().unwrap().to_string())
+            .collect();
+        let count = |name| round_types.iter().filter(|t| *t == name).count();
+        (count("Fast"), count("Slow"), count("Timeout"))
+    };
+    let (fast, slow, timeout) = run(false);
+    assert_eq!(timeout, 0);
+    assert!(fast > 10 * slow);
+    // Rounds led by the crashed node time out.
+    let (crashed_fast, crashed_slow, crashed_timeout) = run(true);
+    assert!(crashed_timeout > 0);
+    assert!(crashed_fast < crashed_slow + crashed_timeout);
+}
+
+#[test]
+fn test_round_inflation() {
+    let threshold = 5;
+    let mut sim = make_simulator(4, 2).with_round_inflation_threshold(threshold);
+    let summary = sim.loop_until(GlobalTime(3000), None).summary;
+    assert_eq!(summary.round_inflations, Some(0));
+
+    // Without the votes of three nodes, no block is certified: every leader fails and rounds
+    // only advance by timeouts.
+    let config = ByzantineConfig::new()
+        .with_fault(Author(1), ByzantineFault::WithholdVotes)
+        .with_fault(Author(2), ByzantineFault::WithholdVotes)
+        .with_fault(Author(3), ByzantineFault::WithholdVotes);
+    let mut sim = byzantine_simulator(&config, 7).with_round_inflation_threshold(threshold);
+    let mut gaps = [0; 4];
+    for clock in 1..40 {
+        sim.loop_until(GlobalTime(200 * clock), None);
+        for (author, gap) in gaps.iter_mut().enumerate() {
+            let new_gap = sim.round_gap(Author(author));
+            assert!(new_gap >= *gap);
+            *gap = new_gap;
+            // The detector fires as soon as the gap exceeds the threshold, once per node.
+            let reports = sim
+                .round_inflations()
+                .iter()
+                .filter(|inflation| inflation.author == Author(author))
+                .count();
+            assert_eq!(reports, if new_gap > threshold { 1 } else { 0 });
+        }
+    }
+    assert!(gaps.iter().all(|gap| *gap > threshold + 3));
+    for inflation in sim.round_inflations() {
+        assert_eq!(inflation.committed_round, Round(0));
+        assert_eq!(inflation.gap(), threshold + 1);
+    }
+}
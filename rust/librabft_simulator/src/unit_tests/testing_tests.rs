@@ -0,0 +1,57 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+
+#[test]
+fn test_run_rounds() {
+    let mut net = TestNet::new(3);
+    net.run_rounds(5);
+    for index in 0..3 {
+        assert!(net.node(Author(index)).active_round() >= Round(5));
+    }
+    // Under the 3-chain rule, commits lag a few rounds behind.
+    net.deliver_all_pending();
+    net.assert_all_committed_through(Round(2));
+    assert!(!net.context(Author(0)).committed_history().is_empty());
+}
+
+#[test]
+fn test_deliver_all_pending() {
+    let mut net = TestNet::new(3);
+    net.run_rounds(1);
+    assert!(net.simulator().last_delivery_time().is_some());
+    let clock = net.simulator().current_clock();
+    net.deliver_all_pending();
+    // Delays are constant so messages in flight are delivered within one network delay.
+    assert!(net.simulator().current_clock() <= clock + NETWORK_DELAY as Duration);
+}
+
+#[test]
+fn test_deterministic() {
+    let mut net1 = TestNet::new(3);
+    let mut net2 = TestNet::new(3);
+    net1.run_rounds(4);
+    net2.run_rounds(4);
+    assert!(!net1.context(Author(1)).committed_history().is_empty());
+    assert_eq!(
+        net1.context(Author(1)).committed_history(),
+        net2.context(Author(1)).committed_history()
+    );
+}
+
+#[test]
+#[should_panic(expected = "committed through")]
+fn test_assert_all_committed_through() {
+    let net = TestNet::new(3);
+    net.assert_all_committed_through(Round(1));
+}
+
+#[test]
+#[should_panic(expected = "did not reach")]
+fn test_run_rounds_with_crashed_node() {
+    let mut net = TestNet::new(3).with_time_limit(1000);
+    // Two out of three nodes are not a quorum.
+    net.simulator_mut().crash_node(Author(2));
+    net.run_rounds(3);
+}
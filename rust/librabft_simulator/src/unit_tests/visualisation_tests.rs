@@ -0,0 +1,48 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use testing::TestNet;
+
+#[test]
+fn test_visualise_qc_chain() {
+    let mut net = TestNet::new(2);
+    net.run_rounds(2);
+    let store = net.node(Author(0)).record_store();
+    let qc = store.highest_quorum_certificate().unwrap();
+    let block = store.block(qc.certified_block_hash).unwrap();
+    let output = net.simulator().visualise_qc_chain(qc);
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines[0], format!("{}:QC", qc.round));
+    assert_eq!(
+        lines[1],
+        format!("  → {}:Block(author={})", block.round, block.author.0)
+    );
+    // Records alternate between QCs and blocks down to the first round.
+    for (depth, line) in lines.iter().enumerate().skip(1) {
+        assert!(line.starts_with(&format!("{:indent$}→ ", "", indent = 2 * depth)));
+        let kind = if depth % 2 == 0 { ":QC" } else { ":Block" };
+        assert!(line.contains(kind), "{}", line);
+    }
+    assert!(lines.last().unwrap().trim_start().starts_with("→ r1:Block"));
+}
+
+#[test]
+fn test_visualise_long_qc_chain() {
+    let mut net = TestNet::new(2);
+    net.run_rounds(15);
+    let qc = net
+        .node(Author(0))
+        .record_store()
+        .highest_quorum_certificate()
+        .unwrap();
+    let output = net.simulator().visualise_qc_chain(qc);
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines.len(), MAX_VISUALISED_HOPS + 2);
+    assert_eq!(lines[0], format!("{}:QC", qc.round));
+    assert_eq!(
+        lines[MAX_VISUALISED_HOPS].trim_start(),
+        format!("→ {}:QC", Round(qc.round.0 - 10))
+    );
+    assert_eq!(lines[MAX_VISUALISED_HOPS + 1].trim_start(), "→ ...");
+}
@@ -0,0 +1,81 @@
+// Copyright (c) Calibra Research
+// SPDX-License-Identifier: Apache-2.0
+
+use super::*;
+use node::NodeState;
+use record::QuorumCertificate;
+use simulated_context::SimulatedContext;
+use simulator::Simulator;
+use std::fmt::Write;
+
+#[cfg(test)]
+#[path = "unit_tests/visualisation_tests.rs"]
+mod visualisation_tests;
+
+/// Maximal number of links followed by `SimulatorVisualisation::visualise_qc_chain`.
+pub const MAX_VISUALISED_HOPS: usize = 20;
+
+/// Text renderings of a simulation, for debugging without a graphical frontend.
+pub trait SimulatorVisualisation {
+    /// Render the chain of records ending with the given QC, one record per line, indented by
+    /// depth. Records are looked up in the record stores of all the nodes. The traversal stops
+    /// after `MAX_VISUALISED_HOPS` hops.
+    fn visualise_qc_chain(&self, committed_qc: &QuorumCertificate) -> String;
+}
+
+impl SimulatorVisualisation
+    for Simulator<
+        NodeState,
+        SimulatedContext,
+        BundledNotification,
+        DataSyncRequest,
+        DataSyncResponse,
+    >
+{
+    fn visualise_qc_chain(&self, committed_qc: &QuorumCertificate) -> String {
+        let block = |hash| {
+            self.nodes_iter()
+                .find_map(|(_, node)| node.node().record_store().block(hash))
+        };
+        let quorum_certificate = |hash| {
+            self.nodes_iter()
+                .find_map(|(_, node)| node.node().quorum_certificate(hash))
+        };
+        let mut records = vec![format!("{}:QC", committed_qc.round)];
+        let mut qc = committed_qc;
+        let mut truncated = false;
+        while let Some(block) = block(qc.certified_block_hash) {
+            if records.len() > MAX_VISUALISED_HOPS {
+                truncated = true;
+                break;
+            }
+            records.push(format!("{}:Block(author={})", block.round, block.author.0));
+            qc = match quorum_certificate(block.previous_quorum_certificate_hash) {
+                Some(qc) => qc,
+                None => break,
+            };
+            if records.len() > MAX_VISUALISED_HOPS {
+                truncated = true;
+                break;
+            }
+            records.push(format!("{}:QC", qc.round));
+        }
+        if truncated {
+            records.push("...".to_string());
+        }
+        let mut output = String::new();
+        for (depth, record) in records.iter().enumerate() {
+            let arrow = if depth == 0 { "" } else { "→ " };
+            writeln!(
+                output,
+                "{:indent$}{}{}",
+                "",
+                arrow,
+                record,
+                indent = 2 * depth
+            )
+            .unwrap();
+        }
+        output
+    }
+}